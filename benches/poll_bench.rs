@@ -0,0 +1,77 @@
+use chrono::{Duration, TimeZone, Utc};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::collections::HashMap;
+use std::fmt::Write;
+use wxlistener::air_quality::AirQualityAverager;
+use wxlistener::decoder::{parse_livedata, LIVEDATA_FIELDS};
+use wxlistener::derived::{add_derived_fields, DerivedConfig};
+use wxlistener::output::{FieldValue, PrecisionConfig};
+use wxlistener::sensor_watch::{SensorWatch, SensorWatchConfig};
+use wxlistener::status::DaemonStatus;
+use wxlistener::units::Units;
+
+/// Live data payload with every known field
+fn full_payload() -> Vec<u8> {
+    let values: HashMap<String, f64> = LIVEDATA_FIELDS
+        .iter()
+        .flat_map(|field| field.values.iter())
+        .map(|(name, _)| (name.to_string(), 12.3))
+        .collect();
+    LIVEDATA_FIELDS
+        .iter()
+        .flat_map(|field| field.encode(&values))
+        .collect()
+}
+
+fn benchmark_parse_livedata(c: &mut Criterion) {
+    let payload = full_payload();
+    c.bench_function("parse_livedata_full", |b| {
+        b.iter(|| parse_livedata(black_box(&payload)))
+    });
+}
+
+fn benchmark_format_fields(c: &mut Criterion) {
+    let data = parse_livedata(&full_payload());
+    let mut line = String::new();
+    c.bench_function("format_fields", |b| {
+        b.iter(|| {
+            for (key, value) in &data {
+                line.clear();
+                write!(line, "{}", FieldValue(key, *value, Units::default())).unwrap();
+                black_box(&line);
+            }
+        })
+    });
+}
+
+fn benchmark_poll_pipeline(c: &mut Criterion) {
+    let payload = full_payload();
+    let start = Utc.with_ymd_and_hms(2026, 1, 15, 0, 0, 0).unwrap();
+    let derived = DerivedConfig::default();
+    let precision = PrecisionConfig::default();
+    let mut sensor_watch = SensorWatch::new(SensorWatchConfig::default());
+    let mut averager = AirQualityAverager::new();
+    let mut status = DaemonStatus::new(start);
+    let mut i = 0;
+
+    c.bench_function("poll_pipeline", |b| {
+        b.iter(|| {
+            // 16s apart, so the averaging window fills and then stays a day long
+            i += 1;
+            let now = start + Duration::seconds(16 * i);
+            let mut data = parse_livedata(black_box(&payload));
+            averager.add_averages(&mut data, now);
+            add_derived_fields(&mut data, &derived);
+            sensor_watch.observe(&data, now);
+            status.record_poll(&precision.rounded(&data), now);
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    benchmark_parse_livedata,
+    benchmark_format_fields,
+    benchmark_poll_pipeline
+);
+criterion_main!(benches);
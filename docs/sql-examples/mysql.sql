@@ -8,7 +8,7 @@
 -- Create the wx_records table
 CREATE TABLE IF NOT EXISTS wx_records (
     id INT AUTO_INCREMENT PRIMARY KEY,
-    timestamp TIMESTAMP NOT NULL,
+    timestamp TIMESTAMP NOT NULL UNIQUE,
     intemp DOUBLE,
     outtemp DOUBLE,
     dewpoint DOUBLE,
@@ -36,7 +36,7 @@ CREATE TABLE IF NOT EXISTS wx_records (
 
 -- Optional: Create a user for wxlistener (replace 'your_password' with a secure password)
 -- CREATE USER 'wxlistener'@'localhost' IDENTIFIED BY 'your_password';
--- GRANT SELECT, INSERT ON weather.wx_records TO 'wxlistener'@'localhost';
+-- GRANT SELECT, INSERT, UPDATE ON weather.wx_records TO 'wxlistener'@'localhost';
 -- FLUSH PRIVILEGES;
 
 -- Verify the table was created
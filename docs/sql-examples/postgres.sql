@@ -8,7 +8,7 @@
 -- Create the wx_records table
 CREATE TABLE IF NOT EXISTS wx_records (
     id SERIAL PRIMARY KEY,
-    timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL UNIQUE,
     intemp DOUBLE PRECISION,
     outtemp DOUBLE PRECISION,
     dewpoint DOUBLE PRECISION,
@@ -39,7 +39,7 @@ CREATE INDEX IF NOT EXISTS idx_wx_records_timestamp ON wx_records(timestamp DESC
 -- Optional: Create a user for wxlistener (replace 'your_password' with a secure password)
 -- CREATE USER wxlistener WITH PASSWORD 'your_password';
 -- GRANT CONNECT ON DATABASE weather TO wxlistener;
--- GRANT SELECT, INSERT ON wx_records TO wxlistener;
+-- GRANT SELECT, INSERT, UPDATE ON wx_records TO wxlistener;
 -- GRANT USAGE, SELECT ON SEQUENCE wx_records_id_seq TO wxlistener;
 
 -- Verify the table was created
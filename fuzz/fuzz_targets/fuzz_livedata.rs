@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wxlistener::decoder::parse_livedata;
+
+fuzz_target!(|data: &[u8]| {
+    // Feed arbitrary live data payloads to the field parser
+    // Truncated or unknown fields must never panic
+    let _ = parse_livedata(data);
+});
@@ -24,5 +24,9 @@ fuzz_target!(|data: &[u8]| {
         if data.len() >= 5 {
             let _ = verify_response(&data, cmd);
         }
+
+        // Test payload extraction against corrupt size fields
+        let _ = response_payload(&data, false);
+        let _ = response_payload(&data, true);
     }
 });
@@ -0,0 +1,98 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, VecDeque};
+
+/// Air quality readings the console shows as 24-hour averages, with the name
+/// of their average field
+pub const AVERAGED_FIELDS: &[(&str, &str)] = &[
+    ("co2", "co2_24h"),
+    ("co2_pm25", "co2_pm25_24h"),
+    ("co2_pm10", "co2_pm10_24h"),
+    ("co2_pm1", "co2_pm1_24h"),
+    ("co2_pm4", "co2_pm4_24h"),
+];
+
+/// Length of the averaging window
+const WINDOW_HOURS: i64 = 24;
+
+/// Rolling 24-hour averages of the WH45/WH46 readings, kept in memory.
+///
+/// The gateway reports 24h averages for CO2, PM2.5 and PM10 but not for the
+/// WH46's PM1.0 and PM4.0. Averages the gateway provides are left untouched;
+/// missing ones are filled in as `<field>_24h` from the samples seen since
+/// startup.
+#[derive(Debug, Default)]
+pub struct AirQualityAverager {
+    samples: HashMap<&'static str, VecDeque<(DateTime<Utc>, f64)>>,
+}
+
+impl AirQualityAverager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record this poll's readings and add any missing `_24h` fields
+    pub fn add_averages(&mut self, data: &mut HashMap<String, f64>, now: DateTime<Utc>) {
+        let cutoff = now - Duration::hours(WINDOW_HOURS);
+
+        for &(field, average) in AVERAGED_FIELDS {
+            let Some(&value) = data.get(field) else {
+                continue;
+            };
+
+            let samples = self.samples.entry(field).or_default();
+            samples.push_back((now, value));
+            while samples.front().is_some_and(|(ts, _)| *ts <= cutoff) {
+                samples.pop_front();
+            }
+
+            if !data.contains_key(average) {
+                let mean = samples.iter().map(|(_, v)| v).sum::<f64>() / samples.len() as f64;
+                data.insert(average.to_string(), (mean * 10.0).round() / 10.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fills_missing_averages() {
+        let mut averager = AirQualityAverager::new();
+        let start = Utc::now();
+
+        for (i, pm1) in [4.0, 6.0, 8.0].iter().enumerate() {
+            let mut data = HashMap::new();
+            data.insert("co2_pm1".to_string(), *pm1);
+            averager.add_averages(&mut data, start + Duration::minutes(i as i64));
+            if i == 2 {
+                assert_eq!(data.get("co2_pm1_24h"), Some(&6.0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_keeps_gateway_averages() {
+        let mut averager = AirQualityAverager::new();
+        let mut data = HashMap::new();
+        data.insert("co2".to_string(), 900.0);
+        data.insert("co2_24h".to_string(), 612.0);
+
+        averager.add_averages(&mut data, Utc::now());
+        assert_eq!(data.get("co2_24h"), Some(&612.0));
+    }
+
+    #[test]
+    fn test_drops_samples_outside_window() {
+        let mut averager = AirQualityAverager::new();
+        let start = Utc::now();
+
+        let mut data = HashMap::from([("co2_pm4".to_string(), 20.0)]);
+        averager.add_averages(&mut data, start);
+
+        let mut data = HashMap::from([("co2_pm4".to_string(), 10.0)]);
+        averager.add_averages(&mut data, start + Duration::hours(25));
+        assert_eq!(data.get("co2_pm4_24h"), Some(&10.0));
+    }
+}
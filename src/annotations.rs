@@ -0,0 +1,164 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Longest annotation text accepted
+pub const MAX_TEXT_LEN: usize = 1000;
+
+/// A note on a stretch of the record, such as "sensor relocated" or
+/// "gauge cleaned", to explain the data around it later
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Annotation {
+    pub start: DateTime<Utc>,
+    /// Same as `start` for a single moment
+    pub end: DateTime<Utc>,
+    pub text: String,
+    pub tags: Vec<String>,
+}
+
+/// Body of POST /api/v1/annotations
+#[derive(Debug, Deserialize)]
+struct NewAnnotation {
+    /// Default: now
+    start: Option<DateTime<Utc>>,
+    /// Default: `start`
+    end: Option<DateTime<Utc>>,
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl Annotation {
+    /// Parse and check a JSON annotation, placing it at `now` when it has
+    /// no start
+    pub fn parse(body: &str, now: DateTime<Utc>) -> Result<Self> {
+        let new: NewAnnotation =
+            serde_json::from_str(body).map_err(|e| anyhow::anyhow!("Invalid annotation: {}", e))?;
+        let start = new.start.unwrap_or(now);
+        let annotation = Self {
+            start,
+            end: new.end.unwrap_or(start),
+            text: new.text.trim().to_string(),
+            tags: new.tags.iter().map(|tag| tag.trim().to_string()).collect(),
+        };
+        annotation.validate()?;
+        Ok(annotation)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.text.is_empty() {
+            anyhow::bail!("Annotation text is empty");
+        }
+        if self.text.chars().count() > MAX_TEXT_LEN {
+            anyhow::bail!("Annotation text is over {} characters", MAX_TEXT_LEN);
+        }
+        if self.end < self.start {
+            anyhow::bail!("Annotation ends before it starts");
+        }
+        // Tags are stored comma-separated
+        if let Some(tag) = self
+            .tags
+            .iter()
+            .find(|tag| tag.is_empty() || tag.contains(','))
+        {
+            anyhow::bail!(
+                "Invalid annotation tag '{}': tags can't be empty or hold commas",
+                tag
+            );
+        }
+        Ok(())
+    }
+
+    /// Tags as stored in the database
+    pub fn joined_tags(&self) -> String {
+        self.tags.join(",")
+    }
+
+    /// Tags read back from the database
+    pub fn split_tags(tags: &str) -> Vec<String> {
+        tags.split(',')
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Annotations in the shape Grafana's JSON data sources read: epoch
+/// milliseconds in `time` and `timeEnd`
+pub fn grafana_json(annotations: &[Annotation]) -> Value {
+    annotations
+        .iter()
+        .map(|annotation| {
+            json!({
+                "time": annotation.start.timestamp_millis(),
+                "timeEnd": annotation.end.timestamp_millis(),
+                "text": annotation.text,
+                "tags": annotation.tags,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_annotation() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+
+        let annotation = Annotation::parse(
+            r#"{"start":"2026-03-01T09:00:00Z","end":"2026-03-01T10:30:00Z",
+                "text":" Gauge cleaned ","tags":["maintenance"]}"#,
+            now,
+        )
+        .unwrap();
+        assert_eq!(annotation.text, "Gauge cleaned");
+        assert_eq!(
+            annotation.end - annotation.start,
+            chrono::TimeDelta::minutes(90)
+        );
+        assert_eq!(annotation.joined_tags(), "maintenance");
+
+        // A moment, now
+        let annotation = Annotation::parse(r#"{"text":"Sensor relocated"}"#, now).unwrap();
+        assert_eq!((annotation.start, annotation.end), (now, now));
+        assert!(annotation.tags.is_empty());
+
+        for bad in [
+            r#"{"text":"  "}"#,
+            r#"{"start":"2026-03-01T10:00:00Z","end":"2026-03-01T09:00:00Z","text":"x"}"#,
+            r#"{"text":"x","tags":["a,b"]}"#,
+            r#"{"text":"x","tags":[""]}"#,
+            r#"{"start":"yesterday","text":"x"}"#,
+            "gauge cleaned",
+        ] {
+            assert!(Annotation::parse(bad, now).is_err(), "{}", bad);
+        }
+        let long = format!(r#"{{"text":"{}"}}"#, "x".repeat(MAX_TEXT_LEN + 1));
+        assert!(Annotation::parse(&long, now).is_err());
+    }
+
+    #[test]
+    fn test_grafana_json() {
+        let start = Utc.with_ymd_and_hms(2026, 3, 1, 9, 0, 0).unwrap();
+        let annotation = Annotation {
+            start,
+            end: start + chrono::TimeDelta::hours(1),
+            text: "Gauge cleaned".to_string(),
+            tags: Annotation::split_tags("maintenance,rain"),
+        };
+        assert_eq!(
+            grafana_json(&[annotation]),
+            json!([{
+                "time": 1_772_355_600_000_i64,
+                "timeEnd": 1_772_359_200_000_i64,
+                "text": "Gauge cleaned",
+                "tags": ["maintenance", "rain"],
+            }])
+        );
+        assert!(Annotation::split_tags("").is_empty());
+    }
+}
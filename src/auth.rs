@@ -0,0 +1,255 @@
+/// API tokens with read-only and admin scopes for the web server
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::public::PublicConfig;
+
+/// What a token may access. Admin includes everything read-only allows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// Live data, summary and status
+    #[default]
+    Read,
+    /// Also the control endpoint: pause/resume outputs, poll, reload
+    Admin,
+}
+
+/// One `[[web.tokens]]` entry
+#[derive(Clone, Deserialize)]
+pub struct ApiToken {
+    pub token: String,
+    #[serde(default)]
+    pub scope: TokenScope,
+}
+
+// Keep tokens out of debug logs
+impl fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiToken")
+            .field("token", &"<redacted>")
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+/// The scope a group of routes requires
+#[derive(Debug, Clone)]
+pub struct RequireScope {
+    tokens: Arc<Vec<ApiToken>>,
+    scope: TokenScope,
+    /// Served to read requests without a token instead of rejecting them
+    public: Option<Arc<PublicConfig>>,
+}
+
+impl RequireScope {
+    pub fn new(tokens: Arc<Vec<ApiToken>>, scope: TokenScope) -> Self {
+        Self {
+            tokens,
+            scope,
+            public: None,
+        }
+    }
+
+    pub fn with_public(mut self, public: Option<Arc<PublicConfig>>) -> Self {
+        self.public = public;
+        self
+    }
+
+    /// Whether a request gets the public view: a read without a token, or
+    /// any read when no tokens are configured
+    fn is_public(&self, presented: Option<&str>) -> bool {
+        self.public.is_some()
+            && self.scope == TokenScope::Read
+            && (presented.is_none() || self.tokens.is_empty())
+    }
+
+    /// Check a presented token. Without configured tokens the read-only
+    /// endpoints stay open, as before tokens existed, and admin ones are off.
+    pub fn check(&self, presented: Option<&str>) -> Result<(), (StatusCode, &'static str)> {
+        if self.tokens.is_empty() {
+            return match self.scope {
+                TokenScope::Read => Ok(()),
+                TokenScope::Admin => Err((
+                    StatusCode::FORBIDDEN,
+                    "Control endpoints require an admin token in [web] tokens",
+                )),
+            };
+        }
+
+        let Some(presented) = presented else {
+            return Err((StatusCode::UNAUTHORIZED, "Missing API token"));
+        };
+        match self
+            .tokens
+            .iter()
+            .find(|token| constant_time_eq(token.token.as_bytes(), presented.as_bytes()))
+        {
+            None => Err((StatusCode::UNAUTHORIZED, "Invalid API token")),
+            Some(token) if token.scope < self.scope => Err((
+                StatusCode::FORBIDDEN,
+                "Token scope does not allow this endpoint",
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The token from `Authorization: Bearer <token>`, or from a `token` query
+/// parameter since browsers can't set headers on WebSocket requests
+fn presented_token(request: &Request) -> Option<&str> {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer.or_else(|| {
+        request
+            .uri()
+            .query()?
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+    })
+}
+
+/// Middleware rejecting requests without a token of the required scope.
+/// Requests let through for the public view carry the `PublicConfig` as an
+/// extension.
+pub async fn require_scope(
+    State(required): State<RequireScope>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(public) = required
+        .public
+        .clone()
+        .filter(|_| required.is_public(presented_token(&request)))
+    {
+        request.extensions_mut().insert(public);
+        return next.run(request).await;
+    }
+    match required.check(presented_token(&request)) {
+        Ok(()) => next.run(request).await,
+        Err((status, message)) => {
+            (status, Json(serde_json::json!({ "error": message }))).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens() -> Arc<Vec<ApiToken>> {
+        Arc::new(vec![
+            ApiToken {
+                token: "reader".to_string(),
+                scope: TokenScope::Read,
+            },
+            ApiToken {
+                token: "boss".to_string(),
+                scope: TokenScope::Admin,
+            },
+        ])
+    }
+
+    #[test]
+    fn test_scopes() {
+        let read = RequireScope::new(tokens(), TokenScope::Read);
+        let admin = RequireScope::new(tokens(), TokenScope::Admin);
+
+        assert!(read.check(Some("reader")).is_ok());
+        assert!(read.check(Some("boss")).is_ok());
+        assert!(admin.check(Some("boss")).is_ok());
+        assert_eq!(
+            admin.check(Some("reader")).unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            read.check(Some("guess")).unwrap_err().0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(read.check(None).unwrap_err().0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_no_tokens_configured() {
+        let none = Arc::new(Vec::new());
+        assert!(RequireScope::new(none.clone(), TokenScope::Read)
+            .check(None)
+            .is_ok());
+        assert_eq!(
+            RequireScope::new(none, TokenScope::Admin)
+                .check(Some("anything"))
+                .unwrap_err()
+                .0,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn test_public_view() {
+        let public = Some(Arc::new(PublicConfig::default()));
+        let read = RequireScope::new(tokens(), TokenScope::Read).with_public(public.clone());
+        assert!(read.is_public(None));
+        // A token gets the full view, or is rejected as usual
+        assert!(!read.is_public(Some("reader")));
+        assert!(!read.is_public(Some("guess")));
+        assert!(!RequireScope::new(tokens(), TokenScope::Admin)
+            .with_public(public.clone())
+            .is_public(None));
+        // Without tokens every read is unauthenticated
+        assert!(RequireScope::new(Arc::new(Vec::new()), TokenScope::Read)
+            .with_public(public)
+            .is_public(Some("anything")));
+    }
+
+    #[test]
+    fn test_token_scope_deserialization() {
+        let token: ApiToken = toml::from_str(r#"token = "abc""#).unwrap();
+        assert_eq!(token.scope, TokenScope::Read);
+        let token: ApiToken = toml::from_str(
+            r#"
+            token = "abc"
+            scope = "admin"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(token.scope, TokenScope::Admin);
+        assert!(!format!("{:?}", token).contains("abc"));
+    }
+
+    #[test]
+    fn test_presented_token() {
+        let request = Request::builder()
+            .uri("/api/v1/status.json")
+            .header(header::AUTHORIZATION, "Bearer abc")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(presented_token(&request), Some("abc"));
+
+        let request = Request::builder()
+            .uri("/ws?x=1&token=def")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(presented_token(&request), Some("def"));
+
+        let request = Request::builder()
+            .uri("/ws")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(presented_token(&request), None);
+    }
+}
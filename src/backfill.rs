@@ -0,0 +1,324 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::database::DatabaseWriter;
+use crate::provenance::Provenance;
+use crate::{dns, proxy};
+
+const DEFAULT_BASE_URL: &str = "https://api.ecowitt.net/api/v3";
+
+/// Maps (group, field) in the Ecowitt cloud history response to our field names
+const FIELD_MAP: &[(&str, &str, &str)] = &[
+    ("outdoor", "temperature", "outtemp"),
+    ("outdoor", "humidity", "outhumid"),
+    ("outdoor", "dew_point", "dewpoint"),
+    ("indoor", "temperature", "intemp"),
+    ("indoor", "humidity", "inhumid"),
+    ("pressure", "relative", "relbarometer"),
+    ("pressure", "absolute", "absbarometer"),
+    ("wind", "wind_speed", "wind_speed"),
+    ("wind", "wind_gust", "gust_speed"),
+    ("wind", "wind_direction", "wind_dir"),
+    ("solar_and_uvi", "uvi", "uvi"),
+    ("rainfall", "rain_rate", "rain_rate"),
+    ("rainfall", "event", "rain_event"),
+    ("rainfall", "daily", "rain_day"),
+    ("rainfall", "weekly", "rain_week"),
+    ("rainfall", "monthly", "rain_month"),
+    ("rainfall", "yearly", "rain_year"),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EcowittCloudConfig {
+    /// Application key from the Ecowitt user center
+    pub application_key: String,
+    /// API key from the Ecowitt user center
+    pub api_key: String,
+    /// Station MAC address (default: read from the device)
+    pub mac: Option<String>,
+    /// History resolution: 5min, 30min, 4hour or 1day (default: 5min)
+    #[serde(default = "default_cycle_type")]
+    pub cycle_type: String,
+    /// API base URL (default: https://api.ecowitt.net/api/v3)
+    pub base_url: Option<String>,
+}
+
+fn default_cycle_type() -> String {
+    "5min".to_string()
+}
+
+impl EcowittCloudConfig {
+    /// Length of one history sample
+    pub fn cycle_duration(&self) -> Duration {
+        match self.cycle_type.as_str() {
+            "30min" => Duration::minutes(30),
+            "4hour" => Duration::hours(4),
+            "1day" => Duration::days(1),
+            _ => Duration::minutes(5),
+        }
+    }
+}
+
+/// Parse a backfill bound given as `YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS` (UTC)
+pub fn parse_datetime(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(dt) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Ok(Utc.from_utc_datetime(&dt));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").context(format!(
+        "Invalid date '{}': expected YYYY-MM-DD or YYYY-MM-DD HH:MM:SS",
+        value
+    ))?;
+    Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default()))
+}
+
+/// Turn a history response into readings keyed by unix timestamp
+pub fn parse_history(response: &Value) -> Result<BTreeMap<i64, HashMap<String, f64>>> {
+    let code = response.get("code").and_then(Value::as_i64).unwrap_or(-1);
+    if code != 0 {
+        let msg = response
+            .get("msg")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error");
+        anyhow::bail!("Ecowitt cloud API error {}: {}", code, msg);
+    }
+
+    let mut readings: BTreeMap<i64, HashMap<String, f64>> = BTreeMap::new();
+    let Some(data) = response.get("data") else {
+        return Ok(readings);
+    };
+
+    for (group, field, name) in FIELD_MAP {
+        let Some(list) = data
+            .get(group)
+            .and_then(|g| g.get(field))
+            .and_then(|f| f.get("list"))
+            .and_then(Value::as_object)
+        else {
+            continue;
+        };
+
+        for (ts, value) in list {
+            let (Ok(ts), Some(value)) = (ts.parse::<i64>(), value_as_f64(value)) else {
+                continue;
+            };
+            readings
+                .entry(ts)
+                .or_default()
+                .insert(name.to_string(), value);
+        }
+    }
+
+    Ok(readings)
+}
+
+fn value_as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        Value::Number(n) => n.as_f64(),
+        _ => None,
+    }
+}
+
+/// Fetch history for a single window (the API limits each call to one day)
+async fn fetch_history(
+    client: &Client,
+    config: &EcowittCloudConfig,
+    mac: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<BTreeMap<i64, HashMap<String, f64>>> {
+    let base_url = config.base_url.as_deref().unwrap_or(DEFAULT_BASE_URL);
+    let url = format!("{}/device/history", base_url.trim_end_matches('/'));
+    let start_date = start.to_rfc3339();
+    let end_date = end.to_rfc3339();
+
+    let response: Value = client
+        .get(&url)
+        .query(&[
+            ("application_key", config.application_key.as_str()),
+            ("api_key", config.api_key.as_str()),
+            ("mac", mac),
+            ("start_date", start_date.as_str()),
+            ("end_date", end_date.as_str()),
+            ("cycle_type", config.cycle_type.as_str()),
+            (
+                "call_back",
+                "outdoor,indoor,pressure,wind,solar_and_uvi,rainfall",
+            ),
+            ("temp_unitid", "1"),
+            ("pressure_unitid", "3"),
+            ("wind_speed_unitid", "6"),
+            ("rainfall_unitid", "12"),
+        ])
+        .send()
+        .await
+        .inspect_err(dns::forget_failed)
+        .context("Failed to reach Ecowitt cloud API")?
+        .error_for_status()
+        .context("Ecowitt cloud API request failed")?
+        .json()
+        .await
+        .context("Failed to parse Ecowitt cloud API response")?;
+
+    parse_history(&response)
+}
+
+/// Whether a reading at `ts` is already covered by a stored record
+pub fn is_duplicate(ts: DateTime<Utc>, existing: &[DateTime<Utc>], window: Duration) -> bool {
+    existing
+        .iter()
+        .any(|stored| (*stored - ts).abs() < window / 2)
+}
+
+/// Pull history from the Ecowitt cloud and insert readings that are not
+/// already in the database. Returns (inserted, skipped).
+pub async fn run_backfill(
+    config: &EcowittCloudConfig,
+    mac: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    writer: &DatabaseWriter,
+) -> Result<(usize, usize)> {
+    if end <= start {
+        anyhow::bail!("Backfill end must be after start");
+    }
+
+    let client = proxy::client_builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .context("Failed to create HTTP client")?;
+    let window = config.cycle_duration();
+    let existing = writer.existing_timestamps(&start, &end).await?;
+    let provenance = Provenance::replay("ecowitt_cloud", Some(mac.to_string()));
+
+    let mut inserted = 0;
+    let mut skipped = 0;
+    let mut chunk_start = start;
+
+    while chunk_start < end {
+        let chunk_end = (chunk_start + Duration::days(1)).min(end);
+        println!(
+            "Fetching {} to {}...",
+            chunk_start.format("%Y-%m-%d %H:%M"),
+            chunk_end.format("%Y-%m-%d %H:%M")
+        );
+
+        let readings = fetch_history(&client, config, mac, chunk_start, chunk_end).await?;
+        let mut records = Vec::with_capacity(readings.len());
+        for (ts, data) in readings {
+            let Some(timestamp) = DateTime::from_timestamp(ts, 0) else {
+                continue;
+            };
+            if timestamp < start || timestamp >= end || is_duplicate(timestamp, &existing, window) {
+                skipped += 1;
+                continue;
+            }
+            records.push((timestamp, data, provenance.clone()));
+        }
+        writer.bulk_insert(&records).await?;
+        inserted += records.len();
+
+        chunk_start = chunk_end;
+    }
+
+    Ok((inserted, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_datetime() {
+        let dt = parse_datetime("2025-03-01").unwrap();
+        assert_eq!(dt.to_rfc3339(), "2025-03-01T00:00:00+00:00");
+
+        let dt = parse_datetime("2025-03-01 12:30:00").unwrap();
+        assert_eq!(dt.to_rfc3339(), "2025-03-01T12:30:00+00:00");
+
+        assert!(parse_datetime("03/01/2025").is_err());
+    }
+
+    #[test]
+    fn test_parse_history() {
+        let response = serde_json::json!({
+            "code": 0,
+            "msg": "success",
+            "data": {
+                "outdoor": {
+                    "temperature": {
+                        "unit": "℃",
+                        "list": { "1740787200": "12.5", "1740787500": "12.7" }
+                    },
+                    "humidity": {
+                        "unit": "%",
+                        "list": { "1740787200": "81" }
+                    }
+                },
+                "pressure": {
+                    "relative": {
+                        "unit": "hPa",
+                        "list": { "1740787200": "1012.3" }
+                    }
+                }
+            }
+        });
+
+        let readings = parse_history(&response).unwrap();
+        assert_eq!(readings.len(), 2);
+
+        let first = &readings[&1740787200];
+        assert_eq!(first.get("outtemp"), Some(&12.5));
+        assert_eq!(first.get("outhumid"), Some(&81.0));
+        assert_eq!(first.get("relbarometer"), Some(&1012.3));
+
+        let second = &readings[&1740787500];
+        assert_eq!(second.get("outtemp"), Some(&12.7));
+        assert!(!second.contains_key("outhumid"));
+    }
+
+    #[test]
+    fn test_parse_history_api_error() {
+        let response = serde_json::json!({
+            "code": 40010,
+            "msg": "Illegal Application_Key Parameter"
+        });
+
+        let result = parse_history(&response);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("40010"));
+    }
+
+    #[test]
+    fn test_is_duplicate() {
+        let base = parse_datetime("2025-03-01 12:00:00").unwrap();
+        let existing = vec![base + Duration::seconds(90)];
+        let window = Duration::minutes(5);
+
+        assert!(is_duplicate(base, &existing, window));
+        assert!(!is_duplicate(
+            base + Duration::minutes(5),
+            &existing,
+            window
+        ));
+    }
+
+    #[test]
+    fn test_cycle_duration() {
+        let mut config = EcowittCloudConfig {
+            application_key: "app".to_string(),
+            api_key: "key".to_string(),
+            mac: None,
+            cycle_type: default_cycle_type(),
+            base_url: None,
+        };
+        assert_eq!(config.cycle_duration(), Duration::minutes(5));
+
+        config.cycle_type = "30min".to_string();
+        assert_eq!(config.cycle_duration(), Duration::minutes(30));
+    }
+}
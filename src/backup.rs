@@ -0,0 +1,267 @@
+/// Backup archives for moving an install to another machine: a gzipped tar
+/// of a manifest, the config file, the state file and optionally the weather
+/// records
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "db")]
+use crate::database::DatabaseWriter;
+#[cfg(feature = "db")]
+use crate::provenance::Provenance;
+#[cfg(feature = "db")]
+use std::collections::HashMap;
+
+const MANIFEST: &str = "manifest.json";
+const CONFIG: &str = "wxlistener.toml";
+const STATE: &str = "state.json";
+const RECORDS: &str = "records.jsonl";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// wxlistener version that made the backup
+    pub version: String,
+    pub created_at: DateTime<Utc>,
+    /// Where the config file was on the machine that made the backup
+    pub config_path: PathBuf,
+    /// Where the state file was, when it was included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_file: Option<PathBuf>,
+    /// Number of weather records, when they were included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub records: Option<usize>,
+}
+
+/// The contents of a backup. Records can run to gigabytes, so they're
+/// extracted to a file instead of held in memory.
+#[derive(Debug)]
+pub struct Extracted {
+    pub manifest: Manifest,
+    pub config: Vec<u8>,
+    pub state: Option<Vec<u8>>,
+    pub records: Option<PathBuf>,
+}
+
+/// One line of records.jsonl
+#[cfg(feature = "db")]
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    timestamp: DateTime<Utc>,
+    data: HashMap<String, f64>,
+}
+
+/// Create a file only the owner can read; backups hold credentials
+fn create_private(path: &Path) -> Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .context(format!("Failed to create {:?}", path))
+}
+
+fn append(archive: &mut tar::Builder<impl Write>, name: &str, contents: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    archive
+        .append_data(&mut header, name, contents)
+        .context("Failed to write backup")
+}
+
+/// Write a backup of `config_path`, of `state_file` if it exists, and of
+/// `records` (a file from `export_records` and its record count)
+pub fn create(
+    output: &Path,
+    config_path: &Path,
+    state_file: Option<&Path>,
+    records: Option<(&Path, usize)>,
+) -> Result<Manifest> {
+    let state_file = state_file.filter(|path| path.exists());
+    let manifest = Manifest {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now(),
+        config_path: fs::canonicalize(config_path).unwrap_or_else(|_| config_path.to_path_buf()),
+        state_file: state_file.map(Path::to_path_buf),
+        records: records.map(|(_, count)| count),
+    };
+
+    let file = create_private(output)?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    append(
+        &mut archive,
+        MANIFEST,
+        &serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    let config =
+        fs::read(config_path).context(format!("Failed to read config file: {:?}", config_path))?;
+    append(&mut archive, CONFIG, &config)?;
+    if let Some(path) = state_file {
+        let state = fs::read(path).context(format!("Failed to read state file: {:?}", path))?;
+        append(&mut archive, STATE, &state)?;
+    }
+    // Records go last so everything else is restored before the long part
+    if let Some((path, _)) = records {
+        archive
+            .append_path_with_name(path, RECORDS)
+            .context("Failed to write records to backup")?;
+    }
+    archive
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .context(format!("Failed to write backup: {:?}", output))?;
+
+    Ok(manifest)
+}
+
+/// Read a backup, extracting any records to `records_to`
+pub fn extract(archive: &Path, records_to: &Path) -> Result<Extracted> {
+    let file = File::open(archive).context(format!("Failed to open backup: {:?}", archive))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+
+    let mut manifest = None;
+    let mut config = None;
+    let mut state = None;
+    let mut records = None;
+    for entry in archive.entries().context("Failed to read backup")? {
+        let mut entry = entry.context("Failed to read backup")?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut contents = || -> Result<Vec<u8>> {
+            let mut contents = Vec::new();
+            entry
+                .read_to_end(&mut contents)
+                .context(format!("Failed to read {} from backup", name))?;
+            Ok(contents)
+        };
+        match name.as_str() {
+            MANIFEST => {
+                manifest =
+                    Some(serde_json::from_slice(&contents()?).context("Invalid backup manifest")?)
+            }
+            CONFIG => config = Some(contents()?),
+            STATE => state = Some(contents()?),
+            RECORDS => {
+                entry
+                    .unpack(records_to)
+                    .context(format!("Failed to extract records to {:?}", records_to))?;
+                records = Some(records_to.to_path_buf());
+            }
+            // Files added by newer releases
+            _ => {}
+        }
+    }
+
+    Ok(Extracted {
+        manifest: manifest.context("Not a wxlistener backup: no manifest.json")?,
+        config: config.context("Backup has no config file")?,
+        state,
+        records,
+    })
+}
+
+/// Write every stored record to `path`, one JSON object per line. Returns
+/// the number of records.
+#[cfg(feature = "db")]
+pub async fn export_records(writer: &DatabaseWriter, path: &Path) -> Result<usize> {
+    let mut out = std::io::BufWriter::new(create_private(path)?);
+    let count = writer
+        .for_each_record(|timestamp, data| {
+            serde_json::to_writer(&mut out, &Record { timestamp, data })?;
+            out.write_all(b"\n")?;
+            Ok(())
+        })
+        .await?;
+    out.flush()
+        .context(format!("Failed to write records: {:?}", path))?;
+    Ok(count)
+}
+
+/// Insert the records extracted from a backup. Existing rows with the same
+/// timestamp are replaced. Returns the number of records.
+#[cfg(feature = "db")]
+pub async fn import_records(path: &Path, writer: &DatabaseWriter) -> Result<usize> {
+    use std::io::BufRead;
+
+    let file = File::open(path).context(format!("Failed to open records: {:?}", path))?;
+    let provenance = Provenance::replay("backup", None);
+    let mut count = 0;
+    for line in std::io::BufReader::new(file).lines() {
+        let record: Record = serde_json::from_str(&line?).context("Invalid record in backup")?;
+        writer
+            .insert_data(&record.data, &record.timestamp, &provenance)
+            .await?;
+        count += 1;
+        if count % 10_000 == 0 {
+            println!("  ... {} records restored", count);
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_create_and_extract() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().join("wxlistener.toml");
+        let state = dir.path().join("state.json");
+        let records = dir.path().join("records.jsonl");
+        fs::write(&config, "ip = \"192.168.1.100\"\n").unwrap();
+        fs::write(&state, "{}").unwrap();
+        fs::write(
+            &records,
+            "{\"timestamp\":\"2026-03-01T12:00:00Z\",\"data\":{}}\n",
+        )
+        .unwrap();
+
+        let output = dir.path().join("backup.tar.gz");
+        let manifest = create(&output, &config, Some(&state), Some((&records, 1))).unwrap();
+        assert_eq!(manifest.records, Some(1));
+
+        let extracted = extract(&output, &dir.path().join("restored.jsonl")).unwrap();
+        assert_eq!(extracted.manifest, manifest);
+        assert_eq!(extracted.config, b"ip = \"192.168.1.100\"\n");
+        assert_eq!(extracted.state.as_deref(), Some(&b"{}"[..]));
+        assert_eq!(
+            fs::read(extracted.records.unwrap()).unwrap(),
+            fs::read(&records).unwrap()
+        );
+
+        // A missing state file is left out
+        let output = dir.path().join("config-only.tar.gz");
+        let manifest = create(&output, &config, Some(&dir.path().join("none")), None).unwrap();
+        assert_eq!(manifest.state_file, None);
+        let extracted = extract(&output, &dir.path().join("unused.jsonl")).unwrap();
+        assert!(extracted.state.is_none() && extracted.records.is_none());
+    }
+
+    #[test]
+    fn test_extract_rejects_other_archives() {
+        let dir = TempDir::new().unwrap();
+        let output = dir.path().join("other.tar.gz");
+        let mut archive = tar::Builder::new(GzEncoder::new(
+            File::create(&output).unwrap(),
+            Compression::default(),
+        ));
+        append(&mut archive, "notes.txt", b"hello").unwrap();
+        archive.into_inner().unwrap().finish().unwrap();
+
+        let error = extract(&output, &dir.path().join("records.jsonl")).unwrap_err();
+        assert!(error.to_string().contains("manifest"));
+    }
+}
@@ -0,0 +1,216 @@
+/// Batching of database inserts and HTTP posts: readings are held back and
+/// written in one transaction or request once enough rows have gathered or
+/// the oldest has waited long enough, whichever comes first
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+#[cfg(feature = "db")]
+use std::collections::{HashMap, VecDeque};
+
+#[cfg(feature = "db")]
+use crate::database::{DatabaseConfig, DatabaseWriter};
+#[cfg(feature = "db")]
+use crate::provenance::Provenance;
+
+/// When a batch is due
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchPolicy {
+    max_rows: Option<usize>,
+    max_age: Option<Duration>,
+}
+
+impl BatchPolicy {
+    /// The policy `batch_rows` and `batch_interval` of `[section]` set, None
+    /// when neither is set and every reading is sent right away
+    pub fn new(section: &str, rows: Option<usize>, interval: Option<u64>) -> Result<Option<Self>> {
+        if rows == Some(0) || interval == Some(0) {
+            anyhow::bail!(
+                "[{}] batch_rows and batch_interval must be at least 1",
+                section
+            );
+        }
+        if rows.is_none() && interval.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            max_rows: rows,
+            max_age: interval.map(|secs| Duration::seconds(secs as i64)),
+        }))
+    }
+
+    /// The policy of `[database]`
+    #[cfg(feature = "db")]
+    pub fn from_config(config: &DatabaseConfig) -> Result<Option<Self>> {
+        if config.batch_rows.is_some_and(|rows| rows > MAX_PENDING) {
+            anyhow::bail!("[database] batch_rows must be at most {}", MAX_PENDING);
+        }
+        Self::new("database", config.batch_rows, config.batch_interval)
+    }
+
+    /// Whether `rows` readings, the oldest taken at `oldest`, are due
+    pub fn is_due(&self, rows: usize, oldest: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        self.max_rows.is_some_and(|max| rows >= max)
+            || self.max_age.is_some_and(|max| now - oldest >= max)
+    }
+}
+
+#[cfg(feature = "db")]
+#[derive(Debug, Clone)]
+struct Pending {
+    timestamp: DateTime<Utc>,
+    data: HashMap<String, f64>,
+    provenance: Provenance,
+}
+
+/// Readings a batch holds at most while the database is down, like the HTTP
+/// output's default `max_queue`; the oldest are dropped beyond this
+#[cfg(feature = "db")]
+pub const MAX_PENDING: usize = 10_000;
+
+/// Readings waiting to be written in the next transaction
+#[cfg(feature = "db")]
+#[derive(Debug)]
+pub struct InsertBatch {
+    policy: BatchPolicy,
+    pending: VecDeque<Pending>,
+    /// Readings dropped since the last successful flush
+    dropped: usize,
+}
+
+#[cfg(feature = "db")]
+impl InsertBatch {
+    pub fn new(policy: BatchPolicy) -> Self {
+        Self {
+            policy,
+            pending: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Add a reading, dropping the oldest once `MAX_PENDING` are held.
+    /// Returns true for the first drop since the last successful flush.
+    pub fn push(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        data: &HashMap<String, f64>,
+        provenance: &Provenance,
+    ) -> bool {
+        let dropping = self.pending.len() >= MAX_PENDING;
+        if dropping {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+        self.pending.push_back(Pending {
+            timestamp,
+            data: data.clone(),
+            provenance: provenance.clone(),
+        });
+        dropping && self.dropped == 1
+    }
+
+    /// Readings waiting for the next flush
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.pending.front().is_some_and(|oldest| {
+            self.policy
+                .is_due(self.pending.len(), oldest.timestamp, now)
+        })
+    }
+
+    /// Write every pending reading in one transaction. They stay pending
+    /// if it fails. Returns the number written.
+    pub async fn flush(&mut self, writer: &DatabaseWriter) -> Result<usize> {
+        if self.is_empty() {
+            return Ok(0);
+        }
+        writer
+            .insert_batch(
+                self.pending
+                    .iter()
+                    .map(|p| (&p.timestamp, &p.data, &p.provenance)),
+            )
+            .await?;
+        self.dropped = 0;
+        Ok(std::mem::take(&mut self.pending).len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[cfg(feature = "db")]
+    fn config(toml: &str) -> DatabaseConfig {
+        toml::from_str(&format!("connection_string = \"postgres://x\"\n{}", toml)).unwrap()
+    }
+
+    #[test]
+    fn test_batch_policy() {
+        assert_eq!(BatchPolicy::new("http", None, None).unwrap(), None);
+        assert!(BatchPolicy::new("http", Some(0), None).is_err());
+
+        let policy = BatchPolicy::new("http", Some(10), Some(5))
+            .unwrap()
+            .unwrap();
+        let oldest = Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        assert!(!policy.is_due(3, oldest, oldest + Duration::seconds(4)));
+        // Whichever limit is reached first
+        assert!(policy.is_due(3, oldest, oldest + Duration::seconds(5)));
+        assert!(policy.is_due(10, oldest, oldest + Duration::seconds(1)));
+
+        let policy = BatchPolicy::new("http", Some(10), None).unwrap().unwrap();
+        assert!(!policy.is_due(9, oldest, oldest + Duration::days(1)));
+    }
+
+    #[cfg(feature = "db")]
+    #[test]
+    fn test_insert_batch_is_due() {
+        let policy = BatchPolicy::from_config(&config("batch_rows = 2"))
+            .unwrap()
+            .unwrap();
+        let mut batch = InsertBatch::new(policy);
+        let now = Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        assert!(!batch.is_due(now));
+
+        let provenance = Provenance::poll(None, None, 0);
+        let data = HashMap::from([("outtemp".to_string(), 12.5)]);
+        batch.push(now, &data, &provenance);
+        assert!(!batch.is_due(now));
+        batch.push(now, &data, &provenance);
+        assert!(batch.is_due(now));
+        assert_eq!(batch.len(), 2);
+    }
+
+    #[cfg(feature = "db")]
+    #[test]
+    fn test_insert_batch_drops_oldest() {
+        let policy = BatchPolicy::from_config(&config("batch_rows = 60"))
+            .unwrap()
+            .unwrap();
+        assert!(BatchPolicy::from_config(&config("batch_rows = 10001")).is_err());
+        let mut batch = InsertBatch::new(policy);
+        let start = Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        let provenance = Provenance::poll(None, None, 0);
+        let data = HashMap::from([("outtemp".to_string(), 12.5)]);
+        for i in 0..MAX_PENDING {
+            assert!(!batch.push(start + Duration::seconds(i as i64), &data, &provenance));
+        }
+
+        // Only the first drop is reported
+        let later = start + Duration::seconds(MAX_PENDING as i64);
+        assert!(batch.push(later, &data, &provenance));
+        assert!(!batch.push(later + Duration::seconds(1), &data, &provenance));
+        assert_eq!(batch.len(), MAX_PENDING);
+        assert_eq!(
+            batch.pending.front().unwrap().timestamp,
+            start + Duration::seconds(2)
+        );
+    }
+}
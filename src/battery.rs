@@ -0,0 +1,372 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::sensors::SensorSignal;
+
+/// At most one stored sample per sensor in this many hours, so the state
+/// file stays small
+const SAMPLE_HOURS: i64 = 6;
+/// Samples older than this are dropped
+const HISTORY_DAYS: i64 = 60;
+/// Level reported by mains-powered WH41/WH45/WH55/WH57 sensors
+const LEVEL_DC: u8 = 6;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatteryConfig {
+    /// Warn when a battery is predicted empty within this many weeks
+    /// (default: 4)
+    #[serde(default = "default_warn_weeks")]
+    pub warn_weeks: f64,
+    /// Days of history needed before predicting (default: 7)
+    #[serde(default = "default_min_history_days")]
+    pub min_history_days: u64,
+    /// Voltage at which a sensor's battery counts as empty, overriding the
+    /// 1.2 V default, e.g. `{ ws90 = 2.4 }`
+    #[serde(default)]
+    pub empty_voltage: HashMap<String, f64>,
+}
+
+fn default_warn_weeks() -> f64 {
+    4.0
+}
+
+fn default_min_history_days() -> u64 {
+    7
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            warn_weeks: default_warn_weeks(),
+            min_history_days: default_min_history_days(),
+            empty_voltage: HashMap::new(),
+        }
+    }
+}
+
+/// How a sensor reports its battery in the sensor-ID response
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scale {
+    /// 0 normal, 1 low; nothing to trend
+    Flag,
+    /// Voltage in steps of this many volts
+    Volts(f64),
+    /// Level 0-5, empty at 1
+    Level,
+}
+
+fn scale(sensor: &str) -> Scale {
+    let model = sensor.split('_').next().unwrap_or(sensor);
+    match model {
+        "wh40" | "wh51" => Scale::Volts(0.1),
+        "wh68" | "ws80" | "wh34" | "wh35" | "ws90" => Scale::Volts(0.02),
+        "wh41" | "wh45" | "wh55" | "wh57" => Scale::Level,
+        _ => Scale::Flag,
+    }
+}
+
+/// A decoded battery reading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatteryLevel {
+    pub value: f64,
+    pub unit: BatteryUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BatteryUnit {
+    #[serde(rename = "V")]
+    Volts,
+    /// Level 0-5
+    #[serde(rename = "level")]
+    Level,
+}
+
+impl BatteryUnit {
+    /// A level for display, e.g. "1.38 V" or "level 4 of 5"
+    pub fn format(self, level: f64) -> String {
+        match self {
+            Self::Volts => format!("{:.2} V", level),
+            Self::Level => format!("level {:.0} of 5", level),
+        }
+    }
+}
+
+/// Decode the battery byte of a sensor that reports a voltage or level.
+/// None for sensors with only a low-battery flag, and mains-powered ones.
+pub fn decode(sensor: &str, raw: u8) -> Option<BatteryLevel> {
+    match scale(sensor) {
+        Scale::Flag => None,
+        Scale::Volts(step) => Some(BatteryLevel {
+            value: raw as f64 * step,
+            unit: BatteryUnit::Volts,
+        }),
+        Scale::Level if raw < LEVEL_DC => Some(BatteryLevel {
+            value: raw as f64,
+            unit: BatteryUnit::Level,
+        }),
+        Scale::Level => None,
+    }
+}
+
+/// One stored battery reading
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BatterySample {
+    pub at: DateTime<Utc>,
+    pub value: f64,
+}
+
+/// Battery history persisted in the state file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatteryState {
+    pub history: BTreeMap<String, Vec<BatterySample>>,
+    /// Sensors already warned about since their battery was last replaced
+    #[serde(default)]
+    pub warned: BTreeSet<String>,
+}
+
+/// Current level and predicted time left for one sensor, as shown in
+/// status.json
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatteryForecast {
+    pub sensor: String,
+    pub level: f64,
+    pub unit: BatteryUnit,
+    /// Predicted weeks until empty; absent while there's too little history
+    /// or the level isn't falling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weeks_to_empty: Option<f64>,
+}
+
+/// A battery predicted to run out within `warn_weeks`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryWarning {
+    pub sensor: String,
+    pub level: f64,
+    pub unit: BatteryUnit,
+    pub weeks_to_empty: f64,
+}
+
+/// Keeps a history of each sensor's battery and fits a line through it to
+/// predict when it runs out
+#[derive(Debug)]
+pub struct BatteryTracker {
+    config: BatteryConfig,
+    history: BTreeMap<String, Vec<BatterySample>>,
+    warned: BTreeSet<String>,
+    forecasts: Vec<BatteryForecast>,
+}
+
+impl BatteryTracker {
+    pub fn new(config: BatteryConfig) -> Self {
+        Self {
+            config,
+            history: BTreeMap::new(),
+            warned: BTreeSet::new(),
+            forecasts: Vec::new(),
+        }
+    }
+
+    /// Record the batteries from one sensor-ID response and return a warning
+    /// for each battery newly predicted to run out within `warn_weeks`
+    pub fn observe(&mut self, signals: &[SensorSignal], now: DateTime<Utc>) -> Vec<BatteryWarning> {
+        let mut warnings = Vec::new();
+        self.forecasts.clear();
+
+        for signal in signals {
+            let Some(level) = decode(&signal.sensor, signal.battery) else {
+                continue;
+            };
+            let history = self.history.entry(signal.sensor.clone()).or_default();
+
+            // A clear rise means a fresh battery: start a new curve
+            let rise = match level.unit {
+                BatteryUnit::Volts => 0.2,
+                BatteryUnit::Level => 2.0,
+            };
+            let highest = history.iter().map(|s| s.value).fold(f64::MIN, f64::max);
+            if level.value > highest + rise {
+                history.clear();
+                self.warned.remove(&signal.sensor);
+            }
+
+            let due = history
+                .last()
+                .is_none_or(|last| now - last.at >= Duration::hours(SAMPLE_HOURS));
+            if due {
+                history.push(BatterySample {
+                    at: now,
+                    value: level.value,
+                });
+                history.retain(|s| now - s.at <= Duration::days(HISTORY_DAYS));
+            }
+
+            let empty = match level.unit {
+                BatteryUnit::Volts => self
+                    .config
+                    .empty_voltage
+                    .get(&signal.sensor)
+                    .copied()
+                    .unwrap_or(1.2),
+                BatteryUnit::Level => 1.0,
+            };
+            let min_history = Duration::days(self.config.min_history_days as i64);
+            let weeks_to_empty = weeks_to_empty(history, empty, min_history);
+
+            if let Some(weeks) = weeks_to_empty.filter(|w| *w <= self.config.warn_weeks) {
+                if self.warned.insert(signal.sensor.clone()) {
+                    warnings.push(BatteryWarning {
+                        sensor: signal.sensor.clone(),
+                        level: level.value,
+                        unit: level.unit,
+                        weeks_to_empty: weeks,
+                    });
+                }
+            }
+            self.forecasts.push(BatteryForecast {
+                sensor: signal.sensor.clone(),
+                level: level.value,
+                unit: level.unit,
+                weeks_to_empty,
+            });
+        }
+
+        warnings
+    }
+
+    /// Forecasts from the last `observe`
+    pub fn forecasts(&self) -> Vec<BatteryForecast> {
+        self.forecasts.clone()
+    }
+
+    pub fn state(&self) -> BatteryState {
+        BatteryState {
+            history: self.history.clone(),
+            warned: self.warned.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, state: BatteryState) {
+        self.history = state.history;
+        self.warned = state.warned;
+    }
+}
+
+/// Least-squares fit of the samples; weeks until the fitted line reaches
+/// `empty`, or None with too little history or a level that isn't falling
+fn weeks_to_empty(samples: &[BatterySample], empty: f64, min_history: Duration) -> Option<f64> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    if last.at - first.at < min_history {
+        return None;
+    }
+
+    let days: Vec<f64> = samples
+        .iter()
+        .map(|s| (s.at - first.at).num_seconds() as f64 / 86_400.0)
+        .collect();
+    let n = samples.len() as f64;
+    let mean_x = days.iter().sum::<f64>() / n;
+    let mean_y = samples.iter().map(|s| s.value).sum::<f64>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (x, sample) in days.iter().zip(samples) {
+        covariance += (x - mean_x) * (sample.value - mean_y);
+        variance += (x - mean_x).powi(2);
+    }
+    let slope = covariance / variance;
+    if !slope.is_finite() || slope >= 0.0 {
+        return None;
+    }
+
+    let fitted_now = mean_y + slope * (days.last()? - mean_x);
+    let days_left = ((fitted_now - empty) / -slope).max(0.0);
+    Some(days_left / 7.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn signal(sensor: &str, battery: u8) -> SensorSignal {
+        SensorSignal {
+            sensor: sensor.to_string(),
+            id: 1,
+            battery,
+            signal: 4,
+            voltage: None,
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let wh51 = decode("wh51_ch1", 14).unwrap();
+        assert!((wh51.value - 1.4).abs() < 1e-9);
+        assert_eq!(wh51.unit, BatteryUnit::Volts);
+        let ws90 = decode("ws90", 160).unwrap();
+        assert!((ws90.value - 3.2).abs() < 1e-9);
+        assert_eq!(decode("wh57", 4).unwrap().unit, BatteryUnit::Level);
+        // Mains powered
+        assert_eq!(decode("wh45", LEVEL_DC), None);
+        // Low-battery flag only
+        assert_eq!(decode("wh31_ch2", 1), None);
+        assert_eq!(decode("wh65", 0), None);
+    }
+
+    #[test]
+    fn test_battery_warning() {
+        let mut tracker = BatteryTracker::new(BatteryConfig::default());
+        let start = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+
+        // 1.50 V falling 0.1 V a week: 1.2 V is two weeks away after a week
+        let mut warnings = Vec::new();
+        for hour in (0..=7 * 24).step_by(6) {
+            let volts = 1.5 - 0.1 * hour as f64 / (7.0 * 24.0);
+            let raw = (volts / 0.02).round() as u8;
+            warnings
+                .extend(tracker.observe(&[signal("wh34_ch1", raw)], start + Duration::hours(hour)));
+        }
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].sensor, "wh34_ch1");
+        assert!(
+            (warnings[0].weeks_to_empty - 2.0).abs() < 0.3,
+            "{:?}",
+            warnings
+        );
+        let forecast = &tracker.forecasts()[0];
+        assert_eq!(forecast.unit, BatteryUnit::Volts);
+        assert!(forecast.weeks_to_empty.is_some());
+
+        // Warned once per battery
+        let later = start + Duration::days(8);
+        assert!(tracker.observe(&[signal("wh34_ch1", 70)], later).is_empty());
+
+        // A fresh battery starts a new curve
+        tracker.observe(&[signal("wh34_ch1", 80 + 10)], later + Duration::hours(6));
+        assert_eq!(tracker.state().history["wh34_ch1"].len(), 1);
+        assert!(tracker.state().warned.is_empty());
+        assert_eq!(tracker.forecasts()[0].weeks_to_empty, None);
+    }
+
+    #[test]
+    fn test_steady_battery_not_predicted() {
+        let mut tracker = BatteryTracker::new(BatteryConfig::default());
+        let start = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        for day in 0..10 {
+            let warnings = tracker.observe(&[signal("wh40", 15)], start + Duration::days(day));
+            assert!(warnings.is_empty());
+        }
+        assert_eq!(tracker.forecasts()[0].weeks_to_empty, None);
+        // One sample per six hours at most
+        tracker.observe(&[signal("wh40", 15)], start + Duration::hours(9 * 24 + 1));
+        assert_eq!(tracker.state().history["wh40"].len(), 10);
+    }
+
+    #[test]
+    fn test_battery_config_deserialization() {
+        let config: BatteryConfig =
+            toml::from_str("warn_weeks = 2\nempty_voltage = { ws90 = 2.4 }").unwrap();
+        assert_eq!(config.warn_weeks, 2.0);
+        assert_eq!(config.min_history_days, 7);
+        assert_eq!(config.empty_voltage["ws90"], 2.4);
+    }
+}
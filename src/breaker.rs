@@ -0,0 +1,179 @@
+/// Circuit breakers for outputs. An output that fails several polls in a row
+/// is left alone for a cool-down period instead of being tried, and timing
+/// out, on every poll; after the cool-down one poll tries it again.
+use anyhow::Result;
+use serde::Deserialize;
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BreakerConfig {
+    /// Failures in a row that open the breaker (default: 3)
+    #[serde(default = "default_failures")]
+    pub failures: u32,
+    /// Seconds an open breaker skips the output (default: 60)
+    #[serde(default = "default_cooldown")]
+    pub cooldown: u64,
+}
+
+fn default_failures() -> u32 {
+    3
+}
+
+fn default_cooldown() -> u64 {
+    60
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failures: default_failures(),
+            cooldown: default_cooldown(),
+        }
+    }
+}
+
+impl BreakerConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.failures == 0 {
+            anyhow::bail!("[circuit_breaker] failures must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+/// Failures of one output and, once they reach the threshold, when it is
+/// tried again
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    name: &'static str,
+    threshold: u32,
+    cooldown: Duration,
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str, threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            name,
+            threshold: threshold.max(1),
+            cooldown,
+            failures: 0,
+            open_until: None,
+        }
+    }
+
+    pub fn from_config(name: &'static str, config: &BreakerConfig) -> Self {
+        Self::new(name, config.failures, Duration::from_secs(config.cooldown))
+    }
+
+    /// Whether to try the output this poll: while closed, and once the
+    /// cool-down of an open breaker has passed
+    pub fn allows(&self) -> bool {
+        self.open_until
+            .is_none_or(|open_until| Instant::now() >= open_until)
+    }
+
+    /// Whether the output failed often enough to be skipped for a while
+    pub fn is_open(&self) -> bool {
+        self.open_until.is_some()
+    }
+
+    /// How long until the output may be tried again, zero if it may now
+    pub fn retry_in(&self) -> Duration {
+        self.open_until.map_or(Duration::ZERO, |open_until| {
+            open_until.saturating_duration_since(Instant::now())
+        })
+    }
+
+    /// Count the outcome of trying the output. Opening the breaker and
+    /// closing it again are each logged once; a failed try after a
+    /// cool-down just starts another one.
+    pub fn record<T, E: Display>(&mut self, result: &Result<T, E>) {
+        match result {
+            Ok(_) => {
+                if self.is_open() {
+                    println!(
+                        "[OK] {} recovered after {} failures in a row",
+                        self.name, self.failures
+                    );
+                }
+                self.failures = 0;
+                self.open_until = None;
+            }
+            Err(e) => {
+                self.failures += 1;
+                if self.failures < self.threshold {
+                    return;
+                }
+                if !self.is_open() {
+                    eprintln!(
+                        "[WARN] {} failed {} times in a row, skipping it for {}s: {:#}",
+                        self.name,
+                        self.failures,
+                        self.cooldown.as_secs(),
+                        e
+                    );
+                }
+                self.open_until = Some(Instant::now() + self.cooldown);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fail(breaker: &mut CircuitBreaker) {
+        breaker.record::<(), _>(&Err("connection refused"));
+    }
+
+    #[test]
+    fn test_breaker_opens_and_closes() {
+        let mut breaker = CircuitBreaker::new("MQTT", 3, Duration::from_secs(60));
+        fail(&mut breaker);
+        fail(&mut breaker);
+        assert!(breaker.allows());
+        assert!(!breaker.is_open());
+
+        fail(&mut breaker);
+        assert!(breaker.is_open());
+        assert!(!breaker.allows());
+
+        // A success closes it and starts the count over
+        breaker.record::<_, &str>(&Ok(()));
+        assert!(!breaker.is_open());
+        assert!(breaker.allows());
+        fail(&mut breaker);
+        assert!(breaker.allows());
+    }
+
+    #[test]
+    fn test_breaker_cooldown() {
+        let mut breaker = CircuitBreaker::new("database", 1, Duration::from_millis(20));
+        fail(&mut breaker);
+        assert!(!breaker.allows());
+        assert!(breaker.retry_in() > Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allows());
+        assert_eq!(breaker.retry_in(), Duration::ZERO);
+
+        // A failed try after the cool-down keeps it open for another one
+        fail(&mut breaker);
+        assert!(breaker.is_open());
+        assert!(!breaker.allows());
+    }
+
+    #[test]
+    fn test_breaker_config() {
+        let config: BreakerConfig = toml::from_str("cooldown = 300").unwrap();
+        assert_eq!(config.failures, 3);
+        assert_eq!(config.cooldown, 300);
+        assert!(config.validate().is_ok());
+
+        let config: BreakerConfig = toml::from_str("failures = 0").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
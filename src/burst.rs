@@ -0,0 +1,233 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+
+/// Adaptive polling: poll faster while the weather changes quickly, then
+/// ease back to the normal interval
+#[derive(Debug, Clone, Deserialize)]
+pub struct BurstConfig {
+    /// Seconds between polls during a burst (default: 5)
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// Seconds a burst lasts after the last rapid change (default: 600)
+    #[serde(default = "default_hold")]
+    pub hold: u64,
+    /// Seconds of readings compared to spot a rapid change (default: 1800)
+    #[serde(default = "default_window")]
+    pub window: u64,
+    /// Fall in absbarometer, hPa, within the window that starts a burst
+    /// (default: 1.0)
+    #[serde(default = "default_pressure_drop")]
+    pub pressure_drop: f64,
+    /// Rise in gust_speed, m/s, within the window that starts a burst
+    /// (default: 5.0)
+    #[serde(default = "default_gust_rise")]
+    pub gust_rise: f64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+fn default_hold() -> u64 {
+    600
+}
+
+fn default_window() -> u64 {
+    1800
+}
+
+fn default_pressure_drop() -> f64 {
+    1.0
+}
+
+fn default_gust_rise() -> f64 {
+    5.0
+}
+
+impl BurstConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.interval == 0 {
+            anyhow::bail!("[burst] interval must be at least 1");
+        }
+        if self.pressure_drop <= 0.0 || self.gust_rise <= 0.0 {
+            anyhow::bail!("[burst] pressure_drop and gust_rise must be above 0");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: DateTime<Utc>,
+    pressure: Option<f64>,
+    gust: Option<f64>,
+}
+
+/// Watches pressure and gusts, and shortens the poll interval while either
+/// changes fast
+#[derive(Debug)]
+pub struct BurstPolling {
+    config: BurstConfig,
+    samples: VecDeque<Sample>,
+    last_trigger: Option<DateTime<Utc>>,
+    /// Interval in effect during a burst and while it decays
+    current: Option<u64>,
+}
+
+impl BurstPolling {
+    pub fn new(config: BurstConfig) -> Self {
+        Self {
+            config,
+            samples: VecDeque::new(),
+            last_trigger: None,
+            current: None,
+        }
+    }
+
+    /// Record one poll. Returns why a burst started, when this reading
+    /// starts one.
+    pub fn observe(&mut self, data: &HashMap<String, f64>, now: DateTime<Utc>) -> Option<String> {
+        let window = Duration::seconds(self.config.window as i64);
+        while self.samples.front().is_some_and(|s| now - s.at > window) {
+            self.samples.pop_front();
+        }
+        let sample = Sample {
+            at: now,
+            pressure: data.get("absbarometer").copied(),
+            gust: data.get("gust_speed").copied(),
+        };
+
+        let minutes = self
+            .samples
+            .front()
+            .map_or(0, |first| (now - first.at).num_minutes());
+        let highest_pressure = self
+            .samples
+            .iter()
+            .filter_map(|s| s.pressure)
+            .reduce(f64::max);
+        let lowest_gust = self.samples.iter().filter_map(|s| s.gust).reduce(f64::min);
+        let reason =
+            match (sample.pressure, highest_pressure, sample.gust, lowest_gust) {
+                (Some(now), Some(high), _, _) if high - now >= self.config.pressure_drop => Some(
+                    format!("pressure fell {:.1} hPa in {} min", high - now, minutes),
+                ),
+                (_, _, Some(now), Some(low)) if now - low >= self.config.gust_rise => Some(
+                    format!("gusts rose {:.1} m/s in {} min", now - low, minutes),
+                ),
+                _ => None,
+            };
+        self.samples.push_back(sample);
+
+        let reason = reason?;
+        let active = self.is_active(now);
+        self.last_trigger = Some(now);
+        (!active).then_some(reason)
+    }
+
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.last_trigger
+            .is_some_and(|at| now - at < Duration::seconds(self.config.hold as i64))
+    }
+
+    /// Interval for the next poll given the normal one. After a burst it
+    /// doubles each poll until it is back to `base`.
+    pub fn interval(&mut self, base: u64, now: DateTime<Utc>) -> u64 {
+        if self.is_active(now) {
+            self.current = Some(self.config.interval);
+        } else if let Some(current) = self.current {
+            self.current = Some(current * 2).filter(|next| *next < base);
+        }
+        self.current.map_or(base, |current| current.min(base))
+    }
+
+    /// Whether the interval is shortened, during a burst or its decay
+    pub fn is_bursting(&self) -> bool {
+        self.current.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn reading(pressure: f64, gust: f64) -> HashMap<String, f64> {
+        HashMap::from([
+            ("absbarometer".to_string(), pressure),
+            ("gust_speed".to_string(), gust),
+        ])
+    }
+
+    fn new_burst() -> BurstPolling {
+        BurstPolling::new(toml::from_str("").unwrap())
+    }
+
+    #[test]
+    fn test_pressure_drop_starts_burst() {
+        let mut burst = new_burst();
+        let start = Utc.with_ymd_and_hms(2026, 2, 1, 12, 0, 0).unwrap();
+
+        assert_eq!(burst.observe(&reading(1010.0, 2.0), start), None);
+        let at = start + Duration::minutes(10);
+        assert_eq!(burst.observe(&reading(1009.5, 2.0), at), None);
+        assert_eq!(burst.interval(60, at), 60);
+
+        let at = start + Duration::minutes(20);
+        let reason = burst.observe(&reading(1008.8, 2.0), at).unwrap();
+        assert_eq!(reason, "pressure fell 1.2 hPa in 20 min");
+        assert_eq!(burst.interval(60, at), 5);
+        assert!(burst.is_bursting());
+
+        // Still falling: the burst goes on without starting again
+        let at = at + Duration::seconds(5);
+        assert_eq!(burst.observe(&reading(1008.7, 2.0), at), None);
+        assert_eq!(burst.interval(60, at), 5);
+    }
+
+    #[test]
+    fn test_gust_rise_starts_burst() {
+        let mut burst = new_burst();
+        let start = Utc.with_ymd_and_hms(2026, 2, 1, 12, 0, 0).unwrap();
+        burst.observe(&reading(1010.0, 3.0), start);
+        let reason = burst
+            .observe(&reading(1010.0, 9.5), start + Duration::minutes(1))
+            .unwrap();
+        assert!(reason.starts_with("gusts rose 6.5 m/s"));
+
+        // Changes older than the window don't count
+        let mut burst = new_burst();
+        burst.observe(&reading(1010.0, 3.0), start);
+        let late = start + Duration::minutes(31);
+        assert_eq!(burst.observe(&reading(1010.0, 9.5), late), None);
+    }
+
+    #[test]
+    fn test_burst_decays_to_base() {
+        let mut burst = new_burst();
+        let start = Utc.with_ymd_and_hms(2026, 2, 1, 12, 0, 0).unwrap();
+        burst.observe(&reading(1010.0, 2.0), start);
+        burst.observe(&reading(1010.0, 9.0), start);
+        assert_eq!(burst.interval(60, start), 5);
+
+        // Ten minutes after the last rapid change the interval doubles each
+        // poll until it is back to the base interval
+        let after = start + Duration::minutes(10);
+        let intervals: Vec<u64> = (0..5).map(|_| burst.interval(60, after)).collect();
+        assert_eq!(intervals, vec![10, 20, 40, 60, 60]);
+        assert!(!burst.is_bursting());
+    }
+
+    #[test]
+    fn test_burst_config() {
+        let config: BurstConfig = toml::from_str("interval = 2\npressure_drop = 0.5").unwrap();
+        assert_eq!(config.interval, 2);
+        assert_eq!(config.hold, 600);
+        assert!(config.validate().is_ok());
+
+        let config: BurstConfig = toml::from_str("interval = 0").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
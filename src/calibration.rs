@@ -0,0 +1,110 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Per-sensor software calibration, e.g. `outtemp_offset = -0.4` or
+/// `outhumid_scale = 1.03`. Readings are corrected as `value * scale + offset`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CalibrationConfig {
+    #[serde(flatten)]
+    pub adjustments: HashMap<String, f64>,
+}
+
+impl CalibrationConfig {
+    /// Check that every key names an offset or scale factor
+    pub fn validate(&self) -> Result<()> {
+        for key in self.adjustments.keys() {
+            if !key.ends_with("_offset") && !key.ends_with("_scale") {
+                anyhow::bail!(
+                    "Invalid calibration key '{}': expected <field>_offset or <field>_scale",
+                    key
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Look up `<field><suffix>`, building the key in a reused buffer
+    fn adjustment(&self, key: &mut String, field: &str, suffix: &str) -> Option<f64> {
+        key.clear();
+        key.push_str(field);
+        key.push_str(suffix);
+        self.adjustments.get(key.as_str()).copied()
+    }
+
+    /// Apply offsets and scale factors to the decoded live data in place
+    pub fn apply(&self, data: &mut HashMap<String, f64>) {
+        if self.adjustments.is_empty() {
+            return;
+        }
+
+        let mut lookup = String::new();
+        for (key, value) in data.iter_mut() {
+            let scale = self.adjustment(&mut lookup, key, "_scale");
+            let offset = self.adjustment(&mut lookup, key, "_offset");
+            let (scale, offset) = match (scale, offset) {
+                (None, None) => continue,
+                (scale, offset) => (scale.unwrap_or(1.0), offset.unwrap_or(0.0)),
+            };
+            let corrected = *value * scale + offset;
+            // Keep the 0.1 resolution the gateway reports
+            *value = (corrected * 10.0).round() / 10.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration_deserialization() {
+        let toml_str = r#"
+            outtemp_offset = -0.4
+            outhumid_scale = 1.05
+        "#;
+
+        let config: CalibrationConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.adjustments.get("outtemp_offset"), Some(&-0.4));
+        assert_eq!(config.adjustments.get("outhumid_scale"), Some(&1.05));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_calibration_invalid_key() {
+        let toml_str = r#"
+            outtemp = 1.0
+        "#;
+
+        let config: CalibrationConfig = toml::from_str(toml_str).unwrap();
+        let result = config.validate();
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid calibration key"));
+    }
+
+    #[test]
+    fn test_calibration_apply() {
+        let mut config = CalibrationConfig::default();
+        config
+            .adjustments
+            .insert("outtemp_offset".to_string(), -0.4);
+        config.adjustments.insert("outhumid_scale".to_string(), 1.1);
+        config
+            .adjustments
+            .insert("outhumid_offset".to_string(), 1.0);
+
+        let mut data = HashMap::new();
+        data.insert("outtemp".to_string(), 20.0);
+        data.insert("outhumid".to_string(), 50.0);
+        data.insert("intemp".to_string(), 22.5);
+
+        config.apply(&mut data);
+
+        assert_eq!(data.get("outtemp"), Some(&19.6));
+        assert_eq!(data.get("outhumid"), Some(&56.0));
+        assert_eq!(data.get("intemp"), Some(&22.5));
+    }
+}
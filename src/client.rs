@@ -1,35 +1,255 @@
 use anyhow::{Context, Result};
+use chacha20poly1305::aead::rand_core::{OsRng, RngCore};
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::io::{Read, Write};
-use std::net::TcpStream;
-use std::time::Duration;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream, UdpSocket};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
 
-use crate::decoder::*;
-use crate::protocol::{build_cmd_packet, verify_response};
+use crate::decoder::parse_livedata_with_warnings;
+use crate::device::{CalibrationOffsets, SystemParams};
+use crate::protocol::{
+    build_cmd_packet, calc_checksum, check_response, hex_frame, response_payload, ResponseError,
+};
+use crate::sensors::{parse_sensor_ids, SensorSignal};
 
 // API Command codes
 const CMD_READ_FIRMWARE_VERSION: u8 = 0x50;
 const CMD_READ_STATION_MAC: u8 = 0x26;
 const CMD_GW1000_LIVEDATA: u8 = 0x27;
+const CMD_READ_SENSOR_ID_NEW: u8 = 0x3C;
+const CMD_READ_SSSS: u8 = 0x30;
+const CMD_WRITE_SSSS: u8 = 0x31;
+const CMD_READ_CALIBRATION: u8 = 0x38;
+const CMD_BROADCAST: u8 = 0x12;
+
+/// UDP port gateways answer CMD_BROADCAST on
+pub const DISCOVERY_PORT: u16 = 46000;
+
+/// How long to wait for gateways to answer a broadcast
+pub const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
 
 // Protocol constants
 const SOCKET_TIMEOUT: Duration = Duration::from_secs(16);
 
+/// Further tries of a command the gateway answers as busy, and the pause
+/// before each
+const BUSY_RETRIES: u32 = 2;
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Largest response accepted unless configured otherwise
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 64 * 1024;
+
+/// A kept connection idle longer than this is closed rather than reused, as
+/// the gateway may have dropped it without telling us
+const KEEP_ALIVE_IDLE: Duration = Duration::from_secs(60);
+
+/// How a command that fails to reach the gateway is tried again: up to
+/// `attempts` tries in all, pausing `backoff_ms` before the first retry and
+/// doubling the pause for each one after, up to `max_backoff_ms`. Each pause
+/// is varied by up to `jitter` of itself so clients don't retry in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct RetryPolicy {
+    /// Tries per command, including the first (default: 3)
+    #[serde(default = "default_attempts")]
+    pub attempts: u32,
+    /// Milliseconds before the first retry (default: 500)
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+    /// Longest pause between tries in milliseconds (default: 8000)
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// Fraction of each pause added or taken away at random (default: 0.2)
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+}
+
+fn default_attempts() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    8000
+}
+
+fn default_jitter() -> f64 {
+    0.2
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: default_attempts(),
+            backoff_ms: default_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            jitter: default_jitter(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Every command is tried once
+    pub fn none() -> Self {
+        Self {
+            attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.attempts == 0 {
+            anyhow::bail!("[retry] attempts must be at least 1");
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            anyhow::bail!("[retry] jitter must be between 0 and 1");
+        }
+        Ok(())
+    }
+
+    /// The pause before retry number `retry` (1 for the first) without
+    /// jitter
+    fn base_delay(&self, retry: u32) -> Duration {
+        let factor = 1u64
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        Duration::from_millis(
+            self.backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+
+    /// The pause before retry number `retry`, with jitter
+    fn delay(&self, retry: u32) -> Duration {
+        // A random factor in [-1, 1]
+        let spread = OsRng.next_u32() as f64 / u32::MAX as f64 * 2.0 - 1.0;
+        self.base_delay(retry)
+            .mul_f64((1.0 + spread * self.jitter).max(0.0))
+    }
+}
+
 pub struct GW1000Client {
     ip: String,
     port: u16,
+    max_response_size: usize,
+    trace_protocol: bool,
+    keep_alive: bool,
+    retry: RetryPolicy,
+    /// Connection kept open for the next command, and when it was last used
+    connection: Mutex<Option<(TcpStream, Instant)>>,
 }
 
 impl GW1000Client {
     pub fn new(ip: String, port: u16) -> Self {
-        Self { ip, port }
+        Self {
+            ip,
+            port,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            trace_protocol: false,
+            keep_alive: false,
+            retry: RetryPolicy::none(),
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Try commands that fail to reach the gateway again, as `policy` says
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Keep the connection open between commands instead of connecting for
+    /// each, reconnecting when it fails
+    pub fn with_keep_alive(mut self, enabled: bool) -> Self {
+        self.keep_alive = enabled;
+        self
+    }
+
+    /// Reject responses whose size field claims more than `max` bytes
+    pub fn with_max_response_size(mut self, max: usize) -> Self {
+        self.max_response_size = max;
+        self
+    }
+
+    /// Log every frame sent and received, checksums and parse timings
+    pub fn with_protocol_trace(mut self, enabled: bool) -> Self {
+        self.trace_protocol = enabled;
+        self
+    }
+
+    fn trace(&self, message: impl FnOnce() -> String) {
+        if self.trace_protocol {
+            eprintln!("[DEBUG] {}", message());
+        }
     }
 
     fn build_cmd_packet(&self, cmd_code: u8, payload: &[u8]) -> Vec<u8> {
         build_cmd_packet(cmd_code, payload)
     }
 
+    /// Send a command and read its response frame, trying again with
+    /// backoff when the gateway can't be reached or the connection breaks,
+    /// as a Wi-Fi hiccup shouldn't cost a reading
     fn send_cmd(&self, packet: &[u8]) -> Result<Vec<u8>> {
+        let mut retry = 0;
+        loop {
+            match self.send_once(packet) {
+                Ok(response) => {
+                    if retry > 0 {
+                        eprintln!(
+                            "[INFO] {} succeeded after {}",
+                            command_name(packet[2]),
+                            retries(retry)
+                        );
+                    }
+                    return Ok(response);
+                }
+                Err(e) if retry + 1 < self.retry.attempts && is_transient(&e) => {
+                    retry += 1;
+                    let delay = self.retry.delay(retry);
+                    eprintln!(
+                        "[WARN] {} failed ({:#}), retry {} of {} in {:?}",
+                        command_name(packet[2]),
+                        e,
+                        retry,
+                        self.retry.attempts - 1,
+                        delay
+                    );
+                    std::thread::sleep(delay);
+                }
+                Err(e) if retry > 0 => {
+                    return Err(e.context(format!(
+                        "{} failed after {}",
+                        command_name(packet[2]),
+                        retries(retry)
+                    )))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Send a command once over the kept connection if there is one. A kept
+    /// connection that fails, most likely because the gateway closed it, is
+    /// dropped and the command sent once more over a new one.
+    fn send_once(&self, packet: &[u8]) -> Result<Vec<u8>> {
+        if let Some(mut stream) = self.take_connection() {
+            match self.exchange(&mut stream, packet) {
+                Ok(response) => {
+                    self.keep_connection(stream);
+                    return Ok(response);
+                }
+                Err(e) => self.trace(|| format!("Kept connection failed ({:#}), reconnecting", e)),
+            }
+        }
+
         let addr = format!("{}:{}", self.ip, self.port);
         let mut stream = TcpStream::connect_timeout(&addr.parse()?, SOCKET_TIMEOUT)
             .context("Failed to connect to device")?;
@@ -37,306 +257,577 @@ impl GW1000Client {
         stream.set_read_timeout(Some(SOCKET_TIMEOUT))?;
         stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
 
+        let response = self.exchange(&mut stream, packet)?;
+        self.keep_connection(stream);
+        Ok(response)
+    }
+
+    /// The kept connection, unless it has been idle too long
+    fn take_connection(&self) -> Option<TcpStream> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        match connection.take() {
+            Some((stream, last_used)) if last_used.elapsed() < KEEP_ALIVE_IDLE => Some(stream),
+            _ => None,
+        }
+    }
+
+    /// Keep `stream` for the next command when keep-alive is on; it is only
+    /// kept after a whole frame was read, so the next read starts in step
+    fn keep_connection(&self, stream: TcpStream) {
+        if self.keep_alive {
+            *self.connection.lock().unwrap_or_else(|e| e.into_inner()) =
+                Some((stream, Instant::now()));
+        }
+    }
+
+    /// Drop the kept connection, if any
+    fn close_connection(&self) {
+        self.connection
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+    }
+
+    /// Write `packet` and read the response frame
+    fn exchange(&self, stream: &mut TcpStream, packet: &[u8]) -> Result<Vec<u8>> {
+        let cmd = packet[2];
+        self.trace(|| format!("TX {}: {}", command_name(cmd), hex_frame(packet)));
+        let started = Instant::now();
+
         stream.write_all(packet)?;
 
-        let mut response = vec![0u8; 1024];
-        let n = stream.read(&mut response)?;
-        response.truncate(n);
+        let response = self.read_response(stream, uses_wide_size(cmd))?;
+        self.trace(|| {
+            format!(
+                "RX {} ({} bytes in {:?}): {}",
+                command_name(cmd),
+                response.len(),
+                started.elapsed(),
+                hex_frame(&response)
+            )
+        });
 
         Ok(response)
     }
 
-    fn check_response(&self, response: &[u8], expected_cmd: u8) -> bool {
-        verify_response(response, expected_cmd)
-    }
+    /// Read one response frame, sized by its own size field
+    fn read_response(&self, stream: &mut impl Read, wide_size: bool) -> Result<Vec<u8>> {
+        let mut response = vec![0u8; header_len(wide_size)];
+        stream
+            .read_exact(&mut response)
+            .context("Failed to read response header")?;
 
-    pub fn get_firmware_version(&self) -> Result<String> {
-        let packet = self.build_cmd_packet(CMD_READ_FIRMWARE_VERSION, &[]);
-        let response = self.send_cmd(&packet)?;
+        let total = self.frame_len(&response, wide_size)?;
+        if total > response.len() {
+            let header_len = response.len();
+            response.resize(total, 0);
+            stream
+                .read_exact(&mut response[header_len..])
+                .context("Truncated response")?;
+        }
 
-        if self.check_response(&response, CMD_READ_FIRMWARE_VERSION) {
-            let size = response[3] as usize;
-            let data = &response[4..4 + size - 3];
-            Ok(String::from_utf8_lossy(data).to_string())
+        Ok(response)
+    }
+
+    /// Length of the whole frame whose header is `header`, within
+    /// `max_response_size`
+    fn frame_len(&self, header: &[u8], wide_size: bool) -> Result<usize> {
+        let size = if wide_size {
+            ((header[3] as usize) << 8) | (header[4] as usize)
         } else {
-            anyhow::bail!("Invalid firmware version response")
+            header[3] as usize
+        };
+
+        // The size field excludes the 2-byte header
+        let total = size + 2;
+        if total > self.max_response_size {
+            anyhow::bail!(
+                "Device claims a {} byte response, above the {} byte limit (max_response_size)",
+                total,
+                self.max_response_size
+            );
         }
+        Ok(total)
     }
 
-    pub fn get_mac_address(&self) -> Result<String> {
-        let packet = self.build_cmd_packet(CMD_READ_STATION_MAC, &[]);
-        let response = self.send_cmd(&packet)?;
-
-        if self.check_response(&response, CMD_READ_STATION_MAC) {
-            let size = response[3] as usize;
-            let data = &response[4..4 + size - 3];
-            let mac = data
-                .iter()
-                .map(|b| format!("{:02X}", b))
-                .collect::<Vec<_>>()
-                .join(":");
-            Ok(mac)
-        } else {
-            anyhow::bail!("Invalid MAC address response")
+    /// Send a command and return its verified response. A busy gateway gets
+    /// a couple more tries after a short pause before it is reported.
+    fn request(&self, cmd: u8, payload: &[u8]) -> Result<Vec<u8>> {
+        let packet = self.build_cmd_packet(cmd, payload);
+        let mut retries = 0;
+        loop {
+            let response = self.send_cmd(&packet)?;
+            // A frame that fails its checks may have left the connection
+            // out of step, so the next command starts on a new one
+            let accepted = self
+                .accept_response(response, cmd, &mut retries)
+                .inspect_err(|_| self.close_connection())?;
+            if let Some(response) = accepted {
+                return Ok(response);
+            }
+            std::thread::sleep(BUSY_RETRY_DELAY);
+        }
+    }
+
+    /// The response if it is valid, None if the gateway was busy and the
+    /// command should be sent again after BUSY_RETRY_DELAY
+    fn accept_response(
+        &self,
+        response: Vec<u8>,
+        cmd: u8,
+        retries: &mut u32,
+    ) -> Result<Option<Vec<u8>>> {
+        match self.check_response(&response, cmd) {
+            Ok(()) => Ok(Some(response)),
+            Err(ResponseError::Busy { .. }) if *retries < BUSY_RETRIES => {
+                *retries += 1;
+                self.trace(|| {
+                    format!(
+                        "{} busy, retry {} of {} in {:?}",
+                        command_name(cmd),
+                        retries,
+                        BUSY_RETRIES,
+                        BUSY_RETRY_DELAY
+                    )
+                });
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
         }
     }
 
+    fn check_response(&self, response: &[u8], expected_cmd: u8) -> Result<(), ResponseError> {
+        let result = check_response(response, expected_cmd);
+        let valid = result.is_ok();
+        if response.len() >= 5 {
+            self.trace(|| {
+                format!(
+                    "{} checksum: calculated 0x{:02X}, received 0x{:02X}, response {}",
+                    command_name(expected_cmd),
+                    calc_checksum(&response[2..response.len() - 1]),
+                    response[response.len() - 1],
+                    if valid { "valid" } else { "INVALID" }
+                )
+            });
+        }
+        result?;
+
+        // Writes are acknowledged with a status byte; anything else that
+        // returns one instead of data is turning the request away
+        if expected_cmd != CMD_WRITE_SSSS {
+            if let Ok(&[status]) = response_payload(response, uses_wide_size(expected_cmd)) {
+                return Err(ResponseError::Busy {
+                    command: expected_cmd,
+                    status,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_firmware_version(&self) -> Result<String> {
+        decode_firmware_version(&self.request(CMD_READ_FIRMWARE_VERSION, &[])?)
+    }
+
+    pub fn get_mac_address(&self) -> Result<String> {
+        decode_mac_address(&self.request(CMD_READ_STATION_MAC, &[])?)
+    }
+
+    #[allow(dead_code)]
     pub fn get_livedata(&self) -> Result<HashMap<String, f64>> {
-        let packet = self.build_cmd_packet(CMD_GW1000_LIVEDATA, &[]);
-        let response = self.send_cmd(&packet)?;
-
-        if self.check_response(&response, CMD_GW1000_LIVEDATA) {
-            // CMD_GW1000_LIVEDATA uses 2-byte size field (big-endian)
-            let size = ((response[3] as usize) << 8) | (response[4] as usize);
-            let data = &response[5..5 + size - 4];
-            self.parse_livedata(data)
-        } else {
-            anyhow::bail!("Invalid live data response")
+        self.get_livedata_with_warnings().map(|(data, _)| data)
+    }
+
+    /// The live data response as received, header to checksum, once it has
+    /// been verified
+    pub fn get_livedata_frame(&self) -> Result<Vec<u8>> {
+        self.request(CMD_GW1000_LIVEDATA, &[])
+    }
+
+    /// Live data and the number of records the decoder had to skip
+    pub fn get_livedata_with_warnings(&self) -> Result<(HashMap<String, f64>, u32)> {
+        self.decode_livedata(&self.get_livedata_frame()?)
+    }
+
+    fn decode_livedata(&self, response: &[u8]) -> Result<(HashMap<String, f64>, u32)> {
+        let data = response_payload(response, uses_wide_size(CMD_GW1000_LIVEDATA))?;
+        let started = Instant::now();
+        let (result, warnings) = parse_livedata_with_warnings(data);
+        self.trace(|| {
+            format!(
+                "{} parsed {} fields in {:?} ({} decode warnings)",
+                command_name(CMD_GW1000_LIVEDATA),
+                result.len(),
+                started.elapsed(),
+                warnings
+            )
+        });
+        Ok((result, warnings))
+    }
+
+    pub fn get_sensor_signals(&self) -> Result<Vec<SensorSignal>> {
+        self.decode_sensor_signals(&self.request(CMD_READ_SENSOR_ID_NEW, &[])?)
+    }
+
+    fn decode_sensor_signals(&self, response: &[u8]) -> Result<Vec<SensorSignal>> {
+        let data = response_payload(response, uses_wide_size(CMD_READ_SENSOR_ID_NEW))?;
+        let started = Instant::now();
+        let signals = parse_sensor_ids(data);
+        self.trace(|| {
+            format!(
+                "{} parsed {} sensors in {:?}",
+                command_name(CMD_READ_SENSOR_ID_NEW),
+                signals.len(),
+                started.elapsed()
+            )
+        });
+        Ok(signals)
+    }
+
+    pub fn get_system_params(&self) -> Result<SystemParams> {
+        decode_system_params(&self.request(CMD_READ_SSSS, &[])?)
+    }
+
+    pub fn set_system_params(&self, params: &SystemParams) -> Result<()> {
+        decode_write_ack(&self.request(CMD_WRITE_SSSS, &params.to_bytes())?)
+    }
+
+    pub fn get_calibration_offsets(&self) -> Result<CalibrationOffsets> {
+        decode_calibration_offsets(&self.request(CMD_READ_CALIBRATION, &[])?)
+    }
+
+    /// Set the gateway clock, keeping its other system parameters. Returns
+    /// the clock drift in seconds (gateway minus `utc`) before the change.
+    pub fn set_time(&self, utc: u32) -> Result<i64> {
+        let mut params = self.get_system_params()?;
+        let drift = params.utc as i64 - utc as i64;
+        params.utc = utc;
+        self.set_system_params(&params)?;
+        Ok(drift)
+    }
+}
+
+/// The same commands over tokio's TcpStream, for async code that talks to the
+/// gateway directly; waiting on a slow gateway then holds up only its own
+/// task. Frames are checked and decoded exactly as by GW1000Client.
+pub struct AsyncGW1000Client {
+    client: GW1000Client,
+}
+
+impl AsyncGW1000Client {
+    pub fn new(ip: String, port: u16) -> Self {
+        Self {
+            client: GW1000Client::new(ip, port),
         }
     }
 
-    fn parse_livedata(&self, data: &[u8]) -> Result<HashMap<String, f64>> {
-        let mut result = HashMap::new();
-        let mut index = 0;
+    /// Reject responses whose size field claims more than `max` bytes
+    pub fn with_max_response_size(mut self, max: usize) -> Self {
+        self.client = self.client.with_max_response_size(max);
+        self
+    }
+
+    /// Log every frame sent and received, checksums and parse timings
+    pub fn with_protocol_trace(mut self, enabled: bool) -> Self {
+        self.client = self.client.with_protocol_trace(enabled);
+        self
+    }
 
-        while index < data.len() {
-            let field_addr = data[index];
+    async fn send_cmd(&self, packet: &[u8]) -> Result<Vec<u8>> {
+        let addr: std::net::SocketAddr =
+            format!("{}:{}", self.client.ip, self.client.port).parse()?;
+        let mut stream = timeout(SOCKET_TIMEOUT, tokio::net::TcpStream::connect(addr))
+            .await
+            .context("Timed out connecting to device")?
+            .context("Failed to connect to device")?;
 
-            match field_addr {
-                0x01 => {
-                    // intemp
-                    if index + 2 < data.len() {
-                        let val = decode_temp(&data[index + 1..index + 3]);
-                        result.insert("intemp".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x02 => {
-                    // outtemp
-                    if index + 2 < data.len() {
-                        let val = decode_temp(&data[index + 1..index + 3]);
-                        result.insert("outtemp".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x03 => {
-                    // dew point
-                    if index + 2 < data.len() {
-                        let val = decode_temp(&data[index + 1..index + 3]);
-                        result.insert("dewpoint".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x04 => {
-                    // wind chill
-                    if index + 2 < data.len() {
-                        let val = decode_temp(&data[index + 1..index + 3]);
-                        result.insert("windchill".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x05 => {
-                    // heat index
-                    if index + 2 < data.len() {
-                        let val = decode_temp(&data[index + 1..index + 3]);
-                        result.insert("heatindex".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x06 => {
-                    // inhumid
-                    if index + 1 < data.len() {
-                        result.insert("inhumid".to_string(), data[index + 1] as f64);
-                        index += 2;
-                    } else {
-                        break;
-                    }
-                }
-                0x07 => {
-                    // outhumid
-                    if index + 1 < data.len() {
-                        result.insert("outhumid".to_string(), data[index + 1] as f64);
-                        index += 2;
-                    } else {
-                        break;
-                    }
-                }
-                0x08 => {
-                    // absbarometer
-                    if index + 2 < data.len() {
-                        let val = decode_pressure(&data[index + 1..index + 3]);
-                        result.insert("absbarometer".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x09 => {
-                    // relbarometer
-                    if index + 2 < data.len() {
-                        let val = decode_pressure(&data[index + 1..index + 3]);
-                        result.insert("relbarometer".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x0A => {
-                    // wind_dir
-                    if index + 2 < data.len() {
-                        let val = decode_short(&data[index + 1..index + 3]);
-                        result.insert("wind_dir".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x0B => {
-                    // wind_speed
-                    if index + 2 < data.len() {
-                        let val = decode_wind(&data[index + 1..index + 3]);
-                        result.insert("wind_speed".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x0C => {
-                    // gust_speed
-                    if index + 2 < data.len() {
-                        let val = decode_wind(&data[index + 1..index + 3]);
-                        result.insert("gust_speed".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x0D => {
-                    // rain_event
-                    if index + 2 < data.len() {
-                        let val = decode_rain(&data[index + 1..index + 3]);
-                        result.insert("rain_event".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x0E => {
-                    // rain_rate
-                    if index + 2 < data.len() {
-                        let val = decode_rain(&data[index + 1..index + 3]);
-                        result.insert("rain_rate".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x10 => {
-                    // rain_day
-                    if index + 2 < data.len() {
-                        let val = decode_rain(&data[index + 1..index + 3]);
-                        result.insert("rain_day".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x11 => {
-                    // rain_week
-                    if index + 2 < data.len() {
-                        let val = decode_rain(&data[index + 1..index + 3]);
-                        result.insert("rain_week".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x12 => {
-                    // rain_month
-                    if index + 4 < data.len() {
-                        let val = decode_int(&data[index + 1..index + 5]) / 10.0;
-                        result.insert("rain_month".to_string(), val);
-                        index += 5;
-                    } else {
-                        break;
-                    }
-                }
-                0x13 => {
-                    // rain_year
-                    if index + 4 < data.len() {
-                        let val = decode_int(&data[index + 1..index + 5]) / 10.0;
-                        result.insert("rain_year".to_string(), val);
-                        index += 5;
-                    } else {
-                        break;
-                    }
-                }
-                0x15 => {
-                    // light
-                    if index + 4 < data.len() {
-                        let val = decode_int(&data[index + 1..index + 5]) / 10.0;
-                        result.insert("light".to_string(), val);
-                        index += 5;
-                    } else {
-                        break;
-                    }
-                }
-                0x16 => {
-                    // uv
-                    if index + 2 < data.len() {
-                        let val = decode_short(&data[index + 1..index + 3]);
-                        result.insert("uv".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x17 => {
-                    // uvi
-                    if index + 1 < data.len() {
-                        result.insert("uvi".to_string(), data[index + 1] as f64);
-                        index += 2;
-                    } else {
-                        break;
-                    }
-                }
-                0x19 => {
-                    // day_max_wind
-                    if index + 2 < data.len() {
-                        let val = decode_wind(&data[index + 1..index + 3]);
-                        result.insert("day_max_wind".to_string(), val);
-                        index += 3;
-                    } else {
-                        break;
-                    }
-                }
-                0x6C => {
-                    // heap_free
-                    if index + 4 < data.len() {
-                        let val = decode_int(&data[index + 1..index + 5]);
-                        result.insert("heap_free".to_string(), val);
-                        index += 5;
-                    } else {
-                        break;
-                    }
-                }
-                _ => {
-                    // Unknown field, skip it
-                    index += 1;
-                }
+        let cmd = packet[2];
+        self.client
+            .trace(|| format!("TX {}: {}", command_name(cmd), hex_frame(packet)));
+        let started = Instant::now();
+
+        timeout(SOCKET_TIMEOUT, stream.write_all(packet))
+            .await
+            .context("Timed out sending to device")??;
+
+        let response = self.read_response(&mut stream, uses_wide_size(cmd)).await?;
+        self.client.trace(|| {
+            format!(
+                "RX {} ({} bytes in {:?}): {}",
+                command_name(cmd),
+                response.len(),
+                started.elapsed(),
+                hex_frame(&response)
+            )
+        });
+
+        Ok(response)
+    }
+
+    async fn read_response(
+        &self,
+        stream: &mut tokio::net::TcpStream,
+        wide_size: bool,
+    ) -> Result<Vec<u8>> {
+        let mut response = vec![0u8; header_len(wide_size)];
+        timeout(SOCKET_TIMEOUT, stream.read_exact(&mut response))
+            .await
+            .context("Timed out reading response header")?
+            .context("Failed to read response header")?;
+
+        let total = self.client.frame_len(&response, wide_size)?;
+        if total > response.len() {
+            let header_len = response.len();
+            response.resize(total, 0);
+            timeout(
+                SOCKET_TIMEOUT,
+                stream.read_exact(&mut response[header_len..]),
+            )
+            .await
+            .context("Timed out reading response")?
+            .context("Truncated response")?;
+        }
+
+        Ok(response)
+    }
+
+    /// Send a command and return its verified response, retrying while the
+    /// gateway is busy
+    async fn request(&self, cmd: u8, payload: &[u8]) -> Result<Vec<u8>> {
+        let packet = self.client.build_cmd_packet(cmd, payload);
+        let mut retries = 0;
+        loop {
+            let response = self.send_cmd(&packet).await?;
+            if let Some(response) = self.client.accept_response(response, cmd, &mut retries)? {
+                return Ok(response);
             }
+            tokio::time::sleep(BUSY_RETRY_DELAY).await;
         }
+    }
+
+    pub async fn get_firmware_version(&self) -> Result<String> {
+        decode_firmware_version(&self.request(CMD_READ_FIRMWARE_VERSION, &[]).await?)
+    }
+
+    pub async fn get_mac_address(&self) -> Result<String> {
+        decode_mac_address(&self.request(CMD_READ_STATION_MAC, &[]).await?)
+    }
+
+    /// The live data response as received, header to checksum, once it has
+    /// been verified
+    pub async fn get_livedata_frame(&self) -> Result<Vec<u8>> {
+        self.request(CMD_GW1000_LIVEDATA, &[]).await
+    }
+
+    /// Live data and the number of records the decoder had to skip
+    pub async fn get_livedata_with_warnings(&self) -> Result<(HashMap<String, f64>, u32)> {
+        self.client
+            .decode_livedata(&self.get_livedata_frame().await?)
+    }
+
+    #[allow(dead_code)]
+    pub async fn get_sensor_signals(&self) -> Result<Vec<SensorSignal>> {
+        self.client
+            .decode_sensor_signals(&self.request(CMD_READ_SENSOR_ID_NEW, &[]).await?)
+    }
+
+    pub async fn get_system_params(&self) -> Result<SystemParams> {
+        decode_system_params(&self.request(CMD_READ_SSSS, &[]).await?)
+    }
 
-        Ok(result)
+    pub async fn set_system_params(&self, params: &SystemParams) -> Result<()> {
+        decode_write_ack(&self.request(CMD_WRITE_SSSS, &params.to_bytes()).await?)
+    }
+
+    #[allow(dead_code)]
+    pub async fn get_calibration_offsets(&self) -> Result<CalibrationOffsets> {
+        decode_calibration_offsets(&self.request(CMD_READ_CALIBRATION, &[]).await?)
+    }
+
+    /// Set the gateway clock, keeping its other system parameters. Returns
+    /// the clock drift in seconds (gateway minus `utc`) before the change.
+    #[allow(dead_code)]
+    pub async fn set_time(&self, utc: u32) -> Result<i64> {
+        let mut params = self.get_system_params().await?;
+        let drift = params.utc as i64 - utc as i64;
+        params.utc = utc;
+        self.set_system_params(&params).await?;
+        Ok(drift)
+    }
+}
+
+fn decode_firmware_version(response: &[u8]) -> Result<String> {
+    let data = response_payload(response, uses_wide_size(CMD_READ_FIRMWARE_VERSION))?;
+    Ok(String::from_utf8_lossy(data).to_string())
+}
+
+fn decode_mac_address(response: &[u8]) -> Result<String> {
+    let data = response_payload(response, uses_wide_size(CMD_READ_STATION_MAC))?;
+    Ok(format_mac(data))
+}
+
+fn decode_system_params(response: &[u8]) -> Result<SystemParams> {
+    SystemParams::parse(response_payload(response, uses_wide_size(CMD_READ_SSSS))?)
+}
+
+/// A single status byte, 0x00 on success
+fn decode_write_ack(response: &[u8]) -> Result<()> {
+    match response_payload(response, uses_wide_size(CMD_WRITE_SSSS))? {
+        [0x00] => Ok(()),
+        _ => anyhow::bail!("Gateway rejected system parameters"),
+    }
+}
+
+fn decode_calibration_offsets(response: &[u8]) -> Result<CalibrationOffsets> {
+    CalibrationOffsets::parse(response_payload(
+        response,
+        uses_wide_size(CMD_READ_CALIBRATION),
+    )?)
+}
+
+/// MAC address bytes as "EC:62:60:E0:6E:6F"
+fn format_mac(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Whether `error` came from the network rather than the gateway's answer,
+/// so trying again may work
+fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<std::io::Error>())
+}
+
+/// "1 retry", "2 retries"
+fn retries(count: u32) -> String {
+    format!("{} {}", count, if count == 1 { "retry" } else { "retries" })
+}
+
+/// Bytes before the size field ends: header(2) + cmd(1) + size field
+fn header_len(wide_size: bool) -> usize {
+    if wide_size {
+        5
+    } else {
+        4
+    }
+}
+
+/// Command name for protocol traces
+fn command_name(cmd: u8) -> &'static str {
+    match cmd {
+        CMD_READ_FIRMWARE_VERSION => "CMD_READ_FIRMWARE_VERSION",
+        CMD_READ_STATION_MAC => "CMD_READ_STATION_MAC",
+        CMD_GW1000_LIVEDATA => "CMD_GW1000_LIVEDATA",
+        CMD_READ_SENSOR_ID_NEW => "CMD_READ_SENSOR_ID_NEW",
+        CMD_READ_SSSS => "CMD_READ_SSSS",
+        CMD_WRITE_SSSS => "CMD_WRITE_SSSS",
+        CMD_READ_CALIBRATION => "CMD_READ_CALIBRATION",
+        CMD_BROADCAST => "CMD_BROADCAST",
+        _ => "CMD_UNKNOWN",
+    }
+}
+
+/// Whether a command's response carries a 2-byte (big-endian) size field
+fn uses_wide_size(cmd: u8) -> bool {
+    matches!(
+        cmd,
+        CMD_GW1000_LIVEDATA | CMD_READ_SENSOR_ID_NEW | CMD_BROADCAST
+    )
+}
+
+/// A gateway that answered a discovery broadcast
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredGateway {
+    pub ip: Ipv4Addr,
+    /// Port of its TCP API
+    pub port: u16,
+    pub mac: String,
+    /// e.g. "GW2000A"
+    pub model: String,
+    /// e.g. "V3.1.4", when the gateway includes it
+    pub firmware: Option<String>,
+}
+
+/// Parse a CMD_BROADCAST response: MAC(6), IP(4), port(2), then the
+/// length-prefixed name of the gateway's access point, e.g.
+/// "GW1000A-WIFI5E23 V1.6.8"
+pub fn parse_broadcast_response(response: &[u8]) -> Result<DiscoveredGateway> {
+    check_response(response, CMD_BROADCAST)?;
+    let data = response_payload(response, uses_wide_size(CMD_BROADCAST))?;
+    if data.len() < 13 || data.len() < 13 + data[12] as usize {
+        anyhow::bail!("Broadcast response too short ({} bytes)", data.len());
+    }
+    let mac = format_mac(&data[..6]);
+    let ip = Ipv4Addr::new(data[6], data[7], data[8], data[9]);
+    let port = u16::from_be_bytes([data[10], data[11]]);
+    let name = String::from_utf8_lossy(&data[13..13 + data[12] as usize]);
+    let mut parts = name.split_whitespace();
+    let model = parts
+        .next()
+        .and_then(|ssid| ssid.split('-').next())
+        .unwrap_or_default()
+        .to_string();
+    Ok(DiscoveredGateway {
+        ip,
+        port,
+        mac,
+        model,
+        firmware: parts.next().map(str::to_string),
+    })
+}
+
+/// Gateways on the local network, by IP; each answers a broadcast to
+/// DISCOVERY_PORT with its address
+pub fn discover(timeout: Duration) -> Result<Vec<DiscoveredGateway>> {
+    discover_at(
+        SocketAddr::from((Ipv4Addr::BROADCAST, DISCOVERY_PORT)),
+        timeout,
+    )
+}
+
+/// `discover`, sending the broadcast packet to `target`
+pub fn discover_at(target: SocketAddr, timeout: Duration) -> Result<Vec<DiscoveredGateway>> {
+    let socket =
+        UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).context("Failed to open a UDP socket")?;
+    socket.set_broadcast(true)?;
+    socket
+        .send_to(&build_cmd_packet(CMD_BROADCAST, &[]), target)
+        .context(format!(
+            "Failed to send the discovery broadcast to {}",
+            target
+        ))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut found: Vec<DiscoveredGateway> = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let len = match socket.recv_from(&mut buf) {
+            Ok((len, _)) => len,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                break
+            }
+            Err(e) => return Err(e).context("Failed to read discovery responses"),
+        };
+        // Our own broadcast comes back too, and other devices may answer
+        // with something else entirely
+        if let Ok(gateway) = parse_broadcast_response(&buf[..len]) {
+            if !found.iter().any(|known| known.mac == gateway.mac) {
+                found.push(gateway);
+            }
+        }
     }
+    found.sort_by_key(|gateway| gateway.ip);
+    Ok(found)
 }
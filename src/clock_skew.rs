@@ -0,0 +1,90 @@
+/// Clock skew between this host and the database server. Readings are
+/// stamped with the host's clock, so a database whose clock differs makes
+/// "latest record" queries using its NOW() and time-based cleanup miss or
+/// drop the wrong rows.
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+
+use crate::database::DatabaseWriter;
+
+/// Seconds between checks after the one at startup
+const CHECK_INTERVAL: u64 = 3600;
+
+/// How far the server's clock is ahead of the host's (negative: behind),
+/// given the server time read between `sent` and `received` host times.
+/// The server is assumed to have answered halfway through the round trip.
+pub fn skew(sent: DateTime<Utc>, received: DateTime<Utc>, server: DateTime<Utc>) -> Duration {
+    server - (sent + (received - sent) / 2)
+}
+
+/// E.g. "database clock is 12.3s ahead of this host"
+pub fn describe(skew: Duration) -> String {
+    let seconds = skew.num_milliseconds() as f64 / 1000.0;
+    format!(
+        "database clock is {:.1}s {} this host",
+        seconds.abs(),
+        if seconds < 0.0 { "behind" } else { "ahead of" }
+    )
+}
+
+/// Measure the skew once
+pub async fn measure(writer: &DatabaseWriter) -> Result<Duration> {
+    let sent = Utc::now();
+    let server = writer.server_time().await?;
+    Ok(skew(sent, Utc::now(), server))
+}
+
+/// Check now and then every hour, warning while the skew is above
+/// `max_skew` and noting once when it is back within it
+pub fn run_clock_check_background(writer: Arc<DatabaseWriter>, max_skew: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(CHECK_INTERVAL));
+        let mut skewed = false;
+        loop {
+            interval.tick().await;
+            match measure(&writer).await {
+                Ok(skew) if skew.abs() > max_skew => {
+                    eprintln!(
+                        "[WARN] Clock skew: {} (more than {}s)",
+                        describe(skew),
+                        max_skew.num_seconds()
+                    );
+                    skewed = true;
+                }
+                Ok(skew) if skewed => {
+                    println!("[OK] Clock skew back within limits: {}", describe(skew));
+                    skewed = false;
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("[WARN] Failed to check database clock: {:#}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_skew() {
+        let sent = Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        let received = sent + Duration::milliseconds(200);
+
+        let server = sent + Duration::milliseconds(100);
+        assert_eq!(skew(sent, received, server), Duration::zero());
+
+        let server = sent + Duration::milliseconds(12_400);
+        assert_eq!(skew(sent, received, server), Duration::milliseconds(12_300));
+        assert_eq!(
+            describe(Duration::milliseconds(12_300)),
+            "database clock is 12.3s ahead of this host"
+        );
+        assert_eq!(
+            describe(Duration::milliseconds(-4_000)),
+            "database clock is 4.0s behind this host"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+/// Aligned time series of several fields or stations for
+/// /api/v1/compare.json, e.g. to compare sensor placements or microclimates
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::history::bucket;
+use crate::storage::Points;
+
+/// Most buckets a comparison may return
+pub const MAX_BUCKETS: i64 = 10_000;
+
+/// One series to compare: `field` of this station, or `station:field` of a
+/// station named in `[database] stations`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeriesSpec {
+    pub station: Option<String>,
+    pub field: String,
+}
+
+impl SeriesSpec {
+    /// Parse a comma-separated list of series, e.g. "outtemp,cabin:outtemp"
+    pub fn parse_list(value: &str) -> Result<Vec<Self>> {
+        let specs = value
+            .split(',')
+            .map(str::trim)
+            .filter(|spec| !spec.is_empty())
+            .map(|spec| match spec.split_once(':') {
+                Some((station, field)) if !station.is_empty() && !field.is_empty() => Ok(Self {
+                    station: Some(station.to_string()),
+                    field: field.to_string(),
+                }),
+                Some(_) => anyhow::bail!("Invalid series '{}': use field or station:field", spec),
+                None => Ok(Self {
+                    station: None,
+                    field: spec.to_string(),
+                }),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if specs.len() < 2 {
+            anyhow::bail!("Comparing needs two or more series, e.g. series=outtemp,cabin:outtemp");
+        }
+        Ok(specs)
+    }
+
+    pub fn name(&self) -> String {
+        match &self.station {
+            Some(station) => format!("{}:{}", station, self.field),
+            None => self.field.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Series {
+    pub name: String,
+    /// Mean of each bucket; None without readings in it
+    pub values: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comparison {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Bucket size in seconds
+    pub step: i64,
+    /// Start of each bucket, epoch milliseconds
+    pub timestamps: Vec<i64>,
+    pub series: Vec<Series>,
+    /// The first series minus each of the others
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub differences: Vec<Series>,
+}
+
+/// Default bucket for a range: whole minutes, at most 500 buckets
+pub fn default_step(from: DateTime<Utc>, to: DateTime<Utc>) -> TimeDelta {
+    let minutes = ((to - from).num_seconds().max(1) as f64 / 500.0 / 60.0).ceil() as i64;
+    TimeDelta::minutes(minutes.max(1))
+}
+
+/// Average each series into the `step` buckets from `from` on, covering
+/// `to`, and with `difference` subtract each other series from the first
+pub fn compare(
+    specs: &[SeriesSpec],
+    points: &[Points],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    step: TimeDelta,
+    difference: bool,
+) -> Result<Comparison> {
+    if step <= TimeDelta::zero() || to <= from {
+        anyhow::bail!("Comparing needs a step above 0 and `to` after `from`");
+    }
+    let step_ms = step.num_milliseconds();
+    let buckets = ((to - from).num_milliseconds() + step_ms - 1) / step_ms;
+    if buckets > MAX_BUCKETS {
+        anyhow::bail!(
+            "{} buckets is too many: use a larger step or a shorter range (at most {})",
+            buckets,
+            MAX_BUCKETS
+        );
+    }
+    let end = from + TimeDelta::milliseconds(buckets * step_ms);
+    let series: Vec<Series> = specs
+        .iter()
+        .zip(points)
+        .map(|(spec, points)| Series {
+            name: spec.name(),
+            values: bucket(&spec.field, points, from, end, buckets as usize),
+        })
+        .collect();
+
+    let mut differences = Vec::new();
+    if difference {
+        let first = &series[0];
+        for (spec, other) in specs.iter().zip(&series).skip(1) {
+            let directions = specs[0].field == "wind_dir" && spec.field == "wind_dir";
+            differences.push(Series {
+                name: format!("{} - {}", first.name, other.name),
+                values: first
+                    .values
+                    .iter()
+                    .zip(&other.values)
+                    .map(|(a, b)| Some(difference_of(a.as_ref()?, b.as_ref()?, directions)))
+                    .collect(),
+            });
+        }
+    }
+
+    Ok(Comparison {
+        from,
+        to,
+        step: step.num_seconds(),
+        timestamps: (0..buckets)
+            .map(|i| (from + TimeDelta::milliseconds(i * step_ms)).timestamp_millis())
+            .collect(),
+        series,
+        differences,
+    })
+}
+
+/// `a - b`; for directions the shorter way round, in (-180, 180]
+fn difference_of(a: &f64, b: &f64, directions: bool) -> f64 {
+    let diff = a - b;
+    if directions {
+        let wrapped = diff.rem_euclid(360.0);
+        if wrapped > 180.0 {
+            wrapped - 360.0
+        } else {
+            wrapped
+        }
+    } else {
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(minute: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 5, 1, 0, 0, 0).unwrap() + TimeDelta::minutes(minute)
+    }
+
+    #[test]
+    fn test_parse_series() {
+        let specs = SeriesSpec::parse_list("outtemp, cabin:outtemp").unwrap();
+        assert_eq!(specs[0].station, None);
+        assert_eq!(specs[1].station.as_deref(), Some("cabin"));
+        assert_eq!(specs[1].name(), "cabin:outtemp");
+
+        assert!(SeriesSpec::parse_list("outtemp").is_err());
+        assert!(SeriesSpec::parse_list("outtemp,:outtemp").is_err());
+    }
+
+    #[test]
+    fn test_compare_aligns_and_differences() {
+        let specs = SeriesSpec::parse_list("outtemp,cabin:outtemp").unwrap();
+        // Polled at different moments; buckets line them up
+        let home = vec![(at(1), 10.0), (at(4), 12.0), (at(11), 14.0)];
+        let cabin = vec![(at(2), 7.0), (at(13), 9.0), (at(22), 8.0)];
+        let comparison = compare(
+            &specs,
+            &[home, cabin],
+            at(0),
+            at(25),
+            TimeDelta::minutes(10),
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(comparison.timestamps.len(), 3);
+        assert_eq!(comparison.timestamps[1], at(10).timestamp_millis());
+        assert_eq!(
+            comparison.series[0].values,
+            vec![Some(11.0), Some(14.0), None]
+        );
+        assert_eq!(
+            comparison.series[1].values,
+            vec![Some(7.0), Some(9.0), Some(8.0)]
+        );
+        let difference = &comparison.differences[0];
+        assert_eq!(difference.name, "outtemp - cabin:outtemp");
+        assert_eq!(difference.values, vec![Some(4.0), Some(5.0), None]);
+
+        let too_many = compare(
+            &specs,
+            &[vec![], vec![]],
+            at(0),
+            at(20_000),
+            TimeDelta::minutes(1),
+            false,
+        );
+        assert!(too_many.is_err());
+    }
+
+    #[test]
+    fn test_direction_difference_wraps() {
+        assert_eq!(difference_of(&10.0, &350.0, true), 20.0);
+        assert_eq!(difference_of(&350.0, &10.0, true), -20.0);
+        assert_eq!(difference_of(&350.0, &10.0, false), 340.0);
+        assert_eq!(default_step(at(0), at(24 * 60)), TimeDelta::minutes(3));
+    }
+}
@@ -1,15 +1,49 @@
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use serde::Deserialize;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
+#[cfg(all(feature = "db", feature = "http"))]
+use crate::backfill::EcowittCloudConfig;
+use crate::battery::BatteryConfig;
+#[cfg(any(feature = "db", feature = "mqtt", feature = "http"))]
+use crate::breaker::BreakerConfig;
+use crate::burst::BurstConfig;
+use crate::calibration::CalibrationConfig;
+use crate::client::{discover, RetryPolicy, DEFAULT_MAX_RESPONSE_SIZE, DISCOVERY_TIMEOUT};
+use crate::cron::ScheduleRule;
+#[cfg(feature = "db")]
 use crate::database::DatabaseConfig;
+use crate::degree_days::DegreeDayConfig;
+use crate::derived::{
+    default_mismatch_threshold, DerivedConfig, PrecipTypeConfig, PressureConfig,
+    VirtualSensorConfig,
+};
+#[cfg(all(feature = "web", feature = "http"))]
+use crate::federation::FederationConfig;
+#[cfg(feature = "http")]
+use crate::heartbeat::HeartbeatConfig;
+#[cfg(feature = "http")]
 use crate::http_output::HttpConfig;
+use crate::local_api::GatewayApi;
+#[cfg(feature = "mqtt")]
 use crate::mqtt::MqttConfig;
+use crate::output::PrecisionConfig;
+#[cfg(any(feature = "http", feature = "mqtt"))]
+use crate::proxy::ProxyConfig;
+use crate::schedule::{PollProfile, TimestampAlignment};
+use crate::secrets;
+use crate::sensor_watch::SensorWatchConfig;
+use crate::station::StationConfig;
+use crate::storage::StorageConfig;
+use crate::units::{Units, UnitsConfig};
+#[cfg(feature = "web")]
+use crate::web::{tcp_listen_addr, ListenAddr, WebConfig};
 
 /// GW1000/Ecowitt Gateway Weather Station Listener
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(
     author,
     version,
@@ -18,7 +52,8 @@ use crate::mqtt::MqttConfig;
                   Supports continuous monitoring, web interface, JSON/text output, and database logging to PostgreSQL or MySQL."
 )]
 pub struct Args {
-    /// Weather station IP address (e.g., 192.168.1.100)
+    /// Weather station IP address (e.g., 192.168.1.100), or "auto" for the
+    /// first gateway found on the local network
     #[arg(short, long)]
     pub ip: Option<String>,
 
@@ -34,6 +69,11 @@ pub struct Args {
     #[arg(short = 'f', long, default_value = "text")]
     pub format: String,
 
+    /// Color the text output: auto (a terminal without NO_COLOR set), always
+    /// or never
+    #[arg(long, value_name = "WHEN", default_value = "auto")]
+    pub color: String,
+
     /// Continuous mode - poll every N seconds (default: 16)
     #[arg(long, default_value = "16")]
     pub continuous: u64,
@@ -53,6 +93,185 @@ pub struct Args {
     /// Create database table and exit (requires database config in config file)
     #[arg(long)]
     pub db_create_table: bool,
+
+    /// Log hex-encoded frames, checksums and parse timings for each gateway command
+    #[arg(long)]
+    pub trace_protocol: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Backfill the database from the Ecowitt cloud history API
+    /// (requires [database] and [ecowitt_cloud] in the config file)
+    Backfill {
+        /// Start of the range (UTC): YYYY-MM-DD or "YYYY-MM-DD HH:MM:SS"
+        #[arg(long)]
+        start: String,
+
+        /// End of the range (UTC, default: now)
+        #[arg(long)]
+        end: Option<String>,
+    },
+    /// Import history from WeeWX or Cumulus into the database
+    /// (requires [database] in the config file)
+    Import {
+        /// WeeWX SQLite database, Cumulus monthly log, or directory of logs
+        #[arg(long)]
+        from: PathBuf,
+
+        /// Source format: auto, weewx or cumulus
+        #[arg(long, default_value = "auto")]
+        format: String,
+
+        /// Units of Cumulus logs: metricwx (°C, hPa, m/s, mm), metric or us
+        #[arg(long, default_value = "metricwx")]
+        units: String,
+    },
+    /// Show the status of a running instance (requires it to run with --web)
+    Status {
+        /// Base URL of the running instance (default: http://127.0.0.1:<web-port>)
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Print the raw JSON status instead of a summary
+        #[arg(long)]
+        json: bool,
+
+        /// API token (default: the first of [web] tokens in the config file)
+        #[arg(long)]
+        token: Option<String>,
+
+        /// Also list the most recent events (start/stop, gateway offline,
+        /// missing sensors, output failures)
+        #[arg(long, value_name = "COUNT", num_args = 0..=1, default_missing_value = "20")]
+        events: Option<usize>,
+    },
+    /// Check the gateway, its firmware and live data, and each configured
+    /// output, and print what passed and what failed
+    Doctor,
+    /// Turn maintenance mode of a running instance on or off: readings are
+    /// flagged as suspect and alerts suppressed while it is on (requires it
+    /// to run with --web and an admin token)
+    Maintenance {
+        /// on or off
+        #[arg(value_parser = ["on", "off"])]
+        state: String,
+
+        /// Base URL of the running instance (default: http://127.0.0.1:<web-port>)
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Admin API token (default: the first admin token in [web] tokens)
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Chart a stored field in the terminal, e.g. `history --graph outtemp
+    /// --last 24h` (requires [database] in the config file)
+    History {
+        /// Field to chart, e.g. outtemp or absbarometer
+        #[arg(long, value_name = "FIELD")]
+        graph: String,
+
+        /// Period to cover: a number with s, m, h, d or w
+        #[arg(long, default_value = "24h")]
+        last: String,
+
+        /// Chart width in columns
+        #[arg(long, default_value_t = 72)]
+        width: usize,
+
+        /// Chart height in rows
+        #[arg(long, default_value_t = 10)]
+        height: usize,
+    },
+    /// Print a Home Assistant automation blueprint that uses the [mqtt]
+    /// topics from the config file
+    Blueprint {
+        /// offline (station stopped publishing) or frost (outdoor
+        /// temperature at or below a threshold)
+        #[arg(value_parser = ["offline", "frost"])]
+        kind: String,
+    },
+    /// Add upcoming monthly partitions to a MySQL table created with
+    /// mysql_partition_by_month (run monthly, e.g. from cron)
+    AddPartitions {
+        /// How many months after the current one to cover
+        #[arg(long, default_value_t = 3)]
+        months: u32,
+    },
+    /// List the gateways on the local network, found by UDP broadcast
+    Discover {
+        /// Seconds to wait for answers
+        #[arg(long, default_value_t = 3)]
+        timeout: u64,
+    },
+    /// Manage the gateway itself
+    Device {
+        #[command(subcommand)]
+        action: DeviceCommand,
+    },
+    /// Manage encrypted credentials in the config file's [secrets] table
+    Secret {
+        #[command(subcommand)]
+        action: SecretCommand,
+    },
+    /// Write the config file and state file to one archive, e.g. to move to
+    /// another machine
+    Backup {
+        /// Archive to write (default: wxlistener-backup-<date>-<time>.tar.gz)
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        /// Also export the weather records from [database]
+        #[arg(long)]
+        with_database: bool,
+    },
+    /// Restore a backup: the config file to --config, the state file to the
+    /// config's state_file and any weather records into its [database]
+    Restore {
+        /// Archive written by `backup`
+        archive: PathBuf,
+
+        /// Replace an existing config file and state file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DeviceCommand {
+    /// Set the gateway clock to the host's time (run periodically, e.g. from
+    /// cron, so the gateway's daily rain reset happens at midnight)
+    SetTime,
+    /// Show the calibration offsets configured on the gateway
+    Calibration,
+    /// Save one live data frame and what it decodes to as a test vector, to
+    /// submit in tests/vectors/contrib/
+    CaptureVector {
+        /// Vector file to write, e.g. gw2000a-v3.1.4.toml
+        output: PathBuf,
+        /// What the gateway has connected, e.g. "WS90 and two WH31"
+        #[arg(long)]
+        description: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SecretCommand {
+    /// Encrypt a value (read from stdin) into [secrets]; reference it
+    /// elsewhere in the config as ${secret:NAME}
+    Set {
+        /// Secret name, e.g. db_password
+        name: String,
+    },
+    /// Remove a secret from [secrets]
+    Remove {
+        /// Secret name
+        name: String,
+    },
 }
 
 #[derive(Debug, Deserialize)]
@@ -60,38 +279,234 @@ pub struct Config {
     pub ip: String,
     #[serde(default = "default_port")]
     pub port: u16,
+    /// Read live data over the TCP API, the local HTTP API, or TCP with HTTP
+    /// as a fallback
+    #[serde(default)]
+    pub api: GatewayApi,
+    /// Port of the gateway's local HTTP API
+    #[serde(default = "default_http_port")]
+    pub http_port: u16,
+    /// Station name, location, elevation and timezone
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub station: Option<StationConfig>,
+    #[cfg(feature = "db")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub database: Option<DatabaseConfig>,
+    /// Readings kept for history queries when there is no [database]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<StorageConfig>,
+    #[cfg(feature = "mqtt")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mqtt: Option<MqttConfig>,
+    #[cfg(feature = "http")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub http: Option<HttpConfig>,
+    /// Pings to an external monitor while the pipeline is healthy
+    #[cfg(feature = "http")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// Proxy for outbound HTTP and MQTT over WebSocket connections
+    #[cfg(any(feature = "http", feature = "mqtt"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+    /// When to stop trying a failing output for a while
+    #[cfg(any(feature = "db", feature = "mqtt", feature = "http"))]
+    #[serde(default)]
+    pub circuit_breaker: BreakerConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub degree_days: Option<DegreeDayConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pressure: Option<PressureConfig>,
+    /// Estimate whether precipitation is rain, mixed or snow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precip_type: Option<PrecipTypeConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<CalibrationConfig>,
+    #[serde(default)]
+    pub virtual_sensors: Vec<VirtualSensorConfig>,
+    #[cfg(all(feature = "db", feature = "http"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ecowitt_cloud: Option<EcowittCloudConfig>,
+    /// Where to persist aggregation state between restarts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_file: Option<PathBuf>,
+    /// Align record timestamps to the poll interval: none, round or truncate
+    #[serde(default)]
+    pub timestamp_alignment: TimestampAlignment,
+    /// Unix domain socket accepting JSON control commands
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub control_socket: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensor_watch: Option<SensorWatchConfig>,
+    /// Battery trend tracking and early warnings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery: Option<BatteryConfig>,
+    /// Largest response in bytes accepted from the gateway
+    #[serde(default = "default_max_response_size")]
+    pub max_response_size: usize,
+    /// Keep the gateway TCP connection open between polls
+    #[serde(default = "default_keep_alive")]
+    pub keep_alive: bool,
+    /// Retries of gateway commands that fail to connect or are cut off
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Seconds resolved output hosts are reused before resolving them again
+    #[serde(default = "default_dns_ttl")]
+    pub dns_ttl: u64,
+    /// Maintenance tasks run on cron schedules
+    #[serde(default)]
+    pub schedules: Vec<ScheduleRule>,
+    /// Time-of-day windows overriding the --continuous poll interval
+    #[serde(default)]
+    pub poll_profiles: Vec<PollProfile>,
+    /// Poll faster while pressure or gusts change quickly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub burst: Option<BurstConfig>,
+    /// Decimal places per field in published output
+    #[serde(default)]
+    pub precision: PrecisionConfig,
+    /// Hold calibrated gateway readings in tenths instead of floats
+    #[serde(default)]
+    pub fixed_point: bool,
+    /// Units for readings formatted for display
+    #[serde(default)]
+    pub units: UnitsConfig,
+    #[cfg(feature = "web")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web: Option<WebConfig>,
+    /// Peers exchanging readings with this instance
+    #[cfg(all(feature = "web", feature = "http"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub federation: Option<FederationConfig>,
 }
 
 fn default_port() -> u16 {
     45000
 }
 
+fn default_http_port() -> u16 {
+    80
+}
+
+/// Config sections and the cargo features that implement them
+const FEATURE_SECTIONS: &[(&str, &str, bool)] = &[
+    ("database", "db", cfg!(feature = "db")),
+    ("mqtt", "mqtt", cfg!(feature = "mqtt")),
+    ("http", "http", cfg!(feature = "http")),
+    ("heartbeat", "http", cfg!(feature = "http")),
+    (
+        "proxy",
+        "http or mqtt",
+        cfg!(any(feature = "http", feature = "mqtt")),
+    ),
+    (
+        "circuit_breaker",
+        "db, mqtt or http",
+        cfg!(any(feature = "db", feature = "mqtt", feature = "http")),
+    ),
+    ("web", "web", cfg!(feature = "web")),
+    (
+        "ecowitt_cloud",
+        "db and http",
+        cfg!(all(feature = "db", feature = "http")),
+    ),
+    (
+        "federation",
+        "web and http",
+        cfg!(all(feature = "web", feature = "http")),
+    ),
+];
+
+/// Sections present in a config file that this build was compiled without,
+/// as (section, feature) pairs
+pub fn disabled_sections(config_str: &str) -> Result<Vec<(&'static str, &'static str)>> {
+    let table: toml::Table = toml::from_str(config_str).context("Failed to parse config file")?;
+    Ok(FEATURE_SECTIONS
+        .iter()
+        .filter(|(section, _, enabled)| !enabled && table.contains_key(*section))
+        .map(|(section, feature, _)| (*section, *feature))
+        .collect())
+}
+
+fn default_max_response_size() -> usize {
+    DEFAULT_MAX_RESPONSE_SIZE
+}
+
+fn default_keep_alive() -> bool {
+    true
+}
+
+fn default_dns_ttl() -> u64 {
+    300
+}
+
+/// The gateway found for `ip = "auto"`, so the network is only searched
+/// once per run
+static DISCOVERED: Mutex<Option<(String, u16)>> = Mutex::new(None);
+
+/// `ip` and `port` as given, or those of the first gateway to answer a
+/// discovery broadcast for "auto"
+fn resolve_ip(ip: String, port: u16) -> Result<(String, u16)> {
+    if !ip.eq_ignore_ascii_case("auto") {
+        return Ok((ip, port));
+    }
+    let mut discovered = DISCOVERED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(found) = discovered.as_ref() {
+        return Ok(found.clone());
+    }
+    let gateway = discover(DISCOVERY_TIMEOUT)?
+        .into_iter()
+        .next()
+        .context("No gateway answered the discovery broadcast; give its IP address instead")?;
+    println!(
+        "[OK] Found {} at {}:{} (MAC {})",
+        gateway.model, gateway.ip, gateway.port, gateway.mac
+    );
+    let found = (gateway.ip.to_string(), gateway.port);
+    *discovered = Some(found.clone());
+    Ok(found)
+}
+
+/// Read and parse the config file, filling in `${secret:NAME}` references
+/// from its `[secrets]` table
+fn load_config(config_path: &Path) -> Result<Config> {
+    let config_str = fs::read_to_string(config_path)
+        .context(format!("Failed to read config file: {:?}", config_path))?;
+    let mut table: toml::Table =
+        toml::from_str(&config_str).context("Failed to parse config file")?;
+    secrets::resolve(&mut table)?;
+    table.try_into().context("Failed to parse config file")
+}
+
 impl Args {
+    /// Config file sections this build ignores because their feature is
+    /// disabled, as (section, feature) pairs
+    pub fn get_disabled_sections(&self) -> Result<Vec<(&'static str, &'static str)>> {
+        if let Some(config_path) = &self.config {
+            let config_str = fs::read_to_string(config_path)
+                .context(format!("Failed to read config file: {:?}", config_path))?;
+            disabled_sections(&config_str)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
     /// Get IP and port from either command line args, config file, or environment variables
     pub fn get_connection_info(&self) -> Result<(String, u16)> {
         // Priority: CLI args > config file > environment variables
         if let Some(config_path) = &self.config {
-            let config_str = fs::read_to_string(config_path)
-                .context(format!("Failed to read config file: {:?}", config_path))?;
-            let config: Config =
-                toml::from_str(&config_str).context("Failed to parse config file")?;
-            Ok((config.ip, config.port))
+            let config = load_config(config_path)?;
+            resolve_ip(config.ip, config.port)
         } else if let Some(ip) = &self.ip {
             let port = self.port.unwrap_or(45000);
-            Ok((ip.clone(), port))
+            resolve_ip(ip.clone(), port)
         } else if let Ok(ip) = std::env::var("WXLISTENER_IP") {
             // Try environment variables
             let port = std::env::var("WXLISTENER_PORT")
                 .ok()
                 .and_then(|p| p.parse().ok())
                 .unwrap_or(45000);
-            Ok((ip, port))
+            resolve_ip(ip, port)
         } else {
             anyhow::bail!(
                 "Weather station IP must be specified via:\n\
@@ -106,38 +521,48 @@ impl Args {
     }
 
     /// Get database configuration from config file if present
+    #[cfg(feature = "db")]
     pub fn get_database_config(&self) -> Result<Option<DatabaseConfig>> {
         if let Some(config_path) = &self.config {
-            let config_str = fs::read_to_string(config_path)
-                .context(format!("Failed to read config file: {:?}", config_path))?;
-            let config: Config =
-                toml::from_str(&config_str).context("Failed to parse config file")?;
+            let config = load_config(config_path)?;
             Ok(config.database)
         } else {
             Ok(None)
         }
     }
 
+    /// Get local storage configuration from config file if present
+    pub fn get_storage_config(&self) -> Result<Option<StorageConfig>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.storage)
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Get MQTT configuration from config file if present
+    #[cfg(feature = "mqtt")]
     pub fn get_mqtt_config(&self) -> Result<Option<MqttConfig>> {
         if let Some(config_path) = &self.config {
-            let config_str = fs::read_to_string(config_path)
-                .context(format!("Failed to read config file: {:?}", config_path))?;
-            let config: Config =
-                toml::from_str(&config_str).context("Failed to parse config file")?;
-            Ok(config.mqtt)
+            let config = load_config(config_path)?;
+            // {station} falls back to the [station] name
+            Ok(config.mqtt.map(|mqtt| MqttConfig {
+                station: mqtt
+                    .station
+                    .or_else(|| config.station.and_then(|station| station.name)),
+                ..mqtt
+            }))
         } else {
             Ok(None)
         }
     }
 
     /// Get HTTP configuration from config file or environment variables
+    #[cfg(feature = "http")]
     pub fn get_http_config(&self) -> Result<Option<HttpConfig>> {
         if let Some(config_path) = &self.config {
-            let config_str = fs::read_to_string(config_path)
-                .context(format!("Failed to read config file: {:?}", config_path))?;
-            let config: Config =
-                toml::from_str(&config_str).context("Failed to parse config file")?;
+            let config = load_config(config_path)?;
             Ok(config.http)
         } else if std::env::var("WXLISTENER_HTTP_URL").is_ok() {
             // Create config from environment variables
@@ -146,6 +571,371 @@ impl Args {
             Ok(None)
         }
     }
+
+    /// Get federation settings from config file if present. The name sent
+    /// to peers falls back to the [station] name.
+    #[cfg(all(feature = "web", feature = "http"))]
+    pub fn get_federation_config(&self) -> Result<Option<FederationConfig>> {
+        let Some(config_path) = &self.config else {
+            return Ok(None);
+        };
+        let config = load_config(config_path)?;
+        let Some(federation) = config.federation else {
+            return Ok(None);
+        };
+        let federation = FederationConfig {
+            name: federation
+                .name
+                .or_else(|| config.station.and_then(|station| station.name)),
+            ..federation
+        };
+        federation.validate()?;
+        Ok(Some(federation))
+    }
+
+    /// Get heartbeat ping settings from config file if present
+    #[cfg(feature = "http")]
+    pub fn get_heartbeat_config(&self) -> Result<Option<HeartbeatConfig>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            if let Some(ref heartbeat) = config.heartbeat {
+                heartbeat.validate()?;
+            }
+            Ok(config.heartbeat)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get the outbound proxy from config file if present
+    #[cfg(any(feature = "http", feature = "mqtt"))]
+    pub fn get_proxy_config(&self) -> Result<Option<ProxyConfig>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.proxy)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Output circuit breaker settings from config file, or the defaults
+    #[cfg(any(feature = "db", feature = "mqtt", feature = "http"))]
+    pub fn get_breaker_config(&self) -> Result<BreakerConfig> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            config.circuit_breaker.validate()?;
+            Ok(config.circuit_breaker)
+        } else {
+            Ok(BreakerConfig::default())
+        }
+    }
+
+    /// Get degree day configuration from config file if present
+    pub fn get_degree_day_config(&self) -> Result<Option<DegreeDayConfig>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.degree_days)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get sensor calibration from config file if present
+    pub fn get_calibration_config(&self) -> Result<Option<CalibrationConfig>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            if let Some(ref calibration) = config.calibration {
+                calibration.validate()?;
+            }
+            Ok(config.calibration)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get Ecowitt cloud API configuration from config file if present
+    #[cfg(all(feature = "db", feature = "http"))]
+    pub fn get_ecowitt_cloud_config(&self) -> Result<Option<EcowittCloudConfig>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.ecowitt_cloud)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get the station description from config file, empty if not configured
+    pub fn get_station_config(&self) -> Result<StationConfig> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            let station = config.station.unwrap_or_default();
+            station.validate()?;
+            Ok(station)
+        } else {
+            Ok(StationConfig::default())
+        }
+    }
+
+    /// Get the state file path from config file if present
+    pub fn get_state_file(&self) -> Result<Option<PathBuf>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.state_file)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get the control socket path from config file if present
+    pub fn get_control_socket(&self) -> Result<Option<PathBuf>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.control_socket)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get missing-sensor detection settings, using defaults if not configured
+    pub fn get_sensor_watch_config(&self) -> Result<SensorWatchConfig> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.sensor_watch.unwrap_or_default())
+        } else {
+            Ok(SensorWatchConfig::default())
+        }
+    }
+
+    /// Get battery tracking settings, using defaults if not configured
+    pub fn get_battery_config(&self) -> Result<BatteryConfig> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.battery.unwrap_or_default())
+        } else {
+            Ok(BatteryConfig::default())
+        }
+    }
+
+    /// Get the timestamp alignment mode from config file if present
+    pub fn get_timestamp_alignment(&self) -> Result<TimestampAlignment> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.timestamp_alignment)
+        } else {
+            Ok(TimestampAlignment::default())
+        }
+    }
+
+    /// Get the response size limit from config file, or the default
+    pub fn get_max_response_size(&self) -> Result<usize> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.max_response_size)
+        } else {
+            Ok(DEFAULT_MAX_RESPONSE_SIZE)
+        }
+    }
+
+    /// Whether to keep the gateway TCP connection open between polls, from
+    /// config file, or yes
+    pub fn get_keep_alive(&self) -> Result<bool> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.keep_alive)
+        } else {
+            Ok(default_keep_alive())
+        }
+    }
+
+    /// Retries of failed gateway commands, from config file, or the default
+    pub fn get_retry_policy(&self) -> Result<RetryPolicy> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            config.retry.validate()?;
+            Ok(config.retry)
+        } else {
+            Ok(RetryPolicy::default())
+        }
+    }
+
+    /// How long resolved output hosts are reused, from config file, or the
+    /// default
+    pub fn get_dns_ttl(&self) -> Result<std::time::Duration> {
+        let secs = if let Some(config_path) = &self.config {
+            load_config(config_path)?.dns_ttl
+        } else {
+            default_dns_ttl()
+        };
+        Ok(std::time::Duration::from_secs(secs))
+    }
+
+    /// Get the live data API and the gateway's HTTP port from config file,
+    /// or the TCP API
+    pub fn get_gateway_api(&self) -> Result<(GatewayApi, u16)> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok((config.api, config.http_port))
+        } else {
+            Ok((GatewayApi::default(), default_http_port()))
+        }
+    }
+
+    /// Get scheduled tasks from config file if present
+    pub fn get_schedules(&self) -> Result<Vec<ScheduleRule>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.schedules)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Get time-of-day poll profiles from config file if present
+    pub fn get_poll_profiles(&self) -> Result<Vec<PollProfile>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            for profile in &config.poll_profiles {
+                profile.validate()?;
+            }
+            Ok(config.poll_profiles)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Get adaptive burst polling settings from config file if present
+    pub fn get_burst_config(&self) -> Result<Option<BurstConfig>> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            if let Some(ref burst) = config.burst {
+                burst.validate()?;
+            }
+            Ok(config.burst)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Web server settings from config file. Without `[web] listen` the
+    /// server listens on --web-host and --web-port.
+    #[cfg(feature = "web")]
+    pub fn get_web_config(&self) -> Result<WebConfig> {
+        let mut web = if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            let mut web = config.web.unwrap_or_default();
+            // clientraw.txt shows the [station] name unless [web] sets one
+            if let Some(name) = config.station.and_then(|station| station.name) {
+                if web.station_name == WebConfig::default().station_name {
+                    web.station_name = name;
+                }
+            }
+            web
+        } else {
+            WebConfig::default()
+        };
+        if web.tokens.iter().any(|token| token.token.is_empty()) {
+            anyhow::bail!("Invalid [web] tokens: a token must not be empty");
+        }
+        for (key, path) in [
+            ("realtime_txt", &web.realtime_txt),
+            ("clientraw_txt", &web.clientraw_txt),
+        ] {
+            // Fixed paths clear of the built-in routes; `:` and `*` would be
+            // axum captures
+            if let Some(path) = path {
+                if !path.starts_with('/')
+                    || path.contains([':', '*'])
+                    || path.starts_with("/api/")
+                    || ["/", "/ws", "/get_livedata_info"].contains(&path.as_str())
+                {
+                    anyhow::bail!(
+                        "Invalid [web] {}: {:?} must be a path like \"/{}\" outside /api/",
+                        key,
+                        path,
+                        key.replace('_', ".")
+                    );
+                }
+            }
+        }
+        if web.realtime_txt.is_some() && web.realtime_txt == web.clientraw_txt {
+            anyhow::bail!("Invalid [web]: realtime_txt and clientraw_txt must differ");
+        }
+        if web.listen.is_empty() {
+            web.listen = vec![ListenAddr::Tcp(tcp_listen_addr(
+                &self.web_host,
+                self.web_port,
+            ))];
+        }
+        Ok(web)
+    }
+
+    /// Whether readings are held in fixed-point tenths (default: false)
+    pub fn get_fixed_point(&self) -> Result<bool> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.fixed_point)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Get output precision from config file, or no rounding
+    pub fn get_precision_config(&self) -> Result<PrecisionConfig> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.precision)
+        } else {
+            Ok(PrecisionConfig::default())
+        }
+    }
+
+    /// Get display units from config file, or metric
+    pub fn get_units(&self) -> Result<Units> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            Ok(config.units.units())
+        } else {
+            Ok(Units::default())
+        }
+    }
+
+    /// Get configuration for derived fields from config file if present
+    pub fn get_derived_config(&self) -> Result<DerivedConfig> {
+        if let Some(config_path) = &self.config {
+            let config = load_config(config_path)?;
+            for sensor in &config.virtual_sensors {
+                sensor.validate()?;
+            }
+            if let Some(ref precip_type) = config.precip_type {
+                precip_type.validate()?;
+            }
+            // Pressure correction takes the [station] elevation unless
+            // [pressure] sets its own altitude
+            let elevation = config.station.and_then(|station| station.elevation);
+            let pressure = match (config.pressure, elevation) {
+                (Some(pressure), _) if pressure.altitude.is_some() => Some(pressure),
+                (Some(pressure), Some(elevation)) => Some(PressureConfig {
+                    altitude: Some(elevation),
+                    ..pressure
+                }),
+                (Some(_), None) => {
+                    anyhow::bail!("Invalid [pressure]: set altitude, or elevation in [station]")
+                }
+                (None, Some(elevation)) => Some(PressureConfig {
+                    altitude: Some(elevation),
+                    mismatch_threshold: default_mismatch_threshold(),
+                }),
+                (None, None) => None,
+            };
+            Ok(DerivedConfig {
+                pressure,
+                virtual_sensors: config.virtual_sensors,
+                precip_type: config.precip_type,
+            })
+        } else {
+            Ok(DerivedConfig::default())
+        }
+    }
 }
 
 #[cfg(test)]
@@ -162,11 +952,14 @@ mod tests {
             port: Some(45000),
             config: None,
             format: "text".to_string(),
+            color: "auto".to_string(),
             continuous: 16,
             web: false,
             web_host: "0.0.0.0".to_string(),
             web_port: 18888,
             db_create_table: false,
+            trace_protocol: false,
+            command: None,
         };
 
         let (ip, port) = args.get_connection_info().unwrap();
@@ -181,11 +974,14 @@ mod tests {
             port: None,
             config: None,
             format: "text".to_string(),
+            color: "auto".to_string(),
             continuous: 16,
             web: false,
             web_host: "0.0.0.0".to_string(),
             web_port: 18888,
             db_create_table: false,
+            trace_protocol: false,
+            command: None,
         };
 
         let (ip, port) = args.get_connection_info().unwrap();
@@ -204,11 +1000,14 @@ mod tests {
             port: None,
             config: Some(temp_file.path().to_path_buf()),
             format: "text".to_string(),
+            color: "auto".to_string(),
             continuous: 16,
             web: false,
             web_host: "0.0.0.0".to_string(),
             web_port: 18888,
             db_create_table: false,
+            trace_protocol: false,
+            command: None,
         };
 
         let (ip, port) = args.get_connection_info().unwrap();
@@ -216,6 +1015,166 @@ mod tests {
         assert_eq!(port, 12345);
     }
 
+    #[cfg(feature = "mqtt")]
+    #[test]
+    #[serial]
+    fn test_secret_reference() {
+        let encrypted = secrets::encrypt_with("mqtt_password", "s3cret", "pass", 4).unwrap();
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "ip = \"172.16.0.1\"").unwrap();
+        writeln!(temp_file, "[mqtt]\nhost = \"localhost\"").unwrap();
+        writeln!(temp_file, "password = \"${{secret:mqtt_password}}\"").unwrap();
+        writeln!(temp_file, "[secrets]\nmqtt_password = \"{}\"", encrypted).unwrap();
+
+        let args = Args {
+            ip: None,
+            port: None,
+            config: Some(temp_file.path().to_path_buf()),
+            format: "text".to_string(),
+            color: "auto".to_string(),
+            continuous: 16,
+            web: false,
+            web_host: "0.0.0.0".to_string(),
+            web_port: 18888,
+            db_create_table: false,
+            trace_protocol: false,
+            command: None,
+        };
+        std::env::set_var(secrets::PASSPHRASE_ENV, "pass");
+        let mqtt = args.get_mqtt_config();
+        std::env::remove_var(secrets::PASSPHRASE_ENV);
+        assert_eq!(mqtt.unwrap().unwrap().password.as_deref(), Some("s3cret"));
+        // The whole file is resolved, so nothing loads without the passphrase
+        assert!(args.get_connection_info().is_err());
+    }
+
+    #[test]
+    fn test_station_config() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "ip = \"172.16.0.1\"").unwrap();
+        writeln!(
+            temp_file,
+            "[station]\nname = \"Backyard\"\nelevation = 152.0"
+        )
+        .unwrap();
+        writeln!(temp_file, "timezone = \"Europe/Amsterdam\"").unwrap();
+        writeln!(temp_file, "[pressure]\nmismatch_threshold = 0.5").unwrap();
+        writeln!(temp_file, "[precip_type]\nsnow_below = 1.0").unwrap();
+
+        let args = Args {
+            ip: None,
+            port: None,
+            config: Some(temp_file.path().to_path_buf()),
+            format: "text".to_string(),
+            color: "auto".to_string(),
+            continuous: 16,
+            web: false,
+            web_host: "0.0.0.0".to_string(),
+            web_port: 18888,
+            db_create_table: false,
+            trace_protocol: false,
+            command: None,
+        };
+        let station = args.get_station_config().unwrap();
+        assert_eq!(station.name.as_deref(), Some("Backyard"));
+        assert_eq!(station.timezone.as_deref(), Some("Europe/Amsterdam"));
+
+        // Pressure correction takes the elevation, keeping its own threshold
+        let pressure = args.get_derived_config().unwrap().pressure.unwrap();
+        assert_eq!(pressure.altitude, Some(152.0));
+        assert_eq!(pressure.mismatch_threshold, 0.5);
+
+        let precip_type = args.get_derived_config().unwrap().precip_type.unwrap();
+        assert_eq!(precip_type.snow_below, 1.0);
+        assert_eq!(precip_type.rain_above, 2.0);
+    }
+
+    #[cfg(feature = "web")]
+    #[test]
+    fn test_get_web_config() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "ip = \"172.16.0.1\"").unwrap();
+
+        let mut args = Args {
+            ip: None,
+            port: None,
+            config: Some(temp_file.path().to_path_buf()),
+            format: "text".to_string(),
+            color: "auto".to_string(),
+            continuous: 16,
+            web: true,
+            web_host: "::".to_string(),
+            web_port: 18888,
+            db_create_table: false,
+            trace_protocol: false,
+            command: None,
+        };
+        // No [web] section: the command line flags
+        let web = args.get_web_config().unwrap();
+        assert_eq!(web.listen, vec![ListenAddr::Tcp("[::]:18888".to_string())]);
+        assert_eq!(web.socket_mode, 0o660);
+
+        writeln!(temp_file, "[web]").unwrap();
+        writeln!(
+            temp_file,
+            "listen = [\"127.0.0.1:8080\", \"[::1]:8080\", \"unix:/run/wx.sock\"]"
+        )
+        .unwrap();
+        writeln!(temp_file, "socket_mode = 0o600").unwrap();
+        args.web_host = "0.0.0.0".to_string();
+        let web = args.get_web_config().unwrap();
+        assert_eq!(
+            web.listen,
+            vec![
+                ListenAddr::Tcp("127.0.0.1:8080".to_string()),
+                ListenAddr::Tcp("[::1]:8080".to_string()),
+                ListenAddr::Unix("/run/wx.sock".into()),
+            ]
+        );
+        assert_eq!(web.socket_mode, 0o600);
+    }
+
+    #[cfg(feature = "web")]
+    #[test]
+    fn test_get_web_config_template_paths() {
+        let args_for = |web: &str| {
+            let mut temp_file = NamedTempFile::new().unwrap();
+            writeln!(temp_file, "ip = \"172.16.0.1\"\n[web]\n{}", web).unwrap();
+            let args = Args {
+                ip: None,
+                port: None,
+                config: Some(temp_file.path().to_path_buf()),
+                format: "text".to_string(),
+                color: "auto".to_string(),
+                continuous: 16,
+                web: true,
+                web_host: "0.0.0.0".to_string(),
+                web_port: 18888,
+                db_create_table: false,
+                trace_protocol: false,
+                command: None,
+            };
+            (temp_file, args)
+        };
+
+        let (_file, args) = args_for("realtime_txt = \"/realtime.txt\"");
+        let web = args.get_web_config().unwrap();
+        assert_eq!(web.realtime_txt.as_deref(), Some("/realtime.txt"));
+        assert_eq!(web.clientraw_txt, None);
+        assert_eq!(web.station_name, "wxlistener");
+
+        for bad in [
+            "realtime_txt = \"realtime.txt\"",
+            "clientraw_txt = \"/api/v1/clientraw.txt\"",
+            "clientraw_txt = \"/ws\"",
+            "realtime_txt = \"/:file\"",
+            "realtime_txt = \"/wx.txt\"\nclientraw_txt = \"/wx.txt\"",
+        ] {
+            let (_file, args) = args_for(bad);
+            assert!(args.get_web_config().is_err(), "{}", bad);
+        }
+    }
+
     #[test]
     fn test_get_connection_info_from_config_default_port() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -227,11 +1186,14 @@ mod tests {
             port: None,
             config: Some(temp_file.path().to_path_buf()),
             format: "text".to_string(),
+            color: "auto".to_string(),
             continuous: 16,
             web: false,
             web_host: "0.0.0.0".to_string(),
             web_port: 18888,
             db_create_table: false,
+            trace_protocol: false,
+            command: None,
         };
 
         let (ip, port) = args.get_connection_info().unwrap();
@@ -251,11 +1213,14 @@ mod tests {
             port: None,
             config: None,
             format: "text".to_string(),
+            color: "auto".to_string(),
             continuous: 16,
             web: false,
             web_host: "0.0.0.0".to_string(),
             web_port: 18888,
             db_create_table: false,
+            trace_protocol: false,
+            command: None,
         };
 
         let result = args.get_connection_info();
@@ -273,11 +1238,14 @@ mod tests {
             port: None,
             config: Some(PathBuf::from("/nonexistent/config.toml")),
             format: "text".to_string(),
+            color: "auto".to_string(),
             continuous: 16,
             web: false,
             web_host: "0.0.0.0".to_string(),
             web_port: 18888,
             db_create_table: false,
+            trace_protocol: false,
+            command: None,
         };
 
         let result = args.get_connection_info();
@@ -298,11 +1266,14 @@ mod tests {
             port: None,
             config: Some(temp_file.path().to_path_buf()),
             format: "text".to_string(),
+            color: "auto".to_string(),
             continuous: 16,
             web: false,
             web_host: "0.0.0.0".to_string(),
             web_port: 18888,
             db_create_table: false,
+            trace_protocol: false,
+            command: None,
         };
 
         let result = args.get_connection_info();
@@ -313,6 +1284,18 @@ mod tests {
             .contains("Failed to parse config file"));
     }
 
+    #[test]
+    fn test_disabled_sections() {
+        let config = "ip = \"10.0.0.1\"\n[mqtt]\nhost = \"localhost\"\n[web]\nlisten = []\n";
+        let disabled = disabled_sections(config).unwrap();
+        assert_eq!(
+            disabled.contains(&("mqtt", "mqtt")),
+            !cfg!(feature = "mqtt")
+        );
+        assert_eq!(disabled.contains(&("web", "web")), !cfg!(feature = "web"));
+        assert!(!disabled.iter().any(|(section, _)| *section == "database"));
+    }
+
     #[test]
     fn test_default_port() {
         assert_eq!(default_port(), 45000);
@@ -341,6 +1324,96 @@ mod tests {
         assert_eq!(config.port, 45000); // Default
     }
 
+    #[test]
+    fn test_config_deserialization_max_response_size() {
+        let config: Config = toml::from_str(r#"ip = "10.31.100.42""#).unwrap();
+        assert_eq!(config.max_response_size, 64 * 1024); // Default
+
+        let toml_str = r#"
+            ip = "10.31.100.42"
+            max_response_size = 4096
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.max_response_size, 4096);
+    }
+
+    #[test]
+    fn test_config_deserialization_keep_alive() {
+        let config: Config = toml::from_str(r#"ip = "10.31.100.42""#).unwrap();
+        assert!(config.keep_alive); // Default
+
+        let toml_str = r#"
+            ip = "10.31.100.42"
+            keep_alive = false
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(!config.keep_alive);
+    }
+
+    #[test]
+    fn test_config_deserialization_retry() {
+        let config: Config = toml::from_str(r#"ip = "10.31.100.42""#).unwrap();
+        assert_eq!(config.retry, RetryPolicy::default());
+        assert_eq!(config.retry.attempts, 3);
+
+        let toml_str = r#"
+            ip = "10.31.100.42"
+
+            [retry]
+            attempts = 5
+            backoff_ms = 250
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.retry.attempts, 5);
+        assert_eq!(config.retry.backoff_ms, 250);
+        assert_eq!(config.retry.max_backoff_ms, 8000);
+        assert!(config.retry.validate().is_ok());
+
+        for toml_str in ["attempts = 0", "jitter = 1.5"] {
+            let policy: RetryPolicy = toml::from_str(toml_str).unwrap();
+            assert!(policy.validate().is_err(), "{}", toml_str);
+        }
+    }
+
+    #[test]
+    fn test_config_deserialization_dns_ttl() {
+        let config: Config = toml::from_str(r#"ip = "10.31.100.42""#).unwrap();
+        assert_eq!(config.dns_ttl, 300); // Default
+
+        let config: Config = toml::from_str("ip = \"10.31.100.42\"\ndns_ttl = 0").unwrap();
+        assert_eq!(config.dns_ttl, 0);
+    }
+
+    #[test]
+    fn test_config_deserialization_api() {
+        let config: Config = toml::from_str(r#"ip = "10.31.100.42""#).unwrap();
+        assert_eq!(config.api, GatewayApi::Tcp);
+        assert_eq!(config.http_port, 80);
+
+        let toml_str = r#"
+            ip = "10.31.100.42"
+            api = "auto"
+            http_port = 8080
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.api, GatewayApi::Auto);
+        assert_eq!(config.http_port, 8080);
+        assert!(toml::from_str::<Config>("ip = \"10.31.100.42\"\napi = \"udp\"").is_err());
+    }
+
+    #[test]
+    fn test_config_deserialization_fixed_point() {
+        let config: Config = toml::from_str(r#"ip = "10.31.100.42""#).unwrap();
+        assert!(!config.fixed_point);
+
+        let toml_str = r#"
+            ip = "10.31.100.42"
+            fixed_point = true
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(config.fixed_point);
+    }
+
     #[test]
     #[serial]
     fn test_get_connection_info_from_env() {
@@ -357,11 +1430,14 @@ mod tests {
             port: None,
             config: None,
             format: "text".to_string(),
+            color: "auto".to_string(),
             continuous: 16,
             web: false,
             web_host: "0.0.0.0".to_string(),
             web_port: 18888,
             db_create_table: false,
+            trace_protocol: false,
+            command: None,
         };
 
         let (ip, port) = args.get_connection_info().unwrap();
@@ -388,11 +1464,14 @@ mod tests {
             port: None,
             config: None,
             format: "text".to_string(),
+            color: "auto".to_string(),
             continuous: 16,
             web: false,
             web_host: "0.0.0.0".to_string(),
             web_port: 18888,
             db_create_table: false,
+            trace_protocol: false,
+            command: None,
         };
 
         let (ip, port) = args.get_connection_info().unwrap();
@@ -416,11 +1495,14 @@ mod tests {
             port: Some(9999),
             config: None,
             format: "text".to_string(),
+            color: "auto".to_string(),
             continuous: 16,
             web: false,
             web_host: "0.0.0.0".to_string(),
             web_port: 18888,
             db_create_table: false,
+            trace_protocol: false,
+            command: None,
         };
 
         let (ip, port) = args.get_connection_info().unwrap();
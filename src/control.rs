@@ -0,0 +1,478 @@
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+use crate::device_queue::{DeviceQueue, Priority};
+use crate::events::{EventKind, EVENT_LOG_CAPACITY};
+use crate::status::{Settings, SharedStatus};
+
+/// Runtime controls shared between the polling loop and the control socket
+#[derive(Debug, Default)]
+pub struct Controls {
+    poll_now: Notify,
+    reload_requested: AtomicBool,
+    settings_changed: AtomicBool,
+    /// The gateway, for device commands
+    device: Option<DeviceQueue>,
+}
+
+pub type SharedControls = Arc<Controls>;
+
+impl Controls {
+    /// Controls that can also run device commands, queued with the polls
+    pub fn with_device_queue(device: DeviceQueue) -> Self {
+        Self {
+            device: Some(device),
+            ..Default::default()
+        }
+    }
+
+    /// Wake the polling loop for an immediate poll
+    pub fn request_poll(&self) {
+        self.poll_now.notify_one();
+    }
+
+    /// Resolves when an immediate poll has been requested
+    pub async fn poll_requested(&self) {
+        self.poll_now.notified().await;
+    }
+
+    pub fn request_reload(&self) {
+        self.reload_requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether a reload was requested since the last call
+    pub fn take_reload(&self) -> bool {
+        self.reload_requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Have the polling loop save the new settings and poll on the new
+    /// interval right away
+    pub fn notify_settings_changed(&self) {
+        self.settings_changed.store(true, Ordering::SeqCst);
+        self.request_poll();
+    }
+
+    /// Whether the settings changed since the last call
+    pub fn take_settings_changed(&self) -> bool {
+        self.settings_changed.swap(false, Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    command: String,
+    /// Output to pause or resume (default: all outputs)
+    output: Option<String>,
+    /// Most recent events to return (default: all kept)
+    limit: Option<usize>,
+    /// Turn maintenance mode on or off
+    enabled: Option<bool>,
+}
+
+fn error(message: impl Into<String>) -> Value {
+    json!({ "ok": false, "error": message.into() })
+}
+
+/// Handle one JSON request line and build the JSON response
+pub async fn handle_request(line: &str, controls: &Controls, status: &SharedStatus) -> Value {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return error(format!("Invalid request: {}", e)),
+    };
+
+    match request.command.as_str() {
+        "current" => {
+            let report = status.lock().await.report();
+            json!({
+                "ok": true,
+                "timestamp": report.last_poll,
+                "data": report.readings,
+            })
+        }
+        "status" => {
+            let report = status.lock().await.report();
+            json!({ "ok": true, "status": report })
+        }
+        "events" => {
+            let limit = request.limit.unwrap_or(EVENT_LOG_CAPACITY);
+            let events = status.lock().await.recent_events(limit);
+            json!({ "ok": true, "events": events })
+        }
+        "poll" => {
+            controls.request_poll();
+            json!({ "ok": true })
+        }
+        "reload" => {
+            controls.request_reload();
+            json!({ "ok": true })
+        }
+        "pause" | "resume" => {
+            let mut status = status.lock().await;
+            let output = request.output.as_deref();
+            let result = if request.command == "pause" {
+                status.pause(output)
+            } else {
+                status.resume(output)
+            };
+            match result {
+                Ok(()) => json!({ "ok": true, "paused": status.paused() }),
+                Err(e) => error(e.to_string()),
+            }
+        }
+        "maintenance" => {
+            let Some(enabled) = request.enabled else {
+                return error("maintenance needs \"enabled\": true or false");
+            };
+            let mut status = status.lock().await;
+            status.set_maintenance(enabled, Utc::now());
+            json!({ "ok": true, "maintenance_since": status.maintenance_since() })
+        }
+        "set_time" | "calibration" => {
+            let Some(ref device) = controls.device else {
+                return error("No gateway connection to run device commands on");
+            };
+            let result = if request.command == "set_time" {
+                let now = Utc::now();
+                device
+                    .run(Priority::Control, move |gateway| {
+                        gateway.tcp_command()?.set_time(now.timestamp() as u32)
+                    })
+                    .await
+                    .map(|drift| json!({ "ok": true, "time": now, "drift": drift }))
+            } else {
+                device
+                    .run(Priority::Control, |gateway| {
+                        gateway.tcp_command()?.get_calibration_offsets()
+                    })
+                    .await
+                    .map(|offsets| json!({ "ok": true, "offsets": offsets }))
+            };
+            result.unwrap_or_else(|e| error(format!("{:#}", e)))
+        }
+        other => error(format!(
+            "Unknown command '{}': use current, status, events, poll, reload, pause, resume, maintenance, set_time or calibration",
+            other
+        )),
+    }
+}
+
+/// Apply a JSON settings change, as sent to PATCH /api/v1/settings, and
+/// respond with the resulting settings
+pub async fn update_settings(body: &str, controls: &Controls, status: &SharedStatus) -> Value {
+    let change: Settings = match serde_json::from_str(body) {
+        Ok(change) => change,
+        Err(e) => return error(format!("Invalid settings: {}", e)),
+    };
+
+    let mut status = status.lock().await;
+    if let Err(e) = status.update_settings(&change) {
+        return error(e.to_string());
+    }
+    let settings = status.settings().clone();
+    status.log_event(
+        EventKind::SettingsChanged,
+        format!(
+            "Settings changed: {}",
+            serde_json::to_string(&change).unwrap_or_default()
+        ),
+    );
+    drop(status);
+    controls.notify_settings_changed();
+    json!({ "ok": true, "settings": settings })
+}
+
+/// Send a command to a running instance's POST /api/v1/control and return
+/// its response
+#[cfg(feature = "http")]
+pub async fn send_command(
+    base_url: &str,
+    token: Option<&str>,
+    command: &Value,
+) -> anyhow::Result<Value> {
+    use anyhow::Context;
+
+    let url = format!("{}/api/v1/control", base_url.trim_end_matches('/'));
+    let client = crate::proxy::client_builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .context("Failed to create HTTP client")?;
+    let mut request = client.post(&url).body(command.to_string());
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response: Value = request
+        .send()
+        .await
+        .context(format!("Failed to reach wxlistener at {}", url))?
+        .error_for_status()
+        .context("Control request failed")?
+        .json()
+        .await
+        .context("Failed to parse control response")?;
+    match response["ok"].as_bool() {
+        Some(true) => Ok(response),
+        _ => anyhow::bail!(
+            "Control command failed: {}",
+            response["error"].as_str().unwrap_or("unknown error")
+        ),
+    }
+}
+
+/// Listen on a Unix domain socket for newline-delimited JSON commands
+#[cfg(unix)]
+pub async fn run_control_socket(
+    path: std::path::PathBuf,
+    controls: SharedControls,
+    status: SharedStatus,
+) -> anyhow::Result<()> {
+    use anyhow::Context;
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    // A stale socket from a previous run would make bind fail
+    if path.exists() {
+        std::fs::remove_file(&path)
+            .context(format!("Failed to remove stale control socket: {:?}", path))?;
+    }
+    let listener =
+        UnixListener::bind(&path).context(format!("Failed to bind control socket: {:?}", path))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .context("Failed to set control socket permissions")?;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let controls = controls.clone();
+        let status = status.clone();
+
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let response = handle_request(&line, &controls, &status).await;
+                let mut out = response.to_string();
+                out.push('\n');
+                if writer.write_all(out.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Spawns the control socket listener as a background task
+pub fn run_control_socket_background(
+    path: std::path::PathBuf,
+    controls: SharedControls,
+    status: SharedStatus,
+) {
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        if let Err(e) = run_control_socket(path, controls, status).await {
+            eprintln!("[ERROR] Control socket error: {:#}", e);
+        }
+    });
+
+    #[cfg(not(unix))]
+    {
+        let _ = (path, controls, status);
+        eprintln!("[WARN] control_socket is only supported on Unix platforms");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::GW1000Client;
+    use crate::local_api::{Gateway, GatewayApi, LocalHttpClient};
+    use crate::status::DaemonStatus;
+    use std::collections::HashMap;
+    use tokio::sync::Mutex;
+
+    fn test_status() -> SharedStatus {
+        let mut status = DaemonStatus::new(Utc::now());
+        let mut data = HashMap::new();
+        data.insert("outtemp".to_string(), 12.5);
+        status.record_poll(&data, Utc::now());
+        Arc::new(Mutex::new(status))
+    }
+
+    #[tokio::test]
+    async fn test_handle_current() {
+        let controls = Controls::default();
+        let response = handle_request(r#"{"command":"current"}"#, &controls, &test_status()).await;
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["data"]["outtemp"], 12.5);
+    }
+
+    #[tokio::test]
+    async fn test_handle_pause_resume_reload() {
+        let controls = Controls::default();
+        let status = test_status();
+
+        let response = handle_request(
+            r#"{"command":"pause","output":"database"}"#,
+            &controls,
+            &status,
+        )
+        .await;
+        assert_eq!(response["paused"], json!(["database"]));
+        assert!(status.lock().await.is_paused("database"));
+
+        handle_request(r#"{"command":"pause"}"#, &controls, &status).await;
+        assert!(status.lock().await.is_paused("mqtt"));
+        handle_request(r#"{"command":"resume"}"#, &controls, &status).await;
+        assert!(status.lock().await.paused().is_empty());
+
+        let response =
+            handle_request(r#"{"command":"pause","output":"fax"}"#, &controls, &status).await;
+        assert_eq!(response["ok"], false);
+
+        handle_request(r#"{"command":"reload"}"#, &controls, &status).await;
+        assert!(controls.take_reload());
+        assert!(!controls.take_reload());
+    }
+
+    #[tokio::test]
+    async fn test_update_settings() {
+        let controls = Controls::default();
+        let status = test_status();
+
+        let response = update_settings(
+            r#"{"poll_interval":30,"outputs":{"mqtt":false}}"#,
+            &controls,
+            &status,
+        )
+        .await;
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["settings"]["poll_interval"], 30);
+        assert_eq!(response["settings"]["outputs"]["mqtt"], false);
+        assert_eq!(response["settings"]["outputs"]["database"], true);
+        assert!(controls.take_settings_changed());
+        assert!(!status.lock().await.is_active("mqtt"));
+
+        let response = update_settings(r#"{"outputs":{"fax":true}}"#, &controls, &status).await;
+        assert_eq!(response["ok"], false);
+        let response = update_settings("30", &controls, &status).await;
+        assert_eq!(response["ok"], false);
+        assert!(!controls.take_settings_changed());
+    }
+
+    #[tokio::test]
+    async fn test_handle_maintenance() {
+        let controls = Controls::default();
+        let status = test_status();
+
+        let response = handle_request(
+            r#"{"command":"maintenance","enabled":true}"#,
+            &controls,
+            &status,
+        )
+        .await;
+        assert_eq!(response["ok"], true);
+        assert!(response["maintenance_since"].is_string());
+        assert!(status.lock().await.in_maintenance());
+
+        let response = handle_request(
+            r#"{"command":"maintenance","enabled":false}"#,
+            &controls,
+            &status,
+        )
+        .await;
+        assert_eq!(response["maintenance_since"], Value::Null);
+        assert!(!status.lock().await.in_maintenance());
+
+        let response = handle_request(r#"{"command":"maintenance"}"#, &controls, &status).await;
+        assert_eq!(response["ok"], false);
+    }
+
+    #[tokio::test]
+    async fn test_handle_events() {
+        let controls = Controls::default();
+        let status = test_status();
+        status.lock().await.record_poll_error("timeout");
+        status.lock().await.record_poll(&HashMap::new(), Utc::now());
+
+        let response =
+            handle_request(r#"{"command":"events","limit":1}"#, &controls, &status).await;
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["events"].as_array().unwrap().len(), 1);
+        assert_eq!(response["events"][0]["kind"], "device_online");
+    }
+
+    #[tokio::test]
+    async fn test_handle_invalid() {
+        let controls = Controls::default();
+        let status = test_status();
+
+        let response = handle_request("not json", &controls, &status).await;
+        assert_eq!(response["ok"], false);
+
+        let response = handle_request(r#"{"command":"explode"}"#, &controls, &status).await;
+        assert!(response["error"]
+            .as_str()
+            .unwrap()
+            .contains("Unknown command"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_device_commands() {
+        let status = test_status();
+
+        // Nothing to send them to
+        let response =
+            handle_request(r#"{"command":"set_time"}"#, &Controls::default(), &status).await;
+        assert_eq!(response["ok"], false);
+
+        // Device commands are TCP only
+        let controls = Controls::with_device_queue(DeviceQueue::start(Gateway::new(
+            GatewayApi::Http,
+            GW1000Client::new("127.0.0.1".to_string(), 1),
+            LocalHttpClient::new("127.0.0.1".to_string(), 1),
+        )));
+        let response = handle_request(r#"{"command":"calibration"}"#, &controls, &status).await;
+        assert_eq!(response["ok"], false);
+        assert!(response["error"].as_str().unwrap().contains("TCP API"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_control_socket_roundtrip() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wxlistener.sock");
+        let controls: SharedControls = Arc::new(Controls::default());
+        let status = test_status();
+        run_control_socket_background(path.clone(), controls, status.clone());
+
+        // Wait for the listener to bind
+        let mut stream = None;
+        for _ in 0..50 {
+            if let Ok(s) = tokio::net::UnixStream::connect(&path).await {
+                stream = Some(s);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let (reader, mut writer) = stream.expect("control socket").into_split();
+
+        writer
+            .write_all(b"{\"command\":\"pause\"}\n")
+            .await
+            .unwrap();
+        let mut lines = BufReader::new(reader).lines();
+        let line = lines.next_line().await.unwrap().unwrap();
+        let response: Value = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(response["ok"], true);
+        assert!(status.lock().await.is_paused("database"));
+    }
+}
@@ -0,0 +1,346 @@
+/// Cron expressions for scheduled maintenance tasks run inside the daemon
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeDelta, TimeZone, Timelike};
+use serde::Deserialize;
+
+/// A standard five-field cron expression: minute hour day-of-month month
+/// day-of-week. Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`)
+/// and steps (`*/5`, `0-30/10`). Day-of-week is 0-7 with 0 and 7 both
+/// Sunday. `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are
+/// accepted as shorthands.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day-of-month or day-of-week was `*`. When both are restricted a day
+    /// matching either one fires, as in cron.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronExpr {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let expanded = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            anyhow::bail!(
+                "Invalid cron expression '{}': expected 5 fields (minute hour day month weekday)",
+                expr
+            );
+        }
+
+        let parse = |field: &str, min: u32, max: u32, name: &str| {
+            parse_field(field, min, max).context(format!(
+                "Invalid {} field '{}' in cron expression '{}'",
+                name, field, expr
+            ))
+        };
+
+        let mut weekdays = parse(fields[4], 0, 7, "weekday")?;
+        // 7 is an alias for Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse(fields[0], 0, 59, "minute")?,
+            hours: parse(fields[1], 0, 23, "hour")?,
+            days: parse(fields[2], 1, 31, "day")?,
+            months: parse(fields[3], 1, 12, "month")?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    fn matches_day(&self, time: &NaiveDateTime) -> bool {
+        if self.months & (1 << time.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// The first matching minute strictly after `after`, in its time zone.
+    /// Local times skipped by a DST change never fire.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let start = after.naive_local();
+        let mut time = start.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        // Every expression matches within a few years (Feb 29 on a Monday)
+        let limit = start + TimeDelta::days(366 * 8);
+
+        while time < limit {
+            if !self.matches_day(&time) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + TimeDelta::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << time.minute()) == 0 {
+                time += TimeDelta::minutes(1);
+                continue;
+            }
+            if let Some(next) = tz.from_local_datetime(&time).earliest() {
+                return Some(next);
+            }
+            time += TimeDelta::minutes(1);
+        }
+        None
+    }
+}
+
+impl TryFrom<String> for CronExpr {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Self::parse(&value)
+    }
+}
+
+/// Parse one cron field into a bitmask of allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().context("invalid step")?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            anyhow::bail!("step must be at least 1");
+        }
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                start.parse().context("invalid range start")?,
+                end.parse().context("invalid range end")?,
+            )
+        } else {
+            let value: u32 = range.parse().context("invalid value")?;
+            // `5/15` means every 15 starting at 5
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        if start < min || end > max || start > end {
+            anyhow::bail!("values must be between {} and {}", min, max);
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+/// A maintenance task the daemon can run on a schedule
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "task", rename_all = "snake_case")]
+pub enum ScheduledTask {
+    /// Set the gateway clock to the host's time
+    SetTime,
+    /// Fill gaps in the database from the Ecowitt cloud history
+    Backfill {
+        /// How far back to look (default: 24)
+        #[serde(default = "default_backfill_hours")]
+        hours: u32,
+    },
+    /// Add upcoming MySQL monthly partitions
+    AddPartitions {
+        /// How many months after the current one to cover (default: 3)
+        #[serde(default = "default_partition_months")]
+        months: u32,
+    },
+}
+
+fn default_backfill_hours() -> u32 {
+    24
+}
+
+fn default_partition_months() -> u32 {
+    3
+}
+
+/// One `[[schedules]]` entry: a task and when to run it (host local time)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleRule {
+    pub cron: CronExpr,
+    #[serde(flatten)]
+    pub task: ScheduledTask,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    fn next(expr: &str, after: DateTime<Utc>) -> DateTime<Utc> {
+        CronExpr::parse(expr).unwrap().next_after(&after).unwrap()
+    }
+
+    #[test]
+    fn test_every_minute() {
+        let after = at(2025, 3, 1, 12, 4) + TimeDelta::seconds(30);
+        assert_eq!(next("* * * * *", after), at(2025, 3, 1, 12, 5));
+    }
+
+    #[test]
+    fn test_daily() {
+        assert_eq!(
+            next("0 3 * * *", at(2025, 3, 1, 2, 59)),
+            at(2025, 3, 1, 3, 0)
+        );
+        // Strictly after: the current minute does not fire again
+        assert_eq!(
+            next("0 3 * * *", at(2025, 3, 1, 3, 0)),
+            at(2025, 3, 2, 3, 0)
+        );
+        assert_eq!(
+            next("@daily", at(2025, 12, 31, 23, 0)),
+            at(2026, 1, 1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_steps_and_lists() {
+        assert_eq!(
+            next("*/15 * * * *", at(2025, 3, 1, 12, 16)),
+            at(2025, 3, 1, 12, 30)
+        );
+        assert_eq!(
+            next("5/20 * * * *", at(2025, 3, 1, 12, 26)),
+            at(2025, 3, 1, 12, 45)
+        );
+        assert_eq!(
+            next("0 6,18 * * *", at(2025, 3, 1, 7, 0)),
+            at(2025, 3, 1, 18, 0)
+        );
+        assert_eq!(
+            next("0 9-17/4 * * *", at(2025, 3, 1, 9, 0)),
+            at(2025, 3, 1, 13, 0)
+        );
+    }
+
+    #[test]
+    fn test_weekday() {
+        // 2025-03-01 is a Saturday
+        assert_eq!(
+            next("0 0 * * 1", at(2025, 3, 1, 0, 0)),
+            at(2025, 3, 3, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 * * 7", at(2025, 3, 1, 0, 0)),
+            at(2025, 3, 2, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_day_or_weekday() {
+        // Both restricted: the 15th or any Monday, whichever comes first
+        assert_eq!(
+            next("0 0 15 * 1", at(2025, 3, 1, 0, 0)),
+            at(2025, 3, 3, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 15 * 1", at(2025, 3, 11, 0, 0)),
+            at(2025, 3, 15, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_monthly_skips_short_months() {
+        assert_eq!(
+            next("0 0 31 * *", at(2025, 4, 1, 0, 0)),
+            at(2025, 5, 31, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 29 2 *", at(2025, 3, 1, 0, 0)),
+            at(2028, 2, 29, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        assert!(CronExpr::parse("* * * *").is_err());
+        assert!(CronExpr::parse("60 * * * *").is_err());
+        assert!(CronExpr::parse("* 24 * * *").is_err());
+        assert!(CronExpr::parse("* * 0 * *").is_err());
+        assert!(CronExpr::parse("*/0 * * * *").is_err());
+        assert!(CronExpr::parse("5-1 * * * *").is_err());
+        assert!(CronExpr::parse("a * * * *").is_err());
+    }
+
+    #[test]
+    fn test_schedule_rule_deserialization() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            schedules: Vec<ScheduleRule>,
+        }
+        let w: Wrapper = toml::from_str(
+            r#"
+            [[schedules]]
+            cron = "0 3 * * *"
+            task = "set_time"
+
+            [[schedules]]
+            cron = "30 * * * *"
+            task = "backfill"
+            hours = 6
+
+            [[schedules]]
+            cron = "@monthly"
+            task = "add_partitions"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(w.schedules[0].task, ScheduledTask::SetTime);
+        assert_eq!(w.schedules[1].task, ScheduledTask::Backfill { hours: 6 });
+        assert_eq!(
+            w.schedules[2].task,
+            ScheduledTask::AddPartitions { months: 3 }
+        );
+        assert_eq!(w.schedules[2].cron, CronExpr::parse("0 0 1 * *").unwrap());
+    }
+
+    #[test]
+    fn test_schedule_rule_invalid_cron() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Wrapper {
+            schedules: Vec<ScheduleRule>,
+        }
+        let result: Result<Wrapper, _> = toml::from_str(
+            r#"
+            [[schedules]]
+            cron = "0 25 * * *"
+            task = "set_time"
+            "#,
+        );
+        assert!(result.is_err());
+    }
+}
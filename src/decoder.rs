@@ -1,18 +1,17 @@
 /// Decoding functions for GW1000 binary data
+use std::collections::HashMap;
+
+/// Signed 16-bit temperature in tenths of a degree
 pub fn decode_temp(data: &[u8]) -> f64 {
-    let value = ((data[0] as u16) << 8) | (data[1] as u16);
-    let value = if value > 32767 {
-        value as i32 - 65536
-    } else {
-        value as i32
-    };
-    value as f64 / 10.0
+    decode_signed_short(data) / 10.0
 }
 
+/// Unsigned 16-bit, big-endian
 pub fn decode_short(data: &[u8]) -> f64 {
     (((data[0] as u16) << 8) | (data[1] as u16)) as f64
 }
 
+/// Unsigned 32-bit, big-endian
 pub fn decode_int(data: &[u8]) -> f64 {
     (((data[0] as u32) << 24)
         | ((data[1] as u32) << 16)
@@ -20,6 +19,21 @@ pub fn decode_int(data: &[u8]) -> f64 {
         | (data[3] as u32)) as f64
 }
 
+/// Two's complement 8-bit
+pub fn decode_signed_byte(data: &[u8]) -> f64 {
+    data[0] as i8 as f64
+}
+
+/// Two's complement 16-bit, big-endian
+pub fn decode_signed_short(data: &[u8]) -> f64 {
+    i16::from_be_bytes([data[0], data[1]]) as f64
+}
+
+/// Two's complement 32-bit, big-endian
+pub fn decode_signed_int(data: &[u8]) -> f64 {
+    i32::from_be_bytes([data[0], data[1], data[2], data[3]]) as f64
+}
+
 pub fn decode_wind(data: &[u8]) -> f64 {
     let value = ((data[0] as u16) << 8) | (data[1] as u16);
     value as f64 / 10.0
@@ -35,6 +49,236 @@ pub fn decode_pressure(data: &[u8]) -> f64 {
     value as f64 / 10.0
 }
 
+/// How one value inside a live data field is encoded on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueFormat {
+    /// Signed 16-bit, tenths (temperatures)
+    Temp,
+    /// Unsigned 8-bit
+    Byte,
+    /// Unsigned 16-bit
+    Short,
+    /// Unsigned 16-bit, tenths
+    ShortTenths,
+    /// Wind speed, m/s in tenths
+    Wind,
+    /// Rain, mm in tenths
+    Rain,
+    /// Pressure, hPa in tenths
+    Pressure,
+    /// Unsigned 32-bit
+    Int,
+    /// Unsigned 32-bit, tenths
+    IntTenths,
+}
+
+impl ValueFormat {
+    /// Number of bytes the value occupies
+    pub const fn width(self) -> usize {
+        match self {
+            ValueFormat::Byte => 1,
+            ValueFormat::Temp
+            | ValueFormat::Short
+            | ValueFormat::ShortTenths
+            | ValueFormat::Wind
+            | ValueFormat::Rain
+            | ValueFormat::Pressure => 2,
+            ValueFormat::Int | ValueFormat::IntTenths => 4,
+        }
+    }
+
+    pub fn decode(self, data: &[u8]) -> f64 {
+        match self {
+            ValueFormat::Temp => decode_temp(data),
+            ValueFormat::Byte => data[0] as f64,
+            ValueFormat::Short => decode_short(data),
+            ValueFormat::ShortTenths => decode_short(data) / 10.0,
+            ValueFormat::Wind => decode_wind(data),
+            ValueFormat::Rain => decode_rain(data),
+            ValueFormat::Pressure => decode_pressure(data),
+            ValueFormat::Int => decode_int(data),
+            ValueFormat::IntTenths => decode_int(data) / 10.0,
+        }
+    }
+
+    /// Inverse of `decode`, used to build test payloads
+    #[allow(dead_code)]
+    pub fn encode(self, value: f64) -> Vec<u8> {
+        match self {
+            ValueFormat::Temp => ((value * 10.0).round() as i16).to_be_bytes().to_vec(),
+            ValueFormat::Byte => vec![value as u8],
+            ValueFormat::Short => (value as u16).to_be_bytes().to_vec(),
+            ValueFormat::ShortTenths
+            | ValueFormat::Wind
+            | ValueFormat::Rain
+            | ValueFormat::Pressure => ((value * 10.0).round() as u16).to_be_bytes().to_vec(),
+            ValueFormat::Int => (value as u32).to_be_bytes().to_vec(),
+            ValueFormat::IntTenths => ((value * 10.0).round() as u32).to_be_bytes().to_vec(),
+        }
+    }
+}
+
+/// One field of the live data response: an address byte followed by `len`
+/// bytes holding one or more values. `len` is derived from the value formats.
+#[derive(Debug)]
+pub struct LiveField {
+    pub addr: u8,
+    pub len: usize,
+    pub values: &'static [(&'static str, ValueFormat)],
+}
+
+impl LiveField {
+    pub const fn new(addr: u8, values: &'static [(&'static str, ValueFormat)]) -> Self {
+        let mut len = 0;
+        let mut i = 0;
+        while i < values.len() {
+            len += values[i].1.width();
+            i += 1;
+        }
+        Self { addr, len, values }
+    }
+
+    /// Decode the field's bytes (excluding the address) into `result`
+    pub fn decode(&self, data: &[u8], result: &mut HashMap<String, f64>) {
+        let mut offset = 0;
+        for (name, format) in self.values {
+            let end = offset + format.width();
+            result.insert(name.to_string(), format.decode(&data[offset..end]));
+            offset = end;
+        }
+    }
+
+    /// Encode the field including its address byte; missing values are zero
+    #[allow(dead_code)]
+    pub fn encode(&self, values: &HashMap<String, f64>) -> Vec<u8> {
+        let mut bytes = vec![self.addr];
+        for (name, format) in self.values {
+            bytes.extend(format.encode(values.get(*name).copied().unwrap_or(0.0)));
+        }
+        bytes
+    }
+}
+
+use ValueFormat::*;
+
+/// Live data fields by address. Adding a sensor is a single row here.
+pub const LIVEDATA_FIELDS: &[LiveField] = &[
+    LiveField::new(0x01, &[("intemp", Temp)]),
+    LiveField::new(0x02, &[("outtemp", Temp)]),
+    LiveField::new(0x03, &[("dewpoint", Temp)]),
+    LiveField::new(0x04, &[("windchill", Temp)]),
+    LiveField::new(0x05, &[("heatindex", Temp)]),
+    LiveField::new(0x06, &[("inhumid", Byte)]),
+    LiveField::new(0x07, &[("outhumid", Byte)]),
+    LiveField::new(0x08, &[("absbarometer", Pressure)]),
+    LiveField::new(0x09, &[("relbarometer", Pressure)]),
+    LiveField::new(0x0A, &[("wind_dir", Short)]),
+    LiveField::new(0x0B, &[("wind_speed", Wind)]),
+    LiveField::new(0x0C, &[("gust_speed", Wind)]),
+    LiveField::new(0x0D, &[("rain_event", Rain)]),
+    LiveField::new(0x0E, &[("rain_rate", Rain)]),
+    LiveField::new(0x10, &[("rain_day", Rain)]),
+    LiveField::new(0x11, &[("rain_week", Rain)]),
+    LiveField::new(0x12, &[("rain_month", IntTenths)]),
+    LiveField::new(0x13, &[("rain_year", IntTenths)]),
+    LiveField::new(0x15, &[("light", IntTenths)]),
+    LiveField::new(0x16, &[("uv", Short)]),
+    LiveField::new(0x17, &[("uvi", Byte)]),
+    LiveField::new(0x19, &[("day_max_wind", Wind)]),
+    // WH31 multi-channel temperature/humidity sensors
+    LiveField::new(0x1A, &[("temp_ch1", Temp)]),
+    LiveField::new(0x1B, &[("temp_ch2", Temp)]),
+    LiveField::new(0x1C, &[("temp_ch3", Temp)]),
+    LiveField::new(0x1D, &[("temp_ch4", Temp)]),
+    LiveField::new(0x1E, &[("temp_ch5", Temp)]),
+    LiveField::new(0x1F, &[("temp_ch6", Temp)]),
+    LiveField::new(0x20, &[("temp_ch7", Temp)]),
+    LiveField::new(0x21, &[("temp_ch8", Temp)]),
+    LiveField::new(0x22, &[("humid_ch1", Byte)]),
+    LiveField::new(0x23, &[("humid_ch2", Byte)]),
+    LiveField::new(0x24, &[("humid_ch3", Byte)]),
+    LiveField::new(0x25, &[("humid_ch4", Byte)]),
+    LiveField::new(0x26, &[("humid_ch5", Byte)]),
+    LiveField::new(0x27, &[("humid_ch6", Byte)]),
+    LiveField::new(0x28, &[("humid_ch7", Byte)]),
+    LiveField::new(0x29, &[("humid_ch8", Byte)]),
+    // WS90 super-capacitor voltage
+    LiveField::new(0x6B, &[("ws90_cap_volt", ShortTenths)]),
+    LiveField::new(0x6C, &[("heap_free", Int)]),
+    // WH45/WH46 indoor air quality combo sensor
+    LiveField::new(
+        0x70,
+        &[
+            ("co2_temp", Temp),
+            ("co2_humid", Byte),
+            ("co2_pm10", ShortTenths),
+            ("co2_pm10_24h", ShortTenths),
+            ("co2_pm25", ShortTenths),
+            ("co2_pm25_24h", ShortTenths),
+            ("co2", Short),
+            ("co2_24h", Short),
+            ("co2_batt", Byte),
+        ],
+    ),
+    // WH46 PM1.0 and PM4.0 (the gateway reports no 24h averages)
+    LiveField::new(0x71, &[("co2_pm1", ShortTenths), ("co2_pm4", ShortTenths)]),
+    // WS90/WS85 piezo rain
+    LiveField::new(0x80, &[("piezo_rain_rate", Rain)]),
+    LiveField::new(0x81, &[("piezo_rain_event", Rain)]),
+    LiveField::new(0x82, &[("piezo_rain_hour", Rain)]),
+    LiveField::new(0x83, &[("piezo_rain_day", IntTenths)]),
+    LiveField::new(0x84, &[("piezo_rain_week", IntTenths)]),
+    LiveField::new(0x85, &[("piezo_rain_month", IntTenths)]),
+    LiveField::new(0x86, &[("piezo_rain_year", IntTenths)]),
+];
+
+/// Look up a live data field by its address byte
+pub fn livedata_field(addr: u8) -> Option<&'static LiveField> {
+    LIVEDATA_FIELDS.iter().find(|field| field.addr == addr)
+}
+
+/// Parse the field records of a live data payload (the bytes after the size
+/// field, without the checksum). Unknown addresses are skipped one byte at a
+/// time and a truncated trailing field ends parsing.
+#[allow(dead_code)]
+pub fn parse_livedata(data: &[u8]) -> HashMap<String, f64> {
+    parse_livedata_with_warnings(data).0
+}
+
+/// `parse_livedata`, also counting what it had to skip: each run of unknown
+/// bytes and a truncated trailing field are one warning apiece
+pub fn parse_livedata_with_warnings(data: &[u8]) -> (HashMap<String, f64>, u32) {
+    let mut result = HashMap::new();
+    let mut warnings = 0;
+    let mut skipping = false;
+    let mut index = 0;
+
+    while index < data.len() {
+        match livedata_field(data[index]) {
+            Some(field) => {
+                skipping = false;
+                if index + field.len < data.len() {
+                    field.decode(&data[index + 1..index + 1 + field.len], &mut result);
+                    index += 1 + field.len;
+                } else {
+                    warnings += 1;
+                    break;
+                }
+            }
+            None => {
+                // Unknown field, skip it
+                if !skipping {
+                    warnings += 1;
+                    skipping = true;
+                }
+                index += 1;
+            }
+        }
+    }
+
+    (result, warnings)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,6 +317,41 @@ mod tests {
         assert_eq!(decode_int(&data), 1000000.0);
     }
 
+    #[test]
+    fn test_decode_signed_byte() {
+        assert_eq!(decode_signed_byte(&[0x05]), 5.0);
+        assert_eq!(decode_signed_byte(&[0xFB]), -5.0);
+        assert_eq!(decode_signed_byte(&[0x80]), -128.0);
+        assert_eq!(decode_signed_byte(&[0x7F]), 127.0);
+    }
+
+    #[test]
+    fn test_decode_signed_short() {
+        assert_eq!(decode_signed_short(&[0x01, 0x68]), 360.0);
+        // -15 = 0xFFF1
+        assert_eq!(decode_signed_short(&[0xFF, 0xF1]), -15.0);
+        assert_eq!(decode_signed_short(&[0x80, 0x00]), -32768.0);
+        // The same bytes read unsigned
+        assert_eq!(decode_short(&[0xFF, 0xF1]), 65521.0);
+    }
+
+    #[test]
+    fn test_decode_signed_int() {
+        assert_eq!(decode_signed_int(&[0x00, 0x0F, 0x42, 0x40]), 1000000.0);
+        // -32 = 0xFFFFFFE0
+        assert_eq!(decode_signed_int(&[0xFF, 0xFF, 0xFF, 0xE0]), -32.0);
+        assert_eq!(decode_int(&[0xFF, 0xFF, 0xFF, 0xE0]), 4294967264.0);
+    }
+
+    #[test]
+    fn test_channel_temp_negative() {
+        // WH31 channel 3 at -12.7°C, 64% humidity
+        let data = [0x1C, 0xFF, 0x81, 0x24, 64];
+        let result = parse_livedata(&data);
+        assert_eq!(result["temp_ch3"], -12.7);
+        assert_eq!(result["humid_ch3"], 64.0);
+    }
+
     #[test]
     fn test_decode_wind() {
         // 12.5 m/s = 125 = 0x007D
@@ -94,6 +373,56 @@ mod tests {
         assert_eq!(decode_pressure(&data), 1013.2);
     }
 
+    #[test]
+    fn test_livedata_fields_unique() {
+        let mut addrs = std::collections::HashSet::new();
+        let mut names = std::collections::HashSet::new();
+        for field in LIVEDATA_FIELDS {
+            assert!(
+                addrs.insert(field.addr),
+                "duplicate address {:#04X}",
+                field.addr
+            );
+            for (name, _) in field.values {
+                assert!(names.insert(*name), "duplicate field name {}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_livedata_field_roundtrip() {
+        let field = livedata_field(0x70).unwrap();
+        let mut values = HashMap::new();
+        values.insert("co2_temp".to_string(), -3.4);
+        values.insert("co2".to_string(), 612.0);
+        values.insert("co2_pm25".to_string(), 5.8);
+
+        let bytes = field.encode(&values);
+        assert_eq!(field.len, 16);
+        assert_eq!(bytes.len(), 1 + field.len);
+
+        let mut decoded = HashMap::new();
+        field.decode(&bytes[1..], &mut decoded);
+        assert_eq!(decoded["co2_temp"], -3.4);
+        assert_eq!(decoded["co2"], 612.0);
+        assert_eq!(decoded["co2_pm25"], 5.8);
+        assert_eq!(decoded["co2_batt"], 0.0);
+    }
+
+    #[test]
+    fn test_parse_livedata_skips_unknown_and_truncated() {
+        // Unknown 0xEE, outtemp 25.5, then an intemp cut short
+        let data = [0xEE, 0x02, 0x00, 0xFF, 0x01, 0x00];
+        let result = parse_livedata(&data);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result["outtemp"], 25.5);
+
+        let (_, warnings) = parse_livedata_with_warnings(&data);
+        assert_eq!(warnings, 2);
+        let (_, warnings) = parse_livedata_with_warnings(&[0x02, 0x00, 0xFF]);
+        assert_eq!(warnings, 0);
+    }
+
     // Property-based tests
     mod proptests {
         use super::*;
@@ -109,6 +438,16 @@ mod tests {
                 prop_assert!((decoded - expected).abs() < 0.1);
             }
 
+            #[test]
+            fn prop_decode_signed_short_roundtrip(value: i16) {
+                prop_assert_eq!(decode_signed_short(&value.to_be_bytes()), value as f64);
+            }
+
+            #[test]
+            fn prop_decode_signed_int_roundtrip(value: i32) {
+                prop_assert_eq!(decode_signed_int(&value.to_be_bytes()), value as f64);
+            }
+
             #[test]
             fn prop_decode_short_always_positive(high: u8, low: u8) {
                 // decode_short should always return a positive number
@@ -153,6 +492,12 @@ mod tests {
                 prop_assert!(result >= 0.0);
                 prop_assert!(result <= 4294967295.0); // Max u32
             }
+
+            #[test]
+            fn prop_parse_livedata_never_panics(data in prop::collection::vec(any::<u8>(), 0..256)) {
+                // Arbitrary payloads must parse without panicking
+                let _ = parse_livedata(&data);
+            }
         }
     }
 }
@@ -0,0 +1,356 @@
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::fixed::Tenths;
+
+/// Accumulator shared between the polling loop and the web server
+pub type SharedDegreeDays = Arc<Mutex<DegreeDayAccumulator>>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DegreeDayConfig {
+    /// Base temperature for heating degree days in °C (default: 18.0)
+    #[serde(default = "default_heating_base")]
+    pub heating_base: f64,
+
+    /// Base temperature for cooling degree days in °C (default: 18.0)
+    #[serde(default = "default_cooling_base")]
+    pub cooling_base: f64,
+
+    /// Base temperature for growing degree days in °C (default: 10.0)
+    #[serde(default = "default_growing_base")]
+    pub growing_base: f64,
+
+    /// Upper temperature cap for growing degree days in °C (default: 30.0)
+    #[serde(default = "default_growing_cap")]
+    pub growing_cap: f64,
+
+    /// Month (1-12) on which the season totals reset (default: 1, January)
+    #[serde(default = "default_season_start_month")]
+    pub season_start_month: u32,
+}
+
+fn default_heating_base() -> f64 {
+    18.0
+}
+
+fn default_cooling_base() -> f64 {
+    18.0
+}
+
+fn default_growing_base() -> f64 {
+    10.0
+}
+
+fn default_growing_cap() -> f64 {
+    30.0
+}
+
+fn default_season_start_month() -> u32 {
+    1
+}
+
+impl Default for DegreeDayConfig {
+    fn default() -> Self {
+        Self {
+            heating_base: default_heating_base(),
+            cooling_base: default_cooling_base(),
+            growing_base: default_growing_base(),
+            growing_cap: default_growing_cap(),
+            season_start_month: default_season_start_month(),
+        }
+    }
+}
+
+/// Heating, cooling and growing degree day totals. Held in tenths so that a
+/// season of daily sums doesn't drift.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DegreeDays {
+    pub hdd: Tenths,
+    pub cdd: Tenths,
+    pub gdd: Tenths,
+}
+
+/// `(a - b) / 2`, floored at zero and rounded half up to a tenth. The
+/// difference of a base and a daily mean, with both sides doubled.
+fn half_excess(a: Tenths, b: Tenths) -> Tenths {
+    Tenths(((a - b).0.max(0) + 1) / 2)
+}
+
+impl DegreeDays {
+    /// Compute degree days for a single day from its min/max temperature
+    /// using the mean temperature method
+    pub fn from_min_max(min: Tenths, max: Tenths, config: &DegreeDayConfig) -> Self {
+        let heating_base = Tenths::from_f64(config.heating_base);
+        let cooling_base = Tenths::from_f64(config.cooling_base);
+        let growing_base = Tenths::from_f64(config.growing_base);
+        let growing_cap = Tenths::from_f64(config.growing_cap);
+        // Twice the mean, so the division happens once at the end
+        let mean2 = min + max;
+
+        // GDD clamps both extremes into [base, cap] before averaging
+        let gdd_max = max.max(growing_base).min(growing_cap);
+        let gdd_min = min.max(growing_base).min(growing_cap);
+
+        Self {
+            hdd: half_excess(heating_base + heating_base, mean2),
+            cdd: half_excess(mean2, cooling_base + cooling_base),
+            gdd: half_excess(gdd_min + gdd_max, growing_base + growing_base),
+        }
+    }
+
+    fn add(&mut self, other: &DegreeDays) {
+        self.hdd += other.hdd;
+        self.cdd += other.cdd;
+        self.gdd += other.gdd;
+    }
+}
+
+/// A completed day, ready to be written to the summary table
+#[derive(Debug, Clone, Serialize)]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    pub min_temp: Tenths,
+    pub max_temp: Tenths,
+    #[serde(flatten)]
+    pub degree_days: DegreeDays,
+}
+
+/// Snapshot of the accumulator exposed through the summary API
+#[derive(Debug, Clone, Serialize)]
+pub struct DegreeDaySummary {
+    pub today: Option<DailySummary>,
+    pub season_start: NaiveDate,
+    pub season: DegreeDays,
+}
+
+/// Accumulator progress that survives a restart
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DegreeDayState {
+    /// Date, min and max temperature of the day in progress
+    pub current_day: Option<(NaiveDate, Tenths, Tenths)>,
+    pub season_start: Option<NaiveDate>,
+    /// Totals of completed days in the current season
+    pub season: DegreeDays,
+}
+
+/// Accumulates outdoor temperature samples into daily and seasonal degree days
+pub struct DegreeDayAccumulator {
+    config: DegreeDayConfig,
+    current_day: Option<(NaiveDate, Tenths, Tenths)>,
+    season_start: Option<NaiveDate>,
+    season: DegreeDays,
+}
+
+impl DegreeDayAccumulator {
+    pub fn new(config: DegreeDayConfig) -> Self {
+        Self {
+            config,
+            current_day: None,
+            season_start: None,
+            season: DegreeDays::default(),
+        }
+    }
+
+    /// Capture the accumulator's progress for persistence
+    pub fn state(&self) -> DegreeDayState {
+        DegreeDayState {
+            current_day: self.current_day,
+            season_start: self.season_start,
+            season: self.season,
+        }
+    }
+
+    /// Resume from previously persisted progress
+    pub fn restore(&mut self, state: DegreeDayState) {
+        self.current_day = state.current_day;
+        self.season_start = state.season_start;
+        self.season = state.season;
+    }
+
+    /// Record a temperature sample for the given (local) date.
+    /// Returns the summary of the previous day when the date rolls over.
+    pub fn add_sample(&mut self, date: NaiveDate, temp: f64) -> Option<DailySummary> {
+        let temp = Tenths::from_f64(temp);
+        let finished = match self.current_day {
+            Some((day, min, max)) if day == date => {
+                self.current_day = Some((day, min.min(temp), max.max(temp)));
+                return None;
+            }
+            Some((day, min, max)) => Some(self.finish_day(day, min, max)),
+            None => None,
+        };
+
+        let season_start = self.season_start_for(date);
+        if self.season_start != Some(season_start) {
+            self.season_start = Some(season_start);
+            self.season = DegreeDays::default();
+        }
+
+        self.current_day = Some((date, temp, temp));
+        finished
+    }
+
+    fn finish_day(&mut self, date: NaiveDate, min: Tenths, max: Tenths) -> DailySummary {
+        let degree_days = DegreeDays::from_min_max(min, max, &self.config);
+        self.season.add(&degree_days);
+        DailySummary {
+            date,
+            min_temp: min,
+            max_temp: max,
+            degree_days,
+        }
+    }
+
+    /// First day of the season that `date` belongs to
+    fn season_start_for(&self, date: NaiveDate) -> NaiveDate {
+        let month = self.config.season_start_month.clamp(1, 12);
+        let year = if date.month() >= month {
+            date.year()
+        } else {
+            date.year() - 1
+        };
+        NaiveDate::from_ymd_opt(year, month, 1).unwrap_or(date)
+    }
+
+    /// Current totals, including the partial contribution of today
+    pub fn summary(&self) -> Option<DegreeDaySummary> {
+        let season_start = self.season_start?;
+        let today = self.current_day.map(|(date, min, max)| DailySummary {
+            date,
+            min_temp: min,
+            max_temp: max,
+            degree_days: DegreeDays::from_min_max(min, max, &self.config),
+        });
+
+        let mut season = self.season;
+        if let Some(ref today) = today {
+            season.add(&today.degree_days);
+        }
+
+        Some(DegreeDaySummary {
+            today,
+            season_start,
+            season,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(value: f64) -> Tenths {
+        Tenths::from_f64(value)
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_degree_days_cold_day() {
+        let config = DegreeDayConfig::default();
+        let dd = DegreeDays::from_min_max(t(2.0), t(10.0), &config);
+        assert_eq!(dd.hdd, t(12.0));
+        assert_eq!(dd.cdd, t(0.0));
+        assert_eq!(dd.gdd, t(0.0));
+    }
+
+    #[test]
+    fn test_degree_days_hot_day() {
+        let config = DegreeDayConfig::default();
+        let dd = DegreeDays::from_min_max(t(20.0), t(34.0), &config);
+        assert_eq!(dd.hdd, t(0.0));
+        assert_eq!(dd.cdd, t(9.0));
+        // Max capped at 30: (20 + 30) / 2 - 10
+        assert_eq!(dd.gdd, t(15.0));
+    }
+
+    #[test]
+    fn test_degree_days_gdd_min_floored_at_base() {
+        let config = DegreeDayConfig::default();
+        let dd = DegreeDays::from_min_max(t(4.0), t(20.0), &config);
+        // Min raised to 10: (10 + 20) / 2 - 10
+        assert_eq!(dd.gdd, t(5.0));
+    }
+
+    #[test]
+    fn test_degree_days_half_tenth_mean() {
+        let config = DegreeDayConfig::default();
+        // Mean 8.05: 9.95 heating degree days, rounded half up
+        let dd = DegreeDays::from_min_max(t(5.1), t(11.0), &config);
+        assert_eq!(dd.hdd, t(10.0));
+    }
+
+    #[test]
+    fn test_season_total_is_exact() {
+        let mut acc = DegreeDayAccumulator::new(DegreeDayConfig::default());
+        // 300 days at a 17.9°C mean, 0.1 HDD each
+        let start = date(2025, 1, 1);
+        for day in 0..300 {
+            acc.add_sample(start + chrono::Days::new(day), 17.9);
+        }
+        acc.add_sample(start + chrono::Days::new(300), 18.0);
+        assert_eq!(acc.summary().unwrap().season.hdd.to_f64(), 30.0);
+    }
+
+    #[test]
+    fn test_accumulator_rollover() {
+        let mut acc = DegreeDayAccumulator::new(DegreeDayConfig::default());
+        assert!(acc.add_sample(date(2025, 1, 1), 5.0).is_none());
+        assert!(acc.add_sample(date(2025, 1, 1), 11.0).is_none());
+
+        let day = acc.add_sample(date(2025, 1, 2), 8.0).unwrap();
+        assert_eq!(day.date, date(2025, 1, 1));
+        assert_eq!(day.min_temp, t(5.0));
+        assert_eq!(day.max_temp, t(11.0));
+        assert_eq!(day.degree_days.hdd, t(10.0));
+
+        let summary = acc.summary().unwrap();
+        assert_eq!(summary.season_start, date(2025, 1, 1));
+        // 10 from the finished day + 10 from today's single 8.0 sample
+        assert_eq!(summary.season.hdd, t(20.0));
+    }
+
+    #[test]
+    fn test_accumulator_season_reset() {
+        let config = DegreeDayConfig {
+            season_start_month: 7,
+            ..Default::default()
+        };
+        let mut acc = DegreeDayAccumulator::new(config);
+        acc.add_sample(date(2025, 6, 30), 8.0);
+        assert_eq!(acc.summary().unwrap().season_start, date(2024, 7, 1));
+
+        acc.add_sample(date(2025, 7, 1), 8.0);
+        let summary = acc.summary().unwrap();
+        assert_eq!(summary.season_start, date(2025, 7, 1));
+        assert_eq!(summary.season.hdd, t(10.0));
+    }
+
+    #[test]
+    fn test_accumulator_state_roundtrip() {
+        let mut acc = DegreeDayAccumulator::new(DegreeDayConfig::default());
+        acc.add_sample(date(2025, 1, 1), 5.0);
+        acc.add_sample(date(2025, 1, 2), 8.0);
+        acc.add_sample(date(2025, 1, 2), 2.0);
+
+        let state = acc.state();
+        let mut restored = DegreeDayAccumulator::new(DegreeDayConfig::default());
+        restored.restore(state.clone());
+
+        assert_eq!(restored.state(), state);
+        assert_eq!(
+            restored.summary().unwrap().season.hdd,
+            acc.summary().unwrap().season.hdd
+        );
+    }
+
+    #[test]
+    fn test_accumulator_empty_summary() {
+        let acc = DegreeDayAccumulator::new(DegreeDayConfig::default());
+        assert!(acc.summary().is_none());
+    }
+}
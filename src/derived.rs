@@ -0,0 +1,771 @@
+/// Derived indicators computed from the raw live data
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// How far a ground-level surface is assumed to sit below the air
+/// temperature measured at screen height on a clear, calm night (°C)
+const SURFACE_TEMP_OFFSET: f64 = 2.0;
+
+/// Upper wind speed bound (m/s, exclusive) for Beaufort forces 0-11
+const BEAUFORT_LIMITS: [f64; 12] = [
+    0.5, 1.6, 3.4, 5.5, 8.0, 10.8, 13.9, 17.2, 20.8, 24.5, 28.5, 32.7,
+];
+
+/// Upper rain rate bounds (mm/h, exclusive) for light, moderate and heavy
+/// rain, after the WMO intensity classes; anything faster is violent
+const RAIN_INTENSITY_LIMITS: [f64; 3] = [2.5, 10.0, 50.0];
+
+/// Rain intensity names indexed by class
+pub const RAIN_INTENSITY_LABELS: [&str; 5] = ["none", "light", "moderate", "heavy", "violent"];
+
+/// Descriptive labels indexed by Beaufort force. Swap in a translated
+/// table via `beaufort_description_in` to localize the output.
+pub type BeaufortLabels = [&'static str; 13];
+
+pub const BEAUFORT_LABELS_EN: BeaufortLabels = [
+    "Calm",
+    "Light air",
+    "Light breeze",
+    "Gentle breeze",
+    "Moderate breeze",
+    "Fresh breeze",
+    "Strong breeze",
+    "Near gale",
+    "Gale",
+    "Strong gale",
+    "Storm",
+    "Violent storm",
+    "Hurricane force",
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PressureConfig {
+    /// Station altitude above mean sea level in meters (default: the
+    /// `[station]` elevation)
+    #[serde(default)]
+    pub altitude: Option<f64>,
+
+    /// Difference (hPa) between our QNH and the gateway's relbarometer
+    /// beyond which a warning is raised (default: 1.0)
+    #[serde(default = "default_mismatch_threshold")]
+    pub mismatch_threshold: f64,
+}
+
+pub fn default_mismatch_threshold() -> f64 {
+    1.0
+}
+
+/// Thresholds for `precip_type_estimate`, on the estimated wet-bulb
+/// temperature in °C
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrecipTypeConfig {
+    /// Snow at or below this (default: 0.5)
+    #[serde(default = "default_snow_below")]
+    pub snow_below: f64,
+    /// Rain at or above this, mixed in between (default: 2.0)
+    #[serde(default = "default_rain_above")]
+    pub rain_above: f64,
+}
+
+fn default_snow_below() -> f64 {
+    0.5
+}
+
+fn default_rain_above() -> f64 {
+    2.0
+}
+
+impl PrecipTypeConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.snow_below >= self.rain_above {
+            anyhow::bail!(
+                "Invalid [precip_type]: snow_below ({}) must be below rain_above ({})",
+                self.snow_below,
+                self.rain_above
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Precipitation type names indexed by `precip_type_estimate`
+pub const PRECIP_TYPE_LABELS: [&str; 4] = ["none", "rain", "mixed", "snow"];
+
+/// How a virtual sensor combines its inputs
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AggregateOp {
+    Average,
+    Min,
+    Max,
+    Difference,
+}
+
+/// A field computed from other fields, e.g. the average of two outdoor sensors
+#[derive(Debug, Clone, Deserialize)]
+pub struct VirtualSensorConfig {
+    /// Name of the new field
+    pub name: String,
+    /// Aggregation to apply: average, min, max or difference
+    pub op: AggregateOp,
+    /// Source field names; difference takes exactly two (first minus second)
+    pub inputs: Vec<String>,
+}
+
+impl VirtualSensorConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.inputs.is_empty() {
+            anyhow::bail!(
+                "Virtual sensor '{}' must have at least one input",
+                self.name
+            );
+        }
+        if self.op == AggregateOp::Difference && self.inputs.len() != 2 {
+            anyhow::bail!(
+                "Virtual sensor '{}' uses difference and must have exactly two inputs",
+                self.name
+            );
+        }
+        Ok(())
+    }
+
+    /// Compute the virtual value from the available inputs. Average, min and
+    /// max use whichever inputs are present so a failed sensor doesn't drop
+    /// the field; difference needs both.
+    pub fn compute(&self, data: &HashMap<String, f64>) -> Option<f64> {
+        let values: Vec<f64> = self
+            .inputs
+            .iter()
+            .filter_map(|input| data.get(input).copied())
+            .collect();
+
+        if values.is_empty() {
+            return None;
+        }
+
+        match self.op {
+            AggregateOp::Average => {
+                let mean = values.iter().sum::<f64>() / values.len() as f64;
+                Some((mean * 100.0).round() / 100.0)
+            }
+            AggregateOp::Min => values.iter().copied().reduce(f64::min),
+            AggregateOp::Max => values.iter().copied().reduce(f64::max),
+            AggregateOp::Difference => {
+                if values.len() != 2 {
+                    return None;
+                }
+                Some(((values[0] - values[1]) * 100.0).round() / 100.0)
+            }
+        }
+    }
+}
+
+/// Configuration for derived fields that need station-specific settings
+#[derive(Debug, Clone, Default)]
+pub struct DerivedConfig {
+    pub pressure: Option<PressureConfig>,
+    pub virtual_sensors: Vec<VirtualSensorConfig>,
+    pub precip_type: Option<PrecipTypeConfig>,
+}
+
+/// Add all derived indicator fields to a live data map
+pub fn add_derived_fields(data: &mut HashMap<String, f64>, config: &DerivedConfig) {
+    // Virtual sensors go first so they only ever see measured values
+    for sensor in &config.virtual_sensors {
+        if let Some(value) = sensor.compute(data) {
+            data.insert(sensor.name.clone(), value);
+        }
+    }
+
+    add_risk_indicators(data);
+    add_beaufort(data);
+    add_rain_intensity(data);
+    if let Some(ref pressure) = config.pressure {
+        add_pressure_correction(data, pressure);
+    }
+    if let Some(ref precip_type) = config.precip_type {
+        add_precip_type_estimate(data, precip_type);
+    }
+}
+
+/// Sea-level pressure (QNH) from station pressure and altitude, using the
+/// ICAO standard atmosphere
+pub fn qnh(station_pressure: f64, altitude: f64) -> f64 {
+    station_pressure * (1.0 - 0.0065 * altitude / 288.15).powf(-5.255)
+}
+
+/// Pressure altitude in meters for a station pressure in hPa
+pub fn pressure_altitude(station_pressure: f64) -> f64 {
+    44_307.69 * (1.0 - (station_pressure / 1013.25).powf(0.190_284))
+}
+
+/// Add our own sea-level corrected pressure alongside the gateway's
+/// relbarometer and flag when the two disagree
+pub fn add_pressure_correction(data: &mut HashMap<String, f64>, config: &PressureConfig) {
+    let (Some(&abs), Some(altitude)) = (data.get("absbarometer"), config.altitude) else {
+        return;
+    };
+
+    let corrected = (qnh(abs, altitude) * 10.0).round() / 10.0;
+    data.insert("qnh_barometer".to_string(), corrected);
+    data.insert(
+        "pressure_altitude".to_string(),
+        pressure_altitude(abs).round(),
+    );
+
+    if let Some(&rel) = data.get("relbarometer") {
+        let difference = ((corrected - rel) * 10.0).round() / 10.0;
+        data.insert("barometer_difference".to_string(), difference);
+        data.insert(
+            "barometer_mismatch_warning".to_string(),
+            if difference.abs() > config.mismatch_threshold {
+                1.0
+            } else {
+                0.0
+            },
+        );
+    }
+}
+
+/// Beaufort force (0-12) for a wind speed in m/s
+pub fn beaufort(speed: f64) -> u8 {
+    BEAUFORT_LIMITS
+        .iter()
+        .position(|&limit| speed < limit)
+        .unwrap_or(12) as u8
+}
+
+/// English description of a Beaufort force
+pub fn beaufort_description(force: u8) -> &'static str {
+    beaufort_description_in(force, &BEAUFORT_LABELS_EN)
+}
+
+/// Description of a Beaufort force using the given label table
+pub fn beaufort_description_in(force: u8, labels: &BeaufortLabels) -> &'static str {
+    labels[(force as usize).min(12)]
+}
+
+/// Add Beaufort force for the sustained wind and gust speeds
+pub fn add_beaufort(data: &mut HashMap<String, f64>) {
+    if let Some(&speed) = data.get("wind_speed") {
+        data.insert("beaufort".to_string(), beaufort(speed) as f64);
+    }
+    if let Some(&gust) = data.get("gust_speed") {
+        data.insert("gust_beaufort".to_string(), beaufort(gust) as f64);
+    }
+}
+
+/// Rain intensity class (0 = none, 1 = light, 2 = moderate, 3 = heavy,
+/// 4 = violent) for a rain rate in mm/h
+pub fn rain_intensity(rate: f64) -> u8 {
+    if rate <= 0.0 {
+        return 0;
+    }
+    1 + RAIN_INTENSITY_LIMITS
+        .iter()
+        .position(|&limit| rate < limit)
+        .unwrap_or(RAIN_INTENSITY_LIMITS.len()) as u8
+}
+
+/// Name of a rain intensity class
+pub fn rain_intensity_description(intensity: u8) -> &'static str {
+    RAIN_INTENSITY_LABELS[(intensity as usize).min(RAIN_INTENSITY_LABELS.len() - 1)]
+}
+
+/// Add the intensity class of the tipping bucket's rain rate, or of the
+/// piezo gauge's on a WS90 without one
+pub fn add_rain_intensity(data: &mut HashMap<String, f64>) {
+    if let Some(&rate) = data
+        .get("rain_rate")
+        .or_else(|| data.get("piezo_rain_rate"))
+    {
+        data.insert("rain_intensity".to_string(), rain_intensity(rate) as f64);
+    }
+}
+
+/// Wet-bulb temperature estimated with the one-third rule: a third of the
+/// way from the temperature down to the dew point. Falling snow cools the
+/// air towards it, which makes it a better guide to snow than temperature.
+pub fn wet_bulb_estimate(temp: f64, dewpoint: f64) -> f64 {
+    temp - (temp - dewpoint) / 3.0
+}
+
+/// Likely precipitation type (0 = none, 1 = rain, 2 = mixed, 3 = snow).
+/// Precipitation counts as detected when a gauge reports a rate, or when the
+/// air is saturated (dew point within 1°C), since snow collects in the
+/// gauges before it is measured.
+pub fn precip_type_estimate(
+    temp: f64,
+    dewpoint: f64,
+    rain_detected: bool,
+    config: &PrecipTypeConfig,
+) -> u8 {
+    if !rain_detected && temp - dewpoint > 1.0 {
+        return 0;
+    }
+    let wet_bulb = wet_bulb_estimate(temp, dewpoint);
+    if wet_bulb <= config.snow_below {
+        3
+    } else if wet_bulb < config.rain_above {
+        2
+    } else {
+        1
+    }
+}
+
+/// Add `precip_type_estimate` from the outdoor temperature, dew point and
+/// rain rates
+pub fn add_precip_type_estimate(data: &mut HashMap<String, f64>, config: &PrecipTypeConfig) {
+    let Some(&temp) = data.get("outtemp") else {
+        return;
+    };
+    let dewpoint = match (data.get("dewpoint"), data.get("outhumid")) {
+        (Some(&dp), _) => Some(dp),
+        (None, Some(&humidity)) => dew_point(temp, humidity),
+        (None, None) => None,
+    };
+    let Some(dewpoint) = dewpoint else {
+        return;
+    };
+    let rain_detected = ["rain_rate", "piezo_rain_rate"]
+        .iter()
+        .any(|field| data.get(*field).is_some_and(|&rate| rate > 0.0));
+    data.insert(
+        "precip_type_estimate".to_string(),
+        precip_type_estimate(temp, dewpoint, rain_detected, config) as f64,
+    );
+}
+
+/// Name of a `precip_type_estimate` value
+pub fn precip_type_description(value: u8) -> &'static str {
+    PRECIP_TYPE_LABELS[(value as usize).min(PRECIP_TYPE_LABELS.len() - 1)]
+}
+
+/// A change in the rain seen by `RainWatch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RainEvent {
+    Started(u8),
+    Stopped,
+    IntensityChanged { from: u8, to: u8 },
+}
+
+/// Turns successive `rain_intensity` values into start, stop and change
+/// events for the event log
+#[derive(Debug, Default)]
+pub struct RainWatch {
+    intensity: Option<u8>,
+}
+
+impl RainWatch {
+    /// Record this poll's intensity. The first poll after startup only sets
+    /// the baseline, so a restart during a shower doesn't report a start.
+    pub fn observe(&mut self, data: &HashMap<String, f64>) -> Option<RainEvent> {
+        let now = data.get("rain_intensity").map(|&v| v as u8)?;
+        let previous = self.intensity.replace(now)?;
+        match (previous, now) {
+            (from, to) if from == to => None,
+            (0, to) => Some(RainEvent::Started(to)),
+            (_, 0) => Some(RainEvent::Stopped),
+            (from, to) => Some(RainEvent::IntensityChanged { from, to }),
+        }
+    }
+}
+
+/// Dew point in °C from temperature (°C) and relative humidity (%),
+/// using the Magnus formula
+pub fn dew_point(temp: f64, humidity: f64) -> Option<f64> {
+    if humidity <= 0.0 {
+        return None;
+    }
+    let a = 17.62;
+    let b = 243.12;
+    let gamma = (humidity.min(100.0) / 100.0).ln() + a * temp / (b + temp);
+    Some(b * gamma / (a - gamma))
+}
+
+/// Frost risk grade (0 = none, 1 = low, 2 = moderate, 3 = high)
+/// from outdoor air temperature and dew point
+pub fn frost_risk(temp: f64, dewpoint: f64) -> u8 {
+    let surface = temp - SURFACE_TEMP_OFFSET;
+    if surface <= 0.0 && dewpoint >= surface - 1.0 {
+        3
+    } else if surface <= 0.0 {
+        2
+    } else if surface <= 2.0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Condensation risk grade from the spread between temperature and dew point
+pub fn condensation_risk(temp: f64, dewpoint: f64) -> u8 {
+    let spread = temp - dewpoint;
+    if spread <= 1.0 {
+        3
+    } else if spread <= 2.0 {
+        2
+    } else if spread <= 4.0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Mold risk grade from sustained relative humidity
+pub fn mold_risk(humidity: f64) -> u8 {
+    if humidity >= 80.0 {
+        3
+    } else if humidity >= 70.0 {
+        2
+    } else if humidity >= 60.0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Add frost, condensation and mold indicators for each temp/humidity pair
+pub fn add_risk_indicators(data: &mut HashMap<String, f64>) {
+    for prefix in ["in", "out"] {
+        let (Some(&temp), Some(&humidity)) = (
+            data.get(&format!("{}temp", prefix)),
+            data.get(&format!("{}humid", prefix)),
+        ) else {
+            continue;
+        };
+
+        // Prefer the gateway's own outdoor dew point when present
+        let dewpoint = match (prefix, data.get("dewpoint")) {
+            ("out", Some(&dp)) => Some(dp),
+            _ => dew_point(temp, humidity),
+        };
+        let Some(dewpoint) = dewpoint else {
+            continue;
+        };
+
+        if prefix == "in" {
+            data.insert("in_dewpoint".to_string(), (dewpoint * 10.0).round() / 10.0);
+        } else {
+            let frost = frost_risk(temp, dewpoint);
+            data.insert("frost_risk".to_string(), frost as f64);
+            data.insert(
+                "frost_warning".to_string(),
+                if frost >= 2 { 1.0 } else { 0.0 },
+            );
+        }
+
+        data.insert(
+            format!("{}_condensation_risk", prefix),
+            condensation_risk(temp, dewpoint) as f64,
+        );
+        data.insert(format!("{}_mold_risk", prefix), mold_risk(humidity) as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dew_point() {
+        // 20°C at 50% RH is roughly 9.3°C
+        let dp = dew_point(20.0, 50.0).unwrap();
+        assert!((dp - 9.3).abs() < 0.1);
+
+        // Saturated air: dew point equals temperature
+        let dp = dew_point(15.0, 100.0).unwrap();
+        assert!((dp - 15.0).abs() < 0.01);
+
+        assert!(dew_point(15.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn test_frost_risk() {
+        assert_eq!(frost_risk(10.0, 5.0), 0);
+        assert_eq!(frost_risk(3.5, -2.0), 1);
+        assert_eq!(frost_risk(1.0, -10.0), 2);
+        assert_eq!(frost_risk(1.0, -1.0), 3);
+    }
+
+    #[test]
+    fn test_condensation_risk() {
+        assert_eq!(condensation_risk(20.0, 10.0), 0);
+        assert_eq!(condensation_risk(20.0, 17.0), 1);
+        assert_eq!(condensation_risk(20.0, 18.5), 2);
+        assert_eq!(condensation_risk(20.0, 19.5), 3);
+    }
+
+    #[test]
+    fn test_mold_risk() {
+        assert_eq!(mold_risk(45.0), 0);
+        assert_eq!(mold_risk(65.0), 1);
+        assert_eq!(mold_risk(75.0), 2);
+        assert_eq!(mold_risk(85.0), 3);
+    }
+
+    #[test]
+    fn test_beaufort() {
+        assert_eq!(beaufort(0.0), 0);
+        assert_eq!(beaufort(0.5), 1);
+        assert_eq!(beaufort(3.3), 2);
+        assert_eq!(beaufort(9.0), 5);
+        assert_eq!(beaufort(32.6), 11);
+        assert_eq!(beaufort(40.0), 12);
+    }
+
+    #[test]
+    fn test_beaufort_description() {
+        assert_eq!(beaufort_description(0), "Calm");
+        assert_eq!(beaufort_description(5), "Fresh breeze");
+        assert_eq!(beaufort_description(12), "Hurricane force");
+        assert_eq!(beaufort_description(200), "Hurricane force");
+    }
+
+    #[test]
+    fn test_beaufort_description_in_custom_labels() {
+        let mut labels = BEAUFORT_LABELS_EN;
+        labels[5] = "Frische Brise";
+        assert_eq!(beaufort_description_in(5, &labels), "Frische Brise");
+    }
+
+    #[test]
+    fn test_add_beaufort() {
+        let mut data = HashMap::new();
+        data.insert("wind_speed".to_string(), 9.0);
+        data.insert("gust_speed".to_string(), 14.0);
+
+        add_beaufort(&mut data);
+
+        assert_eq!(data.get("beaufort"), Some(&5.0));
+        assert_eq!(data.get("gust_beaufort"), Some(&7.0));
+    }
+
+    #[test]
+    fn test_qnh() {
+        // At sea level QNH equals station pressure
+        assert!((qnh(1013.2, 0.0) - 1013.2).abs() < 0.01);
+        // Roughly 12 hPa per 100 m near sea level
+        let corrected = qnh(1000.0, 100.0);
+        assert!((corrected - 1012.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_pressure_altitude() {
+        assert!(pressure_altitude(1013.25).abs() < 0.01);
+        assert!((pressure_altitude(899.0) - 1000.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn test_add_pressure_correction_mismatch() {
+        let config = PressureConfig {
+            altitude: Some(100.0),
+            mismatch_threshold: 1.0,
+        };
+        let mut data = HashMap::new();
+        data.insert("absbarometer".to_string(), 1000.0);
+        data.insert("relbarometer".to_string(), 1005.0);
+
+        add_pressure_correction(&mut data, &config);
+
+        assert!(data.contains_key("qnh_barometer"));
+        assert!(data.contains_key("pressure_altitude"));
+        assert!(data["barometer_difference"] > 6.0);
+        assert_eq!(data.get("barometer_mismatch_warning"), Some(&1.0));
+    }
+
+    #[test]
+    fn test_add_pressure_correction_agreement() {
+        let config = PressureConfig {
+            altitude: Some(100.0),
+            mismatch_threshold: 1.0,
+        };
+        let mut data = HashMap::new();
+        data.insert("absbarometer".to_string(), 1000.0);
+        data.insert("relbarometer".to_string(), 1012.0);
+
+        add_pressure_correction(&mut data, &config);
+
+        assert_eq!(data.get("barometer_mismatch_warning"), Some(&0.0));
+    }
+
+    fn virtual_sensor(op: AggregateOp, inputs: &[&str]) -> VirtualSensorConfig {
+        VirtualSensorConfig {
+            name: "virtual".to_string(),
+            op,
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_virtual_sensor_ops() {
+        let mut data = HashMap::new();
+        data.insert("outtemp".to_string(), 20.0);
+        data.insert("temp_ch1".to_string(), 21.0);
+
+        let inputs = ["outtemp", "temp_ch1"];
+        assert_eq!(
+            virtual_sensor(AggregateOp::Average, &inputs).compute(&data),
+            Some(20.5)
+        );
+        assert_eq!(
+            virtual_sensor(AggregateOp::Min, &inputs).compute(&data),
+            Some(20.0)
+        );
+        assert_eq!(
+            virtual_sensor(AggregateOp::Max, &inputs).compute(&data),
+            Some(21.0)
+        );
+        assert_eq!(
+            virtual_sensor(AggregateOp::Difference, &inputs).compute(&data),
+            Some(-1.0)
+        );
+    }
+
+    #[test]
+    fn test_rain_intensity() {
+        assert_eq!(rain_intensity(0.0), 0);
+        assert_eq!(rain_intensity(0.3), 1);
+        assert_eq!(rain_intensity(2.5), 2);
+        assert_eq!(rain_intensity(9.9), 2);
+        assert_eq!(rain_intensity(10.0), 3);
+        assert_eq!(rain_intensity(50.0), 4);
+        assert_eq!(rain_intensity_description(3), "heavy");
+        assert_eq!(rain_intensity_description(9), "violent");
+
+        let mut data = HashMap::new();
+        data.insert("piezo_rain_rate".to_string(), 4.2);
+        add_rain_intensity(&mut data);
+        assert_eq!(data["rain_intensity"], 2.0);
+        data.insert("rain_rate".to_string(), 0.0);
+        add_rain_intensity(&mut data);
+        assert_eq!(data["rain_intensity"], 0.0);
+    }
+
+    #[test]
+    fn test_precip_type_estimate() {
+        let config = PrecipTypeConfig {
+            snow_below: default_snow_below(),
+            rain_above: default_rain_above(),
+        };
+        assert!(config.validate().is_ok());
+
+        // Dry air and no rain: nothing falling
+        assert_eq!(precip_type_estimate(-3.0, -10.0, false, &config), 0);
+        // Saturated air just above freezing: snow the gauges haven't seen yet
+        assert_eq!(precip_type_estimate(0.6, 0.0, false, &config), 3);
+        // Dry air cools falling rain to sleet
+        assert_eq!(precip_type_estimate(3.0, -1.5, true, &config), 2);
+        assert_eq!(precip_type_estimate(6.0, 4.0, true, &config), 1);
+
+        let mut data = HashMap::new();
+        data.insert("outtemp".to_string(), 0.5);
+        data.insert("outhumid".to_string(), 97.0);
+        data.insert("rain_rate".to_string(), 0.0);
+        add_precip_type_estimate(&mut data, &config);
+        assert_eq!(data["precip_type_estimate"], 3.0);
+        assert_eq!(precip_type_description(3), "snow");
+
+        let inverted = PrecipTypeConfig {
+            snow_below: 2.0,
+            rain_above: 1.0,
+        };
+        assert!(inverted.validate().is_err());
+    }
+
+    #[test]
+    fn test_rain_watch() {
+        let mut watch = RainWatch::default();
+        let mut observe = |rate: f64| {
+            let mut data = HashMap::new();
+            data.insert("rain_rate".to_string(), rate);
+            add_rain_intensity(&mut data);
+            watch.observe(&data)
+        };
+
+        // Starting up mid-shower sets the baseline without an event
+        assert_eq!(observe(1.0), None);
+        assert_eq!(observe(1.5), None);
+        assert_eq!(
+            observe(12.0),
+            Some(RainEvent::IntensityChanged { from: 1, to: 3 })
+        );
+        assert_eq!(observe(0.0), Some(RainEvent::Stopped));
+        assert_eq!(observe(0.0), None);
+        assert_eq!(observe(0.4), Some(RainEvent::Started(1)));
+    }
+
+    #[test]
+    fn test_virtual_sensor_missing_input() {
+        let mut data = HashMap::new();
+        data.insert("outtemp".to_string(), 20.0);
+
+        let inputs = ["outtemp", "temp_ch1"];
+        assert_eq!(
+            virtual_sensor(AggregateOp::Average, &inputs).compute(&data),
+            Some(20.0)
+        );
+        assert!(virtual_sensor(AggregateOp::Difference, &inputs)
+            .compute(&data)
+            .is_none());
+        assert!(virtual_sensor(AggregateOp::Max, &["temp_ch2"])
+            .compute(&data)
+            .is_none());
+    }
+
+    #[test]
+    fn test_virtual_sensor_validate() {
+        assert!(virtual_sensor(AggregateOp::Average, &["a", "b", "c"])
+            .validate()
+            .is_ok());
+        assert!(virtual_sensor(AggregateOp::Difference, &["a"])
+            .validate()
+            .is_err());
+        assert!(virtual_sensor(AggregateOp::Min, &[]).validate().is_err());
+    }
+
+    #[test]
+    fn test_virtual_sensor_deserialization() {
+        let toml_str = r#"
+            name = "outtemp_avg"
+            op = "average"
+            inputs = ["outtemp", "temp_ch1"]
+        "#;
+
+        let sensor: VirtualSensorConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(sensor.name, "outtemp_avg");
+        assert_eq!(sensor.op, AggregateOp::Average);
+        assert_eq!(sensor.inputs.len(), 2);
+    }
+
+    #[test]
+    fn test_add_risk_indicators() {
+        let mut data = HashMap::new();
+        data.insert("intemp".to_string(), 21.0);
+        data.insert("inhumid".to_string(), 72.0);
+        data.insert("outtemp".to_string(), 0.5);
+        data.insert("outhumid".to_string(), 95.0);
+        data.insert("dewpoint".to_string(), -0.2);
+
+        add_risk_indicators(&mut data);
+
+        assert_eq!(data.get("frost_risk"), Some(&3.0));
+        assert_eq!(data.get("frost_warning"), Some(&1.0));
+        assert_eq!(data.get("out_condensation_risk"), Some(&3.0));
+        assert_eq!(data.get("out_mold_risk"), Some(&3.0));
+        assert_eq!(data.get("in_mold_risk"), Some(&2.0));
+        assert!(data.contains_key("in_dewpoint"));
+        assert!(data.contains_key("in_condensation_risk"));
+    }
+
+    #[test]
+    fn test_add_risk_indicators_missing_humidity() {
+        let mut data = HashMap::new();
+        data.insert("outtemp".to_string(), 0.5);
+
+        add_risk_indicators(&mut data);
+
+        assert!(!data.contains_key("frost_risk"));
+        assert_eq!(data.len(), 1);
+    }
+}
@@ -0,0 +1,152 @@
+/// Gateway system parameters (frequency, sensor type, clock and time zone)
+/// and calibration offsets
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::decoder::{decode_signed_byte, decode_signed_int, decode_signed_short};
+
+/// Length of the system parameter block: frequency(1) + sensor type(1) +
+/// utc(4) + time zone index(1) + DST status(1)
+const SYSTEM_PARAMS_LEN: usize = 8;
+
+/// System parameters as read with CMD_READ_SSSS and written back with
+/// CMD_WRITE_SSSS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemParams {
+    pub frequency: u8,
+    pub sensor_type: u8,
+    /// Gateway clock, seconds since the Unix epoch. The gateway resets its
+    /// daily rain counter when this crosses midnight.
+    pub utc: u32,
+    pub timezone_index: u8,
+    pub dst_status: u8,
+}
+
+impl SystemParams {
+    /// Parse the payload of a CMD_READ_SSSS response
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < SYSTEM_PARAMS_LEN {
+            anyhow::bail!(
+                "System parameters too short ({} bytes, expected {})",
+                data.len(),
+                SYSTEM_PARAMS_LEN
+            );
+        }
+        Ok(Self {
+            frequency: data[0],
+            sensor_type: data[1],
+            utc: u32::from_be_bytes([data[2], data[3], data[4], data[5]]),
+            timezone_index: data[6],
+            dst_status: data[7],
+        })
+    }
+
+    /// Encode as the payload of a CMD_WRITE_SSSS request
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = vec![self.frequency, self.sensor_type];
+        bytes.extend_from_slice(&self.utc.to_be_bytes());
+        bytes.push(self.timezone_index);
+        bytes.push(self.dst_status);
+        bytes
+    }
+}
+
+/// Length of the calibration block: intemp(2) + inhumid(1) + abs(4) +
+/// rel(4) + outtemp(2) + outhumid(1) + wind direction(2)
+const CALIBRATION_LEN: usize = 16;
+
+/// Offsets the gateway applies to its own readings, as set in the WS View
+/// app. All values are signed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CalibrationOffsets {
+    /// °C
+    pub intemp: f64,
+    /// %
+    pub inhumid: f64,
+    /// hPa
+    pub absbarometer: f64,
+    /// hPa
+    pub relbarometer: f64,
+    /// °C
+    pub outtemp: f64,
+    /// %
+    pub outhumid: f64,
+    /// Degrees
+    pub wind_dir: f64,
+}
+
+impl CalibrationOffsets {
+    /// Parse the payload of a CMD_READ_CALIBRATION response
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < CALIBRATION_LEN {
+            anyhow::bail!(
+                "Calibration data too short ({} bytes, expected {})",
+                data.len(),
+                CALIBRATION_LEN
+            );
+        }
+        Ok(Self {
+            intemp: decode_signed_short(&data[0..2]) / 10.0,
+            inhumid: decode_signed_byte(&data[2..3]),
+            absbarometer: decode_signed_int(&data[3..7]) / 10.0,
+            relbarometer: decode_signed_int(&data[7..11]) / 10.0,
+            outtemp: decode_signed_short(&data[11..13]) / 10.0,
+            outhumid: decode_signed_byte(&data[13..14]),
+            wind_dir: decode_signed_short(&data[14..16]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_system_params() {
+        let data = [0x03, 0x01, 0x65, 0x4C, 0x1E, 0x00, 0x0B, 0x01];
+        let params = SystemParams::parse(&data).unwrap();
+        assert_eq!(params.frequency, 3);
+        assert_eq!(params.sensor_type, 1);
+        assert_eq!(params.utc, 0x654C_1E00);
+        assert_eq!(params.timezone_index, 11);
+        assert_eq!(params.dst_status, 1);
+    }
+
+    #[test]
+    fn test_parse_system_params_too_short() {
+        assert!(SystemParams::parse(&[0x03, 0x01, 0x65]).is_err());
+    }
+
+    #[test]
+    fn test_system_params_roundtrip() {
+        let data = [0x03, 0x01, 0x65, 0x4C, 0x1E, 0x00, 0x0B, 0x01];
+        let params = SystemParams::parse(&data).unwrap();
+        assert_eq!(params.to_bytes(), data);
+    }
+
+    #[test]
+    fn test_parse_calibration_offsets() {
+        let data = [
+            0xFF, 0xFC, // intemp -0.4
+            0x03, // inhumid +3
+            0x00, 0x00, 0x00, 0x0C, // abs +1.2
+            0xFF, 0xFF, 0xFF, 0x9C, // rel -10.0
+            0x00, 0x05, // outtemp +0.5
+            0xFE, // outhumid -2
+            0xFF, 0xF6, // wind direction -10
+        ];
+        let offsets = CalibrationOffsets::parse(&data).unwrap();
+        assert_eq!(offsets.intemp, -0.4);
+        assert_eq!(offsets.inhumid, 3.0);
+        assert_eq!(offsets.absbarometer, 1.2);
+        assert_eq!(offsets.relbarometer, -10.0);
+        assert_eq!(offsets.outtemp, 0.5);
+        assert_eq!(offsets.outhumid, -2.0);
+        assert_eq!(offsets.wind_dir, -10.0);
+    }
+
+    #[test]
+    fn test_parse_calibration_offsets_too_short() {
+        assert!(CalibrationOffsets::parse(&[0xFF, 0xFC, 0x03]).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+/// One worker thread talks to the gateway; everything else queues requests
+/// for it. Gateway firmware copes badly with overlapping TCP connections, so
+/// the live data poll, the sensor status refresh, the web poller, scheduled
+/// tasks and control commands take turns instead, most urgent first.
+use anyhow::Result;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use tokio::sync::oneshot;
+
+use crate::local_api::Gateway;
+
+/// Which queued request goes first; requests of the same priority run in
+/// the order they were queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Maintenance on a timer, e.g. setting the gateway clock
+    Scheduled,
+    /// Sensor signal and battery status, read after each poll
+    Sensors,
+    /// Live data and the device info read at startup
+    Poll,
+    /// Commands someone is waiting on, from the control socket or API
+    Control,
+}
+
+type Job = Box<dyn FnOnce(&Gateway) + Send>;
+
+struct Queued {
+    priority: Priority,
+    /// Queue order, to keep equal priorities first in, first out
+    sequence: u64,
+    job: Job,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    /// Highest priority first, then lowest sequence
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    jobs: BinaryHeap<Queued>,
+    next_sequence: u64,
+}
+
+/// Handle to the gateway worker; clones share the same queue
+#[derive(Clone)]
+pub struct DeviceQueue {
+    pending: Arc<(Mutex<Pending>, Condvar)>,
+}
+
+impl DeviceQueue {
+    /// Start the worker thread, which owns `gateway` from now on
+    pub fn start(gateway: Gateway) -> Self {
+        let pending = Arc::new((Mutex::new(Pending::default()), Condvar::new()));
+        let worker = pending.clone();
+        std::thread::Builder::new()
+            .name("gateway".to_string())
+            .spawn(move || loop {
+                let (lock, ready) = &*worker;
+                let job = {
+                    let mut pending = lock.lock().unwrap_or_else(|e| e.into_inner());
+                    loop {
+                        match pending.jobs.pop() {
+                            Some(queued) => break queued.job,
+                            None => {
+                                pending = ready.wait(pending).unwrap_or_else(|e| e.into_inner())
+                            }
+                        }
+                    }
+                };
+                // A request that panics drops its reply, failing only its
+                // own caller; the worker goes on with the next one
+                if panic::catch_unwind(AssertUnwindSafe(|| job(&gateway))).is_err() {
+                    eprintln!("[ERROR] A gateway request panicked");
+                }
+            })
+            .expect("Failed to start the gateway worker thread");
+        Self { pending }
+    }
+
+    /// Queue `request` and wait for its turn and its result
+    pub async fn run<T, F>(&self, priority: Priority, request: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Gateway) -> Result<T> + Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        let job: Job = Box::new(move |gateway| {
+            // The requester may have given up waiting
+            let _ = reply.send(request(gateway));
+        });
+        {
+            let (lock, ready) = &*self.pending;
+            let mut pending = lock.lock().unwrap_or_else(|e| e.into_inner());
+            let sequence = pending.next_sequence;
+            pending.next_sequence += 1;
+            pending.jobs.push(Queued {
+                priority,
+                sequence,
+                job,
+            });
+            ready.notify_one();
+        }
+        result
+            .await
+            .map_err(|_| anyhow::anyhow!("The gateway request panicked"))?
+    }
+
+    /// Requests waiting for their turn
+    pub fn waiting(&self) -> usize {
+        let (lock, _) = &*self.pending;
+        lock.lock().unwrap_or_else(|e| e.into_inner()).jobs.len()
+    }
+}
+
+impl fmt::Debug for DeviceQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceQueue")
+            .field("waiting", &self.waiting())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::GW1000Client;
+    use crate::local_api::{GatewayApi, LocalHttpClient};
+    use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn queue() -> DeviceQueue {
+        DeviceQueue::start(Gateway::new(
+            GatewayApi::Tcp,
+            GW1000Client::new("127.0.0.1".to_string(), 1),
+            LocalHttpClient::new("127.0.0.1".to_string(), 1),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_runs_by_priority() {
+        let queue = queue();
+
+        // Hold the worker until everything else is queued
+        let (release, held) = mpsc::channel::<()>();
+        let started = Arc::new(AtomicBool::new(false));
+        let blocker = tokio::spawn({
+            let queue = queue.clone();
+            let started = started.clone();
+            async move {
+                queue
+                    .run(Priority::Poll, move |_| {
+                        started.store(true, AtomicOrdering::SeqCst);
+                        held.recv().ok();
+                        Ok(())
+                    })
+                    .await
+            }
+        });
+        while !started.load(AtomicOrdering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut requests = Vec::new();
+        for (name, priority) in [
+            ("scheduled", Priority::Scheduled),
+            ("sensors", Priority::Sensors),
+            ("poll", Priority::Poll),
+            ("control", Priority::Control),
+            ("second poll", Priority::Poll),
+        ] {
+            let handle = queue.clone();
+            let order = order.clone();
+            requests.push(tokio::spawn(async move {
+                handle
+                    .run(priority, move |_| {
+                        order.lock().unwrap().push(name);
+                        Ok(())
+                    })
+                    .await
+            }));
+            // Queue them one at a time so their sequence is known
+            let queued = requests.len();
+            while queue.waiting() < queued {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }
+
+        release.send(()).unwrap();
+        blocker.await.unwrap().unwrap();
+        for request in requests {
+            request.await.unwrap().unwrap();
+        }
+        assert_eq!(
+            *order.lock().unwrap(),
+            ["control", "poll", "second poll", "sensors", "scheduled"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_returns_results_and_errors() {
+        let queue = queue();
+        assert_eq!(queue.run(Priority::Poll, |_| Ok(42)).await.unwrap(), 42);
+        let error = queue
+            .run(Priority::Control, |_| -> Result<()> {
+                anyhow::bail!("Gateway busy")
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Gateway busy");
+    }
+
+    #[tokio::test]
+    async fn test_survives_a_panicking_request() {
+        let queue = queue();
+        let error = queue
+            .run(Priority::Control, |_| -> Result<()> {
+                panic!("decoder bug")
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "The gateway request panicked");
+        assert_eq!(queue.run(Priority::Poll, |_| Ok(42)).await.unwrap(), 42);
+    }
+}
@@ -0,0 +1,161 @@
+/// Caching of resolved host names for long-lived outputs. Addresses are
+/// reused for `dns_ttl` seconds, and a host is resolved again as soon as a
+/// connection to it fails, so DNS-based failover of an endpoint is picked up
+/// without a restart.
+#[cfg(feature = "http")]
+use std::collections::HashMap;
+#[cfg(feature = "http")]
+use std::io;
+#[cfg(feature = "http")]
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+#[cfg(feature = "http")]
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(feature = "http")]
+use std::time::Instant;
+
+/// How long resolved addresses are reused unless configured otherwise
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+static TTL: OnceLock<Duration> = OnceLock::new();
+
+/// Reuse resolved addresses for `ttl` from now on, or resolve for every new
+/// connection when zero. Only the first call counts.
+pub fn init(ttl: Duration) {
+    let _ = TTL.set(ttl);
+}
+
+/// How long resolved addresses are reused
+pub fn ttl() -> Duration {
+    *TTL.get().unwrap_or(&DEFAULT_TTL)
+}
+
+/// Addresses of the hosts looked up so far, and when
+#[cfg(feature = "http")]
+#[derive(Debug)]
+pub struct DnsCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Vec<SocketAddr>, Instant)>>,
+}
+
+#[cfg(feature = "http")]
+impl DnsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The addresses of `host`, resolved again once the cached ones are
+    /// older than the TTL
+    pub async fn lookup(&self, host: &str) -> io::Result<Vec<SocketAddr>> {
+        if let Some(addrs) = self.cached(host) {
+            return Ok(addrs);
+        }
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+        if !self.ttl.is_zero() && !addrs.is_empty() {
+            self.entries
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(host.to_string(), (addrs.clone(), Instant::now()));
+        }
+        Ok(addrs)
+    }
+
+    fn cached(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(host)
+            .filter(|(_, resolved)| resolved.elapsed() < self.ttl)
+            .map(|(addrs, _)| addrs.clone())
+    }
+
+    /// Drop the cached addresses of `host`
+    pub fn forget(&self, host: &str) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(host);
+    }
+}
+
+#[cfg(feature = "http")]
+static CACHE: OnceLock<Arc<DnsCache>> = OnceLock::new();
+
+/// The cache shared by every HTTP client
+#[cfg(feature = "http")]
+fn cache() -> &'static Arc<DnsCache> {
+    CACHE.get_or_init(|| Arc::new(DnsCache::new(ttl())))
+}
+
+/// Resolves reqwest's connections through the shared cache
+#[cfg(feature = "http")]
+pub struct Resolver(Arc<DnsCache>);
+
+#[cfg(feature = "http")]
+impl reqwest::dns::Resolve for Resolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let cache = self.0.clone();
+        Box::pin(async move {
+            let addrs = cache.lookup(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// A resolver for `ClientBuilder::dns_resolver` using the shared cache
+#[cfg(feature = "http")]
+pub fn resolver() -> Arc<Resolver> {
+    Arc::new(Resolver(cache().clone()))
+}
+
+/// After a request failed to connect or timed out, forget the addresses of
+/// its host and of the proxy in between, so the next request resolves them
+/// again
+#[cfg(feature = "http")]
+pub fn forget_failed(error: &reqwest::Error) {
+    if !(error.is_connect() || error.is_timeout()) {
+        return;
+    }
+    let Some(url) = error.url() else {
+        return;
+    };
+    if let Some(host) = url.host_str() {
+        cache().forget(host);
+        if let Some(proxy) = crate::proxy::proxy_for(url.scheme(), host) {
+            cache().forget(proxy.host_str().unwrap_or_default());
+        }
+    }
+}
+
+#[cfg(all(test, feature = "http"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dns_cache() {
+        let cache = DnsCache::new(Duration::from_secs(300));
+        let addrs = cache.lookup("127.0.0.1").await.unwrap();
+        assert_eq!(addrs, ["127.0.0.1:0".parse().unwrap()]);
+        assert_eq!(cache.cached("127.0.0.1"), Some(addrs));
+
+        cache.forget("127.0.0.1");
+        assert_eq!(cache.cached("127.0.0.1"), None);
+
+        // A zero TTL resolves every time
+        let uncached = DnsCache::new(Duration::ZERO);
+        uncached.lookup("127.0.0.1").await.unwrap();
+        assert_eq!(uncached.cached("127.0.0.1"), None);
+    }
+
+    #[tokio::test]
+    async fn test_dns_cache_expires() {
+        let cache = DnsCache::new(Duration::from_millis(20));
+        cache.lookup("127.0.0.1").await.unwrap();
+        assert!(cache.cached("127.0.0.1").is_some());
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(cache.cached("127.0.0.1"), None);
+    }
+}
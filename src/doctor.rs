@@ -0,0 +1,169 @@
+/// `wxlistener doctor`: checks each part of the pipeline in turn, from the
+/// gateway to the outputs, and reports what passed and what failed
+use anyhow::Result;
+
+/// Models wxlistener is tested with
+const VERIFIED_MODELS: &[&str] = &["GW1000", "GW1100", "GW1200", "GW2000"];
+
+/// Models that speak the same API but are untested
+const ALLEGED_MODELS: &[&str] = &["WH2650", "WH2680", "WN1900", "WS3800", "WS3900", "WS3910"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    /// Works, but something deserves a look
+    Warn,
+    Fail,
+    /// Not configured, or depends on a check that failed
+    Skip,
+}
+
+impl Outcome {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Pass => "PASS",
+            Self::Warn => "WARN",
+            Self::Fail => "FAIL",
+            Self::Skip => "SKIP",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl Check {
+    /// One report line, e.g. "[PASS] Gateway            192.168.1.50:45000"
+    pub fn format(&self) -> String {
+        format!(
+            "[{}] {:<18} {}",
+            self.outcome.label(),
+            self.name,
+            self.detail
+        )
+    }
+}
+
+/// The checks run so far. Each is printed as soon as it is added, since
+/// some take several seconds to time out.
+#[derive(Debug, Default)]
+pub struct Report {
+    checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn add(&mut self, name: &'static str, outcome: Outcome, detail: impl Into<String>) {
+        let check = Check {
+            name,
+            outcome,
+            detail: detail.into(),
+        };
+        println!("{}", check.format());
+        self.checks.push(check);
+    }
+
+    /// Add a check that passed with the detail in `Ok`, or failed with the
+    /// error. Returns whether it passed.
+    pub fn record(&mut self, name: &'static str, result: Result<String>) -> bool {
+        let passed = result.is_ok();
+        match result {
+            Ok(detail) => self.add(name, Outcome::Pass, detail),
+            Err(e) => self.add(name, Outcome::Fail, format!("{:#}", e)),
+        }
+        passed
+    }
+
+    fn count(&self, outcome: Outcome) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.outcome == outcome)
+            .count()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.count(Outcome::Fail)
+    }
+
+    /// Totals, e.g. "5 passed, 1 warning, 1 failed, 2 skipped"
+    pub fn summary(&self) -> String {
+        let warnings = self.count(Outcome::Warn);
+        format!(
+            "{} passed, {} warning{}, {} failed, {} skipped",
+            self.count(Outcome::Pass),
+            warnings,
+            if warnings == 1 { "" } else { "s" },
+            self.failures(),
+            self.count(Outcome::Skip)
+        )
+    }
+}
+
+/// Judge a firmware version such as "GW1000A_V1.7.3" by the model it names
+pub fn firmware_compatibility(version: &str) -> (Outcome, String) {
+    let Some((model, number)) = version.split_once("_V") else {
+        return (
+            Outcome::Warn,
+            format!("{}: not a firmware version wxlistener recognises", version),
+        );
+    };
+    let known = |models: &[&str]| models.iter().any(|known| model.starts_with(known));
+    if known(VERIFIED_MODELS) {
+        (Outcome::Pass, format!("{} firmware {}", model, number))
+    } else if known(ALLEGED_MODELS) {
+        (
+            Outcome::Warn,
+            format!(
+                "{} firmware {}: should work but is untested with wxlistener",
+                model, number
+            ),
+        )
+    } else {
+        (
+            Outcome::Warn,
+            format!(
+                "{} firmware {}: unknown model, readings may decode wrongly",
+                model, number
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_firmware_compatibility() {
+        let (outcome, detail) = firmware_compatibility("GW1100A_V2.3.1");
+        assert_eq!(outcome, Outcome::Pass);
+        assert_eq!(detail, "GW1100A firmware 2.3.1");
+
+        assert_eq!(firmware_compatibility("WS3910_V1.0.2").0, Outcome::Warn);
+        assert!(firmware_compatibility("HP2560_V1.9.0")
+            .1
+            .contains("unknown model"));
+        assert!(firmware_compatibility("garbage")
+            .1
+            .contains("not a firmware"));
+    }
+
+    #[test]
+    fn test_report() {
+        let mut report = Report::default();
+        assert!(report.record("Gateway", Ok("192.168.1.50:45000".to_string())));
+        assert!(!report.record("Database", Err(anyhow::anyhow!("connection refused"))));
+        report.add("MQTT", Outcome::Skip, "no [mqtt] section");
+        report.add("Firmware", Outcome::Warn, "untested");
+
+        assert_eq!(report.failures(), 1);
+        assert_eq!(report.summary(), "1 passed, 1 warning, 1 failed, 1 skipped");
+        assert_eq!(
+            report.checks[1].format(),
+            "[FAIL] Database           connection refused"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+/// Event log of significant daemon events, served at /api/v1/events.json
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Events kept before the oldest are dropped
+pub const EVENT_LOG_CAPACITY: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Started,
+    Stopped,
+    ConfigReloaded,
+    ReloadFailed,
+    /// Runtime settings changed through the API
+    SettingsChanged,
+    /// Maintenance mode turned on: readings are flagged and alerts held back
+    MaintenanceStarted,
+    MaintenanceEnded,
+    /// Polls started failing after succeeding
+    DeviceOffline,
+    /// A poll succeeded after failures
+    DeviceOnline,
+    SensorMissing,
+    SensorRestored,
+    /// A sensor battery is predicted to run out within `[battery] warn_weeks`
+    BatteryWarning,
+    /// Rain began after a dry poll
+    RainStarted,
+    /// The rain rate dropped to zero
+    RainStopped,
+    /// Rain moved to another intensity class
+    RainIntensityChanged,
+    /// An output started failing after succeeding
+    OutputFailed,
+    /// An output succeeded after failures
+    OutputRecovered,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub at: DateTime<Utc>,
+    pub kind: EventKind,
+    pub message: String,
+}
+
+/// Ring buffer of the most recent events, oldest first
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+    capacity: usize,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::with_capacity(EVENT_LOG_CAPACITY)
+    }
+}
+
+impl EventLog {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, at: DateTime<Utc>, kind: EventKind, message: impl Into<String>) {
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event {
+            at,
+            kind,
+            message: message.into(),
+        });
+    }
+
+    /// The last `limit` events, oldest first
+    pub fn recent(&self, limit: usize) -> Vec<Event> {
+        let skip = self.events.len().saturating_sub(limit);
+        self.events.iter().skip(skip).cloned().collect()
+    }
+
+    /// Events to persist in the state file
+    pub fn state(&self) -> Vec<Event> {
+        self.events.iter().cloned().collect()
+    }
+
+    /// Put back events saved by a previous run, ahead of any logged since
+    pub fn restore(&mut self, saved: Vec<Event>) {
+        let current = std::mem::take(&mut self.events);
+        for event in saved.into_iter().chain(current) {
+            self.push(event.at, event.kind, event.message);
+        }
+    }
+}
+
+/// Render events for the terminal, one per line
+pub fn format_events(events: &[Event]) -> String {
+    events
+        .iter()
+        .map(|event| {
+            let kind = serde_json::to_value(event.kind)
+                .ok()
+                .and_then(|kind| kind.as_str().map(str::to_string))
+                .unwrap_or_default();
+            format!(
+                "{}  {:<16} {}",
+                event.at.format("%Y-%m-%d %H:%M:%S"),
+                kind,
+                event.message
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 15, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_ring_buffer_drops_oldest() {
+        let mut log = EventLog::with_capacity(3);
+        for hour in 0..5 {
+            log.push(at(hour), EventKind::DeviceOffline, format!("poll {}", hour));
+        }
+        let events = log.recent(10);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].message, "poll 2");
+        assert_eq!(events[2].message, "poll 4");
+
+        let last = log.recent(1);
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].message, "poll 4");
+    }
+
+    #[test]
+    fn test_restore_keeps_order() {
+        let mut previous = EventLog::default();
+        previous.push(at(1), EventKind::Started, "first run");
+        previous.push(at(2), EventKind::Stopped, "signal");
+
+        let mut log = EventLog::with_capacity(2);
+        log.push(at(3), EventKind::Started, "second run");
+        log.restore(previous.state());
+
+        let kinds: Vec<_> = log.recent(10).iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![EventKind::Stopped, EventKind::Started]);
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let mut log = EventLog::default();
+        log.push(at(4), EventKind::SensorMissing, "wh31_ch2 missing");
+        let json = serde_json::to_value(log.recent(1)).unwrap();
+        assert_eq!(json[0]["kind"], "sensor_missing");
+        assert_eq!(json[0]["at"], "2026-01-15T04:00:00Z");
+
+        let text = format_events(&log.recent(1));
+        assert!(text.contains("2026-01-15 04:00:00  sensor_missing"));
+    }
+}
@@ -0,0 +1,373 @@
+/// Federation: instances in a small neighbourhood network send each poll's
+/// readings straight to each other over HTTP, no cloud service in between.
+/// Each pair of peers shares a key; every message is signed with HMAC-SHA256
+/// over its body, and a peer's readings are only taken when the signature
+/// matches and they are newer than the last ones it sent.
+use anyhow::{Context, Result};
+use axum::http::StatusCode;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::{dns, proxy};
+
+/// Header carrying the base64 HMAC-SHA256 of the body
+pub const SIGNATURE_HEADER: &str = "x-wxlistener-signature";
+
+/// Path peers send readings to, on their web server
+pub const FEDERATION_PATH: &str = "/api/v1/federation";
+
+/// Shortest key accepted, in bytes
+const MIN_KEY_LEN: usize = 16;
+
+/// `[federation]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+pub struct FederationConfig {
+    /// This station's name to its peers (default: the `[station]` name)
+    #[serde(default)]
+    pub name: Option<String>,
+    pub peers: Vec<PeerConfig>,
+    /// Request timeout in seconds (default: 10)
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Readings timestamped further than this many seconds from now are
+    /// refused (default: 300)
+    #[serde(default = "default_max_skew")]
+    pub max_skew: u64,
+}
+
+/// One `[[federation.peers]]` entry
+#[derive(Debug, Clone, Deserialize)]
+pub struct PeerConfig {
+    /// The name the peer sends its readings under
+    pub name: String,
+    /// The peer's web server, e.g. "http://192.168.1.20:18888"
+    pub url: String,
+    /// Key shared with this peer, signing readings both ways
+    pub key: String,
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+fn default_max_skew() -> u64 {
+    300
+}
+
+impl FederationConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.as_deref().is_none_or(str::is_empty) {
+            anyhow::bail!("[federation] needs a name, or a [station] name");
+        }
+        if self.peers.is_empty() {
+            anyhow::bail!("[federation] needs at least one peer in [[federation.peers]]");
+        }
+        let mut names = HashSet::new();
+        for peer in &self.peers {
+            if peer.name.is_empty() || !names.insert(peer.name.as_str()) {
+                anyhow::bail!(
+                    "[federation] peer names must be set and unique: '{}'",
+                    peer.name
+                );
+            }
+            reqwest::Url::parse(&peer.url)
+                .context(format!("Invalid [federation] URL for '{}'", peer.name))?;
+            if peer.key.len() < MIN_KEY_LEN {
+                anyhow::bail!(
+                    "[federation] key for '{}' must be at least {} characters",
+                    peer.name,
+                    MIN_KEY_LEN
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The body of a message between peers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerMessage {
+    pub station: String,
+    pub timestamp: DateTime<Utc>,
+    pub readings: BTreeMap<String, f64>,
+}
+
+pub fn sign(key: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    BASE64.encode(mac.finalize().into_bytes())
+}
+
+/// Whether `signature` is `body`'s, compared in constant time
+fn verify(key: &str, body: &[u8], signature: &str) -> bool {
+    let Ok(signature) = BASE64.decode(signature.trim()) else {
+        return false;
+    };
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Sends this station's readings to its peers
+pub struct Federation {
+    name: String,
+    peers: Vec<PeerConfig>,
+    client: Client,
+    /// Peers whose last send failed, so each outage is reported once
+    failing: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Federation {
+    pub fn new(config: &FederationConfig) -> Result<Self> {
+        let client = proxy::client_builder()
+            .timeout(std::time::Duration::from_secs(config.timeout))
+            .build()
+            .context("Failed to create HTTP client")?;
+        Ok(Self {
+            name: config.name.clone().unwrap_or_default(),
+            peers: config.peers.clone(),
+            client,
+            failing: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+
+    /// Send one poll's readings to every peer in the background, so a slow
+    /// or unreachable peer doesn't hold up polling
+    pub fn share(&self, timestamp: DateTime<Utc>, data: &HashMap<String, f64>) -> Result<()> {
+        let message = PeerMessage {
+            station: self.name.clone(),
+            timestamp,
+            // JSON has no NaN
+            readings: data
+                .iter()
+                .filter(|(_, value)| value.is_finite())
+                .map(|(field, value)| (field.clone(), *value))
+                .collect(),
+        };
+        let body = serde_json::to_vec(&message)?;
+        for peer in &self.peers {
+            let request = self
+                .client
+                .post(format!(
+                    "{}{}",
+                    peer.url.trim_end_matches('/'),
+                    FEDERATION_PATH
+                ))
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, sign(&peer.key, &body))
+                .body(body.clone());
+            let name = peer.name.clone();
+            let failing = self.failing.clone();
+            tokio::spawn(async move {
+                let result = match request.send().await {
+                    Ok(response) if response.status().is_success() => Ok(()),
+                    Ok(response) => Err(format!("HTTP {}", response.status().as_u16())),
+                    Err(e) => {
+                        dns::forget_failed(&e);
+                        Err(e.to_string())
+                    }
+                };
+                let mut failing = failing.lock().await;
+                match result {
+                    Ok(()) => {
+                        if failing.remove(&name) {
+                            println!("[FEDERATION] Sending to peer '{}' again", name);
+                        }
+                    }
+                    Err(e) => {
+                        if failing.insert(name.clone()) {
+                            eprintln!("[WARN] Failed to send readings to peer '{}': {}", name, e);
+                        }
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A peer's latest readings and when they arrived
+#[derive(Debug, Clone)]
+pub struct PeerReading {
+    pub timestamp: DateTime<Utc>,
+    pub received_at: DateTime<Utc>,
+    pub readings: BTreeMap<String, f64>,
+}
+
+/// What the peers last sent, by name
+#[derive(Debug)]
+pub struct PeerReadings {
+    keys: BTreeMap<String, String>,
+    latest: BTreeMap<String, PeerReading>,
+    max_skew: Duration,
+}
+
+pub type SharedPeers = Arc<Mutex<PeerReadings>>;
+
+impl PeerReadings {
+    pub fn new(config: &FederationConfig) -> Self {
+        Self {
+            keys: config
+                .peers
+                .iter()
+                .map(|peer| (peer.name.clone(), peer.key.clone()))
+                .collect(),
+            latest: BTreeMap::new(),
+            max_skew: Duration::seconds(config.max_skew as i64),
+        }
+    }
+
+    /// Take a message from a peer if it is signed with the peer's key,
+    /// timestamped within `max_skew` of `now` and newer than the last one
+    pub fn receive(
+        &mut self,
+        body: &[u8],
+        signature: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<(), (StatusCode, String)> {
+        let signature =
+            signature.ok_or((StatusCode::UNAUTHORIZED, "Missing signature".to_string()))?;
+        let message: PeerMessage = serde_json::from_slice(body)
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid message: {}", e)))?;
+        let key = self.keys.get(&message.station).ok_or_else(|| {
+            (
+                StatusCode::UNAUTHORIZED,
+                format!("Unknown peer '{}'", message.station),
+            )
+        })?;
+        if !verify(key, body, signature) {
+            return Err((StatusCode::UNAUTHORIZED, "Invalid signature".to_string()));
+        }
+        if (message.timestamp - now).abs() > self.max_skew {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Timestamp {} is too far from now; check the peer's clock",
+                    message.timestamp.to_rfc3339()
+                ),
+            ));
+        }
+        // Also stops a captured message from being replayed
+        if let Some(latest) = self.latest.get(&message.station) {
+            if message.timestamp <= latest.timestamp {
+                return Err((
+                    StatusCode::CONFLICT,
+                    "Not newer than the last readings".to_string(),
+                ));
+            }
+        }
+        self.latest.insert(
+            message.station,
+            PeerReading {
+                timestamp: message.timestamp,
+                received_at: now,
+                readings: message.readings,
+            },
+        );
+        Ok(())
+    }
+
+    /// Every configured peer with its latest readings, None before any
+    pub fn peers(&self) -> impl Iterator<Item = (&str, Option<&PeerReading>)> {
+        self.keys
+            .keys()
+            .map(|name| (name.as_str(), self.latest.get(name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const KEY: &str = "0123456789abcdef";
+
+    fn config() -> FederationConfig {
+        toml::from_str(&format!(
+            r#"
+            name = "home"
+            [[peers]]
+            name = "north"
+            url = "http://192.168.1.20:18888"
+            key = "{}"
+            "#,
+            KEY
+        ))
+        .unwrap()
+    }
+
+    fn message(station: &str, timestamp: DateTime<Utc>) -> Vec<u8> {
+        serde_json::to_vec(&PeerMessage {
+            station: station.to_string(),
+            timestamp,
+            readings: BTreeMap::from([("outtemp".to_string(), 11.5)]),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_federation_config() {
+        let config = config();
+        assert_eq!(config.timeout, 10);
+        assert_eq!(config.max_skew, 300);
+        assert!(config.validate().is_ok());
+
+        let mut short_key = config.clone();
+        short_key.peers[0].key = "secret".to_string();
+        assert!(short_key.validate().is_err());
+        let mut unnamed = config.clone();
+        unnamed.name = None;
+        assert!(unnamed.validate().is_err());
+        let mut twice = config;
+        twice.peers.push(twice.peers[0].clone());
+        assert!(twice.validate().is_err());
+    }
+
+    #[test]
+    fn test_receive() {
+        let mut peers = PeerReadings::new(&config());
+        let now = Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        let body = message("north", now);
+
+        let refused = |result: Result<(), (StatusCode, String)>| result.unwrap_err().0;
+        assert_eq!(
+            refused(peers.receive(&body, Some(&sign("wrong key, wrong", &body)), now)),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            refused(peers.receive(&body, None, now)),
+            StatusCode::UNAUTHORIZED
+        );
+        let stranger = message("south", now);
+        assert_eq!(
+            refused(peers.receive(&stranger, Some(&sign(KEY, &stranger)), now)),
+            StatusCode::UNAUTHORIZED
+        );
+        let stale = message("north", now - Duration::hours(1));
+        assert_eq!(
+            refused(peers.receive(&stale, Some(&sign(KEY, &stale)), now)),
+            StatusCode::BAD_REQUEST
+        );
+
+        peers.receive(&body, Some(&sign(KEY, &body)), now).unwrap();
+        let (name, latest) = peers.peers().next().unwrap();
+        assert_eq!(name, "north");
+        assert_eq!(latest.unwrap().readings["outtemp"], 11.5);
+
+        // The same message again is a replay
+        assert_eq!(
+            refused(peers.receive(&body, Some(&sign(KEY, &body)), now)),
+            StatusCode::CONFLICT
+        );
+    }
+}
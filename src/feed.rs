@@ -0,0 +1,250 @@
+/// Distribution of live messages to web clients. The latest message is kept
+/// for the JSON API, and each WebSocket client gets its own bounded queue so
+/// a slow client only loses its own oldest updates.
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+
+/// Messages queued for each WebSocket client before the oldest are dropped,
+/// a few minutes of polls at the default interval
+pub const SUBSCRIBER_QUEUE: usize = 16;
+
+#[derive(Debug)]
+pub struct LiveFeed {
+    latest: watch::Sender<Option<Arc<str>>>,
+    subscribers: Mutex<Vec<Weak<SubscriberQueue>>>,
+    capacity: usize,
+    published: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Counters served at /api/v1/feed.json
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedMetrics {
+    /// Connected WebSocket clients
+    pub subscribers: usize,
+    /// Messages published since startup
+    pub published: u64,
+    /// Messages dropped from full client queues since startup
+    pub dropped: u64,
+}
+
+#[derive(Debug, Default)]
+struct SubscriberQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    messages: VecDeque<Arc<str>>,
+    /// Dropped since the subscriber last received
+    lagged: u64,
+}
+
+/// What a subscriber receives next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Received {
+    Message(Arc<str>),
+    /// This many older messages were dropped because the queue was full
+    Lagged(u64),
+}
+
+/// One client's queue, removed from the feed when dropped
+#[derive(Debug)]
+pub struct Subscription {
+    queue: Arc<SubscriberQueue>,
+}
+
+impl Default for LiveFeed {
+    fn default() -> Self {
+        Self::with_capacity(SUBSCRIBER_QUEUE)
+    }
+}
+
+impl LiveFeed {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            latest: watch::Sender::new(None),
+            subscribers: Mutex::new(Vec::new()),
+            capacity: capacity.max(1),
+            published: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Make `message` the latest and queue it for every subscriber. Never
+    /// waits on a client.
+    pub fn publish(&self, message: impl Into<Arc<str>>) {
+        let message = message.into();
+        self.latest.send_replace(Some(message.clone()));
+        self.published.fetch_add(1, Ordering::Relaxed);
+
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        subscribers.retain(|subscriber| {
+            let Some(queue) = subscriber.upgrade() else {
+                return false;
+            };
+            let mut state = queue.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.messages.len() >= self.capacity {
+                state.messages.pop_front();
+                state.lagged += 1;
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            state.messages.push_back(message.clone());
+            drop(state);
+            queue.notify.notify_one();
+            true
+        });
+    }
+
+    /// The most recent message, if any has been published
+    pub fn latest(&self) -> Option<Arc<str>> {
+        self.latest.borrow().clone()
+    }
+
+    /// The most recent message, waiting up to `timeout` for the first one
+    pub async fn wait_latest(&self, timeout: Duration) -> Option<Arc<str>> {
+        let mut rx = self.latest.subscribe();
+        let latest = tokio::time::timeout(timeout, rx.wait_for(Option::is_some)).await;
+        match latest {
+            Ok(Ok(message)) => message.clone(),
+            _ => None,
+        }
+    }
+
+    /// A new queue, starting with the latest message so a client doesn't
+    /// wait a whole interval for its first update
+    pub fn subscribe(&self) -> Subscription {
+        let queue = Arc::new(SubscriberQueue::default());
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(latest) = self.latest() {
+            queue
+                .state
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .messages
+                .push_back(latest);
+        }
+        subscribers.push(Arc::downgrade(&queue));
+        Subscription { queue }
+    }
+
+    pub fn metrics(&self) -> FeedMetrics {
+        let subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        FeedMetrics {
+            subscribers: subscribers.iter().filter(|s| s.strong_count() > 0).count(),
+            published: self.published.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Subscription {
+    /// The next message, or how many were dropped before it
+    pub async fn recv(&mut self) -> Received {
+        loop {
+            {
+                let mut state = self.queue.state.lock().unwrap_or_else(|e| e.into_inner());
+                if state.lagged > 0 {
+                    return Received::Lagged(std::mem::take(&mut state.lagged));
+                }
+                if let Some(message) = state.messages.pop_front() {
+                    return Received::Message(message);
+                }
+            }
+            // notify_one keeps a permit, so a publish between the check and
+            // here still wakes us
+            self.queue.notify.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(received: Received) -> String {
+        match received {
+            Received::Message(message) => message.to_string(),
+            Received::Lagged(n) => panic!("lagged by {}", n),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_get_every_message() {
+        let feed = LiveFeed::default();
+        let mut first = feed.subscribe();
+        let mut second = feed.subscribe();
+
+        feed.publish("a");
+        feed.publish("b");
+
+        assert_eq!(message(first.recv().await), "a");
+        assert_eq!(message(first.recv().await), "b");
+        assert_eq!(message(second.recv().await), "a");
+        assert_eq!(message(second.recv().await), "b");
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_lags_alone() {
+        let feed = LiveFeed::with_capacity(2);
+        let mut slow = feed.subscribe();
+        let mut fast = feed.subscribe();
+
+        for m in ["1", "2", "3", "4"] {
+            feed.publish(m);
+            assert_eq!(message(fast.recv().await), m);
+        }
+
+        // The two oldest were dropped for the slow client only
+        assert_eq!(slow.recv().await, Received::Lagged(2));
+        assert_eq!(message(slow.recv().await), "3");
+        assert_eq!(message(slow.recv().await), "4");
+
+        let metrics = feed.metrics();
+        assert_eq!(metrics.published, 4);
+        assert_eq!(metrics.dropped, 2);
+    }
+
+    #[tokio::test]
+    async fn test_new_subscriber_starts_with_latest() {
+        let feed = LiveFeed::default();
+        assert_eq!(feed.latest(), None);
+        feed.publish("old");
+        feed.publish("new");
+
+        let mut late = feed.subscribe();
+        assert_eq!(message(late.recv().await), "new");
+        assert_eq!(feed.latest().as_deref(), Some("new"));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_subscriptions_are_removed() {
+        let feed = LiveFeed::default();
+        let kept = feed.subscribe();
+        drop(feed.subscribe());
+        assert_eq!(feed.metrics().subscribers, 1);
+
+        feed.publish("a");
+        assert_eq!(feed.subscribers.lock().unwrap().len(), 1);
+        drop(kept);
+        assert_eq!(feed.metrics().subscribers, 0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_latest() {
+        let feed = Arc::new(LiveFeed::default());
+        assert_eq!(feed.wait_latest(Duration::from_millis(10)).await, None);
+
+        let publisher = feed.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            publisher.publish("first");
+        });
+        let latest = feed.wait_latest(Duration::from_secs(5)).await;
+        assert_eq!(latest.as_deref(), Some("first"));
+    }
+}
@@ -0,0 +1,171 @@
+/// Fixed-point readings in tenths, the gateway's native resolution
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::{Add, AddAssign, Sub};
+
+/// A value in tenths of its unit, e.g. `Tenths(215)` is 21.5°C. Sums and
+/// comparisons are exact; convert with `to_f64` only when publishing.
+/// Serializes as a plain number so it can replace an `f64` field without
+/// changing stored state or API output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tenths(pub i32);
+
+impl Tenths {
+    /// Nearest tenth, saturating at the limits of `i32`. NaN becomes zero.
+    pub fn from_f64(value: f64) -> Self {
+        Tenths((value * 10.0).round() as i32)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / 10.0
+    }
+
+    pub fn min(self, other: Self) -> Self {
+        Ord::min(self, other)
+    }
+
+    pub fn max(self, other: Self) -> Self {
+        Ord::max(self, other)
+    }
+}
+
+impl From<f64> for Tenths {
+    fn from(value: f64) -> Self {
+        Self::from_f64(value)
+    }
+}
+
+impl Add for Tenths {
+    type Output = Tenths;
+
+    fn add(self, other: Tenths) -> Tenths {
+        Tenths(self.0.saturating_add(other.0))
+    }
+}
+
+impl AddAssign for Tenths {
+    fn add_assign(&mut self, other: Tenths) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Tenths {
+    type Output = Tenths;
+
+    fn sub(self, other: Tenths) -> Tenths {
+        Tenths(self.0.saturating_sub(other.0))
+    }
+}
+
+impl fmt::Display for Tenths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}", self.to_f64())
+    }
+}
+
+impl Serialize for Tenths {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+impl<'de> Deserialize<'de> for Tenths {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f64::deserialize(deserializer).map(Tenths::from_f64)
+    }
+}
+
+/// A set of readings held in tenths. Two polls compare equal exactly when
+/// every field matches to the tenth, whatever float noise produced them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FixedReadings(pub BTreeMap<String, Tenths>);
+
+impl FixedReadings {
+    /// Quantize readings to tenths. Non-finite values are dropped.
+    pub fn from_readings(data: &HashMap<String, f64>) -> Self {
+        FixedReadings(
+            data.iter()
+                .filter(|(_, value)| value.is_finite())
+                .map(|(key, value)| (key.clone(), Tenths::from_f64(*value)))
+                .collect(),
+        )
+    }
+
+    /// Convert back to floats at the output boundary
+    pub fn to_readings(&self) -> HashMap<String, f64> {
+        self.0
+            .iter()
+            .map(|(key, value)| (key.clone(), value.to_f64()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenths_conversion() {
+        assert_eq!(Tenths::from_f64(21.5), Tenths(215));
+        assert_eq!(Tenths::from_f64(-3.25), Tenths(-33));
+        assert_eq!(Tenths::from_f64(f64::NAN), Tenths(0));
+        assert_eq!(Tenths::from_f64(1e12), Tenths(i32::MAX));
+        assert_eq!(Tenths(-105).to_f64(), -10.5);
+        assert_eq!(Tenths(-105).to_string(), "-10.5");
+    }
+
+    #[test]
+    fn test_tenths_sum_is_exact() {
+        let mut float = 0.0;
+        let mut fixed = Tenths::default();
+        for _ in 0..1000 {
+            float += 0.1;
+            fixed += Tenths::from_f64(0.1);
+        }
+        assert_ne!(float, 100.0);
+        assert_eq!(fixed.to_f64(), 100.0);
+    }
+
+    #[test]
+    fn test_tenths_serde_as_number() {
+        let json = serde_json::to_string(&Tenths(123)).unwrap();
+        assert_eq!(json, "12.3");
+        let back: Tenths = serde_json::from_str("12.3").unwrap();
+        assert_eq!(back, Tenths(123));
+    }
+
+    #[test]
+    fn test_fixed_readings_roundtrip() {
+        let mut data = HashMap::new();
+        // 21.0 * 1.03 is 21.630000000000003 in floating point
+        data.insert("outtemp".to_string(), 21.0 * 1.03);
+        data.insert("broken".to_string(), f64::NAN);
+
+        let fixed = FixedReadings::from_readings(&data);
+        let readings = fixed.to_readings();
+        assert_eq!(readings["outtemp"], 21.6);
+        assert!(!readings.contains_key("broken"));
+    }
+
+    #[test]
+    fn test_fixed_readings_equality() {
+        let mut before = HashMap::new();
+        before.insert("rain_rate".to_string(), 0.3);
+        before.insert("outhumid".to_string(), 80.0);
+        let mut after = before.clone();
+        // 0.30000000000000004
+        after.insert("rain_rate".to_string(), 0.1 + 0.2);
+        assert_ne!(before, after);
+        assert_eq!(
+            FixedReadings::from_readings(&before),
+            FixedReadings::from_readings(&after)
+        );
+
+        after.insert("outhumid".to_string(), 81.0);
+        assert_ne!(
+            FixedReadings::from_readings(&before),
+            FixedReadings::from_readings(&after)
+        );
+    }
+}
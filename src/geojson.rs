@@ -0,0 +1,102 @@
+/// GeoJSON of the stations for /api/v1/stations.geojson: a point per station
+/// with a location and its latest readings as properties, ready to drop onto
+/// a Leaflet or MapLibre map
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use crate::station::StationConfig;
+use crate::storage::Record;
+
+/// A point feature for `station`, None without coordinates. `id` is the name
+/// in `[database.stations]`, None for this instance's own station. A failed
+/// read of the latest readings leaves them empty with an `error`.
+pub fn station_feature(
+    id: Option<&str>,
+    station: &StationConfig,
+    latest: &Result<Option<Record>>,
+) -> Option<Value> {
+    let (latitude, longitude) = (station.latitude?, station.longitude?);
+
+    let mut properties = Map::new();
+    properties.insert("station".to_string(), json!(id));
+    properties.insert("name".to_string(), json!(station.name.as_deref().or(id)));
+    if let Some(elevation) = station.elevation {
+        properties.insert("elevation".to_string(), json!(elevation));
+    }
+    let (timestamp, readings) = match latest {
+        Ok(Some((timestamp, data))) => (
+            Some(timestamp.to_rfc3339()),
+            data.iter()
+                .filter(|(_, value)| value.is_finite())
+                .map(|(field, value)| (field.as_str(), *value))
+                .collect(),
+        ),
+        Ok(None) => (None, BTreeMap::new()),
+        Err(e) => {
+            properties.insert("error".to_string(), json!(format!("{:#}", e)));
+            (None, BTreeMap::new())
+        }
+    };
+    properties.insert("timestamp".to_string(), json!(timestamp));
+    properties.insert("readings".to_string(), json!(readings));
+
+    Some(json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "Point",
+            // GeoJSON puts longitude first
+            "coordinates": [longitude, latitude],
+        },
+        "properties": properties,
+    }))
+}
+
+pub fn feature_collection(features: Vec<Value>) -> Value {
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_station_feature() {
+        let station = StationConfig {
+            latitude: Some(52.37),
+            longitude: Some(4.89),
+            elevation: Some(2.0),
+            ..Default::default()
+        };
+        let timestamp = Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        let data = HashMap::from([
+            ("outtemp".to_string(), 12.5),
+            ("outhumid".to_string(), f64::NAN),
+        ]);
+
+        let feature =
+            station_feature(Some("cabin"), &station, &Ok(Some((timestamp, data)))).unwrap();
+        assert_eq!(feature["geometry"]["coordinates"], json!([4.89, 52.37]));
+        assert_eq!(feature["properties"]["station"], "cabin");
+        assert_eq!(feature["properties"]["name"], "cabin");
+        assert_eq!(
+            feature["properties"]["timestamp"],
+            "2026-10-17T12:00:00+00:00"
+        );
+        assert_eq!(feature["properties"]["readings"], json!({"outtemp": 12.5}));
+
+        let feature =
+            station_feature(None, &station, &Err(anyhow::anyhow!("Table not found"))).unwrap();
+        assert_eq!(feature["properties"]["station"], Value::Null);
+        assert_eq!(feature["properties"]["error"], "Table not found");
+        assert_eq!(feature["properties"]["readings"], json!({}));
+
+        // Nothing to put on the map without coordinates
+        assert!(station_feature(None, &StationConfig::default(), &Ok(None)).is_none());
+    }
+}
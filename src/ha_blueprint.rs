@@ -0,0 +1,192 @@
+/// Home Assistant automation blueprints wired to the MQTT topics this
+/// instance publishes, so they work without editing topic names by hand
+use anyhow::Result;
+
+use crate::mqtt::MqttConfig;
+
+/// Reading the frost blueprint watches
+const FROST_FIELD: &str = "outtemp";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlueprintKind {
+    /// The station went offline: "offline" on the availability topic
+    Offline,
+    /// The outdoor temperature dropped to a threshold
+    Frost,
+}
+
+impl BlueprintKind {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "offline" => Ok(Self::Offline),
+            "frost" => Ok(Self::Frost),
+            _ => anyhow::bail!("Unknown blueprint '{}': use offline or frost", name),
+        }
+    }
+}
+
+/// Render a blueprint as YAML for the configured `[mqtt]` topics
+pub fn render(kind: BlueprintKind, config: &MqttConfig) -> Result<String> {
+    match kind {
+        BlueprintKind::Offline => {
+            let Some(topic) = config.get_availability_topic()? else {
+                anyhow::bail!(
+                    "The offline blueprint needs availability_topic in the [mqtt] section"
+                );
+            };
+            Ok(offline(&topic))
+        }
+        BlueprintKind::Frost => {
+            let template = config.get_topic_template()?;
+            // {mac} is only known once connected; match any gateway instead
+            let topic = template.as_str().replace("{mac}", "+");
+            Ok(if template.uses("sensor") {
+                frost(
+                    &topic.replace("{sensor}", FROST_FIELD),
+                    "trigger.payload | float(none)",
+                )
+            } else {
+                frost(
+                    &topic,
+                    &format!("trigger.payload_json.data.{} | float(none)", FROST_FIELD),
+                )
+            })
+        }
+    }
+}
+
+/// A YAML double-quoted scalar; JSON strings are valid YAML
+fn quoted(value: &str) -> String {
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+fn offline(topic: &str) -> String {
+    format!(
+        r#"blueprint:
+  name: wxlistener station offline
+  description: >-
+    Runs actions when wxlistener stops publishing, on a clean shutdown or when
+    the broker sends its last will.
+  domain: automation
+  input:
+    actions:
+      name: Actions
+      description: What to do, e.g. send a notification
+      selector:
+        action: {{}}
+
+triggers:
+  - trigger: mqtt
+    topic: {topic}
+    payload: "offline"
+
+actions: !input actions
+mode: single
+"#,
+        topic = quoted(topic)
+    )
+}
+
+fn frost(topic: &str, value: &str) -> String {
+    format!(
+        r#"blueprint:
+  name: wxlistener frost warning
+  description: >-
+    Runs actions when the outdoor temperature published by wxlistener drops to
+    or below a threshold, at most once per cooldown.
+  domain: automation
+  input:
+    threshold:
+      name: Threshold
+      description: Outdoor temperature at or below which to act
+      default: 2
+      selector:
+        number:
+          min: -20
+          max: 10
+          step: 0.5
+          unit_of_measurement: "°C"
+    cooldown:
+      name: Cooldown
+      description: Hours before the warning can run again
+      default: 12
+      selector:
+        number:
+          min: 1
+          max: 48
+          unit_of_measurement: h
+    actions:
+      name: Actions
+      description: What to do, e.g. send a notification
+      selector:
+        action: {{}}
+
+variables:
+  threshold: !input threshold
+  cooldown: !input cooldown
+
+triggers:
+  - trigger: mqtt
+    topic: {topic}
+
+conditions:
+  - condition: template
+    value_template: "{{{{ ({value}) is not none and ({value}) <= threshold }}}}"
+  - condition: template
+    value_template: >-
+      {{{{ this.attributes.last_triggered is none or
+         now() - this.attributes.last_triggered > timedelta(hours=cooldown) }}}}
+
+actions: !input actions
+mode: single
+"#,
+        topic = quoted(topic),
+        value = value
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(topic: &str, availability: Option<&str>) -> MqttConfig {
+        MqttConfig {
+            host: Some("localhost".to_string()),
+            topic: Some(topic.to_string()),
+            station: Some("garden".to_string()),
+            availability_topic: availability.map(str::to_string),
+            ..MqttConfig::new()
+        }
+    }
+
+    #[test]
+    fn test_offline_blueprint() {
+        let yaml = render(
+            BlueprintKind::Offline,
+            &config("wx/live", Some("wx/{station}/status")),
+        )
+        .unwrap();
+        assert!(yaml.contains("topic: \"wx/garden/status\"\n    payload: \"offline\""));
+
+        let err = render(BlueprintKind::Offline, &config("wx/live", None)).unwrap_err();
+        assert!(err.to_string().contains("availability_topic"));
+    }
+
+    #[test]
+    fn test_frost_blueprint_topics() {
+        let yaml = render(BlueprintKind::Frost, &config("wx/{station}/{mac}", None)).unwrap();
+        assert!(yaml.contains("topic: \"wx/garden/+\""));
+        assert!(yaml.contains("trigger.payload_json.data.outtemp"));
+
+        // One topic per reading carries a plain number
+        let yaml = render(BlueprintKind::Frost, &config("wx/{sensor}", None)).unwrap();
+        assert!(yaml.contains("topic: \"wx/outtemp\""));
+        assert!(yaml.contains("{{ (trigger.payload | float(none)) is not none"));
+    }
+
+    #[test]
+    fn test_parse_blueprint_kind() {
+        assert_eq!(BlueprintKind::parse("frost").unwrap(), BlueprintKind::Frost);
+        assert!(BlueprintKind::parse("battery").is_err());
+    }
+}
@@ -0,0 +1,128 @@
+/// Dead man's switch: ping healthchecks.io, Uptime Kuma push monitors and
+/// the like while the whole pipeline is healthy, so they raise the alarm
+/// when the pings stop
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::status::SharedStatus;
+use crate::{dns, proxy};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HeartbeatConfig {
+    /// URLs to GET on each ping
+    pub urls: Vec<String>,
+    /// Seconds between pings (default: 300)
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// Seconds since the last successful poll after which the pipeline no
+    /// longer counts as healthy (default: 300)
+    #[serde(default = "default_max_poll_age")]
+    pub max_poll_age: u64,
+    /// Request timeout in seconds (default: 10)
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_interval() -> u64 {
+    300
+}
+
+fn default_max_poll_age() -> u64 {
+    300
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+impl HeartbeatConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.urls.is_empty() {
+            anyhow::bail!("[heartbeat] needs at least one URL in urls");
+        }
+        for url in &self.urls {
+            reqwest::Url::parse(url).context(format!("Invalid [heartbeat] URL: {}", url))?;
+        }
+        if self.interval < 10 {
+            anyhow::bail!("[heartbeat] interval must be at least 10 seconds");
+        }
+        Ok(())
+    }
+}
+
+/// Ping every `interval` seconds while the status reports no problem.
+/// Changes between pinging and holding back are printed once.
+pub fn run_heartbeat_background(config: HeartbeatConfig, status: SharedStatus) -> Result<()> {
+    let client = proxy::client_builder()
+        .timeout(std::time::Duration::from_secs(config.timeout))
+        .build()
+        .context("Failed to create HTTP client")?;
+    let max_age = Duration::seconds(config.max_poll_age as i64);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(config.interval));
+        // The first tick is immediate; wait for a poll before judging
+        interval.tick().await;
+        let mut holding = false;
+        loop {
+            interval.tick().await;
+            let problem = status.lock().await.pipeline_problem(Utc::now(), max_age);
+            match (problem, holding) {
+                (Some(problem), false) => {
+                    eprintln!("[HEARTBEAT] Holding back pings: {}", problem);
+                    holding = true;
+                    continue;
+                }
+                (Some(_), true) => continue,
+                (None, true) => {
+                    println!("[HEARTBEAT] Pipeline healthy again, pinging");
+                    holding = false;
+                }
+                (None, false) => {}
+            }
+            for url in &config.urls {
+                if let Err(e) = ping(&client, url).await {
+                    eprintln!("[WARN] Heartbeat ping to {} failed: {:#}", url, e);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn ping(client: &Client, url: &str) -> Result<()> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .inspect_err(dns::forget_failed)
+        .context("Failed to send HTTP request")?;
+    if !response.status().is_success() {
+        anyhow::bail!("HTTP {}", response.status().as_u16());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_config() {
+        let config: HeartbeatConfig =
+            toml::from_str("urls = [\"https://hc-ping.com/0f8b2c4e\"]").unwrap();
+        assert_eq!(config.interval, 300);
+        assert_eq!(config.max_poll_age, 300);
+        assert!(config.validate().is_ok());
+
+        let config: HeartbeatConfig = toml::from_str("urls = []").unwrap();
+        assert!(config.validate().is_err());
+        let config: HeartbeatConfig = toml::from_str("urls = [\"hc-ping.com/x\"]").unwrap();
+        assert!(config.validate().is_err());
+        let config: HeartbeatConfig =
+            toml::from_str("urls = [\"https://hc-ping.com/x\"]\ninterval = 5").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
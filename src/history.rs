@@ -0,0 +1,342 @@
+/// Terminal charts of stored readings, for `wxlistener history --graph` on
+/// machines without a browser
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::annotations::Annotation;
+use crate::output::FieldValue;
+use crate::station::StationTime;
+use crate::units::Units;
+use crate::wind::WindVector;
+
+/// Partial blocks from one eighth to a full cell
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Parse a period like "90m", "24h", "7d" or "2w"
+pub fn parse_period(value: &str) -> Result<TimeDelta> {
+    let invalid = || anyhow::anyhow!("Invalid period '{}': use e.g. 90m, 24h, 7d or 2w", value);
+    let unit = value.chars().last().ok_or_else(invalid)?;
+    let count: i64 = value[..value.len() - unit.len_utf8()]
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(invalid)?;
+    let seconds = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86_400,
+        'w' => 7 * 86_400,
+        _ => return Err(invalid()),
+    };
+    count
+        .checked_mul(seconds)
+        .and_then(TimeDelta::try_seconds)
+        .ok_or_else(invalid)
+}
+
+/// Mean of the readings falling in each of `columns` equal slices of
+/// [start, end); None for slices without readings. Wind direction gets the
+/// vector mean.
+pub fn bucket(
+    field: &str,
+    points: &[(DateTime<Utc>, f64)],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    columns: usize,
+) -> Vec<Option<f64>> {
+    let span = (end - start).num_milliseconds().max(1) as f64;
+    let mut buckets = vec![Mean::for_field(field); columns];
+    for (at, value) in points {
+        let offset = (*at - start).num_milliseconds() as f64 / span;
+        if !(0.0..=1.0).contains(&offset) || !value.is_finite() {
+            continue;
+        }
+        let column = ((offset * columns as f64) as usize).min(columns - 1);
+        buckets[column].add(*value);
+    }
+    buckets.iter().map(Mean::value).collect()
+}
+
+/// Mean of one field's readings
+#[derive(Debug, Clone, Copy)]
+enum Mean {
+    Arithmetic { sum: f64, count: u32 },
+    Direction(WindVector),
+}
+
+impl Mean {
+    fn for_field(field: &str) -> Self {
+        match field {
+            "wind_dir" => Self::Direction(WindVector::default()),
+            _ => Self::Arithmetic { sum: 0.0, count: 0 },
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        match self {
+            Self::Arithmetic { sum, count } => {
+                *sum += value;
+                *count += 1;
+            }
+            Self::Direction(wind) => wind.add(value, 1.0),
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        match *self {
+            Self::Arithmetic { sum, count } => (count > 0).then(|| sum / count as f64),
+            Self::Direction(wind) => wind.mean_direction(),
+        }
+    }
+}
+
+/// Render `field` over [start, end] as a bar chart `width` columns wide and
+/// `height` rows high, with the range on the left and the times, in the
+/// station's local time, underneath
+#[allow(clippy::too_many_arguments)]
+pub fn render_graph(
+    field: &str,
+    points: &[(DateTime<Utc>, f64)],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    width: usize,
+    height: usize,
+    station_time: StationTime,
+    units: Units,
+) -> String {
+    let columns = bucket(field, points, start, end, width.max(1));
+    let values: Vec<f64> = columns.iter().flatten().copied().collect();
+    if values.is_empty() {
+        return format!("No {} readings in this period\n", field);
+    }
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+    // Eighths of a row filled in each column; the minimum still shows
+    let height = height.max(1);
+    let steps = height * BLOCKS.len();
+    let levels: Vec<Option<usize>> = columns
+        .iter()
+        .map(|value| {
+            value.map(|v| match max - min {
+                range if range > 0.0 => {
+                    1 + ((v - min) / range * (steps - 1) as f64).round() as usize
+                }
+                _ => steps / 2,
+            })
+        })
+        .collect();
+
+    let top = FieldValue(field, max, units).to_string();
+    let bottom = FieldValue(field, min, units).to_string();
+    let label_width = top.chars().count().max(bottom.chars().count());
+    let mut out = String::new();
+    for row in (0..height).rev() {
+        let label = match row {
+            r if r == height - 1 => top.as_str(),
+            0 => bottom.as_str(),
+            _ => "",
+        };
+        let axis = if label.is_empty() { '│' } else { '┤' };
+        out.push_str(&format!("{:>w$} {}", label, axis, w = label_width));
+        for level in &levels {
+            let filled = level.map_or(0, |l| l.saturating_sub(row * BLOCKS.len()));
+            out.push(match filled {
+                0 => ' ',
+                n => BLOCKS[n.min(BLOCKS.len()) - 1],
+            });
+        }
+        out.push('\n');
+    }
+    out.push_str(&format!(
+        "{:>w$} └{}\n",
+        "",
+        "─".repeat(levels.len()),
+        w = label_width
+    ));
+
+    let from = station_time
+        .naive_local(start)
+        .format("%m-%d %H:%M")
+        .to_string();
+    let to = station_time
+        .naive_local(end)
+        .format("%m-%d %H:%M")
+        .to_string();
+    let gap = (levels.len() + 1)
+        .saturating_sub(from.len() + to.len())
+        .max(1);
+    out.push_str(&format!(
+        "{:>w$}  {}{}{}\n",
+        "",
+        from,
+        " ".repeat(gap),
+        to,
+        w = label_width
+    ));
+
+    // The scale shows column means; the summary covers every reading
+    let readings: Vec<f64> = points
+        .iter()
+        .filter(|(at, value)| (start..=end).contains(at) && value.is_finite())
+        .map(|(_, value)| *value)
+        .collect();
+    let mut mean = Mean::for_field(field);
+    for value in &readings {
+        mean.add(*value);
+    }
+    out.push_str(&format!(
+        "min {}  max {}  mean {}  ({} readings)\n",
+        FieldValue(
+            field,
+            readings.iter().copied().fold(f64::INFINITY, f64::min),
+            units
+        ),
+        FieldValue(
+            field,
+            readings.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            units
+        ),
+        FieldValue(field, mean.value().unwrap_or(f64::NAN), units),
+        readings.len()
+    ));
+    out
+}
+
+/// List annotations under a graph, one per line with its local time range
+pub fn format_annotations(annotations: &[Annotation], station_time: StationTime) -> String {
+    let mut out = String::new();
+    for annotation in annotations {
+        let start = station_time.naive_local(annotation.start);
+        let mut when = start.format("%m-%d %H:%M").to_string();
+        if annotation.end > annotation.start {
+            let end = station_time.naive_local(annotation.end);
+            let format = if end.date() == start.date() {
+                "%H:%M"
+            } else {
+                "%m-%d %H:%M"
+            };
+            when.push_str(&format!("-{}", end.format(format)));
+        }
+        out.push_str(&format!("{:<17} {}", when, annotation.text));
+        if !annotation.tags.is_empty() {
+            out.push_str(&format!(" [{}]", annotation.tags.join(", ")));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(parse_period("24h").unwrap(), TimeDelta::hours(24));
+        assert_eq!(parse_period("90m").unwrap(), TimeDelta::minutes(90));
+        assert_eq!(parse_period("2w").unwrap(), TimeDelta::days(14));
+        for bad in ["", "h", "24", "0h", "-1d", "3y", "1.5h", "9999999999999w"] {
+            assert!(parse_period(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_render_graph() {
+        let start = Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap();
+        let end = start + TimeDelta::hours(4);
+        let points: Vec<_> = (0..4)
+            .map(|h| (start + TimeDelta::hours(h), 10.0 + h as f64))
+            .collect();
+
+        let graph = render_graph(
+            "outtemp",
+            &points,
+            start,
+            end,
+            4,
+            2,
+            StationTime::default(),
+            Units::default(),
+        );
+        let lines: Vec<&str> = graph.lines().collect();
+        assert_eq!(lines[0], "13.0°C ┤  ▃█");
+        assert_eq!(lines[1], "10.0°C ┤▁▆██");
+        assert_eq!(lines[2], "       └────");
+        assert!(lines[4].starts_with("min 10.0°C  max 13.0°C  mean 11.5°C  (4 readings)"));
+    }
+
+    #[test]
+    fn test_render_graph_gaps_and_flat() {
+        let start = Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap();
+        let end = start + TimeDelta::hours(4);
+        let points = vec![(start, 5.0), (start + TimeDelta::hours(3), 5.0)];
+
+        let graph = render_graph(
+            "outhumid",
+            &points,
+            start,
+            end,
+            4,
+            1,
+            StationTime::default(),
+            Units::default(),
+        );
+        assert!(graph.starts_with("5% ┤▄  ▄\n"));
+
+        let graph = render_graph(
+            "outtemp",
+            &[],
+            start,
+            end,
+            4,
+            1,
+            StationTime::default(),
+            Units::default(),
+        );
+        assert_eq!(graph, "No outtemp readings in this period\n");
+    }
+
+    #[test]
+    fn test_format_annotations() {
+        let start = Utc.with_ymd_and_hms(2026, 3, 1, 9, 0, 0).unwrap();
+        let annotations = [
+            Annotation {
+                start,
+                end: start + TimeDelta::minutes(90),
+                text: "Gauge cleaned".to_string(),
+                tags: vec!["maintenance".to_string()],
+            },
+            Annotation {
+                start: start + TimeDelta::days(1),
+                end: start + TimeDelta::days(1),
+                text: "Sensor relocated".to_string(),
+                tags: Vec::new(),
+            },
+        ];
+        assert_eq!(
+            format_annotations(&annotations, StationTime::default()),
+            "03-01 09:00-10:30 Gauge cleaned [maintenance]\n\
+             03-02 09:00       Sensor relocated\n"
+        );
+    }
+
+    #[test]
+    fn test_bucket_wind_direction() {
+        let start = Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap();
+        let end = start + TimeDelta::hours(2);
+        let points = vec![
+            (start, 350.0),
+            (start + TimeDelta::minutes(30), 20.0),
+            (start + TimeDelta::minutes(60), 90.0),
+        ];
+
+        let columns = bucket("wind_dir", &points, start, end, 2);
+        assert!((columns[0].unwrap() - 5.0).abs() < 1e-9);
+        assert!((columns[1].unwrap() - 90.0).abs() < 1e-9);
+        // Other fields keep the arithmetic mean
+        assert_eq!(bucket("outtemp", &points, start, end, 2)[0], Some(185.0));
+    }
+}
@@ -1,13 +1,18 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use reqwest::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio::time;
 
+use crate::batch::BatchPolicy;
+use crate::breaker::{BreakerConfig, CircuitBreaker};
+use crate::{dns, proxy};
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct HttpConfig {
     /// HTTP endpoint URL to POST weather data to
@@ -16,14 +21,52 @@ pub struct HttpConfig {
     pub timeout: Option<u64>,
     /// Optional authorization header value (e.g., "Bearer <token>")
     pub authorization: Option<String>,
+    /// Records kept for retry while the endpoint is down (default: 10000)
+    pub max_queue: Option<usize>,
+    /// User-Agent header (default: "wxlistener/<version>")
+    pub user_agent: Option<String>,
+    /// Extra headers sent with every request, e.g. an API key or tenant ID
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Send an `Idempotency-Key` header derived from the reading timestamp,
+    /// the same on every retry of a record (default: false)
+    pub idempotency_key: Option<bool>,
+    /// Post readings in batches of this many
+    pub batch_rows: Option<usize>,
+    /// Post readings at least this often in seconds when batching
+    pub batch_interval: Option<u64>,
+    /// Body of a batched post (default: array)
+    pub batch_format: Option<BatchFormat>,
+}
+
+/// How a batch of readings is written in the request body
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchFormat {
+    /// One JSON array of payloads
+    #[default]
+    Array,
+    /// One payload per line, newline-delimited JSON
+    Ndjson,
 }
 
+/// Default retry queue limit, about 44 hours of records at the default
+/// 16 second interval
+const DEFAULT_MAX_QUEUE: usize = 10_000;
+
 impl HttpConfig {
     pub fn new() -> Self {
         Self {
             url: None,
             timeout: None,
             authorization: None,
+            max_queue: None,
+            user_agent: None,
+            headers: BTreeMap::new(),
+            idempotency_key: None,
+            batch_rows: None,
+            batch_interval: None,
+            batch_format: None,
         }
     }
 
@@ -46,11 +89,58 @@ impl HttpConfig {
         Duration::from_secs(secs)
     }
 
+    pub fn get_max_queue(&self) -> usize {
+        self.max_queue.unwrap_or(DEFAULT_MAX_QUEUE).max(1)
+    }
+
     pub fn get_authorization(&self) -> Option<String> {
         self.authorization
             .clone()
             .or_else(|| std::env::var("WXLISTENER_HTTP_AUTH").ok())
     }
+
+    pub fn get_user_agent(&self) -> String {
+        self.user_agent
+            .clone()
+            .unwrap_or_else(|| format!("wxlistener/{}", env!("CARGO_PKG_VERSION")))
+    }
+
+    /// `headers` checked and ready to send; names and values must be valid
+    /// HTTP
+    pub fn get_headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .context(format!("Invalid [http] header name '{}'", name))?;
+            let value = HeaderValue::from_str(value)
+                .context(format!("Invalid [http] value for header '{}'", name))?;
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+
+    /// When a batch is due, None when every reading is posted on its own
+    pub fn get_batch_policy(&self) -> Result<Option<BatchPolicy>> {
+        BatchPolicy::new("http", self.batch_rows, self.batch_interval)
+    }
+}
+
+/// Header carrying the idempotency key
+const IDEMPOTENCY_HEADER: &str = "Idempotency-Key";
+
+/// The idempotency key of the reading taken at `timestamp`: one reading, one
+/// key, however many times it is sent
+pub fn idempotency_key(timestamp: &DateTime<Utc>) -> String {
+    format!("wxlistener-{}", timestamp.timestamp_millis())
+}
+
+/// The idempotency key of a batch, from its first and last reading
+pub fn batch_idempotency_key(first: &DateTime<Utc>, last: &DateTime<Utc>) -> String {
+    format!(
+        "wxlistener-{}-{}",
+        first.timestamp_millis(),
+        last.timestamp_millis()
+    )
 }
 
 impl Default for HttpConfig {
@@ -60,8 +150,7 @@ impl Default for HttpConfig {
 }
 
 /// Weather measurement payload matching the required schema
-#[derive(Debug, Serialize)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Serialize)]
 pub struct WeatherPayload {
     pub weather_measurement: WeatherMeasurement,
 }
@@ -131,10 +220,66 @@ impl WeatherMeasurement {
     }
 }
 
-/// A queued payload waiting to be sent
-#[derive(Debug, Clone, Serialize)]
+/// A queued post, one reading or a batch, waiting to be sent
+#[derive(Debug, Clone)]
 struct QueuedPayload {
-    weather_measurement: WeatherMeasurement,
+    body: Vec<u8>,
+    content_type: &'static str,
+    /// Sent as a header, not in the body
+    idempotency_key: Option<String>,
+    /// Readings in the body
+    records: usize,
+}
+
+impl QueuedPayload {
+    /// A post of one reading
+    fn single(payload: &WeatherPayload, idempotency_key: Option<String>) -> Result<Self> {
+        Ok(Self {
+            body: serde_json::to_vec(payload)?,
+            content_type: "application/json",
+            idempotency_key,
+            records: 1,
+        })
+    }
+
+    /// A post of several readings in `format`
+    fn batch(
+        payloads: &[WeatherPayload],
+        format: BatchFormat,
+        idempotency_key: Option<String>,
+    ) -> Result<Self> {
+        let (body, content_type) = match format {
+            BatchFormat::Array => (serde_json::to_vec(payloads)?, "application/json"),
+            BatchFormat::Ndjson => {
+                let mut body = Vec::new();
+                for payload in payloads {
+                    serde_json::to_writer(&mut body, payload)?;
+                    body.push(b'\n');
+                }
+                (body, "application/x-ndjson")
+            }
+        };
+        Ok(Self {
+            body,
+            content_type,
+            idempotency_key,
+            records: payloads.len(),
+        })
+    }
+
+    fn request(&self, client: &Client, url: &str, authorization: Option<&str>) -> RequestBuilder {
+        let mut request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, self.content_type)
+            .body(self.body.clone());
+        if let Some(auth) = authorization {
+            request = request.header("Authorization", auth);
+        }
+        if let Some(key) = &self.idempotency_key {
+            request = request.header(IDEMPOTENCY_HEADER, key);
+        }
+        request
+    }
 }
 
 pub struct HttpPublisher {
@@ -142,7 +287,27 @@ pub struct HttpPublisher {
     url: String,
     authorization: Option<String>,
     queue: Arc<Mutex<VecDeque<QueuedPayload>>>,
+    max_queue: usize,
+    idempotency_key: bool,
     is_draining: Arc<Mutex<bool>>,
+    /// Pauses the queue's retries while the endpoint keeps failing
+    breaker: Arc<Mutex<CircuitBreaker>>,
+    batch_policy: Option<BatchPolicy>,
+    batch_format: BatchFormat,
+    /// Readings held back for the next batch
+    batch: Mutex<Vec<(DateTime<Utc>, WeatherPayload)>>,
+}
+
+/// Add a record to the retry queue, dropping the oldest once it holds `max`
+/// so an endpoint that stays down can't grow memory without bound. Returns
+/// whether a record was dropped.
+fn enqueue<T>(queue: &mut VecDeque<T>, item: T, max: usize) -> bool {
+    let dropped = queue.len() >= max;
+    if dropped {
+        queue.pop_front();
+    }
+    queue.push_back(item);
+    dropped
 }
 
 impl HttpPublisher {
@@ -150,9 +315,12 @@ impl HttpPublisher {
         let url = config.get_url()?;
         let timeout = config.get_timeout();
         let authorization = config.get_authorization();
+        let batch_policy = config.get_batch_policy()?;
 
-        let client = Client::builder()
+        let client = proxy::client_builder()
             .timeout(timeout)
+            .user_agent(config.get_user_agent())
+            .default_headers(config.get_headers()?)
             .build()
             .context("Failed to create HTTP client")?;
 
@@ -164,23 +332,50 @@ impl HttpPublisher {
             url,
             authorization,
             queue: Arc::new(Mutex::new(VecDeque::new())),
+            max_queue: config.get_max_queue(),
+            idempotency_key: config.idempotency_key.unwrap_or(false),
             is_draining: Arc::new(Mutex::new(false)),
+            breaker: Arc::new(Mutex::new(CircuitBreaker::from_config(
+                "HTTP",
+                &BreakerConfig::default(),
+            ))),
+            batch_policy,
+            batch_format: config.batch_format.unwrap_or_default(),
+            batch: Mutex::new(Vec::new()),
         };
 
         Ok(publisher)
     }
 
-    /// Attempt to send a payload to the HTTP endpoint
-    async fn try_send(&self, payload: &QueuedPayload) -> Result<()> {
-        let mut request = self.client.post(&self.url).json(payload);
+    /// Pause retrying the queue as `config` says while the endpoint keeps
+    /// failing
+    pub fn with_circuit_breaker(mut self, config: &BreakerConfig) -> Self {
+        self.breaker = Arc::new(Mutex::new(CircuitBreaker::from_config("HTTP", config)));
+        self
+    }
 
+    /// Send a HEAD request to the endpoint, without a reading, and return
+    /// the status it answers with
+    pub async fn check_reachable(&self) -> Result<reqwest::StatusCode> {
+        let mut request = self.client.head(&self.url);
         if let Some(auth) = &self.authorization {
             request = request.header("Authorization", auth);
         }
-
         let response = request
             .send()
             .await
+            .inspect_err(dns::forget_failed)
+            .context("Failed to send HTTP request")?;
+        Ok(response.status())
+    }
+
+    /// Attempt to send a payload to the HTTP endpoint
+    async fn try_send(&self, payload: &QueuedPayload) -> Result<()> {
+        let response = payload
+            .request(&self.client, &self.url, self.authorization.as_deref())
+            .send()
+            .await
+            .inspect_err(dns::forget_failed)
             .context("Failed to send HTTP request")?;
 
         if !response.status().is_success() {
@@ -199,6 +394,7 @@ impl HttpPublisher {
         let authorization = self.authorization.clone();
         let queue = self.queue.clone();
         let is_draining = self.is_draining.clone();
+        let breaker = self.breaker.clone();
 
         tokio::spawn(async move {
             loop {
@@ -212,69 +408,122 @@ impl HttpPublisher {
                     q.front().cloned()
                 };
 
+                let mut wait = Duration::from_secs(1);
                 if let Some(payload) = payload {
                     // Try to send
-                    let mut request = client.post(&url).json(&payload);
-                    if let Some(auth) = &authorization {
-                        request = request.header("Authorization", auth);
-                    }
-
-                    match request.send().await {
-                        Ok(response) if response.status().is_success() => {
+                    let request = payload.request(&client, &url, authorization.as_deref());
+                    let result = match request.send().await {
+                        Ok(response) if response.status().is_success() => Ok(()),
+                        Ok(response) => Err(format!("server returned {}", response.status())),
+                        Err(e) => {
+                            // Resolve the host again in case it moved
+                            dns::forget_failed(&e);
+                            Err(format!("connection failed ({})", e))
+                        }
+                    };
+                    // Not held across the queue lock, which send() takes first
+                    let (open, retry_in) = {
+                        let mut breaker = breaker.lock().await;
+                        breaker.record(&result);
+                        (breaker.is_open(), breaker.retry_in())
+                    };
+                    match result {
+                        Ok(()) => {
                             // Success - remove from queue
                             let mut q = queue.lock().await;
                             q.pop_front();
-                            let remaining = q.len();
+                            let remaining = queued_records(&q);
                             drop(q);
 
                             if remaining > 0 {
                                 println!(
-                                    "  [OK] HTTP queue: sent 1 record ({} remaining)",
+                                    "  [OK] HTTP queue: sent {} ({} remaining)",
+                                    records(payload.records),
                                     remaining
                                 );
                             } else {
                                 println!("  [OK] HTTP queue: emptied (all records sent)");
                             }
                         }
-                        Ok(response) => {
-                            // Server error - wait and retry
-                            eprintln!(
-                                "  [WARN] HTTP queue: server returned {}, retrying in 1s...",
-                                response.status()
-                            );
-                        }
-                        Err(e) => {
-                            // Connection error - wait and retry
-                            eprintln!(
-                                "  [WARN] HTTP queue: connection failed ({}), retrying in 1s...",
-                                e
-                            );
+                        // Logged by the breaker once it opens
+                        Err(e) if !open => {
+                            eprintln!("  [WARN] HTTP queue: {}, retrying in 1s...", e);
                         }
+                        Err(_) => {}
                     }
+                    // An open breaker waits out its cool-down
+                    wait = wait.max(retry_in);
                 }
 
-                // Wait 1 second before next attempt
-                time::sleep(Duration::from_secs(1)).await;
+                time::sleep(wait).await;
             }
         });
     }
 
     /// Publish weather data to the HTTP endpoint
-    /// If the endpoint is unreachable, data is queued for later delivery
+    /// If the endpoint is unreachable, data is queued for later delivery.
+    /// When batching, the reading is held back until the batch is due.
     pub async fn publish(&self, data: &HashMap<String, f64>, timestamp: &DateTime<Utc>) {
-        let measurement = WeatherMeasurement::from_data(data, timestamp);
-        let payload = QueuedPayload {
-            weather_measurement: measurement,
+        let measurement = WeatherPayload {
+            weather_measurement: WeatherMeasurement::from_data(data, timestamp),
         };
 
+        let Some(policy) = self.batch_policy else {
+            let key = self.idempotency_key.then(|| idempotency_key(timestamp));
+            match QueuedPayload::single(&measurement, key) {
+                Ok(payload) => self.send(payload, timestamp).await,
+                Err(e) => eprintln!("  [WARN] HTTP payload could not be encoded: {}", e),
+            }
+            return;
+        };
+
+        let due = {
+            let mut batch = self.batch.lock().await;
+            batch.push((*timestamp, measurement));
+            policy.is_due(batch.len(), batch[0].0, Utc::now())
+        };
+        if due {
+            self.flush().await;
+        }
+    }
+
+    /// Post the readings held back for the next batch, e.g. before exiting
+    pub async fn flush(&self) {
+        let batch = std::mem::take(&mut *self.batch.lock().await);
+        let (Some((first, _)), Some((last, _))) = (batch.first(), batch.last()) else {
+            return;
+        };
+        let key = self
+            .idempotency_key
+            .then(|| batch_idempotency_key(first, last));
+        let payloads: Vec<WeatherPayload> = batch.iter().map(|(_, p)| p.clone()).collect();
+        match QueuedPayload::batch(&payloads, self.batch_format, key) {
+            Ok(payload) => self.send(payload, last).await,
+            Err(e) => eprintln!("  [WARN] HTTP batch could not be encoded: {}", e),
+        }
+    }
+
+    /// Send a post, or queue it for retry if the endpoint is unreachable
+    async fn send(&self, payload: QueuedPayload, timestamp: &DateTime<Utc>) {
+        let sent = records(payload.records);
+
         // Check if we're currently draining the queue
         let is_draining = *self.is_draining.lock().await;
 
         if is_draining {
             // Queue is being drained, add to end of queue
             let mut q = self.queue.lock().await;
-            q.push_back(payload);
-            println!("  [QUEUE] HTTP: queued record ({} in queue)", q.len());
+            if enqueue(&mut q, payload, self.max_queue) {
+                eprintln!("  [WARN] HTTP queue full, dropped the oldest post");
+            }
+            // While the breaker is open its warning stands for every poll
+            if !self.breaker.lock().await.is_open() {
+                println!(
+                    "  [QUEUE] HTTP: queued {} ({} in queue)",
+                    sent,
+                    queued_records(&q)
+                );
+            }
             return;
         }
 
@@ -282,7 +531,8 @@ impl HttpPublisher {
         match self.try_send(&payload).await {
             Ok(()) => {
                 println!(
-                    "  [OK] HTTP: sent record ({})",
+                    "  [OK] HTTP: sent {} ({})",
+                    sent,
                     timestamp.format("%Y-%m-%d %H:%M:%S UTC")
                 );
             }
@@ -290,13 +540,15 @@ impl HttpPublisher {
                 // Failed - add to queue and start drain task
                 eprintln!("  [WARN] HTTP publish failed: {}", e);
                 let mut q = self.queue.lock().await;
-                q.push_back(payload);
-                let queue_len = q.len();
+                if enqueue(&mut q, payload, self.max_queue) {
+                    eprintln!("  [WARN] HTTP queue full, dropped the oldest post");
+                }
+                let queue_len = queued_records(&q);
                 drop(q);
 
                 println!(
-                    "  [QUEUE] HTTP: queued record ({} in queue), will retry...",
-                    queue_len
+                    "  [QUEUE] HTTP: queued {} ({} in queue), will retry...",
+                    sent, queue_len
                 );
 
                 // Start drain task if not already running
@@ -310,10 +562,29 @@ impl HttpPublisher {
         &self.url
     }
 
-    /// Get current queue length
+    /// Get current queue length, in readings
     #[allow(dead_code)]
     pub async fn queue_len(&self) -> usize {
-        self.queue.lock().await.len()
+        queued_records(&*self.queue.lock().await)
+    }
+
+    /// Readings held back for the next batch
+    #[allow(dead_code)]
+    pub async fn batch_len(&self) -> usize {
+        self.batch.lock().await.len()
+    }
+}
+
+/// Readings in the queued posts
+fn queued_records(queue: &VecDeque<QueuedPayload>) -> usize {
+    queue.iter().map(|payload| payload.records).sum()
+}
+
+/// "1 record" or "N records", for the log
+fn records(count: usize) -> String {
+    match count {
+        1 => "1 record".to_string(),
+        n => format!("{} records", n),
     }
 }
 
@@ -340,17 +611,230 @@ mod tests {
         let config = HttpConfig {
             url: None,
             timeout: Some(30),
-            authorization: None,
+            ..HttpConfig::new()
         };
         assert_eq!(config.get_timeout(), Duration::from_secs(30));
     }
 
+    #[test]
+    fn test_http_config_headers() {
+        let config: HttpConfig = toml::from_str(
+            r#"
+            url = "https://example.com/api"
+            user_agent = "station-7"
+            idempotency_key = true
+            [headers]
+            X-Api-Key = "abc123"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.get_user_agent(), "station-7");
+        assert_eq!(config.get_headers().unwrap()["x-api-key"], "abc123");
+        assert_eq!(config.idempotency_key, Some(true));
+
+        assert!(HttpConfig::new()
+            .get_user_agent()
+            .starts_with("wxlistener/"));
+        let mut bad = HttpConfig::new();
+        bad.headers
+            .insert("X Api Key".to_string(), "abc123".to_string());
+        assert!(bad.get_headers().is_err());
+        bad.headers.clear();
+        bad.headers
+            .insert("X-Api-Key".to_string(), "abc\n123".to_string());
+        assert!(bad.get_headers().is_err());
+    }
+
+    #[test]
+    fn test_idempotency_key() {
+        let timestamp = DateTime::parse_from_rfc3339("2026-10-17T12:00:00.250Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(idempotency_key(&timestamp), "wxlistener-1792238400250");
+
+        // Only the body is JSON
+        let payload = QueuedPayload::single(
+            &WeatherPayload {
+                weather_measurement: WeatherMeasurement::from_data(&HashMap::new(), &timestamp),
+            },
+            Some(idempotency_key(&timestamp)),
+        )
+        .unwrap();
+        let json = String::from_utf8(payload.body).unwrap();
+        assert!(json.starts_with("{\"weather_measurement\":{"), "{}", json);
+        assert!(!json.contains("idempotency"), "{}", json);
+
+        let last = timestamp + chrono::Duration::seconds(16);
+        assert_eq!(
+            batch_idempotency_key(&timestamp, &last),
+            "wxlistener-1792238400250-1792238416250"
+        );
+    }
+
+    #[test]
+    fn test_batch_body() {
+        let payloads: Vec<WeatherPayload> = [10.0, 11.5]
+            .iter()
+            .map(|temp| WeatherPayload {
+                weather_measurement: WeatherMeasurement::from_data(
+                    &HashMap::from([("outtemp".to_string(), *temp)]),
+                    &DateTime::from_timestamp(1_792_238_400, 0).unwrap(),
+                ),
+            })
+            .collect();
+
+        let array = QueuedPayload::batch(&payloads, BatchFormat::Array, None).unwrap();
+        assert_eq!(array.content_type, "application/json");
+        assert_eq!(array.records, 2);
+        let json: serde_json::Value = serde_json::from_slice(&array.body).unwrap();
+        assert_eq!(json[1]["weather_measurement"]["temperature"], 11.5);
+
+        let ndjson = QueuedPayload::batch(&payloads, BatchFormat::Ndjson, None).unwrap();
+        assert_eq!(ndjson.content_type, "application/x-ndjson");
+        let body = String::from_utf8(ndjson.body).unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["weather_measurement"]["temperature"], 10.0);
+    }
+
+    #[test]
+    fn test_http_config_batching() {
+        let config: HttpConfig = toml::from_str(
+            r#"
+            url = "https://example.com/api"
+            batch_rows = 60
+            batch_format = "ndjson"
+            "#,
+        )
+        .unwrap();
+        assert!(config.get_batch_policy().unwrap().is_some());
+        assert_eq!(config.batch_format, Some(BatchFormat::Ndjson));
+        assert_eq!(HttpConfig::new().get_batch_policy().unwrap(), None);
+        let config = HttpConfig {
+            batch_interval: Some(0),
+            ..HttpConfig::new()
+        };
+        assert!(config.get_batch_policy().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_publish_sends_headers() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = HttpConfig {
+            url: Some(format!("http://{}/weather", listener.local_addr().unwrap())),
+            user_agent: Some("station-7".to_string()),
+            headers: BTreeMap::from([("X-Tenant".to_string(), "north".to_string())]),
+            idempotency_key: Some(true),
+            ..HttpConfig::new()
+        };
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request).to_lowercase()
+        });
+
+        let publisher = HttpPublisher::new(&config).await.unwrap();
+        let timestamp = DateTime::from_timestamp_millis(1_792_238_400_250).unwrap();
+        publisher.publish(&HashMap::new(), &timestamp).await;
+
+        let request = server.await.unwrap();
+        assert!(request.contains("user-agent: station-7\r\n"), "{}", request);
+        assert!(request.contains("x-tenant: north\r\n"), "{}", request);
+        assert!(
+            request.contains("idempotency-key: wxlistener-1792238400250\r\n"),
+            "{}",
+            request
+        );
+        assert_eq!(publisher.queue_len().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_publish_batches() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = HttpConfig {
+            url: Some(format!("http://{}/weather", listener.local_addr().unwrap())),
+            batch_rows: Some(3),
+            idempotency_key: Some(true),
+            ..HttpConfig::new()
+        };
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            // The body ends the array
+            while !request.ends_with(b"]") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        let publisher = HttpPublisher::new(&config).await.unwrap();
+        let start = DateTime::from_timestamp(1_792_238_400, 0).unwrap();
+        for i in 0..3 {
+            let timestamp = start + chrono::Duration::seconds(16 * i);
+            publisher.publish(&HashMap::new(), &timestamp).await;
+            let held = if i < 2 { i as usize + 1 } else { 0 };
+            assert_eq!(publisher.batch_len().await, held);
+        }
+
+        let request = server.await.unwrap();
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        let head = head.to_lowercase();
+        assert!(
+            head.contains("content-type: application/json\r\n"),
+            "{}",
+            head
+        );
+        assert!(
+            head.contains("idempotency-key: wxlistener-1792238400000-1792238432000\r\n"),
+            "{}",
+            head
+        );
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+        assert_eq!(publisher.queue_len().await, 0);
+
+        // Nothing left to flush
+        publisher.flush().await;
+    }
+
+    #[test]
+    fn test_retry_queue_is_bounded() {
+        let mut queue = VecDeque::new();
+        for i in 0..3 {
+            assert!(!enqueue(&mut queue, i, 3));
+        }
+        assert!(enqueue(&mut queue, 3, 3));
+        assert_eq!(queue, [1, 2, 3]);
+
+        assert_eq!(HttpConfig::new().get_max_queue(), DEFAULT_MAX_QUEUE);
+    }
+
     #[test]
     fn test_http_config_get_url_from_config() {
         let config = HttpConfig {
             url: Some("https://example.com/api".to_string()),
             timeout: None,
-            authorization: None,
+            ..HttpConfig::new()
         };
         assert_eq!(config.get_url().unwrap(), "https://example.com/api");
     }
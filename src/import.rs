@@ -0,0 +1,438 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use futures_util::TryStreamExt;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::Row;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::database::DatabaseWriter;
+use crate::pg_copy::COPY_CHUNK;
+use crate::provenance::Provenance;
+
+/// Unit systems used by legacy weather software, named after WeeWX's
+/// `usUnits` values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnitSystem {
+    /// °F, inHg, mph, inches
+    Us,
+    /// °C, hPa, km/h, centimeters
+    Metric,
+    /// °C, hPa, m/s, millimeters
+    MetricWx,
+}
+
+impl UnitSystem {
+    /// Map a WeeWX `usUnits` column value
+    pub fn from_weewx(code: i64) -> Option<Self> {
+        match code {
+            1 => Some(Self::Us),
+            16 => Some(Self::Metric),
+            17 => Some(Self::MetricWx),
+            _ => None,
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "us" => Ok(Self::Us),
+            "metric" => Ok(Self::Metric),
+            "metricwx" => Ok(Self::MetricWx),
+            _ => anyhow::bail!(
+                "Unknown unit system '{}': use us, metric or metricwx",
+                value
+            ),
+        }
+    }
+
+    fn temp(self, value: f64) -> f64 {
+        match self {
+            Self::Us => (value - 32.0) * 5.0 / 9.0,
+            _ => value,
+        }
+    }
+
+    fn pressure(self, value: f64) -> f64 {
+        match self {
+            Self::Us => value * 33.863_886,
+            _ => value,
+        }
+    }
+
+    fn speed(self, value: f64) -> f64 {
+        match self {
+            Self::Us => value * 0.447_04,
+            Self::Metric => value / 3.6,
+            Self::MetricWx => value,
+        }
+    }
+
+    fn rain(self, value: f64) -> f64 {
+        match self {
+            Self::Us => value * 25.4,
+            Self::Metric => value * 10.0,
+            Self::MetricWx => value,
+        }
+    }
+}
+
+/// Legacy source formats that can be imported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    /// WeeWX SQLite archive database (weewx.sdb)
+    Weewx,
+    /// Cumulus monthly log files (e.g. Mar25log.txt), or a directory of them
+    Cumulus,
+}
+
+impl ImportFormat {
+    /// Guess the format from the path: directories and .txt files are Cumulus
+    /// logs, everything else is treated as a WeeWX database
+    pub fn detect(path: &Path) -> Self {
+        let is_txt = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("txt"))
+            .unwrap_or(false);
+        if path.is_dir() || is_txt {
+            Self::Cumulus
+        } else {
+            Self::Weewx
+        }
+    }
+
+    pub fn parse(value: &str, path: &Path) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "auto" => Ok(Self::detect(path)),
+            "weewx" => Ok(Self::Weewx),
+            "cumulus" => Ok(Self::Cumulus),
+            _ => anyhow::bail!(
+                "Unknown import format '{}': use auto, weewx or cumulus",
+                value
+            ),
+        }
+    }
+}
+
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+/// Convert one WeeWX archive row (column name → value) into our field names
+pub fn map_weewx_record(
+    columns: &HashMap<&str, Option<f64>>,
+    units: UnitSystem,
+) -> HashMap<String, f64> {
+    let mut data = HashMap::new();
+    let mut put = |name: &str, column: &str, convert: fn(UnitSystem, f64) -> f64| {
+        if let Some(Some(value)) = columns.get(column) {
+            data.insert(name.to_string(), round1(convert(units, *value)));
+        }
+    };
+
+    let same = |_: UnitSystem, v: f64| v;
+    put("outtemp", "outTemp", UnitSystem::temp);
+    put("intemp", "inTemp", UnitSystem::temp);
+    put("dewpoint", "dewpoint", UnitSystem::temp);
+    put("windchill", "windchill", UnitSystem::temp);
+    put("heatindex", "heatindex", UnitSystem::temp);
+    put("outhumid", "outHumidity", same);
+    put("inhumid", "inHumidity", same);
+    put("relbarometer", "barometer", UnitSystem::pressure);
+    put("absbarometer", "pressure", UnitSystem::pressure);
+    put("wind_speed", "windSpeed", UnitSystem::speed);
+    put("gust_speed", "windGust", UnitSystem::speed);
+    put("wind_dir", "windDir", same);
+    put("rain_rate", "rainRate", UnitSystem::rain);
+    put("uvi", "UV", same);
+
+    data
+}
+
+const WEEWX_COLUMNS: &[&str] = &[
+    "outTemp",
+    "inTemp",
+    "dewpoint",
+    "windchill",
+    "heatindex",
+    "outHumidity",
+    "inHumidity",
+    "barometer",
+    "pressure",
+    "windSpeed",
+    "windGust",
+    "windDir",
+    "rainRate",
+    "UV",
+];
+
+/// Parse one line of a Cumulus monthly log. Returns the local timestamp and
+/// mapped data, or None for blank/unparseable lines.
+pub fn parse_cumulus_line(
+    line: &str,
+    units: UnitSystem,
+) -> Option<(NaiveDateTime, HashMap<String, f64>)> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+
+    // Locales with a decimal comma use ';' as the field separator
+    let fields: Vec<String> = if line.contains(';') {
+        line.split(';')
+            .map(|f| f.trim().replace(',', "."))
+            .collect()
+    } else {
+        line.split(',').map(|f| f.trim().to_string()).collect()
+    };
+    if fields.len() < 14 {
+        return None;
+    }
+
+    let date_str = fields[0].replace(['-', '.'], "/");
+    let date = NaiveDate::parse_from_str(&date_str, "%d/%m/%y").ok()?;
+    let time = NaiveTime::parse_from_str(&fields[1], "%H:%M").ok()?;
+
+    let value = |index: usize| fields.get(index).and_then(|f| f.parse::<f64>().ok());
+    let mut data = HashMap::new();
+    let mut put = |name: &str, index: usize, convert: fn(UnitSystem, f64) -> f64| {
+        if let Some(v) = value(index) {
+            data.insert(name.to_string(), round1(convert(units, v)));
+        }
+    };
+
+    let same = |_: UnitSystem, v: f64| v;
+    put("outtemp", 2, UnitSystem::temp);
+    put("outhumid", 3, same);
+    put("dewpoint", 4, UnitSystem::temp);
+    put("wind_speed", 5, UnitSystem::speed);
+    put("gust_speed", 6, UnitSystem::speed);
+    put("wind_dir", 7, same);
+    put("rain_rate", 8, UnitSystem::rain);
+    put("rain_day", 9, UnitSystem::rain);
+    put("relbarometer", 10, UnitSystem::pressure);
+    put("intemp", 12, UnitSystem::temp);
+    put("inhumid", 13, same);
+    put("windchill", 15, UnitSystem::temp);
+    put("heatindex", 16, UnitSystem::temp);
+    put("uvi", 17, same);
+
+    Some((date.and_time(time), data))
+}
+
+fn local_to_utc(naive: &NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(naive)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Insert records that don't already exist. Returns (inserted, skipped).
+async fn insert_records(
+    writer: &DatabaseWriter,
+    records: Vec<(DateTime<Utc>, HashMap<String, f64>)>,
+    origin: &str,
+) -> Result<(usize, usize)> {
+    let (Some(first), Some(last)) = (
+        records.iter().map(|r| r.0).min(),
+        records.iter().map(|r| r.0).max(),
+    ) else {
+        return Ok((0, 0));
+    };
+
+    let existing: std::collections::HashSet<i64> = writer
+        .existing_timestamps(&first, &last)
+        .await?
+        .into_iter()
+        .map(|ts| ts.timestamp())
+        .collect();
+
+    let provenance = Provenance::replay(origin, None);
+    let mut inserted = 0;
+    let mut skipped = 0;
+    let mut chunk = Vec::with_capacity(COPY_CHUNK);
+    for (timestamp, data) in records {
+        if data.is_empty() || existing.contains(&timestamp.timestamp()) {
+            skipped += 1;
+            continue;
+        }
+        chunk.push((timestamp, data, provenance.clone()));
+        if chunk.len() == COPY_CHUNK {
+            writer.bulk_insert(&chunk).await?;
+            inserted += chunk.len();
+            chunk.clear();
+            println!("  ... {} records imported", inserted);
+        }
+    }
+    writer.bulk_insert(&chunk).await?;
+    inserted += chunk.len();
+
+    Ok((inserted, skipped))
+}
+
+/// Import a WeeWX SQLite archive. Units are taken from each row's usUnits.
+pub async fn import_weewx(path: &Path, writer: &DatabaseWriter) -> Result<(usize, usize)> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = SqlitePool::connect_with(options)
+        .await
+        .context(format!("Failed to open WeeWX database: {:?}", path))?;
+
+    let sql = format!(
+        "SELECT dateTime, usUnits, {} FROM archive ORDER BY dateTime",
+        WEEWX_COLUMNS.join(", ")
+    );
+
+    let mut records = Vec::new();
+    let mut rows = sqlx::query(&sql).fetch(&pool);
+    while let Some(row) = rows
+        .try_next()
+        .await
+        .context("Failed to read WeeWX archive table")?
+    {
+        let ts: i64 = row.try_get("dateTime")?;
+        let us_units: i64 = row.try_get("usUnits")?;
+        let (Some(timestamp), Some(units)) = (
+            DateTime::from_timestamp(ts, 0),
+            UnitSystem::from_weewx(us_units),
+        ) else {
+            continue;
+        };
+
+        let mut columns = HashMap::new();
+        for column in WEEWX_COLUMNS {
+            columns.insert(*column, row.try_get::<Option<f64>, _>(*column)?);
+        }
+        records.push((timestamp, map_weewx_record(&columns, units)));
+    }
+
+    insert_records(writer, records, "weewx").await
+}
+
+/// Import one Cumulus monthly log, or every *log.txt file in a directory
+pub async fn import_cumulus(
+    path: &Path,
+    units: UnitSystem,
+    writer: &DatabaseWriter,
+) -> Result<(usize, usize)> {
+    let files: Vec<PathBuf> = if path.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(path)
+            .context(format!("Failed to read directory: {:?}", path))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .map(|n| n.ends_with("log.txt"))
+                    .unwrap_or(false)
+            })
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut records = Vec::new();
+    for file in files {
+        println!("Reading {:?}", file);
+        let contents =
+            fs::read_to_string(&file).context(format!("Failed to read log file: {:?}", file))?;
+        for line in contents.lines() {
+            if let Some((local, data)) = parse_cumulus_line(line, units) {
+                if let Some(timestamp) = local_to_utc(&local) {
+                    records.push((timestamp, data));
+                }
+            }
+        }
+    }
+
+    insert_records(writer, records, "cumulus").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_conversions() {
+        assert!((UnitSystem::Us.temp(50.0) - 10.0).abs() < 1e-9);
+        assert!((UnitSystem::Us.pressure(29.92) - 1013.2).abs() < 0.1);
+        assert!((UnitSystem::Us.speed(10.0) - 4.4704).abs() < 1e-9);
+        assert!((UnitSystem::Metric.speed(36.0) - 10.0).abs() < 1e-9);
+        assert_eq!(UnitSystem::Metric.rain(1.2), 12.0);
+        assert_eq!(UnitSystem::MetricWx.rain(1.2), 1.2);
+    }
+
+    #[test]
+    fn test_unit_system_from_weewx() {
+        assert_eq!(UnitSystem::from_weewx(1), Some(UnitSystem::Us));
+        assert_eq!(UnitSystem::from_weewx(16), Some(UnitSystem::Metric));
+        assert_eq!(UnitSystem::from_weewx(17), Some(UnitSystem::MetricWx));
+        assert_eq!(UnitSystem::from_weewx(2), None);
+    }
+
+    #[test]
+    fn test_map_weewx_record_us_units() {
+        let mut columns = HashMap::new();
+        columns.insert("outTemp", Some(68.0));
+        columns.insert("outHumidity", Some(55.0));
+        columns.insert("barometer", Some(29.92));
+        columns.insert("windSpeed", Some(10.0));
+        columns.insert("inTemp", None);
+
+        let data = map_weewx_record(&columns, UnitSystem::Us);
+        assert_eq!(data.get("outtemp"), Some(&20.0));
+        assert_eq!(data.get("outhumid"), Some(&55.0));
+        assert_eq!(data.get("relbarometer"), Some(&1013.2));
+        assert_eq!(data.get("wind_speed"), Some(&4.5));
+        assert!(!data.contains_key("intemp"));
+    }
+
+    #[test]
+    fn test_parse_cumulus_line() {
+        let line =
+            "01/03/25,06:10,4.5,92,3.3,1.2,2.4,225,0.0,1.5,1012.3,250.4,21.2,45,2.4,4.5,4.5,0.0";
+        let (ts, data) = parse_cumulus_line(line, UnitSystem::MetricWx).unwrap();
+
+        assert_eq!(
+            ts,
+            NaiveDate::from_ymd_opt(2025, 3, 1)
+                .unwrap()
+                .and_hms_opt(6, 10, 0)
+                .unwrap()
+        );
+        assert_eq!(data.get("outtemp"), Some(&4.5));
+        assert_eq!(data.get("outhumid"), Some(&92.0));
+        assert_eq!(data.get("wind_dir"), Some(&225.0));
+        assert_eq!(data.get("rain_day"), Some(&1.5));
+        assert_eq!(data.get("relbarometer"), Some(&1012.3));
+        assert_eq!(data.get("intemp"), Some(&21.2));
+        assert_eq!(data.get("uvi"), Some(&0.0));
+    }
+
+    #[test]
+    fn test_parse_cumulus_line_decimal_comma() {
+        let line = "01.03.25;06:10;4,5;92;3,3;1,2;2,4;225;0,0;1,5;1012,3;250,4;21,2;45";
+        let (_, data) = parse_cumulus_line(line, UnitSystem::MetricWx).unwrap();
+        assert_eq!(data.get("outtemp"), Some(&4.5));
+        assert_eq!(data.get("relbarometer"), Some(&1012.3));
+    }
+
+    #[test]
+    fn test_parse_cumulus_line_invalid() {
+        assert!(parse_cumulus_line("", UnitSystem::MetricWx).is_none());
+        assert!(parse_cumulus_line("not,a,log,line", UnitSystem::MetricWx).is_none());
+    }
+
+    #[test]
+    fn test_import_format_detect() {
+        assert_eq!(
+            ImportFormat::detect(Path::new("Mar25log.txt")),
+            ImportFormat::Cumulus
+        );
+        assert_eq!(
+            ImportFormat::detect(Path::new("/var/lib/weewx/weewx.sdb")),
+            ImportFormat::Weewx
+        );
+        assert!(ImportFormat::parse("wd", Path::new("x")).is_err());
+    }
+}
@@ -1,9 +1,83 @@
+pub mod air_quality;
+pub mod annotations;
+#[cfg(feature = "web")]
+pub mod auth;
+#[cfg(all(feature = "db", feature = "http"))]
+pub mod backfill;
+pub mod backup;
+#[cfg(any(feature = "db", feature = "http"))]
+pub mod batch;
+pub mod battery;
+#[cfg(any(feature = "db", feature = "mqtt", feature = "http"))]
+pub mod breaker;
+pub mod burst;
+pub mod calibration;
 pub mod client;
+#[cfg(feature = "db")]
+pub mod clock_skew;
+pub mod compare;
 pub mod config;
+pub mod control;
+pub mod cron;
+#[cfg(feature = "db")]
 pub mod database;
 pub mod decoder;
+pub mod degree_days;
+pub mod derived;
+pub mod device;
+pub mod device_queue;
+#[cfg(any(feature = "db", feature = "http"))]
+pub mod dns;
+pub mod doctor;
+pub mod events;
+#[cfg(all(feature = "web", feature = "http"))]
+pub mod federation;
+#[cfg(feature = "web")]
+pub mod feed;
+pub mod fixed;
+#[cfg(feature = "web")]
+pub mod geojson;
+#[cfg(feature = "mqtt")]
+pub mod ha_blueprint;
+#[cfg(feature = "http")]
+pub mod heartbeat;
+pub mod history;
+#[cfg(feature = "http")]
 pub mod http_output;
+#[cfg(feature = "db")]
+pub mod import;
+pub mod livedata_info;
+pub mod local_api;
+pub mod metrics;
+#[cfg(feature = "mqtt")]
 pub mod mqtt;
+#[cfg(feature = "mqtt")]
+pub mod mqtt_presets;
 pub mod output;
+#[cfg(feature = "db")]
+pub mod pg_copy;
 pub mod protocol;
+pub mod provenance;
+#[cfg(any(feature = "http", feature = "mqtt"))]
+pub mod proxy;
+#[cfg(feature = "web")]
+pub mod public;
+#[cfg(feature = "web")]
+pub mod realtime;
+#[cfg(feature = "db")]
+pub mod rollup;
+pub mod schedule;
+pub mod secrets;
+pub mod sensor_watch;
+pub mod sensors;
+pub mod state;
+pub mod station;
+pub mod status;
+pub mod storage;
+pub mod units;
+pub mod vectors;
+#[cfg(feature = "db")]
+pub mod wal;
+#[cfg(feature = "web")]
 pub mod web;
+pub mod wind;
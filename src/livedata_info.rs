@@ -0,0 +1,450 @@
+/// The JSON of the gateway's own local HTTP API (`/get_livedata_info` on a
+/// GW1100/GW2000), for apps that already read it from the gateway, and read
+/// back into readings when live data comes over that API
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::units::LUX_PER_WM2;
+
+/// `common_list` entries in the gateway's order: field id, reading, decimals
+/// and unit
+const COMMON: &[(&str, &str, usize, &str)] = &[
+    ("0x02", "outtemp", 1, "C"),
+    ("0x07", "outhumid", 0, "%"),
+    ("3", "feels_like", 1, "C"),
+    ("0x03", "dewpoint", 1, "C"),
+    ("0x0B", "wind_speed", 2, "m/s"),
+    ("0x0C", "gust_speed", 2, "m/s"),
+    ("0x19", "day_max_wind", 2, "m/s"),
+    ("0x15", "light", 2, "W/m2"),
+    ("0x17", "uvi", 0, ""),
+    ("0x0A", "wind_dir", 0, ""),
+];
+
+/// `rain` entries; `piezoRain` uses the same ids for the piezo gauge
+const RAIN: &[(&str, &str, &str)] = &[
+    ("0x0D", "rain_event", "mm"),
+    ("0x0E", "rain_rate", "mm/Hr"),
+    ("0x10", "rain_day", "mm"),
+    ("0x11", "rain_week", "mm"),
+    ("0x12", "rain_month", "mm"),
+    ("0x13", "rain_year", "mm"),
+];
+
+/// Temperatures carry a separate "unit"; everything else has it in "val"
+fn entry(id: &str, value: f64, decimals: usize, unit: &str) -> Value {
+    match unit {
+        "C" => json!({"id": id, "val": format!("{:.*}", decimals, value), "unit": "C"}),
+        "%" => json!({"id": id, "val": format!("{:.*}%", decimals, value)}),
+        "" => json!({"id": id, "val": format!("{:.*}", decimals, value)}),
+        unit => json!({"id": id, "val": format!("{:.*} {}", decimals, value, unit)}),
+    }
+}
+
+/// "Feels like" the way the gateway shows it: wind chill when cold, heat
+/// index when hot, otherwise the temperature
+fn feels_like(readings: &HashMap<String, f64>) -> Option<f64> {
+    let temp = *readings.get("outtemp")?;
+    let feels = if temp <= 10.0 {
+        readings.get("windchill")
+    } else if temp >= 26.7 {
+        readings.get("heatindex")
+    } else {
+        None
+    };
+    Some(feels.copied().unwrap_or(temp))
+}
+
+/// The `get_livedata_info` document for one poll, in metric units. Sections
+/// and entries are left out when the gateway doesn't report them. Channel
+/// sensor batteries aren't polled, so they report "0" (normal).
+pub fn livedata_info(readings: &HashMap<String, f64>) -> Value {
+    let get = |key: &str| readings.get(key).copied().filter(|v| v.is_finite());
+    let mut document = Map::new();
+
+    let common: Vec<Value> = COMMON
+        .iter()
+        .filter_map(|&(id, key, decimals, unit)| {
+            let value = match key {
+                "feels_like" => feels_like(readings)?,
+                // The gateway shows solar radiation, not lux
+                "light" => get(key)? / LUX_PER_WM2,
+                _ => get(key)?,
+            };
+            Some(entry(id, value, decimals, unit))
+        })
+        .collect();
+    if !common.is_empty() {
+        document.insert("common_list".to_string(), Value::Array(common));
+    }
+
+    for (section, prefix) in [("rain", ""), ("piezoRain", "piezo_")] {
+        let rain: Vec<Value> = RAIN
+            .iter()
+            .filter_map(|&(id, key, unit)| {
+                Some(entry(id, get(&format!("{}{}", prefix, key))?, 1, unit))
+            })
+            .collect();
+        if !rain.is_empty() {
+            document.insert(section.to_string(), Value::Array(rain));
+        }
+    }
+
+    if let (Some(intemp), Some(inhumid)) = (get("intemp"), get("inhumid")) {
+        let mut wh25 = json!({
+            "intemp": format!("{:.1}", intemp),
+            "unit": "C",
+            "inhumi": format!("{:.0}%", inhumid),
+        });
+        if let Some(abs) = get("absbarometer") {
+            wh25["abs"] = json!(format!("{:.1} hPa", abs));
+        }
+        if let Some(rel) = get("relbarometer") {
+            wh25["rel"] = json!(format!("{:.1} hPa", rel));
+        }
+        document.insert("wh25".to_string(), json!([wh25]));
+    }
+
+    let channels: Vec<Value> = (1..=8)
+        .filter_map(|channel| {
+            let temp = get(&format!("temp_ch{}", channel))?;
+            let mut entry = json!({
+                "channel": channel.to_string(),
+                "name": "",
+                "battery": "0",
+                "temp": format!("{:.1}", temp),
+                "unit": "C",
+            });
+            if let Some(humidity) = get(&format!("humid_ch{}", channel)) {
+                entry["humidity"] = json!(format!("{:.0}%", humidity));
+            }
+            Some(entry)
+        })
+        .collect();
+    if !channels.is_empty() {
+        document.insert("ch_aisle".to_string(), Value::Array(channels));
+    }
+
+    if let Some(co2) = get("co2") {
+        let battery = get("co2_batt").unwrap_or(0.0);
+        let mut entry = json!({"CO2": format!("{:.0}", co2), "battery": format!("{:.0}", battery)});
+        for (field, key, decimals) in [
+            ("temp", "co2_temp", 1),
+            ("PM25", "co2_pm25", 1),
+            ("PM10", "co2_pm10", 1),
+            ("CO2_24H", "co2_24h", 0),
+        ] {
+            if let Some(value) = get(key) {
+                entry[field] = json!(format!("{:.*}", decimals, value));
+            }
+        }
+        if entry.get("temp").is_some() {
+            entry["unit"] = json!("C");
+        }
+        if let Some(humidity) = get("co2_humid") {
+            entry["humidity"] = json!(format!("{:.0}%", humidity));
+        }
+        document.insert("co2".to_string(), json!([entry]));
+    }
+
+    Value::Object(document)
+}
+
+/// A number and its unit from a `val` like "1.40 m/s", "68%", or "20.3" with
+/// the unit separate; None for placeholders like "--" while a sensor is out
+fn parse_value(value: &Value, unit: Option<&Value>) -> Option<(f64, String)> {
+    let unit = unit.and_then(Value::as_str).unwrap_or("");
+    if let Some(number) = value.as_f64() {
+        return Some((number, unit.to_string()));
+    }
+    let text = value.as_str()?.trim();
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+')))
+        .unwrap_or(text.len());
+    let number = text[..end].parse().ok()?;
+    let suffix = text[end..].trim();
+    Some((
+        number,
+        if suffix.is_empty() { unit } else { suffix }.to_string(),
+    ))
+}
+
+/// Convert from any unit the gateway can be set to display, None for one
+/// it doesn't know. Light comes back in lux.
+fn to_metric(value: f64, unit: &str) -> Option<f64> {
+    let unit = unit
+        .trim_start_matches(['°', '℃', '℉'])
+        .to_ascii_lowercase();
+    Some(match unit.as_str() {
+        "" | "c" | "%" | "m/s" | "hpa" | "mm" | "mm/hr" | "lux" => value,
+        "f" => (value - 32.0) * 5.0 / 9.0,
+        "km/h" => value / 3.6,
+        "mph" => value * 0.447_04,
+        "knots" | "kn" => value * 1852.0 / 3600.0,
+        "ft/s" => value * 0.3048,
+        "inhg" => value * 33.863_886,
+        "mmhg" => value * 1.333_224,
+        "in" | "in/hr" => value * 25.4,
+        "w/m2" => value * LUX_PER_WM2,
+        "klux" => value * 1000.0,
+        "fc" => value * 10.764,
+        _ => return None,
+    })
+}
+
+/// Readings from a gateway's `get_livedata_info` document, in metric
+/// whatever units the gateway is set to display, and the number of values
+/// that couldn't be converted. Sections and ids it doesn't know are skipped.
+pub fn parse_livedata_info(document: &Value) -> (HashMap<String, f64>, u32) {
+    let mut readings = HashMap::new();
+    let mut warnings = 0;
+    let mut take = |key: String, value: &Value, unit: Option<&Value>| {
+        let Some((number, unit)) = parse_value(value, unit) else {
+            return;
+        };
+        match to_metric(number, &unit) {
+            Some(value) => {
+                readings.insert(key, value);
+            }
+            None => warnings += 1,
+        }
+    };
+    let entries = |section: &str| {
+        document
+            .get(section)
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    };
+
+    for entry in entries("common_list") {
+        let id = entry["id"].as_str().unwrap_or_default();
+        // Feels like is the gateway's own, worked out here from the rest
+        if let Some(&(_, key, _, _)) = COMMON
+            .iter()
+            .find(|&&(known, key, _, _)| known.eq_ignore_ascii_case(id) && key != "feels_like")
+        {
+            take(key.to_string(), &entry["val"], entry.get("unit"));
+        }
+    }
+
+    for (section, prefix) in [("rain", ""), ("piezoRain", "piezo_")] {
+        for entry in entries(section) {
+            let id = entry["id"].as_str().unwrap_or_default();
+            if let Some(&(_, key, _)) = RAIN
+                .iter()
+                .find(|&&(known, _, _)| known.eq_ignore_ascii_case(id))
+            {
+                take(
+                    format!("{}{}", prefix, key),
+                    &entry["val"],
+                    entry.get("unit"),
+                );
+            }
+        }
+    }
+
+    if let Some(wh25) = entries("wh25").first() {
+        for (field, key) in [
+            ("intemp", "intemp"),
+            ("inhumi", "inhumid"),
+            ("abs", "absbarometer"),
+            ("rel", "relbarometer"),
+        ] {
+            if let Some(value) = wh25.get(field) {
+                take(
+                    key.to_string(),
+                    value,
+                    wh25.get("unit").filter(|_| field == "intemp"),
+                );
+            }
+        }
+    }
+
+    for entry in entries("ch_aisle") {
+        let channel = entry["channel"].as_str().and_then(|c| c.parse::<u8>().ok());
+        let Some(channel) = channel.filter(|c| (1..=8).contains(c)) else {
+            continue;
+        };
+        if let Some(temp) = entry.get("temp") {
+            take(format!("temp_ch{}", channel), temp, entry.get("unit"));
+        }
+        if let Some(humidity) = entry.get("humidity") {
+            take(format!("humid_ch{}", channel), humidity, None);
+        }
+    }
+
+    if let Some(co2) = entries("co2").first() {
+        for (field, key) in [
+            ("CO2", "co2"),
+            ("temp", "co2_temp"),
+            ("humidity", "co2_humid"),
+            ("PM25", "co2_pm25"),
+            ("PM10", "co2_pm10"),
+            ("CO2_24H", "co2_24h"),
+            ("battery", "co2_batt"),
+        ] {
+            if let Some(value) = co2.get(field) {
+                take(
+                    key.to_string(),
+                    value,
+                    co2.get("unit").filter(|_| field == "temp"),
+                );
+            }
+        }
+    }
+
+    (readings, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn readings(pairs: &[(&str, f64)]) -> HashMap<String, f64> {
+        pairs.iter().map(|&(k, v)| (k.to_string(), v)).collect()
+    }
+
+    #[test]
+    fn test_livedata_info() {
+        let info = livedata_info(&readings(&[
+            ("outtemp", 20.3),
+            ("outhumid", 68.0),
+            ("dewpoint", 14.2),
+            ("wind_speed", 1.4),
+            ("wind_dir", 270.0),
+            ("light", 12670.0),
+            ("rain_rate", 0.0),
+            ("rain_day", 4.6),
+            ("intemp", 22.1),
+            ("inhumid", 50.0),
+            ("absbarometer", 1008.6),
+            ("relbarometer", 1013.2),
+            ("temp_ch1", 19.8),
+            ("humid_ch1", 67.0),
+            ("temp_ch3", 4.0),
+        ]));
+
+        assert_eq!(
+            info["common_list"],
+            json!([
+                {"id": "0x02", "val": "20.3", "unit": "C"},
+                {"id": "0x07", "val": "68%"},
+                {"id": "3", "val": "20.3", "unit": "C"},
+                {"id": "0x03", "val": "14.2", "unit": "C"},
+                {"id": "0x0B", "val": "1.40 m/s"},
+                {"id": "0x15", "val": "100.00 W/m2"},
+                {"id": "0x0A", "val": "270"},
+            ])
+        );
+        assert_eq!(
+            info["rain"],
+            json!([
+                {"id": "0x0E", "val": "0.0 mm/Hr"},
+                {"id": "0x10", "val": "4.6 mm"},
+            ])
+        );
+        assert!(info.get("piezoRain").is_none());
+        assert_eq!(
+            info["wh25"],
+            json!([{
+                "intemp": "22.1", "unit": "C", "inhumi": "50%",
+                "abs": "1008.6 hPa", "rel": "1013.2 hPa"
+            }])
+        );
+        assert_eq!(info["ch_aisle"][0]["humidity"], "67%");
+        assert_eq!(info["ch_aisle"][1]["channel"], "3");
+        assert!(info["ch_aisle"][1].get("humidity").is_none());
+    }
+
+    #[test]
+    fn test_feels_like() {
+        let cold = readings(&[("outtemp", 2.0), ("windchill", -1.5)]);
+        assert_eq!(feels_like(&cold), Some(-1.5));
+        let hot = readings(&[("outtemp", 31.0), ("heatindex", 33.2)]);
+        assert_eq!(feels_like(&hot), Some(33.2));
+        let mild = readings(&[("outtemp", 18.0), ("windchill", 17.0)]);
+        assert_eq!(feels_like(&mild), Some(18.0));
+        assert_eq!(feels_like(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_piezo_and_co2() {
+        let info = livedata_info(&readings(&[
+            ("piezo_rain_day", 2.5),
+            ("co2", 612.0),
+            ("co2_temp", 21.0),
+            ("co2_humid", 48.0),
+        ]));
+        assert_eq!(info["piezoRain"], json!([{"id": "0x10", "val": "2.5 mm"}]));
+        assert_eq!(
+            info["co2"],
+            json!([{
+                "CO2": "612", "battery": "0", "temp": "21.0",
+                "unit": "C", "humidity": "48%"
+            }])
+        );
+        assert!(info.get("common_list").is_none());
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let metric = readings(&[
+            ("outtemp", 20.3),
+            ("outhumid", 68.0),
+            ("wind_speed", 1.4),
+            ("light", 12670.0),
+            ("rain_day", 4.6),
+            ("piezo_rain_rate", 1.2),
+            ("intemp", 22.1),
+            ("inhumid", 50.0),
+            ("relbarometer", 1013.2),
+            ("temp_ch2", 19.8),
+            ("humid_ch2", 67.0),
+            ("co2", 612.0),
+        ]);
+        let (parsed, warnings) = parse_livedata_info(&livedata_info(&metric));
+        assert_eq!(warnings, 0);
+        assert_eq!(parsed.len(), metric.len() + 1);
+        for (key, value) in &metric {
+            assert!((parsed[key] - value).abs() < 1e-9, "{}", key);
+        }
+        // The CO2 sensor's battery comes back as "0", normal
+        assert_eq!(parsed["co2_batt"], 0.0);
+    }
+
+    #[test]
+    fn test_parse_imperial() {
+        let (parsed, warnings) = parse_livedata_info(&json!({
+            "common_list": [
+                {"id": "0x02", "val": "68.0", "unit": "F"},
+                {"id": "3", "val": "68.0", "unit": "F"},
+                {"id": "0x0B", "val": "2.2 mph"},
+                {"id": "0x0C", "val": "5.4 km/h"},
+                {"id": "0x17", "val": "--"},
+                {"id": "0x15", "val": "12.67 Klux"},
+                {"id": "0x0A", "val": "315"},
+                {"id": "0x6D", "val": "300"},
+                {"id": "0x19", "val": "3 bft"},
+            ],
+            "rain": [{"id": "0x10", "val": "0.12 in"}],
+            "wh25": [{"intemp": "71.6", "unit": "F", "inhumi": "40%", "abs": "29.92 inHg"}],
+        }));
+        assert!((parsed["outtemp"] - 20.0).abs() < 1e-9);
+        assert!((parsed["wind_speed"] - 0.983_488).abs() < 1e-6);
+        assert!((parsed["gust_speed"] - 1.5).abs() < 1e-9);
+        assert_eq!(parsed["light"], 12670.0);
+        assert_eq!(parsed["wind_dir"], 315.0);
+        assert!((parsed["rain_day"] - 3.048).abs() < 1e-9);
+        assert!((parsed["intemp"] - 22.0).abs() < 1e-9);
+        assert_eq!(parsed["inhumid"], 40.0);
+        assert!((parsed["absbarometer"] - 1013.2).abs() < 0.1);
+        // Placeholders, unknown ids and feels like are skipped; Beaufort
+        // can't be turned back into a speed
+        assert!(!parsed.contains_key("uvi"));
+        assert!(!parsed.contains_key("feels_like"));
+        assert!(!parsed.contains_key("day_max_wind"));
+        assert_eq!(parsed.len(), 9);
+        assert_eq!(warnings, 1);
+    }
+}
@@ -0,0 +1,307 @@
+/// The gateway's local HTTP API as a second way to read live data. Newer
+/// GW1100/GW2000/GW3000 firmware serves `/get_livedata_info` on its web
+/// port, which keeps working when the TCP API is turned off in the
+/// WSView Plus app or stops answering.
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::client::{GW1000Client, DEFAULT_MAX_RESPONSE_SIZE};
+use crate::livedata_info::parse_livedata_info;
+
+const LIVEDATA_PATH: &str = "/get_livedata_info";
+
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(16);
+
+/// How live data is read from the gateway (`api` in the config file)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GatewayApi {
+    /// The binary TCP API
+    #[default]
+    Tcp,
+    /// The local HTTP API only, for gateways with the TCP API turned off
+    Http,
+    /// The TCP API, falling back to HTTP for polls it fails
+    Auto,
+}
+
+pub struct LocalHttpClient {
+    ip: String,
+    port: u16,
+    max_response_size: usize,
+    trace_protocol: bool,
+}
+
+impl LocalHttpClient {
+    pub fn new(ip: String, port: u16) -> Self {
+        Self {
+            ip,
+            port,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            trace_protocol: false,
+        }
+    }
+
+    /// Reject response bodies larger than `max` bytes
+    pub fn with_max_response_size(mut self, max: usize) -> Self {
+        self.max_response_size = max;
+        self
+    }
+
+    /// Log each request, its status and parse timings
+    pub fn with_protocol_trace(mut self, enabled: bool) -> Self {
+        self.trace_protocol = enabled;
+        self
+    }
+
+    fn trace(&self, message: impl FnOnce() -> String) {
+        if self.trace_protocol {
+            eprintln!("[DEBUG] {}", message());
+        }
+    }
+
+    /// The body of a GET for `path`. HTTP/1.0, so the gateway sends the body
+    /// as is and closes the connection after it.
+    fn get(&self, path: &str) -> Result<Vec<u8>> {
+        let addr = format!("{}:{}", self.ip, self.port);
+        let mut stream = TcpStream::connect_timeout(&addr.parse()?, SOCKET_TIMEOUT)
+            .context("Failed to connect to the gateway's HTTP API")?;
+        stream.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+        stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+
+        self.trace(|| format!("GET http://{}{}", addr, path));
+        let started = Instant::now();
+        let request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n\r\n",
+            path, self.ip
+        );
+        stream.write_all(request.as_bytes())?;
+
+        // Headers are small; allow a little over the body limit for them
+        let mut response = Vec::new();
+        stream
+            .take((self.max_response_size + 4096) as u64 + 1)
+            .read_to_end(&mut response)?;
+        let (status, body) = split_response(&response)?;
+        self.trace(|| {
+            format!(
+                "HTTP {} ({} bytes in {:?})",
+                status,
+                body.len(),
+                started.elapsed()
+            )
+        });
+        if status != 200 {
+            anyhow::bail!("Gateway HTTP API answered {} for {}", status, path);
+        }
+        if body.len() > self.max_response_size {
+            anyhow::bail!(
+                "Gateway HTTP response exceeds the {} byte limit",
+                self.max_response_size
+            );
+        }
+        Ok(body.to_vec())
+    }
+
+    /// Live data and the number of values that couldn't be converted
+    pub fn get_livedata_with_warnings(&self) -> Result<(HashMap<String, f64>, u32)> {
+        let body = self.get(LIVEDATA_PATH)?;
+        let document: serde_json::Value =
+            serde_json::from_slice(&body).context("Invalid live data JSON from the gateway")?;
+        let started = Instant::now();
+        let (data, warnings) = parse_livedata_info(&document);
+        self.trace(|| {
+            format!(
+                "{} parsed {} fields in {:?} ({} conversion warnings)",
+                LIVEDATA_PATH,
+                data.len(),
+                started.elapsed(),
+                warnings
+            )
+        });
+        if data.is_empty() {
+            anyhow::bail!("No readings in the gateway's {}", LIVEDATA_PATH);
+        }
+        Ok((data, warnings))
+    }
+}
+
+/// The status code and body of a raw HTTP response
+fn split_response(response: &[u8]) -> Result<(u16, &[u8])> {
+    let end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .context("Incomplete HTTP response from the gateway")?;
+    let head = String::from_utf8_lossy(&response[..end]);
+    let status = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .context("Invalid HTTP status line from the gateway")?;
+    if head.lines().any(|line| {
+        line.to_ascii_lowercase()
+            .starts_with("transfer-encoding: chunked")
+    }) {
+        anyhow::bail!("Chunked HTTP responses from the gateway aren't supported");
+    }
+    Ok((status, &response[end + 4..]))
+}
+
+/// Where live data is read from: the TCP API, the HTTP API, or the TCP API
+/// with HTTP as a fallback
+pub struct Gateway {
+    api: GatewayApi,
+    tcp: GW1000Client,
+    http: LocalHttpClient,
+    /// Whether the last poll fell back to HTTP, so each switch is reported
+    /// once
+    on_http: AtomicBool,
+}
+
+impl Gateway {
+    pub fn new(api: GatewayApi, tcp: GW1000Client, http: LocalHttpClient) -> Self {
+        Self {
+            api,
+            tcp,
+            http,
+            on_http: AtomicBool::new(false),
+        }
+    }
+
+    /// Live data and the number of records or values that couldn't be read
+    pub fn get_livedata_with_warnings(&self) -> Result<(HashMap<String, f64>, u32)> {
+        match self.api {
+            GatewayApi::Tcp => self.tcp.get_livedata_with_warnings(),
+            GatewayApi::Http => self.http.get_livedata_with_warnings(),
+            GatewayApi::Auto => match self.tcp.get_livedata_with_warnings() {
+                Ok(poll) => {
+                    if self.on_http.swap(false, Ordering::Relaxed) {
+                        println!("[OK] Reading live data over the TCP API again");
+                    }
+                    Ok(poll)
+                }
+                Err(tcp_error) => {
+                    let poll = self.http.get_livedata_with_warnings().map_err(|e| {
+                        anyhow::anyhow!("TCP API: {:#}; HTTP API: {:#}", tcp_error, e)
+                    })?;
+                    if !self.on_http.swap(true, Ordering::Relaxed) {
+                        eprintln!(
+                            "[WARN] TCP API failed ({:#}); reading live data over HTTP",
+                            tcp_error
+                        );
+                    }
+                    Ok(poll)
+                }
+            },
+        }
+    }
+
+    #[cfg_attr(not(feature = "web"), allow(dead_code))]
+    pub fn get_livedata(&self) -> Result<HashMap<String, f64>> {
+        self.get_livedata_with_warnings().map(|(data, _)| data)
+    }
+
+    /// The TCP client, for what only the TCP API answers (device info and
+    /// sensor signals); None while live data comes over HTTP, so those
+    /// requests don't wait out a timeout on every poll
+    pub fn tcp(&self) -> Option<&GW1000Client> {
+        match self.api {
+            GatewayApi::Tcp => Some(&self.tcp),
+            GatewayApi::Http => None,
+            GatewayApi::Auto => (!self.on_http.load(Ordering::Relaxed)).then_some(&self.tcp),
+        }
+    }
+
+    /// The TCP client for commands only the TCP API has, like setting the
+    /// clock; these are tried even while polls fall back to HTTP
+    pub fn tcp_command(&self) -> Result<&GW1000Client> {
+        match self.api {
+            GatewayApi::Http => anyhow::bail!("Gateway commands need the TCP API (api = \"http\")"),
+            GatewayApi::Tcp | GatewayApi::Auto => Ok(&self.tcp),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// Serve `response` to one connection on a free port
+    fn serve_once(response: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 256];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        port
+    }
+
+    #[test]
+    fn test_split_response() {
+        let (status, body) =
+            split_response(b"HTTP/1.0 200 OK\r\nContent-Type: text/json\r\n\r\n{}").unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body, b"{}");
+        assert!(split_response(b"HTTP/1.1 200 OK\r\n").is_err());
+        assert!(
+            split_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n{}")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_get_livedata() {
+        let port = serve_once(
+            "HTTP/1.0 200 OK\r\nContent-Type: text/json\r\n\r\n\
+             {\"common_list\":[{\"id\":\"0x02\",\"val\":\"12.5\",\"unit\":\"C\"}]}",
+        );
+        let client = LocalHttpClient::new("127.0.0.1".to_string(), port);
+        let (data, warnings) = client.get_livedata_with_warnings().unwrap();
+        assert_eq!(data, HashMap::from([("outtemp".to_string(), 12.5)]));
+        assert_eq!(warnings, 0);
+
+        let port = serve_once("HTTP/1.0 404 Not Found\r\n\r\n");
+        let client = LocalHttpClient::new("127.0.0.1".to_string(), port);
+        assert!(client.get_livedata_with_warnings().is_err());
+    }
+
+    #[test]
+    fn test_auto_falls_back_to_http() {
+        // The TCP side hangs up without answering
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp_port = tcp.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in tcp.incoming() {
+                drop(stream);
+            }
+        });
+        let http_port = serve_once(
+            "HTTP/1.0 200 OK\r\n\r\n{\"wh25\":[{\"intemp\":\"21.0\",\"unit\":\"C\",\"inhumi\":\"45%\"}]}",
+        );
+        let gateway = Gateway::new(
+            GatewayApi::Auto,
+            GW1000Client::new("127.0.0.1".to_string(), tcp_port),
+            LocalHttpClient::new("127.0.0.1".to_string(), http_port),
+        );
+        assert!(gateway.tcp().is_some());
+        let (data, _) = gateway.get_livedata_with_warnings().unwrap();
+        assert_eq!(data["inhumid"], 45.0);
+        assert!(gateway.tcp().is_none());
+    }
+}
@@ -1,24 +1,135 @@
+// Builds without some features leave parts of the shared modules unused
+#![cfg_attr(
+    not(all(feature = "db", feature = "web", feature = "http", feature = "mqtt")),
+    allow(dead_code)
+)]
+
+mod air_quality;
+mod annotations;
+#[cfg(feature = "web")]
+mod auth;
+#[cfg(all(feature = "db", feature = "http"))]
+mod backfill;
+mod backup;
+#[cfg(any(feature = "db", feature = "http"))]
+mod batch;
+mod battery;
+#[cfg(any(feature = "db", feature = "mqtt", feature = "http"))]
+mod breaker;
+mod burst;
+mod calibration;
 mod client;
+#[cfg(feature = "db")]
+mod clock_skew;
+mod compare;
 mod config;
+mod control;
+mod cron;
+#[cfg(feature = "db")]
 mod database;
 mod decoder;
+mod degree_days;
+mod derived;
+mod device;
+mod device_queue;
+#[cfg(any(feature = "db", feature = "http"))]
+mod dns;
+mod doctor;
+mod events;
+#[cfg(all(feature = "web", feature = "http"))]
+mod federation;
+#[cfg(feature = "web")]
+mod feed;
+mod fixed;
+#[cfg(feature = "web")]
+mod geojson;
+#[cfg(feature = "mqtt")]
+mod ha_blueprint;
+#[cfg(feature = "http")]
+mod heartbeat;
+mod history;
+#[cfg(feature = "http")]
 mod http_output;
+#[cfg(feature = "db")]
+mod import;
+mod livedata_info;
+mod local_api;
+#[cfg(feature = "db")]
+mod metrics;
+#[cfg(feature = "mqtt")]
 mod mqtt;
+#[cfg(feature = "mqtt")]
+mod mqtt_presets;
 mod output;
+#[cfg(feature = "db")]
+mod pg_copy;
 mod protocol;
+mod provenance;
+#[cfg(any(feature = "http", feature = "mqtt"))]
+mod proxy;
+#[cfg(feature = "web")]
+mod public;
+#[cfg(feature = "web")]
+mod realtime;
+#[cfg(feature = "db")]
+mod rollup;
+mod schedule;
+mod secrets;
+mod sensor_watch;
+mod sensors;
+mod state;
+mod station;
+mod status;
+mod storage;
+mod units;
+mod vectors;
+#[cfg(feature = "db")]
+mod wal;
+#[cfg(feature = "web")]
 mod web;
+mod wind;
 
-use anyhow::Result;
-use chrono::Utc;
+use anyhow::{Context, Result};
+#[cfg(all(feature = "db", feature = "http"))]
+use chrono::{DateTime, TimeDelta};
+use chrono::{Local, Utc};
 use clap::Parser;
-use std::time::Duration;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
 
-use client::GW1000Client;
-use config::Args;
+use air_quality::AirQualityAverager;
+use battery::BatteryTracker;
+use burst::BurstPolling;
+use client::{AsyncGW1000Client, GW1000Client};
+use config::{Args, Command, DeviceCommand, SecretCommand};
+use control::Controls;
+use cron::{ScheduleRule, ScheduledTask};
+#[cfg(feature = "db")]
 use database::DatabaseWriter;
+use degree_days::{DegreeDayAccumulator, SharedDegreeDays};
+use derived::{rain_intensity_description, RainEvent, RainWatch};
+use device_queue::{DeviceQueue, Priority};
+use doctor::Outcome;
+use events::EventKind;
+use fixed::FixedReadings;
+#[cfg(feature = "http")]
 use http_output::HttpPublisher;
+use local_api::{Gateway, GatewayApi, LocalHttpClient};
+#[cfg(feature = "db")]
+use metrics::MetricsRecorder;
+#[cfg(feature = "mqtt")]
 use mqtt::MqttPublisher;
-use output::print_livedata;
+use output::{ColorChoice, LiveTable};
+use protocol::ResponseError;
+use provenance::{Provenance, Quality};
+use sensor_watch::{SensorEvent, SensorWatch};
+use state::{PersistentState, StateStore};
+use status::{DaemonStatus, SharedStatus};
+#[cfg(feature = "db")]
+use wal::WriteAheadLog;
+#[cfg(feature = "web")]
 use web::{run_web_server_background, WebServerConfig};
 
 #[tokio::main]
@@ -27,17 +138,85 @@ async fn main() -> Result<()> {
 
     // Handle database table creation mode
     if args.db_create_table {
-        let db_config = args.get_database_config()?.ok_or_else(|| {
-            anyhow::anyhow!(
-                "Database configuration required. Add [database] section to config file."
-            )
-        })?;
+        #[cfg(feature = "db")]
+        return run_create_table_command(&args).await;
+        #[cfg(not(feature = "db"))]
+        return Err(feature_disabled("--db-create-table", "db"));
+    }
 
-        println!("Creating database table...");
-        let writer = DatabaseWriter::new(&db_config).await?;
-        writer.create_table().await?;
-        println!("[OK] Table '{}' created successfully", db_config.table_name);
-        return Ok(());
+    // Commands run once and exit; without one, poll continuously
+    match args.command {
+        #[cfg(all(feature = "db", feature = "http"))]
+        Some(Command::Backfill { ref start, ref end }) => {
+            return run_backfill_command(&args, start, end.as_deref()).await;
+        }
+        #[cfg(not(all(feature = "db", feature = "http")))]
+        Some(Command::Backfill { .. }) => return Err(feature_disabled("backfill", "db and http")),
+        #[cfg(feature = "db")]
+        Some(Command::Import {
+            ref from,
+            ref format,
+            ref units,
+        }) => return run_import_command(&args, from, format, units).await,
+        #[cfg(not(feature = "db"))]
+        Some(Command::Import { .. }) => return Err(feature_disabled("import", "db")),
+        #[cfg(feature = "http")]
+        Some(Command::Status {
+            ref url,
+            json,
+            ref token,
+            events,
+        }) => {
+            return run_status_command(&args, url.as_deref(), json, token.as_deref(), events).await
+        }
+        #[cfg(not(feature = "http"))]
+        Some(Command::Status { .. }) => return Err(feature_disabled("status", "http")),
+        Some(Command::Doctor) => return run_doctor_command(&args).await,
+        #[cfg(feature = "http")]
+        Some(Command::Maintenance {
+            ref state,
+            ref url,
+            ref token,
+        }) => {
+            return run_maintenance_command(&args, state == "on", url.as_deref(), token.as_deref())
+                .await
+        }
+        #[cfg(not(feature = "http"))]
+        Some(Command::Maintenance { .. }) => {
+            return Err(feature_disabled("maintenance", "http"));
+        }
+        #[cfg(feature = "db")]
+        Some(Command::History {
+            ref graph,
+            ref last,
+            width,
+            height,
+        }) => return run_history_command(&args, graph, last, width, height).await,
+        #[cfg(not(feature = "db"))]
+        Some(Command::History { .. }) => return Err(feature_disabled("history", "db")),
+        #[cfg(feature = "mqtt")]
+        Some(Command::Blueprint { ref kind }) => return run_blueprint_command(&args, kind),
+        #[cfg(not(feature = "mqtt"))]
+        Some(Command::Blueprint { .. }) => return Err(feature_disabled("blueprint", "mqtt")),
+        #[cfg(feature = "db")]
+        Some(Command::AddPartitions { months }) => {
+            return run_add_partitions_command(&args, months).await;
+        }
+        #[cfg(not(feature = "db"))]
+        Some(Command::AddPartitions { .. }) => {
+            return Err(feature_disabled("add-partitions", "db"));
+        }
+        Some(Command::Discover { timeout }) => return run_discover_command(timeout),
+        Some(Command::Device { ref action }) => return run_device_command(&args, action),
+        Some(Command::Secret { ref action }) => return run_secret_command(&args, action),
+        Some(Command::Backup {
+            ref output,
+            with_database,
+        }) => return run_backup_command(&args, output.as_deref(), with_database).await,
+        Some(Command::Restore { ref archive, force }) => {
+            return run_restore_command(&args, archive, force).await;
+        }
+        None => {}
     }
 
     // Get connection info from args or config
@@ -49,14 +228,38 @@ async fn main() -> Result<()> {
         }
     };
 
-    let client = GW1000Client::new(ip.clone(), port);
+    let max_response_size = args.get_max_response_size()?;
+    let (api, http_port) = args.get_gateway_api()?;
+    let gateway = Gateway::new(
+        api,
+        GW1000Client::new(ip.clone(), port)
+            .with_max_response_size(max_response_size)
+            .with_protocol_trace(args.trace_protocol)
+            .with_keep_alive(args.get_keep_alive()?)
+            .with_retry(args.get_retry_policy()?),
+        LocalHttpClient::new(ip.clone(), http_port)
+            .with_max_response_size(max_response_size)
+            .with_protocol_trace(args.trace_protocol),
+    );
+    // Everything that talks to the gateway takes turns through this queue
+    let device = DeviceQueue::start(gateway);
+    init_network(&args)?;
+
+    // Sections for outputs left out of this build are ignored, not fatal
+    for (section, feature) in args.get_disabled_sections()? {
+        eprintln!(
+            "[WARN] Ignoring [{}]: wxlistener was built without the `{}` feature",
+            section, feature
+        );
+    }
 
     // Initialize database writer if configured
+    #[cfg(feature = "db")]
     let db_writer = if let Some(db_config) = args.get_database_config()? {
         match DatabaseWriter::new(&db_config).await {
             Ok(writer) => {
                 println!("[OK] Connected to database and table verified");
-                Some(writer)
+                Some(Arc::new(writer))
             }
             Err(e) => {
                 eprintln!("[ERROR] Database connection failed: {}", e);
@@ -68,14 +271,90 @@ async fn main() -> Result<()> {
         None
     };
 
+    // Warn when the database clock disagrees with ours
+    #[cfg(feature = "db")]
+    if let (Some(ref writer), Some(max_skew)) = (
+        &db_writer,
+        args.get_database_config()?
+            .map(|config| config.max_clock_skew)
+            .filter(|secs| *secs > 0),
+    ) {
+        clock_skew::run_clock_check_background(
+            Arc::clone(writer),
+            chrono::TimeDelta::seconds(max_skew as i64),
+        );
+    }
+
+    // Readings pass through a write-ahead log on their way to the database
+    #[cfg(feature = "db")]
+    let mut db_wal = match args
+        .get_database_config()?
+        .and_then(|config| config.wal_path)
+    {
+        Some(path) if db_writer.is_some() => match WriteAheadLog::open(&path) {
+            Ok(wal) => {
+                if !wal.pending().is_empty() {
+                    println!(
+                        "[WAL] {} readings from a previous run waiting for the database",
+                        wal.pending().len()
+                    );
+                }
+                Some(wal)
+            }
+            Err(e) => {
+                eprintln!("[ERROR] Failed to open WAL: {:#}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
+    // Inserts grouped into transactions if configured
+    #[cfg(feature = "db")]
+    let db_batch_policy = match args.get_database_config()? {
+        Some(config) if db_writer.is_some() => batch::BatchPolicy::from_config(&config)?,
+        _ => None,
+    };
+    #[cfg(feature = "db")]
+    let mut db_batch = db_batch_policy
+        .filter(|_| db_wal.is_none())
+        .map(batch::InsertBatch::new);
+
+    // Outputs that keep failing are left alone for a while
+    #[cfg(any(feature = "db", feature = "mqtt", feature = "http"))]
+    let breaker_config = args.get_breaker_config()?;
+    #[cfg(feature = "db")]
+    let mut db_breaker = breaker::CircuitBreaker::from_config("Database", &breaker_config);
+    #[cfg(feature = "mqtt")]
+    let mut mqtt_breaker = breaker::CircuitBreaker::from_config("MQTT", &breaker_config);
+
     // Initialize MQTT publisher if configured
+    #[cfg(feature = "mqtt")]
     let mqtt_publisher = if let Some(mqtt_config) = args.get_mqtt_config()? {
-        match MqttPublisher::new(&mqtt_config).await {
+        let publisher = match MqttPublisher::new(&mqtt_config).await {
+            Ok(publisher) if publisher.needs_mac() => device
+                .run(Priority::Poll, |gateway| {
+                    gateway
+                        .tcp()
+                        .ok_or_else(|| anyhow::anyhow!("api = \"http\" leaves the TCP API unused"))
+                        .and_then(GW1000Client::get_mac_address)
+                })
+                .await
+                .map_err(|e| {
+                    e.context("Failed to read the gateway MAC for {mac} in the MQTT topic")
+                })
+                .map(|mac| publisher.with_mac(&mac)),
+            result => result,
+        };
+        match publisher {
             Ok(publisher) => {
                 println!(
                     "[OK] Connected to MQTT broker (topic: {})",
                     publisher.topic()
                 );
+                if let Some(topic) = publisher.availability_topic() {
+                    println!("[OK] MQTT availability on {}", topic);
+                }
                 Some(publisher)
             }
             Err(e) => {
@@ -89,11 +368,12 @@ async fn main() -> Result<()> {
     };
 
     // Initialize HTTP publisher if configured
+    #[cfg(feature = "http")]
     let http_publisher = if let Some(http_config) = args.get_http_config()? {
         match HttpPublisher::new(&http_config).await {
             Ok(publisher) => {
                 println!("[OK] HTTP endpoint configured (url: {})", publisher.url());
-                Some(publisher)
+                Some(publisher.with_circuit_breaker(&breaker_config))
             }
             Err(e) => {
                 eprintln!("[ERROR] HTTP configuration failed: {}", e);
@@ -105,6 +385,117 @@ async fn main() -> Result<()> {
         None
     };
 
+    let mut calibration = args.get_calibration_config()?;
+    let mut derived_config = args.get_derived_config()?;
+    let station = args.get_station_config()?;
+    let station_time = station.time_zone()?;
+    let alignment = args.get_timestamp_alignment()?;
+    let poll_profiles = args.get_poll_profiles()?;
+    let mut burst = args.get_burst_config()?.map(BurstPolling::new);
+    let precision = args.get_precision_config()?;
+    let fixed_point = args.get_fixed_point()?;
+    let units = args.get_units()?;
+    let mut live_table = LiveTable::new(ColorChoice::parse(&args.color)?.enabled(), units);
+    let daemon_status = Arc::new(Mutex::new(DaemonStatus::new(Utc::now())));
+    daemon_status.lock().await.set_station(station.clone());
+    let controls = Arc::new(Controls::with_device_queue(device.clone()));
+    let mut sensor_watch = SensorWatch::new(args.get_sensor_watch_config()?);
+    let mut battery_tracker = BatteryTracker::new(args.get_battery_config()?);
+    let mut rain_watch = RainWatch::default();
+    let mut air_quality = AirQualityAverager::new();
+
+    // Initialize degree day tracking if configured
+    let degree_days = args
+        .get_degree_day_config()?
+        .map(|dd_config| Arc::new(Mutex::new(DegreeDayAccumulator::new(dd_config))));
+
+    // Restore aggregation state saved by a previous run
+    let state_store = args.get_state_file()?.map(StateStore::new);
+    if let Some(ref store) = state_store {
+        match store.load() {
+            Ok(state) => {
+                if let (Some(ref acc), Some(dd_state)) = (&degree_days, state.degree_days) {
+                    acc.lock().await.restore(dd_state);
+                }
+                if let Some(sensor_state) = state.sensors {
+                    sensor_watch.restore(sensor_state);
+                }
+                if let Some(battery_state) = state.battery {
+                    battery_tracker.restore(battery_state);
+                }
+                if let Some(events) = state.events {
+                    daemon_status.lock().await.restore_events(events);
+                }
+                if let Some(settings) = state.settings {
+                    if let Err(e) = daemon_status.lock().await.update_settings(&settings) {
+                        eprintln!("[WARN] Ignoring saved settings: {:#}", e);
+                    }
+                }
+                println!("[OK] State restored from {:?}", store.path());
+            }
+            Err(e) => {
+                eprintln!("[WARN] Could not restore state, starting fresh: {:#}", e);
+            }
+        }
+    }
+
+    #[cfg(feature = "db")]
+    if let Some(ref writer) = db_writer {
+        if let Err(e) = writer.create_annotations_table().await {
+            eprintln!("[ERROR] Failed to create annotations table: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    #[cfg(feature = "db")]
+    if let (Some(_), Some(ref writer)) = (&degree_days, &db_writer) {
+        if let Err(e) = writer.create_summary_table().await {
+            eprintln!("[ERROR] Failed to create summary table: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // Hourly and daily rollups for long history queries
+    #[cfg(feature = "db")]
+    if let (Some(ref writer), true) = (
+        &db_writer,
+        args.get_database_config()?
+            .is_some_and(|config| config.rollups),
+    ) {
+        match writer.prepare_rollups().await {
+            Ok(true) => rollup::run_rollup_background(Arc::clone(writer)),
+            Ok(false) => println!("[OK] Rollups kept as TimescaleDB continuous aggregates"),
+            Err(e) => {
+                eprintln!("[ERROR] Failed to prepare rollup tables: {:#}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Readings kept for history queries: [database], or [storage] without one
+    #[cfg_attr(not(feature = "db"), allow(unused_mut))]
+    let mut local_storage = args.get_storage_config()?.map(|config| config.open());
+    #[cfg(feature = "db")]
+    if db_writer.is_some() && local_storage.take().is_some() {
+        eprintln!("[WARN] Ignoring [storage]: readings are kept in [database]");
+    }
+
+    // Record poll and output metrics next to the weather data if configured
+    #[cfg(feature = "db")]
+    let mut pipeline_metrics = None;
+    #[cfg(feature = "db")]
+    if let (Some(ref writer), Some(secs)) = (
+        &db_writer,
+        args.get_database_config()?
+            .and_then(|config| config.metrics_interval),
+    ) {
+        if let Err(e) = writer.create_metrics_table().await {
+            eprintln!("[ERROR] Failed to create metrics table: {}", e);
+            std::process::exit(1);
+        }
+        pipeline_metrics = Some(MetricsRecorder::new(secs));
+    }
+
     println!("============================================================");
     println!("GW1000/Ecowitt Gateway Weather Station Listener");
     println!("============================================================");
@@ -113,90 +504,1642 @@ async fn main() -> Result<()> {
 
     // Get device info
     println!("--- Device Information ---");
-    match client.get_firmware_version() {
-        Ok(version) => println!("[OK] Firmware Version: {}", version),
-        Err(e) => println!("[ERROR] Failed to get firmware: {}", e),
-    }
+    // Kept for the provenance of every reading
+    let firmware = match device
+        .run(Priority::Poll, |gateway| {
+            Ok(gateway.tcp().map(GW1000Client::get_firmware_version))
+        })
+        .await
+        .unwrap_or_else(|e| Some(Err(e)))
+    {
+        Some(Ok(version)) => {
+            println!("[OK] Firmware Version: {}", version);
+            Some(version)
+        }
+        Some(Err(e)) => {
+            println!("[ERROR] Failed to get firmware: {}", e);
+            None
+        }
+        None => None,
+    };
 
-    match client.get_mac_address() {
-        Ok(mac) => println!("[OK] MAC Address: {}", mac),
-        Err(e) => println!("[ERROR] Failed to get MAC: {}", e),
-    }
+    let station_mac = match device
+        .run(Priority::Poll, |gateway| {
+            Ok(gateway.tcp().map(GW1000Client::get_mac_address))
+        })
+        .await
+        .unwrap_or_else(|e| Some(Err(e)))
+    {
+        Some(Ok(mac)) => {
+            println!("[OK] MAC Address: {}", mac);
+            Some(mac)
+        }
+        Some(Err(e)) => {
+            println!("[ERROR] Failed to get MAC: {}", e);
+            None
+        }
+        None => {
+            println!("[INFO] Live data over the HTTP API; firmware and MAC need the TCP API");
+            None
+        }
+    };
 
     // Continuous mode (default)
     println!(
         "\n--- Continuous Mode (every {} seconds) ---",
         args.continuous
     );
+    #[cfg(feature = "db")]
     if db_writer.is_some() {
         println!("Database logging: ENABLED");
     }
+    #[cfg(feature = "mqtt")]
     if mqtt_publisher.is_some() {
         println!("MQTT publishing: ENABLED");
     }
+    #[cfg(feature = "http")]
     if http_publisher.is_some() {
         println!("HTTP publishing: ENABLED");
     }
+    for profile in &poll_profiles {
+        println!(
+            "Poll profile: every {} seconds from {} to {}",
+            profile.interval,
+            profile.start.format("%H:%M"),
+            profile.end.format("%H:%M")
+        );
+    }
+    if burst.is_some() {
+        println!("Burst polling: ENABLED");
+    }
+    if local_storage.is_some() {
+        println!("Local storage: ENABLED");
+    }
+    if degree_days.is_some() {
+        println!("Degree day tracking: ENABLED");
+    }
+    #[cfg(feature = "db")]
+    if pipeline_metrics.is_some() {
+        println!("Pipeline metrics: ENABLED");
+    }
+
+    // Federation: send readings to peers, receive theirs on the web server
+    #[cfg(all(feature = "web", feature = "http"))]
+    let (federation, peer_readings) = match args.get_federation_config()? {
+        Some(config) => {
+            println!("Federation: ENABLED ({} peers)", config.peers.len());
+            if !args.web {
+                eprintln!("[WARN] [federation] needs --web to receive readings from peers");
+            }
+            (
+                Some(federation::Federation::new(&config)?),
+                Some(Arc::new(Mutex::new(federation::PeerReadings::new(&config)))),
+            )
+        }
+        None => (None, None),
+    };
 
     // Start web server in background if enabled
     if args.web {
-        let web_config = WebServerConfig {
-            ip: args.web_host.clone(),
-            port: args.web_port,
-            interval: args.continuous,
-        };
-        run_web_server_background(web_config, ip.clone(), port);
+        #[cfg(feature = "web")]
+        {
+            let web = args.get_web_config()?;
+            #[cfg(feature = "db")]
+            let history_storage = local_storage.clone().or_else(|| {
+                db_writer
+                    .clone()
+                    .map(|writer| writer as storage::SharedStorage)
+            });
+            #[cfg(not(feature = "db"))]
+            let history_storage = local_storage.clone();
+            // Other stations' tables in the same database
+            #[cfg(feature = "db")]
+            let stations = match (&db_writer, args.get_database_config()?) {
+                (Some(writer), Some(db_config)) => db_config
+                    .stations
+                    .iter()
+                    .map(|(name, station)| {
+                        let reader = writer.for_table(station.table())?;
+                        Ok((name.clone(), Arc::new(reader) as storage::SharedStorage))
+                    })
+                    .collect::<Result<_>>()?,
+                _ => Default::default(),
+            };
+            #[cfg(not(feature = "db"))]
+            let stations = Default::default();
+            #[cfg(feature = "db")]
+            let station_locations = match args.get_database_config()? {
+                Some(db_config) => db_config
+                    .stations
+                    .iter()
+                    .map(|(name, other)| {
+                        let location = other.station();
+                        location
+                            .validate()
+                            .with_context(|| format!("Invalid [database.stations] {}", name))?;
+                        Ok((name.clone(), location))
+                    })
+                    .collect::<Result<_>>()?,
+                None => Default::default(),
+            };
+            #[cfg(not(feature = "db"))]
+            let station_locations = Default::default();
+            let web_config = WebServerConfig {
+                listen: web.listen.clone(),
+                socket_mode: web.socket_mode,
+                interval: args.continuous,
+                degree_days: degree_days.clone(),
+                calibration: calibration.clone(),
+                derived: derived_config.clone(),
+                status: Some(daemon_status.clone()),
+                max_response_size,
+                trace_protocol: args.trace_protocol,
+                api,
+                http_port,
+                alignment,
+                poll_profiles: poll_profiles.clone(),
+                precision: precision.clone(),
+                units,
+                fixed_point,
+                storage: history_storage,
+                stations,
+                station: station.clone(),
+                station_locations,
+                tokens: web.tokens.clone(),
+                controls: Some(controls.clone()),
+                realtime_txt: web.realtime_txt.clone(),
+                clientraw_txt: web.clientraw_txt.clone(),
+                station_name: web.station_name.clone(),
+                station_time,
+                public: web.public.clone(),
+                #[cfg(feature = "db")]
+                database: db_writer.clone(),
+                #[cfg(feature = "http")]
+                peers: peer_readings.clone(),
+                device: Some(device.clone()),
+            };
+            run_web_server_background(web_config, ip.clone(), port);
+            for addr in &web.listen {
+                println!("Web server: ENABLED ({})", addr);
+            }
+        }
+        #[cfg(not(feature = "web"))]
+        eprintln!("[WARN] Ignoring --web: wxlistener was built without the `web` feature");
+    }
+
+    // Start the control socket if configured
+    if let Some(socket_path) = args.get_control_socket()? {
+        println!("Control socket: ENABLED ({:?})", socket_path);
+        control::run_control_socket_background(
+            socket_path,
+            controls.clone(),
+            daemon_status.clone(),
+        );
+    }
+
+    // Start scheduled maintenance tasks if configured
+    let schedules = args.get_schedules()?;
+    if !schedules.is_empty() {
+        println!("Scheduled tasks: ENABLED ({})", schedules.len());
+        run_scheduler_background(args.clone(), schedules, device.clone());
+    }
+
+    // Ping external monitors while the pipeline is healthy
+    #[cfg(feature = "http")]
+    if let Some(heartbeat_config) = args.get_heartbeat_config()? {
         println!(
-            "Web server: ENABLED (http://{}:{})",
-            args.web_host, args.web_port
+            "Heartbeat: ENABLED ({} URL(s) every {}s)",
+            heartbeat_config.urls.len(),
+            heartbeat_config.interval
         );
+        heartbeat::run_heartbeat_background(heartbeat_config, daemon_status.clone())?;
     }
 
     println!("Press Ctrl+C to stop\n");
+    daemon_status.lock().await.log_event(
+        EventKind::Started,
+        format!(
+            "wxlistener {} started, polling {}:{}",
+            env!("CARGO_PKG_VERSION"),
+            ip,
+            port
+        ),
+    );
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    // Start on an interval boundary so readings line up with the schedule
+    if alignment.is_aligned() {
+        let interval = daemon_status
+            .lock()
+            .await
+            .poll_interval()
+            .unwrap_or_else(|| {
+                schedule::poll_interval(&poll_profiles, Local::now().time(), args.continuous)
+            });
+        tokio::time::sleep(schedule::until_next_boundary(Utc::now(), interval)).await;
+    }
 
     loop {
-        match client.get_livedata() {
-            Ok(data) => {
-                let timestamp = Utc::now();
+        // An interval set through the settings API overrides the schedule
+        let interval = daemon_status
+            .lock()
+            .await
+            .poll_interval()
+            .unwrap_or_else(|| {
+                schedule::poll_interval(&poll_profiles, Local::now().time(), args.continuous)
+            });
+        // Rapid weather changes shorten it for a while
+        let interval = match burst.as_mut() {
+            Some(burst) => {
+                let was_bursting = burst.is_bursting();
+                let interval = burst.interval(interval, Utc::now());
+                if was_bursting && !burst.is_bursting() {
+                    println!("[BURST] Back to polling every {} seconds", interval);
+                }
+                interval
+            }
+            None => interval,
+        };
+
+        if controls.take_settings_changed() {
+            if let Some(ref store) = state_store {
+                save_state(
+                    store,
+                    &sensor_watch,
+                    &battery_tracker,
+                    &degree_days,
+                    &daemon_status,
+                )
+                .await;
+            }
+        }
+
+        if controls.take_reload() {
+            match (args.get_calibration_config(), args.get_derived_config()) {
+                (Ok(new_calibration), Ok(new_derived)) => {
+                    calibration = new_calibration;
+                    derived_config = new_derived;
+                    println!("[OK] Configuration reloaded");
+                    daemon_status
+                        .lock()
+                        .await
+                        .log_event(EventKind::ConfigReloaded, "Configuration reloaded");
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!(
+                        "[ERROR] Reload failed, keeping current configuration: {:#}",
+                        e
+                    );
+                    daemon_status.lock().await.log_event(
+                        EventKind::ReloadFailed,
+                        format!("Reload failed, keeping current configuration: {:#}", e),
+                    );
+                }
+            }
+        }
+
+        #[cfg(feature = "db")]
+        let poll_started = std::time::Instant::now();
+        let poll = device
+            .run(Priority::Poll, Gateway::get_livedata_with_warnings)
+            .await;
+        #[cfg(feature = "db")]
+        if let Some(ref mut recorder) = pipeline_metrics {
+            let finished = recorder.record_poll(Utc::now(), poll_started.elapsed(), poll.is_ok());
+            write_metrics(finished, &db_writer).await;
+        }
+
+        match poll {
+            Ok((mut data, decode_warnings)) => {
+                let timestamp = alignment.align(Utc::now(), interval);
+                let mut provenance =
+                    Provenance::poll(station_mac.clone(), firmware.clone(), decode_warnings);
+
+                // Alert when a sensor stops reporting (dead battery, out of range)
+                let sensor_events = sensor_watch.observe(&data, timestamp);
+                let mut status = daemon_status.lock().await;
+                // Readings taken while the sensors are being worked on are
+                // flagged, and the alerts they would raise held back
+                let maintenance = status.in_maintenance();
+                if maintenance {
+                    provenance.quality = Quality::Suspect;
+                }
+                for event in sensor_events {
+                    match event {
+                        SensorEvent::Missing { field, last_seen } => {
+                            let message = format!(
+                                "Sensor '{}' missing from live data (last seen {})",
+                                field,
+                                last_seen.format("%Y-%m-%d %H:%M:%S UTC")
+                            );
+                            if maintenance {
+                                println!("[MAINTENANCE] Suppressed alert: {}", message);
+                                continue;
+                            }
+                            eprintln!("[ALERT] {}", message);
+                            status.log_event(EventKind::SensorMissing, message);
+                        }
+                        SensorEvent::Restored {
+                            field,
+                            missing_since,
+                        } => {
+                            let message = format!(
+                                "Sensor '{}' reporting again (missing since {})",
+                                field,
+                                missing_since.format("%Y-%m-%d %H:%M:%S UTC")
+                            );
+                            println!("[OK] {}", message);
+                            status.log_event(EventKind::SensorRestored, message);
+                        }
+                    }
+                }
+                status.set_missing_sensors(sensor_watch.missing());
+                drop(status);
+
+                // Signal quality comes from a separate request; a failure here
+                // shouldn't drop the poll
+                let signals = device
+                    .run(Priority::Sensors, |gateway| {
+                        Ok(gateway.tcp().map(GW1000Client::get_sensor_signals))
+                    })
+                    .await
+                    .unwrap_or_else(|e| Some(Err(e)));
+                match signals {
+                    None => {}
+                    Some(Ok(mut signals)) => {
+                        sensors::add_signal_fields(&mut data, &signals);
+                        sensors::attach_voltages(&mut signals, &data);
+                        // Warn weeks before a battery runs out, not when the
+                        // sensor goes missing
+                        let warnings = battery_tracker.observe(&signals, timestamp);
+                        let mut status = daemon_status.lock().await;
+                        for warning in warnings {
+                            let message = format!(
+                                "Sensor '{}' battery predicted empty in about {:.0} weeks ({})",
+                                warning.sensor,
+                                warning.weeks_to_empty,
+                                warning.unit.format(warning.level)
+                            );
+                            eprintln!("[ALERT] {}", message);
+                            status.log_event(EventKind::BatteryWarning, message);
+                        }
+                        status.set_battery_forecasts(battery_tracker.forecasts());
+                        status.set_sensor_signals(signals);
+                    }
+                    Some(Err(e)) => eprintln!("[WARN] Sensor signal read error: {}", e),
+                }
+
+                if let Some(ref calibration) = calibration {
+                    calibration.apply(&mut data);
+                }
+                // The gateway reports tenths; snap calibrated values back to them
+                if fixed_point {
+                    data = FixedReadings::from_readings(&data).to_readings();
+                }
+                air_quality.add_averages(&mut data, timestamp);
+                derived::add_derived_fields(&mut data, &derived_config);
+                let rain_event = rain_watch.observe(&data);
+                if let Some(reason) = burst.as_mut().and_then(|b| b.observe(&data, timestamp)) {
+                    println!("[BURST] Polling faster: {}", reason);
+                }
+                // Published values are rounded; the database keeps full precision
+                let rounded = precision.rounded(&data);
+                let mut status = daemon_status.lock().await;
+                status.record_poll(&rounded, timestamp);
+                status.set_provenance(provenance.clone());
+                // Rain starting, stopping or changing intensity, e.g. to close awnings
+                if let Some(event) = rain_event {
+                    let (kind, message) = match event {
+                        RainEvent::Started(to) => (
+                            EventKind::RainStarted,
+                            format!("Rain started ({})", rain_intensity_description(to)),
+                        ),
+                        RainEvent::Stopped => (EventKind::RainStopped, "Rain stopped".to_string()),
+                        RainEvent::IntensityChanged { from, to } => (
+                            EventKind::RainIntensityChanged,
+                            format!(
+                                "Rain changed from {} to {}",
+                                rain_intensity_description(from),
+                                rain_intensity_description(to)
+                            ),
+                        ),
+                    };
+                    if maintenance {
+                        println!("[MAINTENANCE] Suppressed alert: {}", message);
+                    } else {
+                        println!("[ALERT] {}", message);
+                        status.log_event(kind, message);
+                    }
+                }
+                drop(status);
+
+                // Accumulate degree days and store each completed day
+                if let (Some(ref acc), Some(&outtemp)) = (&degree_days, data.get("outtemp")) {
+                    let date = station_time.naive_local(timestamp).date();
+                    #[cfg_attr(not(feature = "db"), allow(unused_variables))]
+                    let finished = acc.lock().await.add_sample(date, outtemp);
+                    #[cfg(feature = "db")]
+                    if let (Some(day), Some(ref writer)) = (finished, &db_writer) {
+                        if let Err(e) = writer.upsert_daily_summary(&day).await {
+                            eprintln!("[ERROR] Daily summary write error: {}", e);
+                        }
+                    }
+                }
+
+                // Persist aggregation state so a restart doesn't reset today
+                if let Some(ref store) = state_store {
+                    save_state(
+                        store,
+                        &sensor_watch,
+                        &battery_tracker,
+                        &degree_days,
+                        &daemon_status,
+                    )
+                    .await;
+                }
+
+                let status = daemon_status.lock().await;
+                let paused = status.paused();
+                if !paused.is_empty() {
+                    println!("[PAUSED] Skipping paused outputs: {}", paused.join(", "));
+                }
+                if maintenance {
+                    println!("[MAINTENANCE] Readings flagged as suspect");
+                }
+                #[cfg(feature = "db")]
+                let db_active = status.is_active("database");
+                #[cfg(feature = "mqtt")]
+                let mqtt_active = status.is_active("mqtt");
+                #[cfg(feature = "http")]
+                let http_active = status.is_active("http");
+                drop(status);
 
                 // Write to database if configured
-                if let Some(ref writer) = db_writer {
-                    if let Err(e) = writer.insert_data(&data, &timestamp).await {
-                        eprintln!("[ERROR] Database write error: {}", e);
-                        eprintln!("  Cannot continue with database configuration.");
+                #[cfg(feature = "db")]
+                if let (Some(writer), Some(wal)) =
+                    (db_writer.as_ref().filter(|_| db_active), db_wal.as_mut())
+                {
+                    // Outages only hold readings back, so they don't stop
+                    // the daemon
+                    if let Err(e) = wal.append(timestamp, &data, &provenance) {
+                        eprintln!("[ERROR] WAL write error: {:#}", e);
                         std::process::exit(1);
                     }
+                    // While the breaker is open, readings wait in the WAL
+                    if db_breaker.allows() {
+                        let backlog = wal.pending().len();
+                        let due = match (db_batch_policy, wal.oldest()) {
+                            (Some(policy), Some(oldest)) => {
+                                policy.is_due(backlog, oldest, Utc::now())
+                            }
+                            _ => true,
+                        };
+                        let result = if !due {
+                            Ok(())
+                        } else {
+                            let delivered = wal.deliver(writer, wal::REPLAY_BATCH).await;
+                            db_breaker.record(&delivered);
+                            match delivered {
+                                Ok(written) => {
+                                    // Batches always hold several readings
+                                    if backlog > 1 && db_batch_policy.is_none() {
+                                        println!(
+                                            "[WAL] Replayed {} readings, {} still queued",
+                                            written,
+                                            wal.pending().len()
+                                        );
+                                    }
+                                    match wal.pending().len() {
+                                        0 => Ok(()),
+                                        n => Err(format!("{} readings queued in the WAL", n)),
+                                    }
+                                }
+                                Err(e) => {
+                                    // Logged by the breaker once it opens
+                                    if !db_breaker.is_open() {
+                                        eprintln!(
+                                            "[WARN] Database write error, {} readings queued in the WAL: {:#}",
+                                            wal.pending().len(),
+                                            e
+                                        );
+                                    }
+                                    Err(format!("{:#}", e))
+                                }
+                            }
+                        };
+                        daemon_status.lock().await.record_output("database", result);
+                    }
+                } else if let Some(writer) = db_writer.as_ref().filter(|_| db_active) {
+                    if let Some(batch) = db_batch.as_mut() {
+                        // A batch that fails to write stays pending, so the
+                        // next poll tries it again
+                        if batch.push(timestamp, &data, &provenance) {
+                            eprintln!(
+                                "[WARN] Database batch holds {} readings, dropping the oldest until a write succeeds",
+                                batch::MAX_PENDING
+                            );
+                        }
+                        if batch.is_due(Utc::now()) {
+                            let result = batch.flush(writer).await.map(|_| ());
+                            if let Err(ref e) = result {
+                                eprintln!(
+                                    "[WARN] Database write error, {} batched readings held for the next poll: {:#}",
+                                    batch.len(),
+                                    e
+                                );
+                            }
+                            daemon_status
+                                .lock()
+                                .await
+                                .record_output("database", result.map_err(|e| format!("{:#}", e)));
+                        }
+                    } else {
+                        let result = writer.insert_data(&data, &timestamp, &provenance).await;
+                        daemon_status
+                            .lock()
+                            .await
+                            .record_output("database", result.as_ref().map(|_| ()));
+                        if let Err(e) = result {
+                            eprintln!("[ERROR] Database write error: {}", e);
+                            eprintln!("  Cannot continue with database configuration.");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+
+                // Local storage isn't an output: history queries rely on it
+                if let Some(ref storage) = local_storage {
+                    if let Err(e) = storage.save(timestamp, &data, &provenance).await {
+                        eprintln!("[ERROR] Storage write error: {:#}", e);
+                    }
                 }
 
                 // Publish to MQTT if configured
-                if let Some(ref publisher) = mqtt_publisher {
-                    let json_data = serde_json::json!({
-                        "timestamp": timestamp.to_rfc3339(),
-                        "data": data
-                    });
-                    if let Err(e) = publisher.publish(&json_data.to_string()).await {
-                        eprintln!("[ERROR] MQTT publish error: {}", e);
-                        eprintln!("  Cannot continue with MQTT configuration.");
-                        std::process::exit(1);
+                #[cfg(feature = "mqtt")]
+                if let Some(publisher) = mqtt_publisher
+                    .as_ref()
+                    .filter(|_| mqtt_active && mqtt_breaker.allows())
+                {
+                    let result = publisher.publish_readings(&rounded, &timestamp).await;
+                    mqtt_breaker.record(&result);
+                    daemon_status
+                        .lock()
+                        .await
+                        .record_output("mqtt", result.as_ref().map(|_| ()));
+                    if let Err(e) = result {
+                        // Logged by the breaker once it opens
+                        if !mqtt_breaker.is_open() {
+                            eprintln!("[ERROR] MQTT publish error: {:#}", e);
+                        }
+                        #[cfg(feature = "db")]
+                        if let Some(ref mut recorder) = pipeline_metrics {
+                            recorder.record_output_error("mqtt");
+                        }
                     }
                 }
 
                 // Publish to HTTP endpoint if configured
-                if let Some(ref publisher) = http_publisher {
-                    publisher.publish(&data, &timestamp).await;
+                #[cfg(feature = "http")]
+                if let Some(publisher) = http_publisher.as_ref().filter(|_| http_active) {
+                    publisher.publish(&rounded, &timestamp).await;
+                    // Failed sends are queued for retry, so report the backlog
+                    let queued = publisher.queue_len().await;
+                    let result = match queued {
+                        0 => Ok(()),
+                        n => Err(format!("{} records queued for retry", n)),
+                    };
+                    #[cfg(feature = "db")]
+                    if let (Err(_), Some(ref mut recorder)) = (&result, &mut pipeline_metrics) {
+                        recorder.record_output_error("http");
+                    }
+                    daemon_status.lock().await.record_output("http", result);
+                }
+
+                // Send to federation peers if configured
+                #[cfg(all(feature = "web", feature = "http"))]
+                if let Some(ref federation) = federation {
+                    if let Err(e) = federation.share(timestamp, &rounded) {
+                        eprintln!("[WARN] Failed to share readings with peers: {:#}", e);
+                    }
                 }
 
                 // Display output only if no output sink is configured
-                if db_writer.is_none() && mqtt_publisher.is_none() && http_publisher.is_none() {
+                let outputs: &[bool] = &[
+                    #[cfg(feature = "db")]
+                    db_writer.is_some(),
+                    #[cfg(feature = "mqtt")]
+                    mqtt_publisher.is_some(),
+                    #[cfg(feature = "http")]
+                    http_publisher.is_some(),
+                ];
+                if !outputs.contains(&true) {
                     if args.format == "json" {
-                        println!("{}", serde_json::to_string_pretty(&data)?);
+                        println!("{}", serde_json::to_string_pretty(&rounded)?);
                     } else {
-                        print_livedata(&data, &timestamp);
+                        live_table.print(&rounded, &timestamp);
                     }
                 }
             }
-            Err(e) => eprintln!("Error: {}", e),
+            Err(e) => {
+                match e.downcast_ref::<ResponseError>() {
+                    Some(ResponseError::Busy { .. }) => {
+                        eprintln!("[WARN] {}; skipping this poll", e)
+                    }
+                    _ => eprintln!("Error: {}", e),
+                }
+                daemon_status.lock().await.record_poll_error(&e);
+            }
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(alignment.next_poll_delay(Utc::now(), interval)) => {}
+            _ = controls.poll_requested() => println!("Poll requested via control socket"),
+            signal = &mut shutdown => {
+                println!("Received {}, stopping", signal);
+                daemon_status
+                    .lock()
+                    .await
+                    .log_event(EventKind::Stopped, format!("Stopped by {}", signal));
+                if let Some(ref store) = state_store {
+                    save_state(
+                        store,
+                        &sensor_watch,
+                        &battery_tracker,
+                        &degree_days,
+                        &daemon_status,
+                    )
+                    .await;
+                }
+                #[cfg(feature = "db")]
+                if let Some(ref writer) = db_writer {
+                    flush_database(writer, &mut db_batch, &mut db_wal).await;
+                }
+                #[cfg(feature = "db")]
+                if let Some(ref mut recorder) = pipeline_metrics {
+                    write_metrics(recorder.finish(Utc::now()), &db_writer).await;
+                }
+                #[cfg(feature = "http")]
+                if let Some(ref publisher) = http_publisher {
+                    publisher.flush().await;
+                }
+                #[cfg(feature = "mqtt")]
+                if let Some(ref publisher) = mqtt_publisher {
+                    publisher.shutdown().await;
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Write the readings still held back for the database before exiting.
+/// Readings the WAL can't deliver stay in it for the next start.
+#[cfg(feature = "db")]
+async fn flush_database(
+    writer: &DatabaseWriter,
+    batch: &mut Option<batch::InsertBatch>,
+    wal: &mut Option<WriteAheadLog>,
+) {
+    if let Some(batch) = batch {
+        match batch.flush(writer).await {
+            Ok(0) => {}
+            Ok(written) => println!("[OK] Wrote {} batched readings to the database", written),
+            Err(e) => eprintln!("[ERROR] Failed to write batched readings: {:#}", e),
+        }
+    }
+    if let Some(wal) = wal.as_mut().filter(|wal| !wal.pending().is_empty()) {
+        match wal.deliver(writer, usize::MAX).await {
+            Ok(written) => println!("[OK] Wrote {} queued readings to the database", written),
+            Err(e) => eprintln!(
+                "[WARN] {} readings stay in the WAL for the next start: {:#}",
+                wal.pending().len(),
+                e
+            ),
+        }
+    }
+}
+
+/// Store a finished metrics interval; a failure only loses the metrics
+#[cfg(feature = "db")]
+async fn write_metrics(
+    interval: Option<metrics::MetricsInterval>,
+    writer: &Option<Arc<DatabaseWriter>>,
+) {
+    if let (Some(interval), Some(writer)) = (interval, writer) {
+        if let Err(e) = writer.insert_metrics(&interval).await {
+            eprintln!("[WARN] Metrics write error: {}", e);
         }
-        tokio::time::sleep(Duration::from_secs(args.continuous)).await;
     }
 }
+
+/// Write sensor, battery, degree day and event log state to the state file
+async fn save_state(
+    store: &StateStore,
+    sensor_watch: &SensorWatch,
+    battery_tracker: &BatteryTracker,
+    degree_days: &Option<SharedDegreeDays>,
+    status: &SharedStatus,
+) {
+    let status = status.lock().await;
+    let mut state = PersistentState {
+        sensors: Some(sensor_watch.state()),
+        battery: Some(battery_tracker.state()),
+        events: Some(status.event_log().state()),
+        settings: Some(status.settings().clone()),
+        ..Default::default()
+    };
+    drop(status);
+    if let Some(ref acc) = degree_days {
+        state.degree_days = Some(acc.lock().await.state());
+    }
+    if let Err(e) = store.save(&state) {
+        eprintln!("[WARN] State save error: {:#}", e);
+    }
+}
+
+/// Resolves with the signal name on Ctrl+C, or SIGTERM from systemd/Docker
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "Ctrl+C",
+                _ = terminate.recv() => "SIGTERM",
+            },
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+                "Ctrl+C"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl+C"
+    }
+}
+
+/// Create the database table and exit
+#[cfg(feature = "db")]
+async fn run_create_table_command(args: &Args) -> Result<()> {
+    let db_config = args.get_database_config()?.ok_or_else(|| {
+        anyhow::anyhow!("Database configuration required. Add [database] section to config file.")
+    })?;
+
+    println!("Creating database table...");
+    let writer = DatabaseWriter::new(&db_config).await?;
+    writer.create_table().await?;
+    println!("[OK] Table '{}' created successfully", db_config.table_name);
+    Ok(())
+}
+
+/// Route outbound connections through `[proxy]`, or the proxy environment
+/// variables without one, and set how long resolved output hosts are reused
+#[cfg_attr(
+    not(any(feature = "db", feature = "http", feature = "mqtt")),
+    allow(unused_variables)
+)]
+fn init_network(args: &Args) -> Result<()> {
+    #[cfg(any(feature = "db", feature = "http"))]
+    dns::init(args.get_dns_ttl()?);
+    #[cfg(any(feature = "http", feature = "mqtt"))]
+    proxy::init(args.get_proxy_config()?.as_ref())?;
+    Ok(())
+}
+
+/// Error for a command this build was compiled without
+#[cfg(not(all(feature = "db", feature = "http", feature = "mqtt")))]
+fn feature_disabled(command: &str, feature: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "{} is not available: wxlistener was built without the `{}` feature",
+        command,
+        feature
+    )
+}
+
+/// Print a Home Assistant blueprint for the configured MQTT topics
+#[cfg(feature = "mqtt")]
+fn run_blueprint_command(args: &Args, kind: &str) -> Result<()> {
+    let Some(mqtt_config) = args.get_mqtt_config()? else {
+        anyhow::bail!("blueprint needs an [mqtt] section in the config file");
+    };
+    let kind = ha_blueprint::BlueprintKind::parse(kind)?;
+    print!("{}", ha_blueprint::render(kind, &mqtt_config)?);
+    Ok(())
+}
+
+/// Show the status of a running instance and exit
+#[cfg(feature = "http")]
+async fn run_status_command(
+    args: &Args,
+    url: Option<&str>,
+    json: bool,
+    token: Option<&str>,
+    events: Option<usize>,
+) -> Result<()> {
+    init_network(args)?;
+    let url = url
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("http://127.0.0.1:{}", args.web_port));
+    let token = match token {
+        Some(token) => Some(token.to_string()),
+        #[cfg(feature = "web")]
+        None => args
+            .get_web_config()?
+            .tokens
+            .into_iter()
+            .next()
+            .map(|token| token.token),
+        #[cfg(not(feature = "web"))]
+        None => None,
+    };
+    let report = status::fetch_status(&url, token.as_deref()).await?;
+    let recent = match events {
+        Some(count) => {
+            let mut recent = status::fetch_events(&url, token.as_deref()).await?;
+            recent.drain(..recent.len().saturating_sub(count));
+            Some(recent)
+        }
+        None => None,
+    };
+    if json {
+        let mut value = serde_json::to_value(&report)?;
+        if let Some(ref recent) = recent {
+            value["events"] = serde_json::to_value(recent)?;
+        }
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    } else {
+        println!("{}", status::format_report(&report, args.get_units()?));
+        if let Some(ref recent) = recent {
+            println!("\n--- Recent Events ---");
+            println!("{}", events::format_events(recent));
+        }
+    }
+    Ok(())
+}
+
+/// List the gateways that answer a discovery broadcast and exit
+fn run_discover_command(timeout: u64) -> Result<()> {
+    let gateways = client::discover(std::time::Duration::from_secs(timeout))?;
+    if gateways.is_empty() {
+        anyhow::bail!(
+            "No gateways answered within {}s; broadcasts don't cross routers or VLANs",
+            timeout
+        );
+    }
+    println!(
+        "{:<15}  {:<5}  {:<17}  {:<10}  FIRMWARE",
+        "IP", "PORT", "MAC", "MODEL"
+    );
+    for gateway in &gateways {
+        println!(
+            "{:<15}  {:<5}  {:<17}  {:<10}  {}",
+            gateway.ip.to_string(),
+            gateway.port,
+            gateway.mac,
+            gateway.model,
+            gateway.firmware.as_deref().unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+/// Set the gateway clock to the host's time
+fn set_gateway_time(client: &GW1000Client) -> Result<()> {
+    let now = Utc::now();
+    let drift = client.set_time(now.timestamp() as u32)?;
+    println!(
+        "[OK] Gateway clock set to {} (was {:+} s off)",
+        now.format("%Y-%m-%d %H:%M:%S UTC"),
+        drift
+    );
+    Ok(())
+}
+
+/// Run a gateway management command and exit
+fn run_device_command(args: &Args, action: &DeviceCommand) -> Result<()> {
+    let (ip, port) = args.get_connection_info()?;
+    let client = GW1000Client::new(ip, port)
+        .with_max_response_size(args.get_max_response_size()?)
+        .with_protocol_trace(args.trace_protocol);
+
+    match action {
+        DeviceCommand::SetTime => set_gateway_time(&client)?,
+        DeviceCommand::Calibration => {
+            let offsets = client.get_calibration_offsets()?;
+            println!("Gateway calibration offsets:");
+            println!("  intemp:       {:+.1} °C", offsets.intemp);
+            println!("  inhumid:      {:+} %", offsets.inhumid);
+            println!("  absbarometer: {:+.1} hPa", offsets.absbarometer);
+            println!("  relbarometer: {:+.1} hPa", offsets.relbarometer);
+            println!("  outtemp:      {:+.1} °C", offsets.outtemp);
+            println!("  outhumid:     {:+} %", offsets.outhumid);
+            println!("  wind_dir:     {:+}°", offsets.wind_dir);
+        }
+        DeviceCommand::CaptureVector {
+            output,
+            description,
+        } => {
+            let firmware = client.get_firmware_version().ok();
+            let frame = client.get_livedata_frame()?;
+            let description = description.clone().unwrap_or_else(|| {
+                format!(
+                    "Captured from {}",
+                    firmware.as_deref().unwrap_or("a gateway")
+                )
+            });
+            let vector = vectors::TestVector::capture(description, firmware, &frame)?;
+            std::fs::write(output, toml::to_string(&vector)?)
+                .context(format!("Failed to write {:?}", output))?;
+            // The file must check out as written, or it isn't worth submitting
+            let written: vectors::TestVector = toml::from_str(&std::fs::read_to_string(output)?)?;
+            written
+                .check()
+                .context(format!("{:?} doesn't decode as captured", output))?;
+            println!(
+                "[OK] Wrote {} readings to {:?}; check them against the gateway's own display before submitting",
+                vector.expected.len(),
+                output
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Add or remove an encrypted secret in the config file and exit
+fn run_secret_command(args: &Args, action: &SecretCommand) -> Result<()> {
+    let config_path = args
+        .config
+        .as_ref()
+        .context("secret needs the config file: --config <FILE>")?;
+
+    match action {
+        SecretCommand::Set { name } => {
+            secrets::validate_name(name)?;
+            let value = if std::io::stdin().is_terminal() {
+                rpassword::prompt_password(format!("Value for {}: ", name))?
+            } else {
+                let mut value = String::new();
+                std::io::stdin().read_to_string(&mut value)?;
+                value.trim_end_matches(['\r', '\n']).to_string()
+            };
+            let passphrase = match secrets::passphrase_from_env()? {
+                Some(passphrase) => passphrase,
+                None => {
+                    let passphrase = rpassword::prompt_password("Secrets passphrase: ")?;
+                    if rpassword::prompt_password("Repeat passphrase: ")? != passphrase {
+                        anyhow::bail!("Passphrases don't match");
+                    }
+                    passphrase
+                }
+            };
+            if passphrase.is_empty() {
+                anyhow::bail!("The secrets passphrase can't be empty");
+            }
+            secrets::set(config_path, name, &value, &passphrase)?;
+            println!(
+                "[OK] Secret {} saved; use ${{secret:{}}} in the config",
+                name, name
+            );
+            println!(
+                "  wxlistener needs {} or {} to read it",
+                secrets::PASSPHRASE_ENV,
+                secrets::PASSPHRASE_FILE_ENV
+            );
+        }
+        SecretCommand::Remove { name } => {
+            if secrets::remove(config_path, name)? {
+                println!("[OK] Secret {} removed", name);
+            } else {
+                anyhow::bail!("No secret {} in {:?}", name, config_path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write a backup archive of the config and state file and exit
+async fn run_backup_command(args: &Args, output: Option<&Path>, with_database: bool) -> Result<()> {
+    let config_path = args
+        .config
+        .as_ref()
+        .context("backup needs the config file: --config <FILE>")?;
+    let output = output.map(Path::to_path_buf).unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "wxlistener-backup-{}.tar.gz",
+            Local::now().format("%Y%m%d-%H%M%S")
+        ))
+    });
+    let state_file = args.get_state_file()?;
+
+    let records_file = output.with_extension("records.tmp");
+    let records = if with_database {
+        let result = export_database(args, &records_file).await;
+        if result.is_err() {
+            let _ = std::fs::remove_file(&records_file);
+        }
+        Some(result?)
+    } else {
+        None
+    };
+    let result = backup::create(
+        &output,
+        config_path,
+        state_file.as_deref(),
+        records.map(|count| (records_file.as_path(), count)),
+    );
+    if records.is_some() {
+        let _ = std::fs::remove_file(&records_file);
+    }
+    let manifest = result?;
+
+    println!("[OK] Backup written to {:?}", output);
+    println!("  Config file: {:?}", manifest.config_path);
+    if let Some(ref path) = manifest.state_file {
+        println!("  State file:  {:?}", path);
+    }
+    if let Some(count) = manifest.records {
+        println!("  Records:     {}", count);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "db")]
+async fn export_database(args: &Args, path: &Path) -> Result<usize> {
+    let db_config = args
+        .get_database_config()?
+        .context("--with-database needs a [database] section in the config file")?;
+    let writer = DatabaseWriter::new(&db_config).await?;
+    println!("Exporting records from '{}'...", db_config.table_name);
+    backup::export_records(&writer, path).await
+}
+
+#[cfg(not(feature = "db"))]
+async fn export_database(_args: &Args, _path: &Path) -> Result<usize> {
+    Err(feature_disabled("backup --with-database", "db"))
+}
+
+/// Restore a backup archive and exit
+async fn run_restore_command(args: &Args, archive: &Path, force: bool) -> Result<()> {
+    let config_path = args
+        .config
+        .as_ref()
+        .context("restore needs where to write the config file: --config <FILE>")?;
+    if config_path.exists() && !force {
+        anyhow::bail!(
+            "{:?} already exists; use --force to replace it",
+            config_path
+        );
+    }
+
+    let records_file = config_path.with_extension("records.tmp");
+    let restored = backup::extract(archive, &records_file)?;
+    let result = restore_backup(args, config_path, restored, force).await;
+    let _ = std::fs::remove_file(&records_file);
+    result
+}
+
+async fn restore_backup(
+    args: &Args,
+    config_path: &Path,
+    restored: backup::Extracted,
+    force: bool,
+) -> Result<()> {
+    println!(
+        "Restoring a backup made by wxlistener {} at {}",
+        restored.manifest.version,
+        restored.manifest.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    std::fs::write(config_path, &restored.config)
+        .context(format!("Failed to write config file: {:?}", config_path))?;
+    println!("[OK] Config file restored to {:?}", config_path);
+
+    // The state goes wherever the restored config keeps it
+    if let Some(state) = restored.state {
+        match args.get_state_file()? {
+            Some(path) if path.exists() && !force => {
+                println!(
+                    "[WARN] Keeping the existing state file {:?}; use --force to replace it",
+                    path
+                );
+            }
+            Some(path) => {
+                let state = serde_json::from_slice(&state).context("Invalid state in backup")?;
+                StateStore::new(&path).save(&state)?;
+                println!("[OK] State file restored to {:?}", path);
+            }
+            None => println!("[WARN] The config has no state_file; skipping the saved state"),
+        }
+    }
+
+    if let Some(path) = restored.records {
+        let count = restore_database(args, &path).await?;
+        println!("[OK] {} records restored", count);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "db")]
+async fn restore_database(args: &Args, path: &Path) -> Result<usize> {
+    let db_config = args
+        .get_database_config()?
+        .context("The backup has weather records but the config has no [database] section")?;
+    let writer = DatabaseWriter::new(&db_config).await?;
+    println!("Restoring records into '{}'...", db_config.table_name);
+    backup::import_records(path, &writer).await
+}
+
+#[cfg(not(feature = "db"))]
+async fn restore_database(_args: &Args, _path: &Path) -> Result<usize> {
+    Err(feature_disabled("restoring weather records", "db"))
+}
+
+/// Backfill the database from the Ecowitt cloud history API and exit
+#[cfg(all(feature = "db", feature = "http"))]
+async fn run_backfill_command(args: &Args, start: &str, end: Option<&str>) -> Result<()> {
+    init_network(args)?;
+    let start = backfill::parse_datetime(start)?;
+    let end = match end {
+        Some(end) => backfill::parse_datetime(end)?,
+        None => Utc::now(),
+    };
+    run_backfill_range(args, start, end).await
+}
+
+/// Backfill the database between two UTC times
+#[cfg(all(feature = "db", feature = "http"))]
+async fn run_backfill_range(args: &Args, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<()> {
+    let db_config = args.get_database_config()?.ok_or_else(|| {
+        anyhow::anyhow!("Database configuration required. Add [database] section to config file.")
+    })?;
+    let cloud_config = args.get_ecowitt_cloud_config()?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Ecowitt cloud configuration required. Add [ecowitt_cloud] section to config file."
+        )
+    })?;
+
+    // Fall back to asking the gateway for its MAC address
+    let mac = match cloud_config.mac.clone() {
+        Some(mac) => mac,
+        None => {
+            let (ip, port) = args.get_connection_info()?;
+            AsyncGW1000Client::new(ip, port)
+                .with_protocol_trace(args.trace_protocol)
+                .get_mac_address()
+                .await?
+        }
+    };
+
+    let writer = DatabaseWriter::new(&db_config).await?;
+    println!("Backfilling {} from {} to {}", mac, start, end);
+    let (inserted, skipped) =
+        backfill::run_backfill(&cloud_config, &mac, start, end, &writer).await?;
+    println!(
+        "[OK] Backfill complete: {} records inserted, {} skipped (already present)",
+        inserted, skipped
+    );
+    Ok(())
+}
+
+/// Add upcoming MySQL monthly partitions and exit
+#[cfg(feature = "db")]
+async fn run_add_partitions_command(args: &Args, months: u32) -> Result<()> {
+    let db_config = args.get_database_config()?.ok_or_else(|| {
+        anyhow::anyhow!("Database configuration required. Add [database] section to config file.")
+    })?;
+    let writer = DatabaseWriter::new(&db_config).await?;
+    let added = writer.add_partitions(months).await?;
+    if added.is_empty() {
+        println!("[OK] Partitions already cover the next {} months", months);
+    } else {
+        println!("[OK] Added partitions: {}", added.join(", "));
+    }
+    Ok(())
+}
+
+/// Spawns a task running each `[[schedules]]` entry at its cron times
+fn run_scheduler_background(args: Args, rules: Vec<ScheduleRule>, device: DeviceQueue) {
+    tokio::spawn(async move {
+        // Never fire a minute twice, even if the wall clock lags the timer
+        let mut last_run = Local::now();
+        loop {
+            let now = Local::now().max(last_run);
+            let due: Vec<_> = rules
+                .iter()
+                .map(|rule| (rule.cron.next_after(&now), rule))
+                .collect();
+            let Some(next) = due.iter().filter_map(|(at, _)| *at).min() else {
+                return;
+            };
+            tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+            for (_, rule) in due.iter().filter(|(at, _)| *at == Some(next)) {
+                println!("[OK] Running scheduled task: {:?}", rule.task);
+                if let Err(e) = run_scheduled_task(&args, &rule.task, &device).await {
+                    eprintln!("[ERROR] Scheduled task {:?} failed: {:#}", rule.task, e);
+                }
+            }
+            last_run = next;
+        }
+    });
+}
+
+#[cfg_attr(not(feature = "db"), allow(unused_variables))]
+async fn run_scheduled_task(args: &Args, task: &ScheduledTask, device: &DeviceQueue) -> Result<()> {
+    match *task {
+        ScheduledTask::SetTime => {
+            device
+                .run(Priority::Scheduled, |gateway| {
+                    set_gateway_time(gateway.tcp_command()?)
+                })
+                .await
+        }
+        #[cfg(all(feature = "db", feature = "http"))]
+        ScheduledTask::Backfill { hours } => {
+            let end = Utc::now();
+            run_backfill_range(args, end - TimeDelta::hours(hours as i64), end).await
+        }
+        #[cfg(not(all(feature = "db", feature = "http")))]
+        ScheduledTask::Backfill { .. } => Err(feature_disabled("backfill", "db and http")),
+        #[cfg(feature = "db")]
+        ScheduledTask::AddPartitions { months } => run_add_partitions_command(args, months).await,
+        #[cfg(not(feature = "db"))]
+        ScheduledTask::AddPartitions { .. } => Err(feature_disabled("add_partitions", "db")),
+    }
+}
+
+/// Check every part of the pipeline, print a pass/fail report and exit;
+/// fails when any check does
+async fn run_doctor_command(args: &Args) -> Result<()> {
+    let mut report = doctor::Report::default();
+    let config = match &args.config {
+        Some(path) => path.display().to_string(),
+        None => "none, using the command line and environment".to_string(),
+    };
+    let connection = args
+        .get_disabled_sections()
+        .and_then(|disabled| Ok((disabled, args.get_connection_info()?)));
+    let (disabled, (ip, port)) = match connection {
+        Ok(loaded) => {
+            report.add("Config", Outcome::Pass, config);
+            loaded
+        }
+        Err(e) => {
+            report.add("Config", Outcome::Fail, format!("{:#}", e));
+            anyhow::bail!("The config must load before anything else can be checked");
+        }
+    };
+
+    let max_response_size = args.get_max_response_size()?;
+    let (api, http_port) = args.get_gateway_api()?;
+    init_network(args)?;
+
+    // The gateway: reachable, a known model, and live data that decodes
+    let mac = if api == GatewayApi::Http {
+        for name in ["Gateway", "Firmware", "Live data"] {
+            report.add(name, Outcome::Skip, "api = \"http\" reads over HTTP only");
+        }
+        None
+    } else {
+        let client = AsyncGW1000Client::new(ip.clone(), port)
+            .with_max_response_size(max_response_size)
+            .with_protocol_trace(args.trace_protocol);
+        let mac = client.get_mac_address().await;
+        let reachable = report.record(
+            "Gateway",
+            mac.as_ref()
+                .map(|mac| format!("{}:{} answers, MAC {}", ip, port, mac))
+                .map_err(|e| anyhow::anyhow!("{}:{}: {:#}", ip, port, e)),
+        );
+        let mac = mac.ok();
+        if reachable {
+            match client.get_firmware_version().await {
+                Ok(version) => {
+                    let (outcome, detail) = doctor::firmware_compatibility(&version);
+                    report.add("Firmware", outcome, detail);
+                }
+                Err(e) => report.add("Firmware", Outcome::Fail, format!("{:#}", e)),
+            }
+            match client.get_livedata_with_warnings().await {
+                Ok((data, 0)) if !data.is_empty() => report.add(
+                    "Live data",
+                    Outcome::Pass,
+                    format!("{} readings decoded", data.len()),
+                ),
+                Ok((data, 0)) => report.add(
+                    "Live data",
+                    Outcome::Warn,
+                    format!("{} readings", data.len()),
+                ),
+                Ok((data, warnings)) => report.add(
+                    "Live data",
+                    Outcome::Warn,
+                    format!(
+                        "{} readings decoded with {} warnings; run with --trace-protocol for details",
+                        data.len(),
+                        warnings
+                    ),
+                ),
+                Err(e) => report.add("Live data", Outcome::Fail, format!("{:#}", e)),
+            }
+        } else {
+            for name in ["Firmware", "Live data"] {
+                report.add(name, Outcome::Skip, "the gateway did not answer");
+            }
+        }
+        mac
+    };
+
+    // The local HTTP API, when live data is read over it
+    if api == GatewayApi::Tcp {
+        report.add("HTTP API", Outcome::Skip, "api = \"tcp\"");
+    } else {
+        let http = LocalHttpClient::new(ip.clone(), http_port)
+            .with_max_response_size(max_response_size)
+            .with_protocol_trace(args.trace_protocol);
+        match http.get_livedata_with_warnings() {
+            Ok((data, 0)) => report.add(
+                "HTTP API",
+                Outcome::Pass,
+                format!("{}:{} answers, {} readings", ip, http_port, data.len()),
+            ),
+            Ok((data, warnings)) => report.add(
+                "HTTP API",
+                Outcome::Warn,
+                format!(
+                    "{} readings, {} values in units that couldn't be converted",
+                    data.len(),
+                    warnings
+                ),
+            ),
+            Err(e) => report.add(
+                "HTTP API",
+                Outcome::Fail,
+                format!("{}:{}: {:#}", ip, http_port, e),
+            ),
+        }
+    }
+
+    #[cfg(feature = "db")]
+    doctor_database(args, &mut report).await?;
+    #[cfg(feature = "mqtt")]
+    doctor_mqtt(args, mac.as_deref(), &mut report).await?;
+    #[cfg(not(feature = "mqtt"))]
+    let _ = mac;
+    #[cfg(feature = "http")]
+    doctor_http(args, &mut report).await?;
+    for (section, feature) in disabled {
+        report.add(
+            section,
+            Outcome::Skip,
+            format!("built without the `{}` feature", feature),
+        );
+    }
+
+    println!();
+    println!("{}", report.summary());
+    if report.failures() > 0 {
+        anyhow::bail!("{} of the checks failed", report.failures());
+    }
+    Ok(())
+}
+
+/// Doctor checks: the database connection, then the table's columns and
+/// whether writes to it can be upserts
+#[cfg(feature = "db")]
+async fn doctor_database(args: &Args, report: &mut doctor::Report) -> Result<()> {
+    let Some(db_config) = args.get_database_config()? else {
+        report.add("Database", Outcome::Skip, "no [database] section");
+        return Ok(());
+    };
+    let writer = match DatabaseWriter::connect(&db_config).await {
+        Ok(writer) => {
+            report.add("Database", Outcome::Pass, "connected");
+            writer
+        }
+        Err(e) => {
+            report.add("Database", Outcome::Fail, format!("{:#}", e));
+            report.add("Database table", Outcome::Skip, "no connection");
+            return Ok(());
+        }
+    };
+    let table = writer.table_name().to_string();
+    let exists = writer.table_exists().await;
+    let found = matches!(exists, Ok(true));
+    let columns = match exists {
+        Ok(true) => writer.missing_columns().await.map(Some),
+        Ok(false) => Ok(None),
+        Err(e) => Err(e),
+    };
+    report.record(
+        "Database table",
+        match columns {
+            Ok(Some(missing)) if missing.is_empty() => Ok(format!("'{}' has every column", table)),
+            Ok(Some(missing)) => Err(anyhow::anyhow!(
+                "'{}' lacks columns: {}",
+                table,
+                missing.join(", ")
+            )),
+            Ok(None) => Err(anyhow::anyhow!(
+                "'{}' does not exist: create it with --db-create-table",
+                table
+            )),
+            Err(e) => Err(e),
+        },
+    );
+    if found {
+        match writer.allowed_write_mode().await {
+            Ok(mode) => match writer.write_mode_warning(mode) {
+                Some(warning) => report.add("Database writes", Outcome::Warn, warning),
+                None => report.add("Database writes", Outcome::Pass, "upserts"),
+            },
+            Err(e) => report.add("Database writes", Outcome::Fail, format!("{:#}", e)),
+        }
+    }
+    match clock_skew::measure(&writer).await {
+        Ok(skew)
+            if db_config.max_clock_skew > 0
+                && skew.abs() > chrono::TimeDelta::seconds(db_config.max_clock_skew as i64) =>
+        {
+            report.add(
+                "Database clock",
+                Outcome::Warn,
+                format!(
+                    "{} (more than {}s)",
+                    clock_skew::describe(skew),
+                    db_config.max_clock_skew
+                ),
+            )
+        }
+        result => {
+            report.record("Database clock", result.map(clock_skew::describe));
+        }
+    }
+    Ok(())
+}
+
+/// Doctor check: connect to the broker and publish a test message
+#[cfg(feature = "mqtt")]
+async fn doctor_mqtt(args: &Args, mac: Option<&str>, report: &mut doctor::Report) -> Result<()> {
+    let Some(mqtt_config) = args.get_mqtt_config()? else {
+        report.add("MQTT", Outcome::Skip, "no [mqtt] section");
+        return Ok(());
+    };
+    match mqtt::probe_publish(&mqtt_config, mac).await {
+        Ok((topic, true)) => report.add(
+            "MQTT",
+            Outcome::Pass,
+            format!("connected, published to {}", topic),
+        ),
+        Ok((topic, false)) => report.add(
+            "MQTT",
+            Outcome::Warn,
+            format!(
+                "connected, but a test message to {} did not come back: \
+                 check the broker allows publishing there",
+                topic
+            ),
+        ),
+        Err(e) => report.add("MQTT", Outcome::Fail, format!("{:#}", e)),
+    }
+    Ok(())
+}
+
+/// Doctor check: the HTTP endpoint answers and accepts the authorization
+#[cfg(feature = "http")]
+async fn doctor_http(args: &Args, report: &mut doctor::Report) -> Result<()> {
+    let Some(http_config) = args.get_http_config()? else {
+        report.add("HTTP endpoint", Outcome::Skip, "no [http] section");
+        return Ok(());
+    };
+    let status = match HttpPublisher::new(&http_config).await {
+        Ok(publisher) => publisher
+            .check_reachable()
+            .await
+            .map(|status| (publisher.url().to_string(), status)),
+        Err(e) => Err(e),
+    };
+    report.record(
+        "HTTP endpoint",
+        match status {
+            Ok((url, status))
+                if status == reqwest::StatusCode::UNAUTHORIZED
+                    || status == reqwest::StatusCode::FORBIDDEN =>
+            {
+                Err(anyhow::anyhow!(
+                    "{} refused the authorization (HTTP {})",
+                    url,
+                    status.as_u16()
+                ))
+            }
+            Ok((url, status)) => Ok(format!("{} answers (HTTP {})", url, status.as_u16())),
+            Err(e) => Err(e),
+        },
+    );
+    Ok(())
+}
+
+/// Turn maintenance mode of a running instance on or off and exit
+#[cfg(feature = "http")]
+async fn run_maintenance_command(
+    args: &Args,
+    enabled: bool,
+    url: Option<&str>,
+    token: Option<&str>,
+) -> Result<()> {
+    init_network(args)?;
+    let url = url
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("http://127.0.0.1:{}", args.web_port));
+    let token = match token {
+        Some(token) => Some(token.to_string()),
+        #[cfg(feature = "web")]
+        None => args
+            .get_web_config()?
+            .tokens
+            .into_iter()
+            .find(|token| token.scope == auth::TokenScope::Admin)
+            .map(|token| token.token),
+        #[cfg(not(feature = "web"))]
+        None => None,
+    };
+    let command = serde_json::json!({ "command": "maintenance", "enabled": enabled });
+    let response = control::send_command(&url, token.as_deref(), &command).await?;
+    match response["maintenance_since"].as_str() {
+        Some(since) => println!("Maintenance mode ON since {}", since),
+        None => println!("Maintenance mode OFF"),
+    }
+    Ok(())
+}
+
+/// Chart a stored field in the terminal and exit
+#[cfg(feature = "db")]
+async fn run_history_command(
+    args: &Args,
+    field: &str,
+    last: &str,
+    width: usize,
+    height: usize,
+) -> Result<()> {
+    let db_config = args.get_database_config()?.ok_or_else(|| {
+        anyhow::anyhow!("Database configuration required. Add [database] section to config file.")
+    })?;
+    let period = history::parse_period(last)?;
+    let station_time = args.get_station_config()?.time_zone()?;
+    let units = args.get_units()?;
+
+    let writer = DatabaseWriter::new(&db_config).await?;
+    let end = Utc::now();
+    let start = end - period;
+    let points = writer.field_history(field, &start).await?;
+    println!("{}, last {}", field, last);
+    print!(
+        "{}",
+        history::render_graph(
+            field,
+            &points,
+            start,
+            end,
+            width,
+            height,
+            station_time,
+            units
+        )
+    );
+    // Missing annotations only cost the notes, not the graph
+    match writer.annotations(&start, &end).await {
+        Ok(annotations) if !annotations.is_empty() => {
+            println!("\nAnnotations:");
+            print!(
+                "{}",
+                history::format_annotations(&annotations, station_time)
+            );
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("[WARN] Could not read annotations: {:#}", e),
+    }
+    Ok(())
+}
+
+/// Import history from legacy weather software and exit
+#[cfg(feature = "db")]
+async fn run_import_command(args: &Args, from: &Path, format: &str, units: &str) -> Result<()> {
+    let db_config = args.get_database_config()?.ok_or_else(|| {
+        anyhow::anyhow!("Database configuration required. Add [database] section to config file.")
+    })?;
+    let format = import::ImportFormat::parse(format, from)?;
+    let units = import::UnitSystem::parse(units)?;
+
+    let writer = DatabaseWriter::new(&db_config).await?;
+    println!("Importing {:?} ({:?})", from, format);
+    let (inserted, skipped) = match format {
+        import::ImportFormat::Weewx => import::import_weewx(from, &writer).await?,
+        import::ImportFormat::Cumulus => import::import_cumulus(from, units, &writer).await?,
+    };
+    println!(
+        "[OK] Import complete: {} records inserted, {} skipped (empty or already present)",
+        inserted, skipped
+    );
+    Ok(())
+}
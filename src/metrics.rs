@@ -0,0 +1,180 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use std::time::Duration as StdDuration;
+
+/// Operational counters for one interval, written to `<table_name>_metrics`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsInterval {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Successful polls
+    pub polls: u32,
+    pub poll_failures: u32,
+    /// Round trip of successful polls, in milliseconds
+    pub latency_avg_ms: Option<f64>,
+    pub latency_max_ms: Option<f64>,
+    pub mqtt_errors: u32,
+    pub http_errors: u32,
+    latency_total_ms: f64,
+}
+
+impl MetricsInterval {
+    fn new(start: DateTime<Utc>, length: Duration) -> Self {
+        Self {
+            start,
+            end: start + length,
+            polls: 0,
+            poll_failures: 0,
+            latency_avg_ms: None,
+            latency_max_ms: None,
+            mqtt_errors: 0,
+            http_errors: 0,
+            latency_total_ms: 0.0,
+        }
+    }
+}
+
+/// Collects poll and output outcomes into fixed, wall-clock aligned
+/// intervals (e.g. every 5 minutes on the 5 minute mark)
+#[derive(Debug)]
+pub struct MetricsRecorder {
+    length: Duration,
+    current: Option<MetricsInterval>,
+}
+
+impl MetricsRecorder {
+    /// Intervals of `interval_secs` seconds, at least one minute
+    pub fn new(interval_secs: u64) -> Self {
+        Self {
+            length: Duration::seconds(interval_secs.max(60) as i64),
+            current: None,
+        }
+    }
+
+    /// Interval covering `at`, returning the previous one if it just ended
+    fn advance(&mut self, at: DateTime<Utc>) -> Option<MetricsInterval> {
+        if self.current.as_ref().is_some_and(|c| at < c.end) {
+            return None;
+        }
+        let start = at.duration_trunc(self.length).unwrap_or(at);
+        self.current
+            .replace(MetricsInterval::new(start, self.length))
+    }
+
+    /// Record a poll and its round trip. Returns the previous interval once
+    /// a poll lands in a new one, ready to be written.
+    pub fn record_poll(
+        &mut self,
+        at: DateTime<Utc>,
+        latency: StdDuration,
+        ok: bool,
+    ) -> Option<MetricsInterval> {
+        let finished = self.advance(at);
+        let Some(current) = self.current.as_mut() else {
+            return finished;
+        };
+
+        if ok {
+            let ms = latency.as_micros() as f64 / 1000.0;
+            current.polls += 1;
+            current.latency_total_ms += ms;
+            current.latency_avg_ms = Some(current.latency_total_ms / current.polls as f64);
+            current.latency_max_ms = Some(current.latency_max_ms.map_or(ms, |max| max.max(ms)));
+        } else {
+            current.poll_failures += 1;
+        }
+        finished
+    }
+
+    /// Count a failed write to "mqtt" or "http" in the current interval.
+    /// Database errors stop the daemon, and couldn't be stored anyway.
+    pub fn record_output_error(&mut self, output: &str) {
+        let Some(current) = self.current.as_mut() else {
+            return;
+        };
+        match output {
+            "mqtt" => current.mqtt_errors += 1,
+            "http" => current.http_errors += 1,
+            _ => {}
+        }
+    }
+
+    /// The interval in progress, cut short at `now`, for shutdown
+    pub fn finish(&mut self, now: DateTime<Utc>) -> Option<MetricsInterval> {
+        self.current.take().map(|mut interval| {
+            interval.end = now.min(interval.end);
+            interval
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 1, 12, minute, second)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_intervals_align_to_wall_clock() {
+        let mut recorder = MetricsRecorder::new(300);
+        assert_eq!(
+            recorder.record_poll(at(3, 10), StdDuration::from_millis(40), true),
+            None
+        );
+        recorder.record_poll(at(3, 26), StdDuration::from_millis(80), true);
+        recorder.record_poll(at(3, 42), StdDuration::ZERO, false);
+        recorder.record_output_error("http");
+
+        let finished = recorder
+            .record_poll(at(5, 0), StdDuration::from_millis(50), true)
+            .unwrap();
+        assert_eq!(finished.start, at(0, 0));
+        assert_eq!(finished.end, at(5, 0));
+        assert_eq!(finished.polls, 2);
+        assert_eq!(finished.poll_failures, 1);
+        assert_eq!(finished.latency_avg_ms, Some(60.0));
+        assert_eq!(finished.latency_max_ms, Some(80.0));
+        assert_eq!(finished.http_errors, 1);
+        assert_eq!(finished.mqtt_errors, 0);
+
+        // The poll that closed it starts the next interval
+        let current = recorder.finish(at(6, 30)).unwrap();
+        assert_eq!(current.start, at(5, 0));
+        assert_eq!(current.end, at(6, 30));
+        assert_eq!(current.polls, 1);
+    }
+
+    #[test]
+    fn test_failures_only_have_no_latency() {
+        let mut recorder = MetricsRecorder::new(300);
+        recorder.record_poll(at(1, 0), StdDuration::from_secs(16), false);
+        let interval = recorder.finish(at(2, 0)).unwrap();
+        assert_eq!(interval.poll_failures, 1);
+        assert_eq!(interval.latency_avg_ms, None);
+        assert_eq!(interval.latency_max_ms, None);
+    }
+
+    #[test]
+    fn test_gap_skips_empty_intervals() {
+        // Nothing is written for intervals without polls
+        let mut recorder = MetricsRecorder::new(60);
+        recorder.record_poll(at(0, 10), StdDuration::ZERO, true);
+        let finished = recorder
+            .record_poll(at(30, 10), StdDuration::ZERO, true)
+            .unwrap();
+        assert_eq!(finished.start, at(0, 0));
+        assert_eq!(recorder.finish(at(30, 20)).unwrap().start, at(30, 0));
+    }
+
+    #[test]
+    fn test_minimum_interval() {
+        let mut recorder = MetricsRecorder::new(5);
+        recorder.record_poll(at(0, 10), StdDuration::ZERO, true);
+        assert!(recorder
+            .record_poll(at(0, 50), StdDuration::ZERO, true)
+            .is_none());
+    }
+}
@@ -0,0 +1,342 @@
+/// Presets for cloud IoT brokers, which want client IDs, usernames, topics
+/// and TLS set up in their own formats
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::mqtt::MqttConfig;
+
+/// ALPN protocol AWS IoT Core needs for MQTT with certificates on port 443
+const AWS_ALPN: &str = "x-amzn-mqtt-ca";
+
+/// IoT Hub API version sent in the MQTT username
+const AZURE_API_VERSION: &str = "2021-04-12";
+
+/// Lifetime of generated SAS tokens when `sas_token_ttl` isn't set
+const DEFAULT_SAS_TOKEN_TTL: u64 = 86400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MqttPreset {
+    /// AWS IoT Core: mutual TLS with the thing's certificate
+    AwsIot,
+    /// Azure IoT Hub: a device SAS token or X.509 certificate, publishing to
+    /// the device's telemetry topic
+    AzureIotHub,
+}
+
+/// Signs IoT Hub SAS tokens for one device. Tokens expire, so a new one is
+/// made for every (re)connect.
+#[derive(Debug, Clone)]
+pub struct AzureSas {
+    /// `<hub>.azure-devices.net/devices/<device id>`
+    resource: String,
+    key: Vec<u8>,
+    ttl: u64,
+}
+
+impl AzureSas {
+    /// `SharedAccessSignature sr=...&sig=...&se=...` valid from `now`
+    pub fn token(&self, now: DateTime<Utc>) -> String {
+        let expiry = now.timestamp() + self.ttl as i64;
+        let resource = url_encode(&self.resource);
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any length");
+        mac.update(format!("{}\n{}", resource, expiry).as_bytes());
+        let signature = BASE64.encode(mac.finalize().into_bytes());
+        format!(
+            "SharedAccessSignature sr={}&sig={}&se={}",
+            resource,
+            url_encode(&signature),
+            expiry
+        )
+    }
+}
+
+fn url_encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// The plain broker settings a preset stands for, and the SAS token signer
+/// for Azure IoT Hub devices using a shared access key. Configs without a
+/// preset are returned unchanged.
+pub fn resolve(config: &MqttConfig) -> Result<(MqttConfig, Option<AzureSas>)> {
+    match config.preset {
+        None => Ok((config.clone(), None)),
+        Some(MqttPreset::AwsIot) => Ok((aws_iot(config)?, None)),
+        Some(MqttPreset::AzureIotHub) => azure_iot_hub(config),
+    }
+}
+
+fn aws_iot(config: &MqttConfig) -> Result<MqttConfig> {
+    if config.connection_string.is_some() {
+        anyhow::bail!("The aws_iot MQTT preset takes host, not connection_string");
+    }
+    let host = config.host.as_ref().context(
+        "The aws_iot MQTT preset needs host, the account's device data endpoint \
+         (<prefix>-ats.iot.<region>.amazonaws.com)",
+    )?;
+    if config.client_cert.is_none() || config.client_key.is_none() {
+        anyhow::bail!("The aws_iot MQTT preset needs client_cert and client_key for the thing");
+    }
+    if config.client_id.is_none() {
+        anyhow::bail!(
+            "The aws_iot MQTT preset needs client_id, usually the thing name its policy allows"
+        );
+    }
+
+    // Certificates on 443 are told apart from HTTPS by ALPN
+    let port = config.port.unwrap_or(8883);
+    let mut alpn = config.alpn.clone();
+    if port == 443 && alpn.is_empty() {
+        alpn.push(AWS_ALPN.to_string());
+    }
+
+    Ok(MqttConfig {
+        connection_string: Some(format!("mqtts://{}:{}", host, port)),
+        host: None,
+        port: None,
+        alpn,
+        preset: None,
+        ..config.clone()
+    })
+}
+
+/// HostName, DeviceId and SharedAccessKey from a device connection string
+/// as the Azure portal shows it
+fn parse_device_connection_string(conn_str: &str) -> Result<(String, String, Option<String>)> {
+    let mut host = None;
+    let mut device_id = None;
+    let mut key = None;
+    for part in conn_str.split(';').filter(|part| !part.is_empty()) {
+        let (name, value) = part
+            .split_once('=')
+            .context("Invalid Azure IoT Hub device connection string")?;
+        match name {
+            "HostName" => host = Some(value.to_string()),
+            "DeviceId" => device_id = Some(value.to_string()),
+            "SharedAccessKey" => key = Some(value.to_string()),
+            "x509" => {}
+            _ => anyhow::bail!(
+                "Unsupported {} in the Azure IoT Hub device connection string",
+                name
+            ),
+        }
+    }
+    Ok((
+        host.context("Azure IoT Hub device connection string has no HostName")?,
+        device_id.context("Azure IoT Hub device connection string has no DeviceId")?,
+        key,
+    ))
+}
+
+fn azure_iot_hub(config: &MqttConfig) -> Result<(MqttConfig, Option<AzureSas>)> {
+    let (host, device_id, key) = match config.connection_string.as_deref() {
+        Some(conn_str) => parse_device_connection_string(conn_str)?,
+        None => (
+            config.host.clone().context(
+                "The azure_iot_hub MQTT preset needs the device connection string, \
+                 or host (<hub>.azure-devices.net) and client_id (the device ID)",
+            )?,
+            config
+                .client_id
+                .clone()
+                .context("The azure_iot_hub MQTT preset needs client_id, the device ID")?,
+            config.shared_access_key.clone(),
+        ),
+    };
+    if config.topic.is_some() || config.availability_topic.is_some() {
+        anyhow::bail!(
+            "The azure_iot_hub MQTT preset publishes to devices/{}/messages/events/; \
+             remove topic and availability_topic",
+            device_id
+        );
+    }
+
+    let sas = match key {
+        Some(key) => Some(AzureSas {
+            resource: format!("{}/devices/{}", host, device_id),
+            key: BASE64
+                .decode(key.trim())
+                .context("Azure IoT Hub SharedAccessKey isn't valid base64")?,
+            ttl: config
+                .sas_token_ttl
+                .unwrap_or(DEFAULT_SAS_TOKEN_TTL)
+                .max(60),
+        }),
+        None if config.client_cert.is_some() && config.client_key.is_some() => None,
+        None => anyhow::bail!(
+            "The azure_iot_hub MQTT preset needs a SharedAccessKey, \
+             or client_cert and client_key for X.509 authentication"
+        ),
+    };
+
+    // Port 443 is only open to MQTT over WebSockets
+    let port = config.port.unwrap_or(8883);
+    let connection_string = if port == 443 {
+        format!("wss://{}/$iothub/websocket", host)
+    } else {
+        format!("mqtts://{}:{}", host, port)
+    };
+
+    let resolved = MqttConfig {
+        connection_string: Some(connection_string),
+        host: None,
+        port: None,
+        topic: Some(format!("devices/{}/messages/events/", device_id)),
+        client_id: Some(device_id.clone()),
+        username: Some(format!(
+            "{}/{}/?api-version={}",
+            host, device_id, AZURE_API_VERSION
+        )),
+        password: sas.as_ref().map(|sas| sas.token(Utc::now())),
+        preset: None,
+        ..config.clone()
+    };
+    Ok((resolved, sas))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn aws_config() -> MqttConfig {
+        MqttConfig {
+            preset: Some(MqttPreset::AwsIot),
+            host: Some("abc123-ats.iot.eu-west-1.amazonaws.com".to_string()),
+            client_id: Some("backyard".to_string()),
+            client_cert: Some("/etc/wxlistener/thing.crt".to_string()),
+            client_key: Some("/etc/wxlistener/thing.key".to_string()),
+            ..MqttConfig::new()
+        }
+    }
+
+    #[test]
+    fn test_aws_iot() {
+        let (resolved, sas) = resolve(&aws_config()).unwrap();
+        assert!(sas.is_none());
+        assert_eq!(
+            resolved.connection_string.as_deref(),
+            Some("mqtts://abc123-ats.iot.eu-west-1.amazonaws.com:8883")
+        );
+        assert!(resolved.alpn.is_empty());
+        assert!(resolved.uses_tls());
+        assert_eq!(resolved.get_connection_info().unwrap().2, "wx/live");
+
+        let port_443 = MqttConfig {
+            port: Some(443),
+            ..aws_config()
+        };
+        let (resolved, _) = resolve(&port_443).unwrap();
+        assert_eq!(resolved.alpn, vec![AWS_ALPN]);
+
+        let no_cert = MqttConfig {
+            client_key: None,
+            ..aws_config()
+        };
+        assert!(resolve(&no_cert).is_err());
+        let no_client_id = MqttConfig {
+            client_id: None,
+            ..aws_config()
+        };
+        assert!(resolve(&no_client_id).is_err());
+    }
+
+    #[test]
+    fn test_azure_iot_hub() {
+        let config = MqttConfig {
+            preset: Some(MqttPreset::AzureIotHub),
+            connection_string: Some(
+                "HostName=wx.azure-devices.net;DeviceId=backyard;SharedAccessKey=c2VjcmV0"
+                    .to_string(),
+            ),
+            ..MqttConfig::new()
+        };
+        let (resolved, sas) = resolve(&config).unwrap();
+        let (host, port, topic, username, password) = resolved.get_connection_info().unwrap();
+        assert_eq!(host, "wx.azure-devices.net");
+        assert_eq!(port, 8883);
+        assert_eq!(topic, "devices/backyard/messages/events/");
+        assert_eq!(resolved.client_id.as_deref(), Some("backyard"));
+        assert_eq!(
+            username.as_deref(),
+            Some("wx.azure-devices.net/backyard/?api-version=2021-04-12")
+        );
+        assert!(password.unwrap().starts_with("SharedAccessSignature sr="));
+        assert!(sas.is_some());
+
+        let websocket = MqttConfig {
+            port: Some(443),
+            ..config.clone()
+        };
+        let (resolved, _) = resolve(&websocket).unwrap();
+        assert_eq!(
+            resolved.get_websocket_url().unwrap().as_deref(),
+            Some("wss://wx.azure-devices.net/$iothub/websocket")
+        );
+
+        let custom_topic = MqttConfig {
+            topic: Some("wx/live".to_string()),
+            ..config
+        };
+        assert!(resolve(&custom_topic).is_err());
+    }
+
+    #[test]
+    fn test_azure_iot_hub_x509() {
+        let config = MqttConfig {
+            preset: Some(MqttPreset::AzureIotHub),
+            host: Some("wx.azure-devices.net".to_string()),
+            client_id: Some("backyard".to_string()),
+            client_cert: Some("/etc/wxlistener/device.crt".to_string()),
+            client_key: Some("/etc/wxlistener/device.key".to_string()),
+            ..MqttConfig::new()
+        };
+        let (resolved, sas) = resolve(&config).unwrap();
+        assert!(sas.is_none());
+        assert_eq!(resolved.password, None);
+
+        let no_credentials = MqttConfig {
+            client_cert: None,
+            ..config
+        };
+        assert!(resolve(&no_credentials).is_err());
+    }
+
+    #[test]
+    fn test_sas_token() {
+        let sas = AzureSas {
+            resource: "wx.azure-devices.net/devices/backyard".to_string(),
+            key: b"secret".to_vec(),
+            ttl: 3600,
+        };
+        let now = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        assert_eq!(
+            sas.token(now),
+            "SharedAccessSignature sr=wx.azure-devices.net%2Fdevices%2Fbackyard\
+             &sig=NHkguGy8tiPTTp07zsfLyd5gnB5L9Oq94bsy7MecBbU%3D&se=1772370000"
+        );
+    }
+
+    #[test]
+    fn test_parse_device_connection_string() {
+        let (host, device_id, key) = parse_device_connection_string(
+            "HostName=wx.azure-devices.net;DeviceId=backyard;SharedAccessKey=a2V5==",
+        )
+        .unwrap();
+        assert_eq!(host, "wx.azure-devices.net");
+        assert_eq!(device_id, "backyard");
+        assert_eq!(key.as_deref(), Some("a2V5=="));
+
+        assert!(parse_device_connection_string("HostName=wx.azure-devices.net").is_err());
+        assert!(parse_device_connection_string(
+            "HostName=h;DeviceId=d;ModuleId=m;SharedAccessKey=a2V5"
+        )
+        .is_err());
+    }
+}
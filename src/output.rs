@@ -1,39 +1,254 @@
+use anyhow::Result;
 use chrono::{DateTime, Utc};
+use serde::Deserialize;
 use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
 
-pub fn print_livedata(data: &HashMap<String, f64>, timestamp: &DateTime<Utc>) {
-    println!("============================================================");
-    println!(
-        "LIVE DATA - {}",
-        timestamp.format("%B %d, %Y at %I:%M:%S %p UTC")
-    );
-    println!("============================================================");
+use crate::derived::{beaufort_description, precip_type_description, rain_intensity_description};
+use crate::sensors::MAX_SIGNAL;
+use crate::units::Units;
 
-    let mut keys: Vec<_> = data.keys().collect();
-    keys.sort();
+/// When to color the console table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color a terminal, unless NO_COLOR is set
+    Auto,
+    Always,
+    Never,
+}
 
-    for key in keys {
-        let value = data[key];
-        let formatted = format_value(key, value);
-        println!("{:<20} : {}", key, formatted);
+impl ColorChoice {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            other => anyhow::bail!("Unknown --color '{}': use auto, always or never", other),
+        }
     }
 
-    println!("============================================================");
+    /// Whether to write ANSI colors to stdout
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            // https://no-color.org: set and not empty disables color
+            Self::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && io::stdout().is_terminal()
+            }
+        }
+    }
 }
 
-pub fn format_value(key: &str, value: f64) -> String {
+const RESET: &str = "\x1b[0m";
+const BLUE: &str = "\x1b[34m";
+const CYAN: &str = "\x1b[36m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+
+/// Color for a reading worth a second look: frost and heat, rain that is
+/// falling, and raised risks and warnings
+fn value_color(key: &str, value: f64) -> Option<&'static str> {
     match key {
-        k if k.contains("temp") || k == "dewpoint" || k == "windchill" || k == "heatindex" => {
-            format!("{:.1}°C", value)
+        k if k.ends_with("_risk") => match value as i32 {
+            i32::MIN..=1 => None,
+            2 => Some(YELLOW),
+            _ => Some(RED),
+        },
+        k if k.ends_with("_warning") => (value != 0.0).then_some(YELLOW),
+        k if is_temperature(k) => {
+            if value <= 0.0 {
+                Some(BLUE)
+            } else if value >= 30.0 {
+                Some(RED)
+            } else {
+                None
+            }
+        }
+        "rain_rate" | "piezo_rain_rate" => (value > 0.0).then_some(CYAN),
+        "rain_intensity" => match value as i32 {
+            i32::MIN..=0 => None,
+            1 | 2 => Some(CYAN),
+            _ => Some(RED),
+        },
+        _ => None,
+    }
+}
+
+/// Arrow showing how a reading moved since the previous poll. Wind
+/// direction wraps around, so it gets none.
+fn trend_arrow(key: &str, value: f64, previous: Option<f64>) -> &'static str {
+    match previous {
+        _ if key == "wind_dir" => "",
+        Some(previous) if value > previous => " ↑",
+        Some(previous) if value < previous => " ↓",
+        Some(_) => " →",
+        None => "",
+    }
+}
+
+/// The text table printed each poll when no output is configured, with
+/// trend arrows against the poll printed before it
+#[derive(Debug)]
+pub struct LiveTable {
+    color: bool,
+    units: Units,
+    previous: HashMap<String, f64>,
+}
+
+impl LiveTable {
+    pub fn new(color: bool, units: Units) -> Self {
+        Self {
+            color,
+            units,
+            previous: HashMap::new(),
+        }
+    }
+
+    pub fn print(&mut self, data: &HashMap<String, f64>, timestamp: &DateTime<Utc>) {
+        // One lock for the whole table, and values written straight to stdout
+        let _ = self.write(&mut io::stdout().lock(), data, timestamp);
+        self.previous.clone_from(data);
+    }
+
+    fn write(
+        &self,
+        out: &mut impl Write,
+        data: &HashMap<String, f64>,
+        timestamp: &DateTime<Utc>,
+    ) -> io::Result<()> {
+        writeln!(
+            out,
+            "============================================================"
+        )?;
+        writeln!(
+            out,
+            "LIVE DATA - {}",
+            timestamp.format("%B %d, %Y at %I:%M:%S %p UTC")
+        )?;
+        writeln!(
+            out,
+            "============================================================"
+        )?;
+
+        let mut keys: Vec<_> = data.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            let value = data[key];
+            let arrow = trend_arrow(key, value, self.previous.get(key).copied());
+            let formatted = FieldValue(key, value, self.units);
+            match value_color(key, value).filter(|_| self.color) {
+                Some(color) => writeln!(
+                    out,
+                    "{:<20} : {}{}{}{}",
+                    key, color, formatted, RESET, arrow
+                )?,
+                None => writeln!(out, "{:<20} : {}{}", key, formatted, arrow)?,
+            }
         }
-        k if k.contains("humid") => format!("{}%", value as i32),
-        k if k.contains("barometer") => format!("{:.1} hPa", value),
-        "wind_dir" => format!("{}°", value as i32),
-        k if k.contains("wind") || k.contains("gust") => format!("{:.1} m/s", value),
-        k if k.contains("rain") => format!("{:.1} mm", value),
-        "light" => format!("{:.1} lux", value),
-        "heap_free" => format!("{} bytes ({:.1} KB)", value as i32, value / 1024.0),
-        _ => format!("{}", value),
+
+        writeln!(
+            out,
+            "============================================================"
+        )
+    }
+}
+
+/// Describe a graded risk level (0-3)
+pub fn risk_level(value: f64) -> &'static str {
+    match value as i32 {
+        i32::MIN..=0 => "none",
+        1 => "low",
+        2 => "moderate",
+        _ => "high",
+    }
+}
+
+/// A metric reading displayed in the given units. Writes straight into the
+/// formatter, so printing a table doesn't allocate a string per field.
+pub struct FieldValue<'a>(pub &'a str, pub f64, pub Units);
+
+impl fmt::Display for FieldValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let FieldValue(key, value, units) = *self;
+        match key {
+            k if k.starts_with("sensor_rssi_") => write!(f, "{}/{}", value as u8, MAX_SIGNAL),
+            k if k.ends_with("_risk") => f.write_str(risk_level(value)),
+            k if k.ends_with("_warning") => f.write_str(if value != 0.0 { "yes" } else { "no" }),
+            "rain_intensity" => f.write_str(rain_intensity_description(value as u8)),
+            "precip_type_estimate" => f.write_str(precip_type_description(value as u8)),
+            k if k.ends_with("beaufort") => {
+                write!(f, "{} ({})", value as u8, beaufort_description(value as u8))
+            }
+            k if is_temperature(k) => {
+                let (value, unit) = units.temperature.convert(value);
+                write!(f, "{:.1}{}", value, unit)
+            }
+            k if k.contains("humid") => write!(f, "{}%", value as i32),
+            k if k.contains("barometer") => {
+                let (value, unit, decimals) = units.pressure.convert(value);
+                write!(f, "{:.*} {}", decimals, value, unit)
+            }
+            "wind_dir" => write!(f, "{}°", value as i32),
+            k if k.contains("wind") || k.contains("gust") => {
+                let (value, unit) = units.wind.convert(value);
+                write!(f, "{:.1} {}", value, unit)
+            }
+            k if k.contains("rain") => {
+                let (value, unit, decimals) = units.rain.convert(value);
+                write!(f, "{:.*} {}", decimals, value, unit)
+            }
+            "light" => write!(f, "{:.1} lux", value),
+            "co2" | "co2_24h" => write!(f, "{} ppm", value as i32),
+            k if k.starts_with("co2_pm") => write!(f, "{:.1} µg/m³", value),
+            "pressure_altitude" => write!(f, "{} m", value as i32),
+            k if k.ends_with("_volt") => write!(f, "{:.1} V", value),
+            "heap_free" => write!(f, "{} bytes ({:.1} KB)", value as i32, value / 1024.0),
+            _ => write!(f, "{}", value),
+        }
+    }
+}
+
+fn is_temperature(key: &str) -> bool {
+    key.contains("temp") || key.ends_with("dewpoint") || key == "windchill" || key == "heatindex"
+}
+
+/// Decimal places per field for published values, e.g. `absbarometer = 2`.
+/// `default` applies to every field without its own entry; fields with
+/// neither are left unrounded.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PrecisionConfig {
+    #[serde(flatten)]
+    pub decimals: HashMap<String, u32>,
+}
+
+impl PrecisionConfig {
+    fn decimals_for(&self, field: &str) -> Option<u32> {
+        self.decimals
+            .get(field)
+            .or_else(|| self.decimals.get("default"))
+            .copied()
+    }
+
+    /// Round a value to the configured decimal places for `field`
+    pub fn round(&self, field: &str, value: f64) -> f64 {
+        match self.decimals_for(field) {
+            Some(decimals) => {
+                let factor = 10f64.powi(decimals.min(15) as i32);
+                (value * factor).round() / factor
+            }
+            None => value,
+        }
+    }
+
+    /// Copy of `data` rounded for console, API, MQTT and HTTP output
+    pub fn rounded(&self, data: &HashMap<String, f64>) -> HashMap<String, f64> {
+        data.iter()
+            .map(|(field, &value)| (field.clone(), self.round(field, value)))
+            .collect()
     }
 }
 
@@ -41,6 +256,10 @@ pub fn format_value(key: &str, value: f64) -> String {
 mod tests {
     use super::*;
 
+    fn format_value(key: &str, value: f64) -> String {
+        FieldValue(key, value, Units::default()).to_string()
+    }
+
     #[test]
     fn test_format_value_temperature() {
         assert_eq!(format_value("outtemp", 25.5), "25.5°C");
@@ -100,12 +319,129 @@ mod tests {
         assert_eq!(format_value("heap_free", 1024.0), "1024 bytes (1.0 KB)");
     }
 
+    #[test]
+    fn test_format_value_indicators() {
+        assert_eq!(format_value("frost_risk", 0.0), "none");
+        assert_eq!(format_value("in_mold_risk", 2.0), "moderate");
+        assert_eq!(format_value("out_condensation_risk", 3.0), "high");
+        assert_eq!(format_value("frost_warning", 1.0), "yes");
+        assert_eq!(format_value("frost_warning", 0.0), "no");
+        assert_eq!(format_value("in_dewpoint", 12.3), "12.3°C");
+    }
+
+    #[test]
+    fn test_format_value_beaufort() {
+        assert_eq!(format_value("beaufort", 5.0), "5 (Fresh breeze)");
+        assert_eq!(format_value("gust_beaufort", 0.0), "0 (Calm)");
+        assert_eq!(format_value("rain_intensity", 2.0), "moderate");
+        assert_eq!(format_value("precip_type_estimate", 2.0), "mixed");
+    }
+
+    #[test]
+    fn test_format_value_pressure_correction() {
+        assert_eq!(format_value("qnh_barometer", 1012.04), "1012.0 hPa");
+        assert_eq!(format_value("barometer_difference", -1.5), "-1.5 hPa");
+        assert_eq!(format_value("barometer_mismatch_warning", 1.0), "yes");
+        assert_eq!(format_value("pressure_altitude", 152.0), "152 m");
+    }
+
+    #[test]
+    fn test_format_value_air_quality() {
+        assert_eq!(format_value("co2", 612.0), "612 ppm");
+        assert_eq!(format_value("co2_24h", 580.0), "580 ppm");
+        assert_eq!(format_value("co2_pm25", 7.3), "7.3 µg/m³");
+        assert_eq!(format_value("co2_pm1_24h", 3.0), "3.0 µg/m³");
+        assert_eq!(format_value("co2_temp", 21.4), "21.4°C");
+        assert_eq!(format_value("co2_humid", 48.0), "48%");
+    }
+
+    #[test]
+    fn test_format_value_ws90() {
+        assert_eq!(format_value("piezo_rain_day", 3.4), "3.4 mm");
+        assert_eq!(format_value("ws90_cap_volt", 3.2), "3.2 V");
+    }
+
+    #[test]
+    fn test_format_value_sensor_rssi() {
+        assert_eq!(format_value("sensor_rssi_wh65", 4.0), "4/4");
+        assert_eq!(format_value("sensor_rssi_wh40", 1.0), "1/4");
+    }
+
     #[test]
     fn test_format_value_unknown() {
         assert_eq!(format_value("unknown_field", 42.0), "42");
         assert_eq!(format_value("uv", 5.0), "5");
     }
 
+    #[test]
+    fn test_format_value_units() {
+        use crate::units::{PressureUnit, RainUnit, SpeedUnit, TemperatureUnit};
+
+        let units = Units {
+            wind: SpeedUnit::Knots,
+            pressure: PressureUnit::MillimetersOfMercury,
+            rain: RainUnit::Inches,
+            ..Units::default()
+        };
+        let format = |key, value| FieldValue(key, value, units).to_string();
+        assert_eq!(format("outtemp", 21.5), "21.5°C");
+        assert_eq!(format("wind_speed", 10.0), "19.4 kn");
+        assert_eq!(format("relbarometer", 1013.25), "760.0 mmHg");
+        assert_eq!(format("rain_day", 25.4), "1.00 in");
+        // Fields without a unit setting are unchanged
+        assert_eq!(format("wind_dir", 270.0), "270°");
+        assert_eq!(format("outhumid", 65.0), "65%");
+
+        let units = Units {
+            temperature: TemperatureUnit::Fahrenheit,
+            pressure: PressureUnit::InchesOfMercury,
+            ..Units::default()
+        };
+        assert_eq!(FieldValue("dewpoint", 0.0, units).to_string(), "32.0°F");
+        assert_eq!(
+            FieldValue("absbarometer", 1013.25, units).to_string(),
+            "29.92 inHg"
+        );
+    }
+
+    #[test]
+    fn test_precision_round() {
+        let mut decimals = HashMap::new();
+        decimals.insert("absbarometer".to_string(), 2);
+        decimals.insert("outtemp".to_string(), 0);
+        let precision = PrecisionConfig { decimals };
+
+        assert_eq!(precision.round("absbarometer", 1013.256), 1013.26);
+        assert_eq!(precision.round("outtemp", 21.6), 22.0);
+        assert_eq!(precision.round("outtemp", -3.5), -4.0);
+        // No entry and no default: unchanged
+        assert_eq!(precision.round("dewpoint", 12.345678), 12.345678);
+    }
+
+    #[test]
+    fn test_precision_default() {
+        let precision: PrecisionConfig = toml::from_str(
+            r#"
+            default = 1
+            absbarometer = 2
+            "#,
+        )
+        .unwrap();
+
+        let mut data = HashMap::new();
+        data.insert("dewpoint".to_string(), 12.345678);
+        data.insert("absbarometer".to_string(), 1013.256);
+        let rounded = precision.rounded(&data);
+        assert_eq!(rounded["dewpoint"], 12.3);
+        assert_eq!(rounded["absbarometer"], 1013.26);
+    }
+
+    fn table(table: &LiveTable, data: &HashMap<String, f64>) -> String {
+        let mut out = Vec::new();
+        table.write(&mut out, data, &Utc::now()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
     #[test]
     fn test_print_livedata() {
         let mut data = HashMap::new();
@@ -113,20 +449,25 @@ mod tests {
         data.insert("outhumid".to_string(), 65.0);
         data.insert("wind_speed".to_string(), 5.5);
 
-        let timestamp = Utc::now();
+        let out = table(&LiveTable::new(false, Units::default()), &data);
+        assert!(out.contains("outtemp              : 25.5°C\n"));
+        assert!(out.contains("wind_speed           : 5.5 m/s\n"));
 
-        // This test just ensures the function doesn't panic
-        // We can't easily test stdout without more complex mocking
-        print_livedata(&data, &timestamp);
+        // Printing to stdout doesn't panic
+        LiveTable::new(false, Units::default()).print(&data, &Utc::now());
     }
 
     #[test]
     fn test_print_livedata_empty() {
         let data = HashMap::new();
-        let timestamp = Utc::now();
 
         // Should handle empty data gracefully
-        print_livedata(&data, &timestamp);
+        assert_eq!(
+            table(&LiveTable::new(true, Units::default()), &data)
+                .lines()
+                .count(),
+            4
+        );
     }
 
     #[test]
@@ -136,10 +477,48 @@ mod tests {
         data.insert("a_field".to_string(), 2.0);
         data.insert("m_field".to_string(), 3.0);
 
-        let timestamp = Utc::now();
-
         // Keys should be sorted alphabetically
-        // This test ensures no panic with various keys
-        print_livedata(&data, &timestamp);
+        let out = table(&LiveTable::new(false, Units::default()), &data);
+        let a = out.find("a_field").unwrap();
+        let m = out.find("m_field").unwrap();
+        let z = out.find("z_field").unwrap();
+        assert!(a < m && m < z);
+    }
+
+    #[test]
+    fn test_print_livedata_color_and_trends() {
+        let mut data = HashMap::new();
+        data.insert("outtemp".to_string(), -2.0);
+        data.insert("rain_rate".to_string(), 0.0);
+        data.insert("wind_dir".to_string(), 90.0);
+
+        let mut live = LiveTable::new(true, Units::default());
+        let out = table(&live, &data);
+        assert!(out.contains("outtemp              : \x1b[34m-2.0°C\x1b[0m\n"));
+        assert!(out.contains("rain_rate            : 0.0 mm\n"));
+
+        live.previous.clone_from(&data);
+        data.insert("outtemp".to_string(), -1.5);
+        data.insert("rain_rate".to_string(), 2.4);
+        data.insert("wind_dir".to_string(), 180.0);
+        let out = table(&live, &data);
+        assert!(out.contains("outtemp              : \x1b[34m-1.5°C\x1b[0m ↑\n"));
+        assert!(out.contains("rain_rate            : \x1b[36m2.4 mm\x1b[0m ↑\n"));
+        assert!(out.contains("wind_dir             : 180°\n"));
+
+        // Without color only the arrows remain
+        live.color = false;
+        data.insert("outtemp".to_string(), -1.5);
+        let out = table(&live, &data);
+        assert!(out.contains("outtemp              : -1.5°C ↑\n"));
+        assert!(!out.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_color_choice() {
+        assert_eq!(ColorChoice::parse("always").unwrap(), ColorChoice::Always);
+        assert!(ColorChoice::parse("yes").is_err());
+        assert!(ColorChoice::Always.enabled());
+        assert!(!ColorChoice::Never.enabled());
     }
 }
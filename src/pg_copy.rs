@@ -0,0 +1,197 @@
+/// Bulk loading into PostgreSQL with binary COPY, for imports and backfills
+/// of years of readings. Rows are copied into a temporary staging table and
+/// moved into the weather data table with one INSERT ... SELECT, which keeps
+/// the upsert on the timestamp and lets a partitioned table route each row.
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::HashMap;
+
+use crate::database::WriteMode;
+use crate::provenance::Provenance;
+
+/// Readings copied per transaction
+pub const COPY_CHUNK: usize = 10_000;
+
+/// Temporary table the rows are copied into, dropped on commit
+const STAGING_TABLE: &str = "wxlistener_copy";
+
+/// Signature, flags and header extension length of the binary COPY format
+const HEADER: &[u8] = b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0";
+
+/// The staging table's columns: timestamp, `fields`, then metadata if stored
+fn staging_columns<'a>(fields: &[&'a str], metadata: bool) -> Vec<&'a str> {
+    std::iter::once("timestamp")
+        .chain(fields.iter().copied())
+        .chain(metadata.then_some("metadata"))
+        .collect()
+}
+
+pub fn create_staging_sql(fields: &[&str], metadata: bool) -> String {
+    let columns: Vec<String> = staging_columns(fields, metadata)
+        .into_iter()
+        .map(|column| {
+            let kind = match column {
+                "timestamp" => "TIMESTAMP WITH TIME ZONE",
+                "metadata" => "JSONB",
+                _ => "DOUBLE PRECISION",
+            };
+            format!("{} {}", column, kind)
+        })
+        .collect();
+    format!(
+        "CREATE TEMPORARY TABLE {} ({}) ON COMMIT DROP",
+        STAGING_TABLE,
+        columns.join(", ")
+    )
+}
+
+pub fn copy_sql(fields: &[&str], metadata: bool) -> String {
+    format!(
+        "COPY {} ({}) FROM STDIN (FORMAT binary)",
+        STAGING_TABLE,
+        staging_columns(fields, metadata).join(", ")
+    )
+}
+
+/// Move the staged rows into `target`, whose columns for the staging
+/// columns are `columns`, led by the timestamp column. As with single
+/// inserts, `mode` decides what happens to a timestamp already stored, and
+/// fields a row lacks keep their stored values.
+pub fn merge_sql(
+    target: &str,
+    columns: &[String],
+    fields: &[&str],
+    metadata: bool,
+    mode: WriteMode,
+) -> String {
+    let staged = staging_columns(fields, metadata);
+    if mode == WriteMode::Insert {
+        return format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {}",
+            target,
+            columns.join(", "),
+            staged.join(", "),
+            STAGING_TABLE
+        );
+    }
+    let conflict = if columns.len() == 1 || mode == WriteMode::SkipExisting {
+        "DO NOTHING".to_string()
+    } else {
+        let updates: Vec<String> = columns[1..]
+            .iter()
+            .map(|c| format!("{c} = COALESCE(EXCLUDED.{c}, target.{c})", c = c))
+            .collect();
+        format!("DO UPDATE SET {}", updates.join(", "))
+    };
+    // A timestamp can only be upserted once per statement
+    format!(
+        "INSERT INTO {} AS target ({}) SELECT DISTINCT ON (timestamp) {} FROM {} \
+         ORDER BY timestamp ON CONFLICT ({}) {}",
+        target,
+        columns.join(", "),
+        staged.join(", "),
+        STAGING_TABLE,
+        columns[0],
+        conflict
+    )
+}
+
+/// Encode readings as binary COPY data for `copy_sql`'s columns. Fields a
+/// reading lacks are NULL.
+pub fn encode<'a>(
+    records: impl IntoIterator<Item = (&'a DateTime<Utc>, &'a HashMap<String, f64>, &'a Provenance)>,
+    fields: &[&str],
+    metadata: bool,
+) -> Result<Vec<u8>> {
+    // Timestamps are microseconds since 2000-01-01 UTC
+    let epoch = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+    let column_count = (1 + fields.len() + usize::from(metadata)) as i16;
+
+    let mut buf = HEADER.to_vec();
+    for (timestamp, data, provenance) in records {
+        buf.extend_from_slice(&column_count.to_be_bytes());
+
+        let micros = (*timestamp - epoch).num_microseconds().unwrap_or(i64::MAX);
+        buf.extend_from_slice(&8i32.to_be_bytes());
+        buf.extend_from_slice(&micros.to_be_bytes());
+
+        for field in fields {
+            match data.get(*field) {
+                Some(value) => {
+                    buf.extend_from_slice(&8i32.to_be_bytes());
+                    buf.extend_from_slice(&value.to_be_bytes());
+                }
+                None => buf.extend_from_slice(&(-1i32).to_be_bytes()),
+            }
+        }
+
+        if metadata {
+            // JSONB is a version byte followed by the JSON text
+            let json = serde_json::to_vec(provenance)?;
+            buf.extend_from_slice(&(json.len() as i32 + 1).to_be_bytes());
+            buf.push(1);
+            buf.extend_from_slice(&json);
+        }
+    }
+    buf.extend_from_slice(&(-1i16).to_be_bytes());
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let timestamp = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 1).unwrap();
+        let data = HashMap::from([("outtemp".to_string(), 1.5)]);
+        let provenance = Provenance::poll(None, None, 0);
+        let buf = encode(
+            [(&timestamp, &data, &provenance)],
+            &["outtemp", "outhumid"],
+            false,
+        )
+        .unwrap();
+
+        let mut expected = HEADER.to_vec();
+        expected.extend_from_slice(&3i16.to_be_bytes());
+        expected.extend_from_slice(&8i32.to_be_bytes());
+        expected.extend_from_slice(&1_000_000i64.to_be_bytes());
+        expected.extend_from_slice(&8i32.to_be_bytes());
+        expected.extend_from_slice(&1.5f64.to_be_bytes());
+        expected.extend_from_slice(&(-1i32).to_be_bytes());
+        expected.extend_from_slice(&(-1i16).to_be_bytes());
+        assert_eq!(buf, expected);
+        assert_eq!(HEADER.len(), 19);
+    }
+
+    #[test]
+    fn test_merge_sql() {
+        let columns = vec!["ts".to_string(), "outtemp".to_string()];
+        assert_eq!(
+            merge_sql(
+                "weather.wx_records",
+                &columns,
+                &["outtemp"],
+                false,
+                WriteMode::Upsert
+            ),
+            "INSERT INTO weather.wx_records AS target (ts, outtemp) \
+             SELECT DISTINCT ON (timestamp) timestamp, outtemp FROM wxlistener_copy \
+             ORDER BY timestamp ON CONFLICT (ts) DO UPDATE SET \
+             outtemp = COALESCE(EXCLUDED.outtemp, target.outtemp)"
+        );
+        assert!(
+            merge_sql("wx", &columns, &["outtemp"], false, WriteMode::SkipExisting)
+                .ends_with("ON CONFLICT (ts) DO NOTHING")
+        );
+        assert_eq!(
+            merge_sql("wx", &columns, &["outtemp"], false, WriteMode::Insert),
+            "INSERT INTO wx (ts, outtemp) SELECT timestamp, outtemp FROM wxlistener_copy"
+        );
+        assert_eq!(
+            copy_sql(&["outtemp"], true),
+            "COPY wxlistener_copy (timestamp, outtemp, metadata) FROM STDIN (FORMAT binary)"
+        );
+    }
+}
@@ -1,7 +1,63 @@
 /// Protocol constants and packet building utilities
 /// Separated for easier testing
+use anyhow::Result;
+use std::fmt;
+
 pub const HEADER: [u8; 2] = [0xFF, 0xFF];
 
+/// Why a response was refused, telling a busy gateway apart from a frame
+/// that was damaged or answers something else
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseError {
+    /// A well-formed reply holding only a status byte where data was
+    /// expected: the gateway is busy (serving another client, scanning for
+    /// sensors, updating firmware) or refused the command
+    Busy { command: u8, status: u8 },
+    /// The frame arrived whole, but its checksum doesn't match
+    BadChecksum {
+        command: u8,
+        calculated: u8,
+        received: u8,
+    },
+    /// No header, too short, or the reply to a different command
+    Unexpected { command: u8, received: Option<u8> },
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Busy { command, status } => write!(
+                f,
+                "Gateway busy: command 0x{:02X} answered with status 0x{:02X} instead of data",
+                command, status
+            ),
+            Self::BadChecksum {
+                command,
+                calculated,
+                received,
+            } => write!(
+                f,
+                "Bad checksum in the response to command 0x{:02X}: calculated 0x{:02X}, received 0x{:02X}",
+                command, calculated, received
+            ),
+            Self::Unexpected {
+                command,
+                received: Some(received),
+            } => write!(
+                f,
+                "Expected a response to command 0x{:02X}, got one to 0x{:02X}",
+                command, received
+            ),
+            Self::Unexpected {
+                command,
+                received: None,
+            } => write!(f, "Malformed response to command 0x{:02X}", command),
+        }
+    }
+}
+
+impl std::error::Error for ResponseError {}
+
 pub fn build_cmd_packet(cmd_code: u8, payload: &[u8]) -> Vec<u8> {
     let size = 1 + 1 + payload.len() + 1;
     let mut body = vec![cmd_code, size as u8];
@@ -20,25 +76,74 @@ pub fn calc_checksum(data: &[u8]) -> u8 {
 }
 
 pub fn verify_response(response: &[u8], expected_cmd: u8) -> bool {
-    if response.len() < 5 {
-        return false;
+    check_response(response, expected_cmd).is_ok()
+}
+
+/// `verify_response`, saying what is wrong
+pub fn check_response(response: &[u8], expected_cmd: u8) -> Result<(), ResponseError> {
+    if response.len() < 5 || response[..2] != HEADER {
+        return Err(ResponseError::Unexpected {
+            command: expected_cmd,
+            received: None,
+        });
+    }
+    if response[2] != expected_cmd {
+        return Err(ResponseError::Unexpected {
+            command: expected_cmd,
+            received: Some(response[2]),
+        });
     }
 
-    // Check header
-    if response[0] != HEADER[0] || response[1] != HEADER[1] {
-        return false;
+    let calculated = calc_checksum(&response[2..response.len() - 1]);
+    let received = response[response.len() - 1];
+    if calculated != received {
+        return Err(ResponseError::BadChecksum {
+            command: expected_cmd,
+            calculated,
+            received,
+        });
     }
+    Ok(())
+}
 
-    // Check command code
-    if response[2] != expected_cmd {
-        return false;
+/// Format a frame as space-separated hex bytes for protocol traces
+pub fn hex_frame(frame: &[u8]) -> String {
+    frame
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Return the data bytes of a verified response, checking its size field
+/// against the bytes actually received. `wide_size` selects the 2-byte
+/// big-endian size field used by live data and sensor ID responses.
+pub fn response_payload(response: &[u8], wide_size: bool) -> Result<&[u8]> {
+    // header(2) + cmd(1) + size field
+    let start = if wide_size { 5 } else { 4 };
+    if response.len() < start + 1 {
+        anyhow::bail!("Response too short ({} bytes)", response.len());
     }
 
-    // Verify checksum
-    let calc_checksum = calc_checksum(&response[2..response.len() - 1]);
-    let resp_checksum = response[response.len() - 1];
+    let size = if wide_size {
+        ((response[3] as usize) << 8) | (response[4] as usize)
+    } else {
+        response[3] as usize
+    };
 
-    calc_checksum == resp_checksum
+    // size counts cmd + size field + data + checksum
+    if size < start - 1 {
+        anyhow::bail!("Response size field {} is too small", size);
+    }
+    if size + 2 > response.len() {
+        anyhow::bail!(
+            "Response size field {} exceeds the {} bytes received",
+            size,
+            response.len()
+        );
+    }
+
+    Ok(&response[start..size + 1])
 }
 
 #[cfg(test)]
@@ -109,6 +214,84 @@ mod tests {
         assert!(!verify_response(&response, 0x50));
     }
 
+    #[test]
+    fn test_check_response() {
+        assert_eq!(
+            check_response(&[0xFF, 0xFF, 0x50, 0x03, 0x00, 0xFF], 0x50),
+            Err(ResponseError::BadChecksum {
+                command: 0x50,
+                calculated: 0x53,
+                received: 0xFF
+            })
+        );
+        assert_eq!(
+            check_response(&[0xFF, 0xFF, 0x50, 0x03, 0x00, 0x53], 0x27),
+            Err(ResponseError::Unexpected {
+                command: 0x27,
+                received: Some(0x50)
+            })
+        );
+        assert_eq!(
+            check_response(&[0xFF, 0xFF, 0x50], 0x50),
+            Err(ResponseError::Unexpected {
+                command: 0x50,
+                received: None
+            })
+        );
+        assert_eq!(
+            check_response(&[0xFF, 0xFF, 0x50, 0x03, 0x00, 0x53], 0x50),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_hex_frame() {
+        assert_eq!(hex_frame(&[0xFF, 0xFF, 0x27, 0x03, 0x2A]), "FF FF 27 03 2A");
+        assert_eq!(hex_frame(&[]), "");
+    }
+
+    #[test]
+    fn test_response_payload() {
+        let response = vec![0xFF, 0xFF, 0x50, 0x05, 0x41, 0x42, 0xD8];
+        assert_eq!(response_payload(&response, false).unwrap(), b"AB");
+    }
+
+    #[test]
+    fn test_response_payload_wide() {
+        let response = vec![0xFF, 0xFF, 0x27, 0x00, 0x06, 0x07, 0x41, 0x6F];
+        assert_eq!(response_payload(&response, true).unwrap(), &[0x07, 0x41]);
+    }
+
+    #[test]
+    fn test_response_payload_empty() {
+        let response = vec![0xFF, 0xFF, 0x50, 0x03, 0x53];
+        assert!(response_payload(&response, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_response_payload_size_too_small() {
+        let response = vec![0xFF, 0xFF, 0x50, 0x02, 0x52];
+        assert!(response_payload(&response, false).is_err());
+
+        let response = vec![0xFF, 0xFF, 0x27, 0x00, 0x01, 0x28];
+        assert!(response_payload(&response, true).is_err());
+    }
+
+    #[test]
+    fn test_response_payload_size_too_large() {
+        let response = vec![0xFF, 0xFF, 0x50, 0xFF, 0x41, 0x90];
+        assert!(response_payload(&response, false).is_err());
+
+        let response = vec![0xFF, 0xFF, 0x27, 0x01, 0x00, 0x41, 0x69];
+        assert!(response_payload(&response, true).is_err());
+    }
+
+    #[test]
+    fn test_response_payload_truncated_frame() {
+        assert!(response_payload(&[0xFF, 0xFF, 0x50, 0x03], false).is_err());
+        assert!(response_payload(&[0xFF, 0xFF, 0x27, 0x00, 0x04], true).is_err());
+    }
+
     // Property-based tests
     mod proptests {
         use super::*;
@@ -173,6 +356,22 @@ mod tests {
                 let packet = build_cmd_packet(cmd, &payload);
                 prop_assert!(verify_response(&packet, cmd));
             }
+
+            #[test]
+            fn prop_response_payload_never_panics(
+                data in prop::collection::vec(any::<u8>(), 0..40),
+                wide_size: bool
+            ) {
+                // Arbitrary frames yield a payload or an error, never a panic
+                let _ = response_payload(&data, wide_size);
+            }
+
+            #[test]
+            fn prop_response_payload_roundtrip(cmd: u8, payload in prop::collection::vec(any::<u8>(), 0..20)) {
+                // The payload of a packet we build comes back unchanged
+                let packet = build_cmd_packet(cmd, &payload);
+                prop_assert_eq!(response_payload(&packet, false).unwrap(), &payload[..]);
+            }
         }
     }
 }
@@ -0,0 +1,122 @@
+/// Where a reading came from, carried alongside its values so stored and
+/// reported data can be traced back to the gateway and decode that made it
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadingSource {
+    /// Polled from the gateway's live data
+    Poll,
+    /// Imported or backfilled after the fact
+    Replay,
+}
+
+/// How far a reading can be trusted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    #[default]
+    Good,
+    /// Taken in maintenance mode, e.g. while the sensors were being cleaned
+    Suspect,
+}
+
+impl Quality {
+    fn is_good(&self) -> bool {
+        *self == Self::Good
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub source: ReadingSource,
+    /// Gateway MAC address, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub station: Option<String>,
+    /// Gateway firmware version, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<String>,
+    /// Unknown or truncated records skipped while decoding the live data
+    #[serde(default)]
+    pub decode_warnings: u32,
+    /// What a replayed reading was read from: weewx, cumulus, ecowitt_cloud
+    /// or backup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Only written when the reading is suspect
+    #[serde(default, skip_serializing_if = "Quality::is_good")]
+    pub quality: Quality,
+}
+
+impl Provenance {
+    /// A reading polled from the gateway identified by `station` and `firmware`
+    pub fn poll(station: Option<String>, firmware: Option<String>, decode_warnings: u32) -> Self {
+        Self {
+            source: ReadingSource::Poll,
+            station,
+            firmware,
+            decode_warnings,
+            origin: None,
+            quality: Quality::Good,
+        }
+    }
+
+    /// A reading replayed from `origin`, for `station` when it's known
+    #[cfg(feature = "db")]
+    pub fn replay(origin: &str, station: Option<String>) -> Self {
+        Self {
+            source: ReadingSource::Replay,
+            station,
+            firmware: None,
+            decode_warnings: 0,
+            origin: Some(origin.to_string()),
+            quality: Quality::Good,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poll_provenance_json() {
+        let poll = Provenance::poll(
+            Some("AA:BB:CC:DD:EE:FF".to_string()),
+            Some("GW1000_V1.7.3".to_string()),
+            2,
+        );
+        assert_eq!(
+            serde_json::to_value(&poll).unwrap(),
+            serde_json::json!({
+                "source": "poll",
+                "station": "AA:BB:CC:DD:EE:FF",
+                "firmware": "GW1000_V1.7.3",
+                "decode_warnings": 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_suspect_provenance_json() {
+        let mut poll = Provenance::poll(None, None, 0);
+        poll.quality = Quality::Suspect;
+        let json = serde_json::to_value(&poll).unwrap();
+        assert_eq!(json["quality"], "suspect");
+        assert_eq!(serde_json::from_value::<Provenance>(json).unwrap(), poll);
+    }
+
+    #[test]
+    #[cfg(feature = "db")]
+    fn test_replay_provenance_json() {
+        let replay = Provenance::replay("weewx", None);
+        assert_eq!(
+            serde_json::to_value(&replay).unwrap(),
+            serde_json::json!({
+                "source": "replay",
+                "decode_warnings": 0,
+                "origin": "weewx"
+            })
+        );
+    }
+}
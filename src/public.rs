@@ -0,0 +1,122 @@
+/// Public view of the web API: what requests without an API token see when
+/// `[web.public]` is set, so a dashboard can be shared without revealing
+/// when anyone is home (indoor temperature, humidity and CO2) or exactly
+/// where the station is
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::output::PrecisionConfig;
+use crate::station::StationConfig;
+use crate::units::Units;
+
+/// `[web.public]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+pub struct PublicConfig {
+    /// Fields left out, exact names or prefixes ending in `*` (default: the
+    /// indoor sensors, intemp, inhumid and the WH45 co2* readings)
+    #[serde(default = "default_omit")]
+    pub omit: Vec<String>,
+    /// Decimal places per field, and `default`, applied after `[precision]`
+    #[serde(default)]
+    pub precision: PrecisionConfig,
+    /// Decimal places of the station's latitude and longitude (default: 2,
+    /// about a kilometre)
+    #[serde(default = "default_coordinate_decimals")]
+    pub coordinate_decimals: u32,
+    /// Units for the formatted `data` strings, those of `[web]`
+    #[serde(skip)]
+    pub units: Units,
+}
+
+fn default_omit() -> Vec<String> {
+    ["intemp", "inhumid", "co2*"].map(String::from).to_vec()
+}
+
+fn default_coordinate_decimals() -> u32 {
+    2
+}
+
+impl Default for PublicConfig {
+    fn default() -> Self {
+        Self {
+            omit: default_omit(),
+            precision: PrecisionConfig::default(),
+            coordinate_decimals: default_coordinate_decimals(),
+            units: Units::default(),
+        }
+    }
+}
+
+impl PublicConfig {
+    /// Whether `field` is served publicly
+    pub fn shows(&self, field: &str) -> bool {
+        !self.omit.iter().any(|omit| match omit.strip_suffix('*') {
+            Some(prefix) => field.starts_with(prefix),
+            None => field == omit,
+        })
+    }
+
+    pub fn round(&self, field: &str, value: f64) -> f64 {
+        self.precision.round(field, value)
+    }
+
+    /// The public readings: omitted fields removed, the rest rounded
+    pub fn readings(&self, data: &HashMap<String, f64>) -> HashMap<String, f64> {
+        data.iter()
+            .filter(|(field, _)| self.shows(field))
+            .map(|(field, &value)| (field.clone(), self.round(field, value)))
+            .collect()
+    }
+
+    /// The station with its coordinates rounded
+    pub fn station(&self, station: &StationConfig) -> StationConfig {
+        let factor = 10f64.powi(self.coordinate_decimals.min(15) as i32);
+        let round = |degrees: Option<f64>| degrees.map(|d| (d * factor).round() / factor);
+        StationConfig {
+            latitude: round(station.latitude),
+            longitude: round(station.longitude),
+            ..station.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_readings() {
+        let public: PublicConfig = toml::from_str("precision = { default = 0 }").unwrap();
+        let data = HashMap::from([
+            ("outtemp".to_string(), 12.34),
+            ("intemp".to_string(), 21.5),
+            ("co2".to_string(), 640.0),
+            ("temp_ch1".to_string(), 19.2),
+        ]);
+        let readings = public.readings(&data);
+        assert_eq!(
+            readings,
+            HashMap::from([
+                ("outtemp".to_string(), 12.0),
+                ("temp_ch1".to_string(), 19.0),
+            ])
+        );
+        assert!(!public.shows("co2_24h"));
+        assert!(public.shows("outhumid"));
+    }
+
+    #[test]
+    fn test_public_station() {
+        let public = PublicConfig::default();
+        let station = StationConfig {
+            name: Some("Backyard".to_string()),
+            latitude: Some(52.37403),
+            longitude: Some(4.88969),
+            ..Default::default()
+        };
+        let shown = public.station(&station);
+        assert_eq!(shown.latitude, Some(52.37));
+        assert_eq!(shown.longitude, Some(4.89));
+        assert_eq!(shown.name, station.name);
+    }
+}
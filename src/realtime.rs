@@ -0,0 +1,338 @@
+/// Cumulus realtime.txt and Weather Display clientraw.txt, the files most
+/// weather website templates (Saratoga, Meteotemplate, ...) read
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use std::collections::HashMap;
+
+use crate::derived::beaufort;
+use crate::units::LUX_PER_WM2;
+
+/// Cumulus version and build reported in realtime.txt; templates check these
+/// to pick a parser
+const CUMULUS_VERSION: &str = "1.9.4";
+const CUMULUS_BUILD: &str = "1099";
+
+/// Fields in clientraw.txt, the last being the version marker
+const CLIENTRAW_FIELDS: usize = 178;
+const CLIENTRAW_VERSION: &str = "!!C10.37S112!!";
+
+const MS_TO_KNOTS: f64 = 1.943_844;
+const METRES_TO_FEET: f64 = 3.280_84;
+
+const COMPASS_POINTS: [&str; 16] = [
+    "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW",
+    "NNW",
+];
+
+/// 16-point compass name of a bearing in degrees
+pub fn compass_point(bearing: f64) -> &'static str {
+    let index = (bearing.rem_euclid(360.0) / 22.5).round() as usize % 16;
+    COMPASS_POINTS[index]
+}
+
+/// Estimated cloud base in metres from the temperature/dew point spread
+pub fn cloud_base(temp: f64, dewpoint: f64) -> f64 {
+    ((temp - dewpoint) * 125.0).max(0.0)
+}
+
+/// Canadian humidex from temperature and dew point (°C)
+pub fn humidex(temp: f64, dewpoint: f64) -> f64 {
+    let vapour = 6.11 * (5417.753 * (1.0 / 273.16 - 1.0 / (273.15 + dewpoint))).exp();
+    temp + 0.5555 * (vapour - 10.0)
+}
+
+/// Australian apparent temperature from temperature (°C), relative
+/// humidity (%) and wind speed (m/s)
+pub fn apparent_temperature(temp: f64, humidity: f64, wind_speed: f64) -> f64 {
+    let vapour = humidity / 100.0 * 6.105 * (17.27 * temp / (237.7 + temp)).exp();
+    temp + 0.33 * vapour - 0.70 * wind_speed - 4.0
+}
+
+/// Readings that some fields are calculated from, when the gateway has them
+struct Station<'a> {
+    readings: &'a HashMap<String, f64>,
+}
+
+impl Station<'_> {
+    fn get(&self, key: &str) -> Option<f64> {
+        self.readings.get(key).copied().filter(|v| v.is_finite())
+    }
+
+    fn dewpoint(&self) -> Option<f64> {
+        self.get("dewpoint")
+    }
+
+    fn solar(&self) -> Option<f64> {
+        self.get("light").map(|lux| lux / LUX_PER_WM2)
+    }
+
+    fn cloud_base(&self) -> Option<f64> {
+        Some(cloud_base(self.get("outtemp")?, self.dewpoint()?))
+    }
+
+    fn humidex(&self) -> Option<f64> {
+        Some(humidex(self.get("outtemp")?, self.dewpoint()?))
+    }
+
+    fn apparent(&self) -> Option<f64> {
+        Some(apparent_temperature(
+            self.get("outtemp")?,
+            self.get("outhumid")?,
+            self.get("wind_speed").unwrap_or(0.0),
+        ))
+    }
+}
+
+/// Cumulus realtime.txt for one poll at station local `time`, in m/s, °C,
+/// hPa and mm. Cumulus has no marker for missing values, so readings the
+/// gateway doesn't report are 0. Daily extremes and trends wxlistener
+/// doesn't track repeat the current reading, with times of 00:00.
+pub fn realtime_txt(time: NaiveDateTime, readings: &HashMap<String, f64>) -> String {
+    let station = Station { readings };
+    let num = |value: Option<f64>| format!("{:.1}", value.unwrap_or(0.0));
+    let int = |value: Option<f64>| format!("{:.0}", value.unwrap_or(0.0));
+    let reading = |key: &str| num(station.get(key));
+
+    let wind_dir = station.get("wind_dir");
+    let compass = wind_dir.map_or("---", compass_point);
+    let solar = station.solar();
+    let daylight = if solar.is_some_and(|s| s > 0.0) {
+        "1"
+    } else {
+        "0"
+    };
+
+    let fields: [String; 58] = [
+        time.format("%d/%m/%y").to_string(),
+        time.format("%H:%M:%S").to_string(),
+        reading("outtemp"),
+        int(station.get("outhumid")),
+        reading("dewpoint"),
+        reading("wind_speed"),
+        reading("wind_speed"),
+        int(wind_dir),
+        reading("rain_rate"),
+        reading("rain_day"),
+        reading("relbarometer"),
+        compass.to_string(),
+        int(station
+            .get("wind_speed")
+            .map(|speed| beaufort(speed) as f64)),
+        "m/s".to_string(),
+        "C".to_string(),
+        "hPa".to_string(),
+        "mm".to_string(),
+        // Wind run and pressure trend
+        num(None),
+        num(None),
+        reading("rain_month"),
+        reading("rain_year"),
+        // Yesterday's rain
+        num(None),
+        reading("intemp"),
+        int(station.get("inhumid")),
+        reading("windchill"),
+        // Temperature trend
+        num(None),
+        // Today's high and low temperature
+        reading("outtemp"),
+        "00:00".to_string(),
+        reading("outtemp"),
+        "00:00".to_string(),
+        // Today's high average wind and gust
+        reading("day_max_wind"),
+        "00:00".to_string(),
+        reading("day_max_wind"),
+        "00:00".to_string(),
+        // Today's high and low pressure
+        reading("relbarometer"),
+        "00:00".to_string(),
+        reading("relbarometer"),
+        "00:00".to_string(),
+        CUMULUS_VERSION.to_string(),
+        CUMULUS_BUILD.to_string(),
+        reading("gust_speed"),
+        reading("heatindex"),
+        num(station.humidex()),
+        reading("uvi"),
+        // Evapotranspiration
+        num(None),
+        int(solar),
+        int(wind_dir),
+        reading("rain_hour"),
+        // Forecast number
+        "0".to_string(),
+        daylight.to_string(),
+        // Sensor contact lost
+        "0".to_string(),
+        compass.to_string(),
+        int(station.cloud_base()),
+        "m".to_string(),
+        num(station.apparent()),
+        // Sunshine hours, theoretical max solar and "is sunny"
+        num(None),
+        "0".to_string(),
+        "0".to_string(),
+    ];
+    fields.join(" ") + "\n"
+}
+
+/// Weather Display clientraw.txt for one poll at station local `time`, in
+/// knots, °C, hPa and mm. Fields wxlistener has no value for are "-".
+pub fn clientraw_txt(
+    time: NaiveDateTime,
+    readings: &HashMap<String, f64>,
+    station_name: &str,
+) -> String {
+    let station = Station { readings };
+    let mut fields = vec!["-".to_string(); CLIENTRAW_FIELDS];
+    let mut set = |index: usize, value: Option<f64>, decimals: usize| {
+        if let Some(value) = value {
+            fields[index] = format!("{:.*}", decimals, value);
+        }
+    };
+    let knots = |key: &str| station.get(key).map(|speed| speed * MS_TO_KNOTS);
+
+    set(1, knots("wind_speed"), 1);
+    set(2, knots("gust_speed"), 1);
+    set(3, station.get("wind_dir"), 0);
+    set(4, station.get("outtemp"), 1);
+    set(5, station.get("outhumid"), 0);
+    set(6, station.get("relbarometer"), 1);
+    set(7, station.get("rain_day"), 1);
+    set(8, station.get("rain_month"), 1);
+    set(9, station.get("rain_year"), 1);
+    set(10, station.get("rain_rate").map(|rate| rate / 60.0), 2);
+    set(12, station.get("intemp"), 1);
+    set(13, station.get("inhumid"), 0);
+    for channel in 1..=6 {
+        set(19 + channel, station.get(&format!("temp_ch{}", channel)), 1);
+    }
+    for channel in 1..=3 {
+        set(
+            25 + channel,
+            station.get(&format!("humid_ch{}", channel)),
+            0,
+        );
+    }
+    set(29, Some(time.hour() as f64), 0);
+    set(30, Some(time.minute() as f64), 0);
+    set(31, Some(time.second() as f64), 0);
+    set(35, Some(time.day() as f64), 0);
+    set(36, Some(time.month() as f64), 0);
+    set(44, station.get("windchill"), 1);
+    set(45, station.humidex(), 1);
+    set(71, knots("day_max_wind"), 1);
+    set(72, station.dewpoint(), 1);
+    set(
+        73,
+        station.cloud_base().map(|base| base * METRES_TO_FEET),
+        0,
+    );
+    set(79, station.get("uvi"), 1);
+    set(112, station.get("heatindex"), 1);
+    set(117, station.get("wind_dir"), 0);
+    set(127, station.solar(), 0);
+
+    fields[0] = "12345".to_string();
+    // Spaces separate fields, so the name can't have any
+    fields[32] = format!(
+        "{}-{}",
+        station_name.replace(' ', "_"),
+        time.format("%H:%M:%S")
+    );
+    fields[74] = time.format("%d/%m/%Y").to_string();
+    fields[CLIENTRAW_FIELDS - 1] = CLIENTRAW_VERSION.to_string();
+    fields.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn time() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, 7)
+            .unwrap()
+            .and_hms_opt(14, 5, 9)
+            .unwrap()
+    }
+
+    fn readings() -> HashMap<String, f64> {
+        [
+            ("outtemp", 12.3),
+            ("outhumid", 71.0),
+            ("dewpoint", 7.2),
+            ("wind_speed", 3.4),
+            ("gust_speed", 5.1),
+            ("wind_dir", 225.0),
+            ("rain_rate", 1.2),
+            ("rain_day", 4.6),
+            ("relbarometer", 1013.2),
+            ("intemp", 21.5),
+            ("inhumid", 45.0),
+            ("light", 25340.0),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+    }
+
+    #[test]
+    fn test_compass_point() {
+        assert_eq!(compass_point(0.0), "N");
+        assert_eq!(compass_point(11.0), "N");
+        assert_eq!(compass_point(12.0), "NNE");
+        assert_eq!(compass_point(225.0), "SW");
+        assert_eq!(compass_point(355.0), "N");
+    }
+
+    #[test]
+    fn test_realtime_txt() {
+        let text = realtime_txt(time(), &readings());
+        assert!(text.ends_with('\n'));
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        assert_eq!(fields.len(), 58);
+        assert_eq!(
+            &fields[..12],
+            [
+                "07/03/26", "14:05:09", "12.3", "71", "7.2", "3.4", "3.4", "225", "1.2", "4.6",
+                "1013.2", "SW"
+            ]
+        );
+        assert_eq!(&fields[12..17], ["3", "m/s", "C", "hPa", "mm"]);
+        assert_eq!(fields[38], CUMULUS_VERSION);
+        assert_eq!(fields[45], "200");
+        assert_eq!(fields[49], "1");
+        // (12.3 - 7.2) * 125
+        assert_eq!(fields[52], "638");
+    }
+
+    #[test]
+    fn test_realtime_txt_missing_readings() {
+        let fields: Vec<String> = realtime_txt(time(), &HashMap::new())
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        assert_eq!(fields.len(), 58);
+        assert_eq!(fields[2], "0.0");
+        assert_eq!(fields[11], "---");
+        assert_eq!(fields[49], "0");
+    }
+
+    #[test]
+    fn test_clientraw_txt() {
+        let text = clientraw_txt(time(), &readings(), "Back Garden");
+        let fields: Vec<&str> = text.split(' ').collect();
+        assert_eq!(fields.len(), CLIENTRAW_FIELDS);
+        assert_eq!(fields[0], "12345");
+        // 3.4 m/s and 5.1 m/s in knots
+        assert_eq!(&fields[1..4], ["6.6", "9.9", "225"]);
+        assert_eq!(fields[4], "12.3");
+        assert_eq!(fields[10], "0.02");
+        assert_eq!(fields[11], "-");
+        assert_eq!(&fields[29..33], ["14", "5", "9", "Back_Garden-14:05:09"]);
+        assert_eq!(fields[74], "07/03/2026");
+        assert_eq!(fields[127], "200");
+        assert_eq!(fields[CLIENTRAW_FIELDS - 1], CLIENTRAW_VERSION);
+    }
+}
@@ -0,0 +1,287 @@
+/// Hourly and daily rollups of the weather data table, so history queries
+/// over months or years read a few thousand rows instead of millions
+use chrono::Duration;
+use std::sync::Arc;
+
+use crate::database::DatabaseWriter;
+
+/// Seconds between refreshes of the rollups kept by wxlistener
+const REFRESH_INTERVAL: u64 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollup {
+    Hourly,
+    Daily,
+}
+
+impl Rollup {
+    pub const ALL: [Rollup; 2] = [Rollup::Hourly, Rollup::Daily];
+
+    /// Appended to the weather data table name, e.g. wx_records_1h
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Hourly => "_1h",
+            Self::Daily => "_1d",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Hourly => "hourly",
+            Self::Daily => "daily",
+        }
+    }
+
+    /// The rollup read for a query spanning `span`: hourly rows past a week,
+    /// daily rows past 90 days, raw rows otherwise
+    pub fn for_span(span: Duration) -> Option<Self> {
+        if span > Duration::days(90) {
+            Some(Self::Daily)
+        } else if span > Duration::days(7) {
+            Some(Self::Hourly)
+        } else {
+            None
+        }
+    }
+
+    /// SQL for the bucket a row's timestamp falls in
+    fn bucket_sql(self, dialect: Dialect) -> &'static str {
+        match (dialect, self) {
+            (Dialect::Postgres, Self::Hourly) => {
+                "date_trunc('hour', timestamp AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'"
+            }
+            (Dialect::Postgres, Self::Daily) => {
+                "date_trunc('day', timestamp AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'"
+            }
+            (Dialect::MySql, Self::Hourly) => {
+                "CAST(DATE_FORMAT(timestamp, '%Y-%m-%d %H:00:00') AS DATETIME)"
+            }
+            (Dialect::MySql, Self::Daily) => "CAST(DATE(timestamp) AS DATETIME)",
+            (Dialect::Timescale, Self::Hourly) => "time_bucket(INTERVAL '1 hour', timestamp)",
+            (Dialect::Timescale, Self::Daily) => "time_bucket(INTERVAL '1 day', timestamp)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Postgres,
+    MySql,
+    /// PostgreSQL with the weather data table as a TimescaleDB hypertable
+    Timescale,
+}
+
+/// Aggregates for each field: the mean under the field's own name, plus
+/// `<field>_min` and `<field>_max`. Directions get the vector mean, in
+/// degrees from -180 to 180.
+fn aggregate_columns(fields: &[&str]) -> Vec<String> {
+    fields
+        .iter()
+        .map(|field| {
+            let mean = if *field == "wind_dir" {
+                "DEGREES(ATAN2(AVG(SIN(RADIANS(wind_dir))), AVG(COS(RADIANS(wind_dir)))))"
+                    .to_string()
+            } else {
+                format!("AVG({})", field)
+            };
+            format!(
+                "{mean} AS {field}, MIN({field}) AS {field}_min, MAX({field}) AS {field}_max",
+                mean = mean,
+                field = field
+            )
+        })
+        .collect()
+}
+
+/// SELECT of one row per bucket from `source`, for rows at or after the
+/// first placeholder when `since` is given
+fn select_sql(
+    rollup: Rollup,
+    dialect: Dialect,
+    source: &str,
+    fields: &[&str],
+    since: Option<&str>,
+) -> String {
+    format!(
+        "SELECT {} AS bucket, COUNT(*) AS samples, {} FROM {}{} GROUP BY {}",
+        rollup.bucket_sql(dialect),
+        aggregate_columns(fields).join(", "),
+        source,
+        since.map_or(String::new(), |placeholder| format!(
+            " WHERE timestamp >= {}",
+            placeholder
+        )),
+        rollup.bucket_sql(dialect)
+    )
+}
+
+/// Table a rollup is kept in when wxlistener maintains it
+pub fn create_table_sql(table: &str, fields: &[&str], postgres: bool) -> String {
+    let (timestamp, double) = if postgres {
+        ("TIMESTAMP WITH TIME ZONE", "DOUBLE PRECISION")
+    } else {
+        ("TIMESTAMP", "DOUBLE")
+    };
+    let columns: Vec<String> = fields
+        .iter()
+        .flat_map(|field| {
+            [
+                format!("{} {}", field, double),
+                format!("{}_min {}", field, double),
+                format!("{}_max {}", field, double),
+            ]
+        })
+        .collect();
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (bucket {} NOT NULL PRIMARY KEY, samples INTEGER NOT NULL, {})",
+        table,
+        timestamp,
+        columns.join(", ")
+    )
+}
+
+/// Recompute the buckets holding rows at or after the bound parameter and
+/// upsert them into `target`
+pub fn refresh_sql(
+    rollup: Rollup,
+    postgres: bool,
+    source: &str,
+    target: &str,
+    fields: &[&str],
+) -> String {
+    let columns: Vec<String> = std::iter::once("samples".to_string())
+        .chain(
+            fields
+                .iter()
+                .flat_map(|f| [f.to_string(), format!("{}_min", f), format!("{}_max", f)]),
+        )
+        .collect();
+    let (dialect, placeholder) = if postgres {
+        (Dialect::Postgres, "$1")
+    } else {
+        (Dialect::MySql, "?")
+    };
+    let select = select_sql(rollup, dialect, source, fields, Some(placeholder));
+    let conflict = if postgres {
+        let updates: Vec<String> = columns
+            .iter()
+            .map(|c| format!("{} = EXCLUDED.{}", c, c))
+            .collect();
+        format!("ON CONFLICT (bucket) DO UPDATE SET {}", updates.join(", "))
+    } else {
+        let updates: Vec<String> = columns
+            .iter()
+            .map(|c| format!("{} = VALUES({})", c, c))
+            .collect();
+        format!("ON DUPLICATE KEY UPDATE {}", updates.join(", "))
+    };
+    format!(
+        "INSERT INTO {} (bucket, {}) {} {}",
+        target,
+        columns.join(", "),
+        select,
+        conflict
+    )
+}
+
+/// A TimescaleDB continuous aggregate standing in for the rollup table,
+/// and the policy that keeps it refreshed. Real-time aggregation covers
+/// the buckets the policy hasn't reached yet.
+pub fn continuous_aggregate_sql(
+    rollup: Rollup,
+    source: &str,
+    target: &str,
+    fields: &[&str],
+) -> [String; 2] {
+    let (start_offset, schedule) = match rollup {
+        Rollup::Hourly => ("3 hours", "30 minutes"),
+        Rollup::Daily => ("3 days", "1 hour"),
+    };
+    [
+        format!(
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS {} \
+             WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS {}",
+            target,
+            select_sql(rollup, Dialect::Timescale, source, fields, None)
+        ),
+        format!(
+            "SELECT add_continuous_aggregate_policy('{}', \
+             start_offset => INTERVAL '{}', end_offset => INTERVAL '1 hour', \
+             schedule_interval => INTERVAL '{}', if_not_exists => true)",
+            target, start_offset, schedule
+        ),
+    ]
+}
+
+/// Keep the rollup tables up to date. Each refresh recomputes the buckets
+/// from the latest one stored onwards.
+pub fn run_rollup_background(writer: Arc<DatabaseWriter>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(REFRESH_INTERVAL));
+        // The first tick is immediate, and startup has just refreshed
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            for rollup in Rollup::ALL {
+                if let Err(e) = writer.refresh_rollup(rollup).await {
+                    eprintln!(
+                        "[WARN] Failed to refresh {} rollup: {:#}",
+                        rollup.label(),
+                        e
+                    );
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollup_for_span() {
+        assert_eq!(Rollup::for_span(Duration::days(1)), None);
+        assert_eq!(Rollup::for_span(Duration::days(7)), None);
+        assert_eq!(Rollup::for_span(Duration::days(30)), Some(Rollup::Hourly));
+        assert_eq!(
+            Rollup::for_span(Duration::days(365 * 3)),
+            Some(Rollup::Daily)
+        );
+    }
+
+    #[test]
+    fn test_refresh_sql() {
+        let fields = ["outtemp", "wind_dir"];
+        let sql = refresh_sql(Rollup::Hourly, true, "wx_records", "wx_records_1h", &fields);
+        assert!(sql.starts_with(
+            "INSERT INTO wx_records_1h (bucket, samples, outtemp, outtemp_min, outtemp_max, \
+             wind_dir, wind_dir_min, wind_dir_max) SELECT date_trunc('hour'"
+        ));
+        assert!(sql.contains("AVG(outtemp) AS outtemp, MIN(outtemp) AS outtemp_min"));
+        assert!(sql.contains("ATAN2(AVG(SIN(RADIANS(wind_dir)))"));
+        assert!(sql.contains("FROM wx_records WHERE timestamp >= $1 GROUP BY"));
+        assert!(sql.ends_with("wind_dir_max = EXCLUDED.wind_dir_max"));
+
+        let sql = refresh_sql(Rollup::Daily, false, "wx_records", "wx_records_1d", &fields);
+        assert!(sql.contains("CAST(DATE(timestamp) AS DATETIME) AS bucket"));
+        assert!(sql.contains("WHERE timestamp >= ? GROUP BY"));
+        assert!(sql.contains("ON DUPLICATE KEY UPDATE samples = VALUES(samples)"));
+    }
+
+    #[test]
+    fn test_continuous_aggregate_sql() {
+        let [view, policy] =
+            continuous_aggregate_sql(Rollup::Daily, "wx_records", "wx_records_1d", &["outtemp"]);
+        assert_eq!(
+            view,
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS wx_records_1d \
+             WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS \
+             SELECT time_bucket(INTERVAL '1 day', timestamp) AS bucket, COUNT(*) AS samples, \
+             AVG(outtemp) AS outtemp, MIN(outtemp) AS outtemp_min, MAX(outtemp) AS outtemp_max \
+             FROM wx_records GROUP BY time_bucket(INTERVAL '1 day', timestamp)"
+        );
+        assert!(policy.contains("add_continuous_aggregate_policy('wx_records_1d'"));
+        assert!(policy.contains("start_offset => INTERVAL '3 days'"));
+    }
+}
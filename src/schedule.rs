@@ -0,0 +1,219 @@
+use anyhow::Result;
+use chrono::{DateTime, DurationRound, NaiveTime, SubsecRound, TimeDelta, Utc};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// How record timestamps relate to the poll interval
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampAlignment {
+    /// Use the time the response arrived (microsecond precision)
+    #[default]
+    None,
+    /// Poll on interval boundaries and round to the nearest boundary
+    Round,
+    /// Poll on interval boundaries and truncate to the previous boundary
+    Truncate,
+}
+
+impl TimestampAlignment {
+    pub fn is_aligned(self) -> bool {
+        self != Self::None
+    }
+
+    /// Timestamp to record for a reading taken at `now`
+    pub fn align(self, now: DateTime<Utc>, interval_secs: u64) -> DateTime<Utc> {
+        let step = TimeDelta::seconds(interval_secs.max(1) as i64);
+        let aligned = match self {
+            Self::None => Ok(now.trunc_subsecs(6)),
+            Self::Round => now.duration_round(step),
+            Self::Truncate => now.duration_trunc(step),
+        };
+        aligned.unwrap_or(now)
+    }
+
+    /// How long to sleep before the next poll
+    pub fn next_poll_delay(self, now: DateTime<Utc>, interval_secs: u64) -> Duration {
+        if self.is_aligned() {
+            until_next_boundary(now, interval_secs)
+        } else {
+            Duration::from_secs(interval_secs)
+        }
+    }
+}
+
+/// Time from `now` until the next multiple of the interval since the epoch,
+/// so a 300 second interval polls at :00, :05, :10...
+pub fn until_next_boundary(now: DateTime<Utc>, interval_secs: u64) -> Duration {
+    let interval_ms = interval_secs.max(1) as i64 * 1000;
+    let elapsed_ms = now.timestamp_millis().rem_euclid(interval_ms);
+    Duration::from_millis((interval_ms - elapsed_ms) as u64)
+}
+
+/// A daily time window with its own poll interval, e.g. every 2 seconds from
+/// 06:00 to 22:00. Windows whose end is before their start wrap past midnight.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PollProfile {
+    /// Start of the window, host local time ("HH:MM")
+    pub start: NaiveTime,
+    /// End of the window, exclusive
+    pub end: NaiveTime,
+    /// Seconds between polls inside the window
+    pub interval: u64,
+}
+
+impl PollProfile {
+    pub fn validate(&self) -> Result<()> {
+        if self.interval == 0 {
+            anyhow::bail!(
+                "Poll profile {}-{}: interval must be at least 1",
+                self.start,
+                self.end
+            );
+        }
+        if self.start == self.end {
+            anyhow::bail!(
+                "Poll profile {}-{}: start and end must differ",
+                self.start,
+                self.end
+            );
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Poll interval at local time `now`: the first profile covering it, or
+/// `default` (the --continuous interval) outside all profiles
+pub fn poll_interval(profiles: &[PollProfile], now: NaiveTime, default: u64) -> u64 {
+    profiles
+        .iter()
+        .find(|profile| profile.contains(now))
+        .map_or(default, |profile| profile.interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(h: u32, m: u32, s: u32, ms: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 1, h, m, s).unwrap() + TimeDelta::milliseconds(ms as i64)
+    }
+
+    #[test]
+    fn test_align_round() {
+        let ts = TimestampAlignment::Round.align(at(12, 4, 58, 700), 300);
+        assert_eq!(ts, at(12, 5, 0, 0));
+
+        let ts = TimestampAlignment::Round.align(at(12, 5, 0, 400), 300);
+        assert_eq!(ts, at(12, 5, 0, 0));
+    }
+
+    #[test]
+    fn test_align_truncate() {
+        let ts = TimestampAlignment::Truncate.align(at(12, 9, 59, 900), 300);
+        assert_eq!(ts, at(12, 5, 0, 0));
+    }
+
+    #[test]
+    fn test_align_none_keeps_microseconds() {
+        let now = at(12, 0, 1, 0) + TimeDelta::nanoseconds(123_456_789);
+        let ts = TimestampAlignment::None.align(now, 300);
+        assert_eq!(ts.timestamp_subsec_nanos(), 123_456_000);
+    }
+
+    #[test]
+    fn test_until_next_boundary() {
+        assert_eq!(
+            until_next_boundary(at(12, 4, 58, 500), 300),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(
+            until_next_boundary(at(12, 5, 0, 0), 300),
+            Duration::from_secs(300)
+        );
+    }
+
+    #[test]
+    fn test_next_poll_delay_unaligned() {
+        let delay = TimestampAlignment::None.next_poll_delay(at(12, 4, 58, 500), 16);
+        assert_eq!(delay, Duration::from_secs(16));
+    }
+
+    fn hm(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn profile(start: NaiveTime, end: NaiveTime, interval: u64) -> PollProfile {
+        PollProfile {
+            start,
+            end,
+            interval,
+        }
+    }
+
+    #[test]
+    fn test_poll_interval_daytime_profile() {
+        let profiles = [profile(hm(6, 0), hm(22, 0), 2)];
+        assert_eq!(poll_interval(&profiles, hm(6, 0), 60), 2);
+        assert_eq!(poll_interval(&profiles, hm(21, 59), 60), 2);
+        assert_eq!(poll_interval(&profiles, hm(22, 0), 60), 60);
+        assert_eq!(poll_interval(&profiles, hm(3, 0), 60), 60);
+    }
+
+    #[test]
+    fn test_poll_interval_wraps_midnight() {
+        let profiles = [profile(hm(22, 0), hm(6, 0), 300)];
+        assert_eq!(poll_interval(&profiles, hm(23, 30), 16), 300);
+        assert_eq!(poll_interval(&profiles, hm(2, 0), 16), 300);
+        assert_eq!(poll_interval(&profiles, hm(12, 0), 16), 16);
+    }
+
+    #[test]
+    fn test_poll_interval_first_match_wins() {
+        let profiles = [
+            profile(hm(12, 0), hm(13, 0), 1),
+            profile(hm(6, 0), hm(22, 0), 2),
+        ];
+        assert_eq!(poll_interval(&profiles, hm(12, 30), 60), 1);
+        assert_eq!(poll_interval(&profiles, hm(14, 0), 60), 2);
+    }
+
+    #[test]
+    fn test_poll_profile_validate() {
+        assert!(profile(hm(6, 0), hm(22, 0), 2).validate().is_ok());
+        assert!(profile(hm(6, 0), hm(22, 0), 0).validate().is_err());
+        assert!(profile(hm(6, 0), hm(6, 0), 2).validate().is_err());
+    }
+
+    #[test]
+    fn test_poll_profile_deserialization() {
+        let p: PollProfile = toml::from_str(
+            r#"
+            start = "06:00"
+            end = "22:00"
+            interval = 2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(p, profile(hm(6, 0), hm(22, 0), 2));
+    }
+
+    #[test]
+    fn test_alignment_deserialization() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            mode: TimestampAlignment,
+        }
+        let w: Wrapper = toml::from_str(r#"mode = "truncate""#).unwrap();
+        assert_eq!(w.mode, TimestampAlignment::Truncate);
+    }
+}
@@ -0,0 +1,203 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SensorWatchConfig {
+    /// Seconds a previously seen field may be absent before it is reported
+    /// missing (default: 900)
+    #[serde(default = "default_missing_after")]
+    pub missing_after: u64,
+    /// Fields that are never reported missing
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+fn default_missing_after() -> u64 {
+    900
+}
+
+impl Default for SensorWatchConfig {
+    fn default() -> Self {
+        Self {
+            missing_after: default_missing_after(),
+            ignore: Vec::new(),
+        }
+    }
+}
+
+/// A sensor dropping out of or returning to the live data
+#[derive(Debug, Clone, PartialEq)]
+pub enum SensorEvent {
+    Missing {
+        field: String,
+        last_seen: DateTime<Utc>,
+    },
+    Restored {
+        field: String,
+        missing_since: DateTime<Utc>,
+    },
+}
+
+/// Last-seen times persisted in the state file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SensorWatchState {
+    pub last_seen: BTreeMap<String, DateTime<Utc>>,
+    #[serde(default)]
+    pub missing: BTreeSet<String>,
+}
+
+/// Tracks which fields the gateway has reported and when each was last seen
+#[derive(Debug)]
+pub struct SensorWatch {
+    config: SensorWatchConfig,
+    last_seen: BTreeMap<String, DateTime<Utc>>,
+    missing: BTreeSet<String>,
+}
+
+impl SensorWatch {
+    pub fn new(config: SensorWatchConfig) -> Self {
+        Self {
+            config,
+            last_seen: BTreeMap::new(),
+            missing: BTreeSet::new(),
+        }
+    }
+
+    /// Record the fields present in one poll and return any transitions
+    pub fn observe(&mut self, data: &HashMap<String, f64>, now: DateTime<Utc>) -> Vec<SensorEvent> {
+        let mut events = Vec::new();
+
+        for field in data.keys() {
+            if self.config.ignore.contains(field) {
+                continue;
+            }
+            // Update in place so known fields don't clone their key every poll
+            let Some(seen) = self.last_seen.get_mut(field) else {
+                self.last_seen.insert(field.clone(), now);
+                continue;
+            };
+            let previous = std::mem::replace(seen, now);
+            if self.missing.remove(field) {
+                events.push(SensorEvent::Restored {
+                    field: field.clone(),
+                    missing_since: previous,
+                });
+            }
+        }
+
+        let limit = Duration::seconds(self.config.missing_after as i64);
+        for (field, last_seen) in &self.last_seen {
+            if now - *last_seen > limit && self.missing.insert(field.clone()) {
+                events.push(SensorEvent::Missing {
+                    field: field.clone(),
+                    last_seen: *last_seen,
+                });
+            }
+        }
+
+        events
+    }
+
+    /// Fields currently missing, with the time each was last seen
+    pub fn missing(&self) -> BTreeMap<String, DateTime<Utc>> {
+        self.missing
+            .iter()
+            .filter_map(|field| self.last_seen.get(field).map(|ts| (field.clone(), *ts)))
+            .collect()
+    }
+
+    pub fn state(&self) -> SensorWatchState {
+        SensorWatchState {
+            last_seen: self.last_seen.clone(),
+            missing: self.missing.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, state: SensorWatchState) {
+        self.last_seen = state.last_seen;
+        self.missing = state.missing;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(fields: &[&str]) -> HashMap<String, f64> {
+        fields.iter().map(|f| (f.to_string(), 1.0)).collect()
+    }
+
+    #[test]
+    fn test_sensor_missing_and_restored() {
+        let mut watch = SensorWatch::new(SensorWatchConfig::default());
+        let start = Utc::now();
+
+        assert!(watch
+            .observe(&data(&["outtemp", "intemp"]), start)
+            .is_empty());
+
+        // Briefly absent is not reported
+        let events = watch.observe(&data(&["intemp"]), start + Duration::minutes(5));
+        assert!(events.is_empty());
+
+        let events = watch.observe(&data(&["intemp"]), start + Duration::minutes(16));
+        assert_eq!(
+            events,
+            vec![SensorEvent::Missing {
+                field: "outtemp".to_string(),
+                last_seen: start,
+            }]
+        );
+        assert!(watch.missing().contains_key("outtemp"));
+
+        // Reported only once
+        let events = watch.observe(&data(&["intemp"]), start + Duration::minutes(20));
+        assert!(events.is_empty());
+
+        let events = watch.observe(&data(&["outtemp", "intemp"]), start + Duration::minutes(25));
+        assert_eq!(
+            events,
+            vec![SensorEvent::Restored {
+                field: "outtemp".to_string(),
+                missing_since: start,
+            }]
+        );
+        assert!(watch.missing().is_empty());
+    }
+
+    #[test]
+    fn test_sensor_watch_ignore() {
+        let config = SensorWatchConfig {
+            missing_after: 60,
+            ignore: vec!["heap_free".to_string()],
+        };
+        let mut watch = SensorWatch::new(config);
+        let start = Utc::now();
+
+        watch.observe(&data(&["heap_free", "outtemp"]), start);
+        let events = watch.observe(&data(&["outtemp"]), start + Duration::minutes(5));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_sensor_watch_state_roundtrip() {
+        let mut watch = SensorWatch::new(SensorWatchConfig::default());
+        let start = Utc::now();
+        watch.observe(&data(&["outtemp"]), start);
+
+        let mut restored = SensorWatch::new(SensorWatchConfig::default());
+        restored.restore(watch.state());
+
+        // A sensor that was seen before the restart is still tracked
+        let events = restored.observe(&HashMap::new(), start + Duration::hours(1));
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_sensor_watch_config_deserialization() {
+        let config: SensorWatchConfig = toml::from_str("missing_after = 300").unwrap();
+        assert_eq!(config.missing_after, 300);
+        assert!(config.ignore.is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Sensor names indexed by the type byte of the sensor-ID response
+const SENSOR_TYPES: &[&str] = &[
+    "wh65", "wh68", "ws80", "wh40", "wh25", "wh26", "wh31_ch1", "wh31_ch2", "wh31_ch3", "wh31_ch4",
+    "wh31_ch5", "wh31_ch6", "wh31_ch7", "wh31_ch8", "wh51_ch1", "wh51_ch2", "wh51_ch3", "wh51_ch4",
+    "wh51_ch5", "wh51_ch6", "wh51_ch7", "wh51_ch8", "wh41_ch1", "wh41_ch2", "wh41_ch3", "wh41_ch4",
+    "wh57", "wh55_ch1", "wh55_ch2", "wh55_ch3", "wh55_ch4", "wh34_ch1", "wh34_ch2", "wh34_ch3",
+    "wh34_ch4", "wh34_ch5", "wh34_ch6", "wh34_ch7", "wh34_ch8", "wh45", "wh35_ch1", "wh35_ch2",
+    "wh35_ch3", "wh35_ch4", "wh35_ch5", "wh35_ch6", "wh35_ch7", "wh35_ch8", "ws90",
+];
+
+/// Each record is type(1) + id(4) + battery(1) + signal(1)
+const RECORD_LEN: usize = 7;
+
+/// Sensor slot enabled but not yet paired
+const ID_REGISTERING: u32 = 0xFFFF_FFFF;
+/// Sensor slot disabled on the gateway
+const ID_DISABLED: u32 = 0xFFFF_FFFE;
+
+/// Live data fields carrying a sensor's supply voltage
+const VOLTAGE_FIELDS: &[(&str, &str)] = &[("ws90", "ws90_cap_volt")];
+
+/// Highest signal value the gateway reports (4 of the last 4 packets received)
+pub const MAX_SIGNAL: u8 = 4;
+
+/// RF signal quality of one paired sensor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SensorSignal {
+    pub sensor: String,
+    pub id: u32,
+    pub battery: u8,
+    /// Packets received out of the last four transmissions (0-4)
+    pub signal: u8,
+    /// Supply voltage, for sensors that report one in the live data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voltage: Option<f64>,
+}
+
+impl SensorSignal {
+    /// Field name used in the live data, e.g. `sensor_rssi_wh31_ch1`
+    pub fn field_name(&self) -> String {
+        format!("sensor_rssi_{}", self.sensor)
+    }
+}
+
+/// Parse the payload of a sensor-ID response, skipping unpaired and
+/// disabled slots
+pub fn parse_sensor_ids(data: &[u8]) -> Vec<SensorSignal> {
+    data.chunks_exact(RECORD_LEN)
+        .filter_map(|record| {
+            let sensor = SENSOR_TYPES.get(record[0] as usize)?;
+            let id = u32::from_be_bytes([record[1], record[2], record[3], record[4]]);
+            if id == ID_REGISTERING || id == ID_DISABLED {
+                return None;
+            }
+            Some(SensorSignal {
+                sensor: sensor.to_string(),
+                id,
+                battery: record[5],
+                signal: record[6].min(MAX_SIGNAL),
+                voltage: None,
+            })
+        })
+        .collect()
+}
+
+/// Add a `sensor_rssi_*` field for each paired sensor
+pub fn add_signal_fields(data: &mut HashMap<String, f64>, signals: &[SensorSignal]) {
+    for signal in signals {
+        data.insert(signal.field_name(), signal.signal as f64);
+    }
+}
+
+/// Attach voltages reported in the live data (e.g. the WS90 super-capacitor)
+/// to the matching sensors
+pub fn attach_voltages(signals: &mut [SensorSignal], data: &HashMap<String, f64>) {
+    for signal in signals.iter_mut() {
+        signal.voltage = VOLTAGE_FIELDS
+            .iter()
+            .find(|(sensor, _)| *sensor == signal.sensor)
+            .and_then(|(_, field)| data.get(*field).copied());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(sensor_type: u8, id: u32, battery: u8, signal: u8) -> Vec<u8> {
+        let mut bytes = vec![sensor_type];
+        bytes.extend_from_slice(&id.to_be_bytes());
+        bytes.extend_from_slice(&[battery, signal]);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_sensor_ids() {
+        let mut data = record(0, 0x0000_00C4, 0, 4);
+        data.extend(record(6, 0x0000_1A2B, 0, 2));
+        data.extend(record(1, ID_REGISTERING, 0, 0));
+        data.extend(record(3, ID_DISABLED, 0, 0));
+        data.extend(record(48, 0x0000_0042, 3, 9));
+
+        let signals = parse_sensor_ids(&data);
+        assert_eq!(signals.len(), 3);
+        assert_eq!(signals[0].sensor, "wh65");
+        assert_eq!(signals[0].id, 0xC4);
+        assert_eq!(signals[0].signal, 4);
+        assert_eq!(signals[1].field_name(), "sensor_rssi_wh31_ch1");
+        assert_eq!(signals[1].signal, 2);
+        assert_eq!(signals[2].sensor, "ws90");
+        // Out-of-range values are clamped
+        assert_eq!(signals[2].signal, MAX_SIGNAL);
+    }
+
+    #[test]
+    fn test_parse_sensor_ids_unknown_and_truncated() {
+        let mut data = record(200, 0x01, 0, 4);
+        data.extend(record(2, 0x02, 0, 3));
+        data.extend_from_slice(&[5, 0, 0]);
+
+        let signals = parse_sensor_ids(&data);
+        assert_eq!(signals.len(), 1);
+        assert_eq!(signals[0].sensor, "ws80");
+    }
+
+    #[test]
+    fn test_add_signal_fields() {
+        let mut data = HashMap::new();
+        let signals = parse_sensor_ids(&record(4, 0x10, 0, 3));
+        add_signal_fields(&mut data, &signals);
+        assert_eq!(data.get("sensor_rssi_wh25"), Some(&3.0));
+    }
+
+    #[test]
+    fn test_attach_voltages() {
+        let mut data = record(48, 0x42, 0, 4);
+        data.extend(record(0, 0xC4, 0, 4));
+        let mut signals = parse_sensor_ids(&data);
+
+        let live = HashMap::from([("ws90_cap_volt".to_string(), 3.2)]);
+        attach_voltages(&mut signals, &live);
+        assert_eq!(signals[0].voltage, Some(3.2));
+        assert_eq!(signals[1].voltage, None);
+    }
+}
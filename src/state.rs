@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::battery::BatteryState;
+use crate::degree_days::DegreeDayState;
+use crate::events::Event;
+use crate::sensor_watch::SensorWatchState;
+use crate::status::Settings;
+
+/// Aggregation state persisted between runs so a restart mid-day doesn't
+/// reset today's statistics
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PersistentState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub degree_days: Option<DegreeDayState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensors: Option<SensorWatchState>,
+    /// Battery history for trend predictions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub battery: Option<BatteryState>,
+    /// The event log, so it covers restarts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<Event>>,
+    /// Settings changed at runtime through the API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<Settings>,
+}
+
+/// Reads and writes the JSON state file
+pub struct StateStore {
+    path: PathBuf,
+}
+
+impl StateStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the saved state, or an empty state if the file doesn't exist yet
+    pub fn load(&self) -> Result<PersistentState> {
+        if !self.path.exists() {
+            return Ok(PersistentState::default());
+        }
+
+        let contents = fs::read_to_string(&self.path)
+            .context(format!("Failed to read state file: {:?}", self.path))?;
+        serde_json::from_str(&contents)
+            .context(format!("Failed to parse state file: {:?}", self.path))
+    }
+
+    /// Write the state atomically (write to a temp file, then rename)
+    pub fn save(&self, state: &PersistentState) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)
+                    .context(format!("Failed to create state directory: {:?}", parent))?;
+            }
+        }
+
+        let tmp_path = self.path.with_extension("tmp");
+        let contents = serde_json::to_string_pretty(state)?;
+        fs::write(&tmp_path, contents)
+            .context(format!("Failed to write state file: {:?}", tmp_path))?;
+        fs::rename(&tmp_path, &self.path)
+            .context(format!("Failed to replace state file: {:?}", self.path))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::Tenths;
+    use chrono::NaiveDate;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_missing_file() {
+        let dir = TempDir::new().unwrap();
+        let store = StateStore::new(dir.path().join("state.json"));
+        assert_eq!(store.load().unwrap(), PersistentState::default());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let store = StateStore::new(dir.path().join("nested/state.json"));
+
+        let state = PersistentState {
+            degree_days: Some(DegreeDayState {
+                current_day: Some((
+                    NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(),
+                    Tenths(20),
+                    Tenths(95),
+                )),
+                season_start: NaiveDate::from_ymd_opt(2025, 1, 1),
+                ..Default::default()
+            }),
+            sensors: None,
+            battery: None,
+            events: None,
+            settings: Some(Settings {
+                poll_interval: Some(30),
+                ..Settings::default()
+            }),
+        };
+
+        store.save(&state).unwrap();
+        assert_eq!(store.load().unwrap(), state);
+    }
+
+    #[test]
+    fn test_load_float_degree_days() {
+        // State files written before degree days were held in tenths
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        fs::write(
+            &path,
+            r#"{"degree_days":{"current_day":["2025-03-01",2.0,9.5],
+               "season_start":"2025-01-01",
+               "season":{"hdd":182.39999999999998,"cdd":0.0,"gdd":12.1}}}"#,
+        )
+        .unwrap();
+
+        let state = StateStore::new(path).load().unwrap().degree_days.unwrap();
+        let (_, min, max) = state.current_day.unwrap();
+        assert_eq!((min, max), (Tenths(20), Tenths(95)));
+        assert_eq!(state.season.hdd, Tenths(1824));
+    }
+
+    #[test]
+    fn test_load_corrupt_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        fs::write(&path, "not json").unwrap();
+
+        let result = StateStore::new(path).load();
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Failed to parse state file"));
+    }
+}
@@ -0,0 +1,128 @@
+/// Where the weather station is: its name, coordinates, elevation and
+/// timezone, shared by the outputs and calculations that need them
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StationConfig {
+    /// Display name, e.g. "Backyard"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Decimal degrees, north positive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    /// Decimal degrees, east positive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// Meters above mean sea level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elevation: Option<f64>,
+    /// IANA timezone name, e.g. "Europe/Amsterdam" (default: the host's)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+impl StationConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.latitude.is_some() != self.longitude.is_some() {
+            anyhow::bail!("Invalid [station]: set both latitude and longitude, or neither");
+        }
+        if let Some(latitude) = self.latitude {
+            if !(-90.0..=90.0).contains(&latitude) {
+                anyhow::bail!("Invalid [station] latitude {}: must be -90 to 90", latitude);
+            }
+        }
+        if let Some(longitude) = self.longitude {
+            if !(-180.0..=180.0).contains(&longitude) {
+                anyhow::bail!(
+                    "Invalid [station] longitude {}: must be -180 to 180",
+                    longitude
+                );
+            }
+        }
+        self.time_zone()?;
+        Ok(())
+    }
+
+    /// The station's local time, from `timezone` or else the host's
+    pub fn time_zone(&self) -> Result<StationTime> {
+        let Some(name) = self.timezone.as_deref() else {
+            return Ok(StationTime::default());
+        };
+        let tz = name.parse::<Tz>().map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid [station] timezone {:?}: use an IANA name like \"Europe/Amsterdam\"",
+                name
+            )
+        })?;
+        Ok(StationTime(Some(tz)))
+    }
+}
+
+/// Converts timestamps to the station's local time
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StationTime(Option<Tz>);
+
+impl StationTime {
+    pub fn naive_local(&self, at: DateTime<Utc>) -> NaiveDateTime {
+        match self.0 {
+            Some(tz) => at.with_timezone(&tz).naive_local(),
+            None => at.with_timezone(&Local).naive_local(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_validate() {
+        let station = StationConfig {
+            name: Some("Backyard".to_string()),
+            latitude: Some(52.37),
+            longitude: Some(4.89),
+            elevation: Some(-2.0),
+            timezone: Some("Europe/Amsterdam".to_string()),
+        };
+        assert!(station.validate().is_ok());
+        assert!(StationConfig::default().validate().is_ok());
+
+        for bad in [
+            StationConfig {
+                longitude: None,
+                ..station.clone()
+            },
+            StationConfig {
+                latitude: Some(91.0),
+                ..station.clone()
+            },
+            StationConfig {
+                longitude: Some(-181.0),
+                ..station.clone()
+            },
+            StationConfig {
+                timezone: Some("CEST".to_string()),
+                ..station.clone()
+            },
+        ] {
+            assert!(bad.validate().is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_station_time() {
+        let station = StationConfig {
+            timezone: Some("Pacific/Auckland".to_string()),
+            ..StationConfig::default()
+        };
+        let at = Utc.with_ymd_and_hms(2026, 1, 10, 23, 30, 0).unwrap();
+        assert_eq!(
+            station.time_zone().unwrap().naive_local(at).to_string(),
+            "2026-01-11 12:30:00"
+        );
+    }
+}
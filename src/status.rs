@@ -0,0 +1,811 @@
+#[cfg(feature = "http")]
+use anyhow::Context;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::battery::BatteryForecast;
+use crate::events::{Event, EventKind, EventLog};
+use crate::output::FieldValue;
+use crate::provenance::Provenance;
+use crate::sensors::{SensorSignal, MAX_SIGNAL};
+use crate::station::StationConfig;
+use crate::units::Units;
+
+/// Version of the /api/v1/ payloads, bumped only together with the schema
+pub const API_SCHEMA_VERSION: u32 = 1;
+
+/// Output sinks that can be paused
+pub const OUTPUT_NAMES: &[&str] = &["database", "mqtt", "http"];
+
+/// Settings changed at runtime with PATCH /api/v1/settings, kept in the
+/// state file across restarts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    /// Poll interval in seconds, overriding --continuous and the poll
+    /// profiles. In a change, 0 goes back to the configured schedule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_interval: Option<u64>,
+    /// Whether each output is enabled; unlike a pause, disabling an output
+    /// survives a restart
+    #[serde(default)]
+    pub outputs: BTreeMap<String, bool>,
+}
+
+/// Health of one output sink (database, mqtt, http)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutputHealth {
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub error_count: u64,
+}
+
+impl OutputHealth {
+    /// Healthy when the most recent attempt succeeded
+    pub fn is_healthy(&self) -> bool {
+        match (self.last_success, self.last_error_at) {
+            (_, None) => true,
+            (Some(ok), Some(err)) => ok >= err,
+            (None, Some(_)) => false,
+        }
+    }
+}
+
+/// Snapshot of a running instance, served at /api/v1/status.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusReport {
+    /// API schema version (see /api/v1/schema.json); 0 from older releases
+    #[serde(default)]
+    pub schema_version: u32,
+    pub version: String,
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: i64,
+    pub last_poll: Option<DateTime<Utc>>,
+    pub last_poll_error: Option<String>,
+    pub readings: BTreeMap<String, f64>,
+    pub outputs: BTreeMap<String, OutputHealth>,
+    #[serde(default)]
+    pub paused: Vec<String>,
+    /// Sensors absent from the live data, with the time each was last seen
+    #[serde(default)]
+    pub missing_sensors: BTreeMap<String, DateTime<Utc>>,
+    /// RF signal quality of each paired sensor
+    #[serde(default)]
+    pub sensors: Vec<SensorSignal>,
+    /// Battery level and predicted weeks left of sensors that report one
+    #[serde(default)]
+    pub batteries: Vec<BatteryForecast>,
+    /// Where the last reading came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    /// The configured `[station]`
+    #[serde(default)]
+    pub station: StationConfig,
+    /// Runtime settings from PATCH /api/v1/settings
+    #[serde(default)]
+    pub settings: Settings,
+    /// When maintenance mode was turned on, while it lasts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance_since: Option<DateTime<Utc>>,
+}
+
+/// Live daemon state updated by the polling loop
+#[derive(Debug)]
+pub struct DaemonStatus {
+    started_at: DateTime<Utc>,
+    last_poll: Option<DateTime<Utc>>,
+    last_poll_error: Option<String>,
+    readings: HashMap<String, f64>,
+    outputs: BTreeMap<String, OutputHealth>,
+    paused: BTreeSet<String>,
+    missing_sensors: BTreeMap<String, DateTime<Utc>>,
+    sensors: Vec<SensorSignal>,
+    batteries: Vec<BatteryForecast>,
+    provenance: Option<Provenance>,
+    station: StationConfig,
+    settings: Settings,
+    maintenance_since: Option<DateTime<Utc>>,
+    events: EventLog,
+}
+
+pub type SharedStatus = Arc<Mutex<DaemonStatus>>;
+
+impl DaemonStatus {
+    pub fn new(started_at: DateTime<Utc>) -> Self {
+        Self {
+            started_at,
+            last_poll: None,
+            last_poll_error: None,
+            readings: HashMap::new(),
+            outputs: BTreeMap::new(),
+            paused: BTreeSet::new(),
+            missing_sensors: BTreeMap::new(),
+            sensors: Vec::new(),
+            batteries: Vec::new(),
+            provenance: None,
+            station: StationConfig::default(),
+            settings: Settings {
+                poll_interval: None,
+                outputs: OUTPUT_NAMES.iter().map(|n| (n.to_string(), true)).collect(),
+            },
+            maintenance_since: None,
+            events: EventLog::default(),
+        }
+    }
+
+    pub fn record_poll(&mut self, data: &HashMap<String, f64>, timestamp: DateTime<Utc>) {
+        if self.last_poll_error.take().is_some() {
+            self.log_event(EventKind::DeviceOnline, "Gateway responding again");
+        }
+        self.readings.clone_from(data);
+        self.last_poll = Some(timestamp);
+    }
+
+    pub fn record_poll_error(&mut self, error: impl Display) {
+        let error = error.to_string();
+        if self.last_poll_error.is_none() {
+            self.log_event(
+                EventKind::DeviceOffline,
+                format!("Gateway poll failed: {}", error),
+            );
+        }
+        self.last_poll_error = Some(error);
+    }
+
+    /// Add an event to the log
+    pub fn log_event(&mut self, kind: EventKind, message: impl Into<String>) {
+        self.events.push(Utc::now(), kind, message);
+    }
+
+    /// The last `limit` events, oldest first
+    pub fn recent_events(&self, limit: usize) -> Vec<Event> {
+        self.events.recent(limit)
+    }
+
+    pub fn event_log(&self) -> &EventLog {
+        &self.events
+    }
+
+    pub fn restore_events(&mut self, saved: Vec<Event>) {
+        self.events.restore(saved);
+    }
+
+    pub fn set_missing_sensors(&mut self, missing: BTreeMap<String, DateTime<Utc>>) {
+        self.missing_sensors = missing;
+    }
+
+    pub fn set_station(&mut self, station: StationConfig) {
+        self.station = station;
+    }
+
+    pub fn set_provenance(&mut self, provenance: Provenance) {
+        self.provenance = Some(provenance);
+    }
+
+    pub fn set_sensor_signals(&mut self, sensors: Vec<SensorSignal>) {
+        self.sensors = sensors;
+    }
+
+    pub fn set_battery_forecasts(&mut self, batteries: Vec<BatteryForecast>) {
+        self.batteries = batteries;
+    }
+
+    /// Record the outcome of writing to an output sink. Changes between
+    /// healthy and failing are logged as events.
+    pub fn record_output<E: Display>(&mut self, name: &str, result: Result<(), E>) {
+        let health = self.outputs.entry(name.to_string()).or_default();
+        let was_healthy = health.is_healthy();
+        let now = Utc::now();
+        let event = match result {
+            Ok(()) => {
+                health.last_success = Some(now);
+                (!was_healthy).then(|| {
+                    (
+                        EventKind::OutputRecovered,
+                        format!("Output '{}' recovered", name),
+                    )
+                })
+            }
+            Err(e) => {
+                health.last_error = Some(e.to_string());
+                health.last_error_at = Some(now);
+                health.error_count += 1;
+                was_healthy.then(|| {
+                    (
+                        EventKind::OutputFailed,
+                        format!("Output '{}' failed: {}", name, e),
+                    )
+                })
+            }
+        };
+        if let Some((kind, message)) = event {
+            self.log_event(kind, message);
+        }
+    }
+
+    /// Pause one output, or all of them when `output` is None
+    pub fn pause(&mut self, output: Option<&str>) -> Result<()> {
+        for name in select_outputs(output)? {
+            self.paused.insert(name.to_string());
+        }
+        Ok(())
+    }
+
+    /// Resume one output, or all of them when `output` is None
+    pub fn resume(&mut self, output: Option<&str>) -> Result<()> {
+        for name in select_outputs(output)? {
+            self.paused.remove(name);
+        }
+        Ok(())
+    }
+
+    pub fn is_paused(&self, output: &str) -> bool {
+        self.paused.contains(output)
+    }
+
+    pub fn paused(&self) -> Vec<String> {
+        self.paused.iter().cloned().collect()
+    }
+
+    /// Whether to write to an output: enabled in the settings and not paused
+    pub fn is_active(&self, output: &str) -> bool {
+        !self.is_paused(output) && self.settings.outputs.get(output) != Some(&false)
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// The poll interval set at runtime, if any
+    pub fn poll_interval(&self) -> Option<u64> {
+        self.settings.poll_interval
+    }
+
+    /// Apply the settings given in `change`, leaving the rest as they are.
+    /// Nothing changes when any of it is invalid.
+    pub fn update_settings(&mut self, change: &Settings) -> Result<()> {
+        for name in change.outputs.keys() {
+            select_outputs(Some(name))?;
+        }
+        if let Some(secs) = change.poll_interval {
+            self.settings.poll_interval = (secs > 0).then_some(secs);
+        }
+        for (name, &enabled) in &change.outputs {
+            self.settings.outputs.insert(name.clone(), enabled);
+        }
+        Ok(())
+    }
+
+    /// Turn maintenance mode on or off, logging the change. Turning it on
+    /// again keeps the original start.
+    pub fn set_maintenance(&mut self, enabled: bool, now: DateTime<Utc>) {
+        match (enabled, self.maintenance_since) {
+            (true, None) => {
+                self.maintenance_since = Some(now);
+                self.log_event(
+                    EventKind::MaintenanceStarted,
+                    "Maintenance mode on: readings flagged as suspect, alerts suppressed",
+                );
+            }
+            (false, Some(since)) => {
+                self.maintenance_since = None;
+                self.log_event(
+                    EventKind::MaintenanceEnded,
+                    format!(
+                        "Maintenance mode off after {}",
+                        format_uptime((now - since).num_seconds())
+                    ),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// When maintenance mode was turned on, if it is on
+    pub fn maintenance_since(&self) -> Option<DateTime<Utc>> {
+        self.maintenance_since
+    }
+
+    pub fn in_maintenance(&self) -> bool {
+        self.maintenance_since.is_some()
+    }
+
+    /// Why the pipeline isn't healthy, if it isn't: no poll within
+    /// `max_age`, a failed last poll, or a failing output. Paused and
+    /// disabled outputs don't count.
+    pub fn pipeline_problem(&self, now: DateTime<Utc>, max_age: Duration) -> Option<String> {
+        if let Some(ref error) = self.last_poll_error {
+            return Some(format!("gateway poll failed: {}", error));
+        }
+        match self.last_poll {
+            None => return Some("no poll yet".to_string()),
+            Some(at) if now - at > max_age => {
+                return Some(format!(
+                    "last poll {} ago",
+                    format_uptime((now - at).num_seconds())
+                ))
+            }
+            Some(_) => {}
+        }
+        self.outputs
+            .iter()
+            .find(|(name, health)| self.is_active(name) && !health.is_healthy())
+            .map(|(name, health)| {
+                format!(
+                    "output '{}' failing: {}",
+                    name,
+                    health.last_error.as_deref().unwrap_or_default()
+                )
+            })
+    }
+
+    pub fn report(&self) -> StatusReport {
+        StatusReport {
+            schema_version: API_SCHEMA_VERSION,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: self.started_at,
+            uptime_secs: (Utc::now() - self.started_at).num_seconds(),
+            last_poll: self.last_poll,
+            last_poll_error: self.last_poll_error.clone(),
+            readings: self
+                .readings
+                .iter()
+                .filter(|(_, v)| v.is_finite())
+                .map(|(k, v)| (k.clone(), *v))
+                .collect(),
+            outputs: self.outputs.clone(),
+            paused: self.paused(),
+            missing_sensors: self.missing_sensors.clone(),
+            sensors: self.sensors.clone(),
+            batteries: self.batteries.clone(),
+            provenance: self.provenance.clone(),
+            station: self.station.clone(),
+            settings: self.settings.clone(),
+            maintenance_since: self.maintenance_since,
+        }
+    }
+}
+
+fn select_outputs(output: Option<&str>) -> Result<Vec<&'static str>> {
+    match output {
+        None => Ok(OUTPUT_NAMES.to_vec()),
+        Some(name) => match OUTPUT_NAMES.iter().find(|n| **n == name) {
+            Some(name) => Ok(vec![*name]),
+            None => anyhow::bail!("Unknown output '{}': use {}", name, OUTPUT_NAMES.join(", ")),
+        },
+    }
+}
+
+/// Human-readable duration, e.g. "2d 3h 14m"
+pub fn format_uptime(secs: i64) -> String {
+    let days = secs / 86_400;
+    let hours = secs % 86_400 / 3600;
+    let minutes = secs % 3600 / 60;
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m {}s", minutes, secs % 60)
+    }
+}
+
+/// Render a status report for the terminal
+pub fn format_report(report: &StatusReport, units: Units) -> String {
+    let mut out = String::new();
+    out.push_str("============================================================\n");
+    out.push_str(&format!("wxlistener {} status\n", report.version));
+    out.push_str("============================================================\n");
+    out.push_str(&format!(
+        "{:<20} : {}\n",
+        "started",
+        report.started_at.format("%Y-%m-%d %H:%M:%S UTC")
+    ));
+    out.push_str(&format!(
+        "{:<20} : {}\n",
+        "uptime",
+        format_uptime(report.uptime_secs)
+    ));
+    let last_poll = report
+        .last_poll
+        .map(|ts| ts.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| "never".to_string());
+    out.push_str(&format!("{:<20} : {}\n", "last poll", last_poll));
+    if let Some(secs) = report.settings.poll_interval {
+        out.push_str(&format!(
+            "{:<20} : every {} seconds (set at runtime)\n",
+            "poll interval", secs
+        ));
+    }
+    if let Some(ref error) = report.last_poll_error {
+        out.push_str(&format!("{:<20} : {}\n", "last poll error", error));
+    }
+    if let Some(since) = report.maintenance_since {
+        out.push_str(&format!(
+            "{:<20} : ON since {}, readings flagged as suspect\n",
+            "maintenance",
+            since.format("%Y-%m-%d %H:%M:%S UTC")
+        ));
+    }
+
+    for (field, last_seen) in &report.missing_sensors {
+        out.push_str(&format!(
+            "{:<20} : missing, last seen {}\n",
+            field,
+            last_seen.format("%Y-%m-%d %H:%M:%S UTC")
+        ));
+    }
+
+    if !report.sensors.is_empty() {
+        out.push_str("\n--- Sensor Signal ---\n");
+        for sensor in &report.sensors {
+            let voltage = sensor
+                .voltage
+                .map(|v| format!(", {:.1} V", v))
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "{:<20} : {}/{} (id {:08X}{})\n",
+                sensor.sensor, sensor.signal, MAX_SIGNAL, sensor.id, voltage
+            ));
+        }
+    }
+
+    if !report.batteries.is_empty() {
+        out.push_str("\n--- Battery ---\n");
+        for battery in &report.batteries {
+            let level = battery.unit.format(battery.level);
+            let left = battery
+                .weeks_to_empty
+                .map(|weeks| format!(", about {:.0} weeks left", weeks))
+                .unwrap_or_default();
+            out.push_str(&format!("{:<20} : {}{}\n", battery.sensor, level, left));
+        }
+    }
+
+    out.push_str("\n--- Outputs ---\n");
+    if report.outputs.is_empty() {
+        out.push_str("(none configured)\n");
+    }
+    if !report.paused.is_empty() {
+        out.push_str(&format!(
+            "{:<20} : {}\n",
+            "paused",
+            report.paused.join(", ")
+        ));
+    }
+    for (name, health) in &report.outputs {
+        let state = if report.settings.outputs.get(name) == Some(&false) {
+            "DISABLED"
+        } else if report.paused.contains(name) {
+            "PAUSED"
+        } else if health.is_healthy() {
+            "OK"
+        } else {
+            "ERROR"
+        };
+        out.push_str(&format!(
+            "{:<20} : {} ({} errors)\n",
+            name, state, health.error_count
+        ));
+        if let (Some(error), Some(at)) = (&health.last_error, health.last_error_at) {
+            out.push_str(&format!(
+                "{:<20}   last error {}: {}\n",
+                "",
+                at.format("%Y-%m-%d %H:%M:%S"),
+                error
+            ));
+        }
+    }
+
+    out.push_str("\n--- Current Readings ---\n");
+    for (key, value) in &report.readings {
+        out.push_str(&format!(
+            "{:<20} : {}\n",
+            key,
+            FieldValue(key, *value, units)
+        ));
+    }
+    out.push_str("============================================================");
+    out
+}
+
+/// Fetch the status report from a running instance's web server, with a
+/// token if its API requires one
+#[cfg(feature = "http")]
+pub async fn fetch_status(base_url: &str, token: Option<&str>) -> Result<StatusReport> {
+    let url = format!("{}/api/v1/status.json", base_url.trim_end_matches('/'));
+    let client = crate::proxy::client_builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .context("Failed to create HTTP client")?;
+    let mut request = client.get(&url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request
+        .send()
+        .await
+        .context(format!("Failed to reach wxlistener at {}", url))?
+        .error_for_status()
+        .context("Status request failed")?
+        .json()
+        .await
+        .context("Failed to parse status response")
+}
+
+/// Fetch recent events from a running instance's web server
+#[cfg(feature = "http")]
+pub async fn fetch_events(base_url: &str, token: Option<&str>) -> Result<Vec<Event>> {
+    #[derive(Deserialize)]
+    struct EventsResponse {
+        events: Vec<Event>,
+    }
+
+    let url = format!("{}/api/v1/events.json", base_url.trim_end_matches('/'));
+    let client = crate::proxy::client_builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .context("Failed to create HTTP client")?;
+    let mut request = client.get(&url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response: EventsResponse = request
+        .send()
+        .await
+        .context(format!("Failed to reach wxlistener at {}", url))?
+        .error_for_status()
+        .context("Events request failed")?
+        .json()
+        .await
+        .context("Failed to parse events response")?;
+    Ok(response.events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery::BatteryUnit;
+
+    #[test]
+    fn test_output_health() {
+        let mut status = DaemonStatus::new(Utc::now());
+        status.record_output("database", Ok::<(), String>(()));
+        assert!(status.outputs["database"].is_healthy());
+
+        status.record_output("database", Err("connection refused"));
+        let health = &status.outputs["database"];
+        assert!(!health.is_healthy());
+        assert_eq!(health.error_count, 1);
+        assert_eq!(health.last_error.as_deref(), Some("connection refused"));
+    }
+
+    #[test]
+    fn test_pipeline_problem() {
+        let now = Utc::now();
+        let max_age = Duration::minutes(5);
+        let mut status = DaemonStatus::new(now);
+        assert_eq!(
+            status.pipeline_problem(now, max_age).as_deref(),
+            Some("no poll yet")
+        );
+
+        status.record_poll(&HashMap::new(), now - Duration::minutes(1));
+        status.record_output("mqtt", Ok::<(), String>(()));
+        assert_eq!(status.pipeline_problem(now, max_age), None);
+        assert_eq!(
+            status
+                .pipeline_problem(now + Duration::minutes(10), max_age)
+                .as_deref(),
+            Some("last poll 11m 0s ago")
+        );
+
+        status.record_output("mqtt", Err("not authorized"));
+        assert_eq!(
+            status.pipeline_problem(now, max_age).as_deref(),
+            Some("output 'mqtt' failing: not authorized")
+        );
+        // A paused output is left alone on purpose
+        status.pause(Some("mqtt")).unwrap();
+        assert_eq!(status.pipeline_problem(now, max_age), None);
+
+        status.record_poll_error("timeout");
+        assert!(status
+            .pipeline_problem(now, max_age)
+            .unwrap()
+            .starts_with("gateway poll failed"));
+    }
+
+    #[test]
+    fn test_record_poll_clears_error() {
+        let mut status = DaemonStatus::new(Utc::now());
+        status.record_poll_error("timeout");
+        assert!(status.report().last_poll_error.is_some());
+
+        let mut data = HashMap::new();
+        data.insert("outtemp".to_string(), 12.5);
+        status.record_poll(&data, Utc::now());
+
+        let report = status.report();
+        assert!(report.last_poll_error.is_none());
+        assert_eq!(report.readings.get("outtemp"), Some(&12.5));
+    }
+
+    #[test]
+    fn test_transitions_logged_as_events() {
+        let mut status = DaemonStatus::new(Utc::now());
+        let data = HashMap::new();
+        status.record_poll(&data, Utc::now());
+        status.record_poll_error("timeout");
+        status.record_poll_error("timeout");
+        status.record_poll(&data, Utc::now());
+
+        status.record_output("mqtt", Ok::<(), String>(()));
+        status.record_output("mqtt", Err("broker unreachable"));
+        status.record_output("mqtt", Err("broker unreachable"));
+        status.record_output("mqtt", Ok::<(), String>(()));
+
+        let kinds: Vec<_> = status.recent_events(10).iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                EventKind::DeviceOffline,
+                EventKind::DeviceOnline,
+                EventKind::OutputFailed,
+                EventKind::OutputRecovered,
+            ]
+        );
+        assert_eq!(
+            status.recent_events(10)[2].message,
+            "Output 'mqtt' failed: broker unreachable"
+        );
+    }
+
+    #[test]
+    fn test_pause_resume() {
+        let mut status = DaemonStatus::new(Utc::now());
+        status.pause(Some("database")).unwrap();
+        assert!(status.is_paused("database"));
+        assert!(!status.is_paused("mqtt"));
+
+        status.pause(None).unwrap();
+        assert_eq!(status.report().paused, vec!["database", "http", "mqtt"]);
+
+        status.resume(Some("mqtt")).unwrap();
+        assert!(!status.is_paused("mqtt"));
+        status.resume(None).unwrap();
+        assert!(status.report().paused.is_empty());
+
+        assert!(status.pause(Some("printer")).is_err());
+    }
+
+    #[test]
+    fn test_update_settings() {
+        let mut status = DaemonStatus::new(Utc::now());
+        assert!(status.is_active("mqtt"));
+
+        let change = Settings {
+            poll_interval: Some(30),
+            outputs: BTreeMap::from([("mqtt".to_string(), false)]),
+        };
+        status.update_settings(&change).unwrap();
+        assert_eq!(status.poll_interval(), Some(30));
+        assert!(!status.is_active("mqtt"));
+        assert!(status.is_active("database"));
+
+        // Only the settings given change; 0 clears the interval
+        let change = Settings {
+            poll_interval: None,
+            outputs: BTreeMap::from([("database".to_string(), false)]),
+        };
+        status.update_settings(&change).unwrap();
+        assert_eq!(status.poll_interval(), Some(30));
+        assert!(!status.is_active("mqtt") && !status.is_active("database"));
+        let change = Settings {
+            poll_interval: Some(0),
+            ..Settings::default()
+        };
+        status.update_settings(&change).unwrap();
+        assert_eq!(status.poll_interval(), None);
+
+        // An unknown output rejects the whole change
+        let change = Settings {
+            poll_interval: Some(5),
+            outputs: BTreeMap::from([("printer".to_string(), false)]),
+        };
+        assert!(status.update_settings(&change).is_err());
+        assert_eq!(status.poll_interval(), None);
+
+        // A pause stops an enabled output without changing the setting
+        status.pause(Some("http")).unwrap();
+        assert!(!status.is_active("http"));
+        assert!(status.settings().outputs["http"]);
+    }
+
+    #[test]
+    fn test_maintenance() {
+        let start = Utc::now();
+        let mut status = DaemonStatus::new(start);
+        assert!(!status.in_maintenance());
+
+        status.set_maintenance(true, start);
+        // Turning it on again keeps the original start
+        status.set_maintenance(true, start + chrono::TimeDelta::minutes(5));
+        assert_eq!(status.maintenance_since(), Some(start));
+        assert!(format_report(&status.report(), Units::default()).contains("maintenance"));
+
+        status.set_maintenance(false, start + chrono::TimeDelta::minutes(20));
+        assert!(!status.in_maintenance());
+        assert_eq!(status.report().maintenance_since, None);
+        let events = status.recent_events(10);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, EventKind::MaintenanceStarted);
+        assert_eq!(events[1].message, "Maintenance mode off after 20m 0s");
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(42), "0m 42s");
+        assert_eq!(format_uptime(3 * 3600 + 120), "3h 2m");
+        assert_eq!(format_uptime(2 * 86_400 + 3600), "2d 1h 0m");
+    }
+
+    #[test]
+    fn test_format_report() {
+        let mut status = DaemonStatus::new(Utc::now());
+        let mut data = HashMap::new();
+        data.insert("outtemp".to_string(), 12.5);
+        status.record_poll(&data, Utc::now());
+        status.record_output("mqtt", Err("broker unreachable"));
+
+        let text = format_report(&status.report(), Units::default());
+        assert!(text.contains("outtemp"));
+        assert!(text.contains("12.5°C"));
+        assert!(text.contains("mqtt"));
+        assert!(text.contains("broker unreachable"));
+    }
+
+    #[test]
+    fn test_format_report_sensor_signal() {
+        let mut status = DaemonStatus::new(Utc::now());
+        status.set_sensor_signals(vec![SensorSignal {
+            sensor: "wh31_ch2".to_string(),
+            id: 0x1A2B,
+            battery: 0,
+            signal: 3,
+            voltage: None,
+        }]);
+
+        let text = format_report(&status.report(), Units::default());
+        assert!(text.contains("Sensor Signal"));
+        assert!(text.contains("3/4 (id 00001A2B)"));
+    }
+
+    #[test]
+    fn test_format_report_battery() {
+        let mut status = DaemonStatus::new(Utc::now());
+        status.set_battery_forecasts(vec![
+            BatteryForecast {
+                sensor: "wh40".to_string(),
+                level: 1.3,
+                unit: BatteryUnit::Volts,
+                weeks_to_empty: Some(2.6),
+            },
+            BatteryForecast {
+                sensor: "wh57".to_string(),
+                level: 5.0,
+                unit: BatteryUnit::Level,
+                weeks_to_empty: None,
+            },
+        ]);
+
+        let text = format_report(&status.report(), Units::default());
+        assert!(text.contains("1.30 V, about 3 weeks left"));
+        assert!(text.contains("level 5 of 5\n"));
+    }
+}
@@ -0,0 +1,467 @@
+/// Where readings are kept for history queries: the SQL database, or for
+/// deployments without one, an in-memory ring buffer or a JSON lines file
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "db")]
+use crate::database::DatabaseWriter;
+use crate::provenance::Provenance;
+use crate::wind::WindVector;
+
+/// Result of a [`Storage`] call
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Stored values of one field: (timestamp, value), oldest first
+pub type Points = Vec<(DateTime<Utc>, f64)>;
+
+/// The readings of one poll
+pub type Record = (DateTime<Utc>, HashMap<String, f64>);
+
+pub trait Storage: Send + Sync {
+    /// Store the readings of one poll
+    fn save<'a>(
+        &'a self,
+        timestamp: DateTime<Utc>,
+        data: &'a HashMap<String, f64>,
+        provenance: &'a Provenance,
+    ) -> StorageFuture<'a, ()>;
+
+    /// Values of `field` within [from, to], oldest first
+    fn query<'a>(
+        &'a self,
+        field: &'a str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> StorageFuture<'a, Points>;
+
+    /// Count, minimum, maximum and mean of `field` within [from, to]; None
+    /// without readings
+    fn aggregate<'a>(
+        &'a self,
+        field: &'a str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> StorageFuture<'a, Option<Aggregate>> {
+        Box::pin(async move { Ok(Aggregate::of(field, &self.query(field, from, to).await?)) })
+    }
+
+    /// The most recent poll; None when nothing is stored
+    fn latest(&self) -> StorageFuture<'_, Option<Record>>;
+}
+
+pub type SharedStorage = Arc<dyn Storage>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Aggregate {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    /// Vector mean for wind_dir; None when the directions cancel out
+    pub mean: Option<f64>,
+}
+
+impl Aggregate {
+    pub fn of(field: &str, points: &[(DateTime<Utc>, f64)]) -> Option<Self> {
+        let values: Vec<f64> = points
+            .iter()
+            .map(|(_, value)| *value)
+            .filter(|value| value.is_finite())
+            .collect();
+        let mean = if field == "wind_dir" {
+            let mut wind = WindVector::default();
+            for value in &values {
+                wind.add(*value, 1.0);
+            }
+            wind.mean_direction()
+        } else {
+            Some(values.iter().sum::<f64>() / values.len().max(1) as f64)
+        };
+        Some(Self {
+            count: values.len(),
+            min: values.iter().copied().reduce(f64::min)?,
+            max: values.iter().copied().reduce(f64::max)?,
+            mean,
+        })
+    }
+}
+
+/// The `[storage]` section: keeps readings for history queries without a
+/// `[database]`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// The most recent readings in memory, lost on restart
+    Memory {
+        #[serde(default = "default_max_records")]
+        max_records: usize,
+    },
+    /// Every reading appended to a JSON lines file
+    File { path: PathBuf },
+}
+
+/// A week of one-minute polls
+fn default_max_records() -> usize {
+    10_080
+}
+
+impl StorageConfig {
+    pub fn open(&self) -> SharedStorage {
+        match self {
+            Self::Memory { max_records } => Arc::new(MemoryStore::new(*max_records)),
+            Self::File { path } => Arc::new(FileStore::new(path.clone())),
+        }
+    }
+}
+
+/// Ring buffer of the most recent polls
+#[derive(Debug)]
+pub struct MemoryStore {
+    capacity: usize,
+    records: Mutex<VecDeque<Record>>,
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            records: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl Storage for MemoryStore {
+    fn save<'a>(
+        &'a self,
+        timestamp: DateTime<Utc>,
+        data: &'a HashMap<String, f64>,
+        _provenance: &'a Provenance,
+    ) -> StorageFuture<'a, ()> {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back((timestamp, data.clone()));
+        Box::pin(async { Ok(()) })
+    }
+
+    fn query<'a>(
+        &'a self,
+        field: &'a str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> StorageFuture<'a, Points> {
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        let points = records
+            .iter()
+            .filter(|(at, _)| (from..=to).contains(at))
+            .filter_map(|(at, data)| data.get(field).map(|value| (*at, *value)))
+            .collect();
+        Box::pin(async { Ok(points) })
+    }
+
+    fn latest(&self) -> StorageFuture<'_, Option<Record>> {
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        let latest = records.back().cloned();
+        Box::pin(async { Ok(latest) })
+    }
+}
+
+/// One line of a [`FileStore`]
+#[derive(Debug, Serialize, Deserialize)]
+struct FileRecord {
+    timestamp: DateTime<Utc>,
+    data: HashMap<String, f64>,
+    provenance: Provenance,
+}
+
+/// Appends each poll to a JSON lines file; queries read it back
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn append(&self, record: &FileRecord) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context(format!("Failed to open storage file: {:?}", self.path))?;
+        writeln!(file, "{}", serde_json::to_string(record)?)
+            .context(format!("Failed to write storage file: {:?}", self.path))
+    }
+
+    /// Call `f` with every record in the file, in the order written
+    fn for_each(&self, mut f: impl FnMut(FileRecord)) -> Result<()> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return Err(e).context(format!("Failed to open storage file: {:?}", self.path))
+            }
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line.context(format!("Failed to read storage file: {:?}", self.path))?;
+            // A line cut short by a crash only loses that poll
+            if let Ok(record) = serde_json::from_str::<FileRecord>(&line) {
+                f(record);
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, field: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Points> {
+        let mut points = Vec::new();
+        self.for_each(|record| {
+            if let Some(value) = record.data.get(field) {
+                if (from..=to).contains(&record.timestamp) {
+                    points.push((record.timestamp, *value));
+                }
+            }
+        })?;
+        points.sort_by_key(|(at, _)| *at);
+        Ok(points)
+    }
+
+    fn read_latest(&self) -> Result<Option<Record>> {
+        let mut latest: Option<FileRecord> = None;
+        self.for_each(|record| {
+            if latest
+                .as_ref()
+                .is_none_or(|latest| record.timestamp >= latest.timestamp)
+            {
+                latest = Some(record);
+            }
+        })?;
+        Ok(latest.map(|record| (record.timestamp, record.data)))
+    }
+}
+
+impl Storage for FileStore {
+    fn save<'a>(
+        &'a self,
+        timestamp: DateTime<Utc>,
+        data: &'a HashMap<String, f64>,
+        provenance: &'a Provenance,
+    ) -> StorageFuture<'a, ()> {
+        // JSON has no NaN
+        let record = FileRecord {
+            timestamp,
+            data: data
+                .iter()
+                .filter(|(_, value)| value.is_finite())
+                .map(|(field, value)| (field.clone(), *value))
+                .collect(),
+            provenance: provenance.clone(),
+        };
+        Box::pin(async move { self.append(&record) })
+    }
+
+    fn query<'a>(
+        &'a self,
+        field: &'a str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> StorageFuture<'a, Points> {
+        Box::pin(async move { self.read(field, from, to) })
+    }
+
+    fn latest(&self) -> StorageFuture<'_, Option<Record>> {
+        Box::pin(async move { self.read_latest() })
+    }
+}
+
+#[cfg(feature = "db")]
+impl Storage for DatabaseWriter {
+    fn save<'a>(
+        &'a self,
+        timestamp: DateTime<Utc>,
+        data: &'a HashMap<String, f64>,
+        provenance: &'a Provenance,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(async move { self.insert_data(data, &timestamp, provenance).await })
+    }
+
+    fn query<'a>(
+        &'a self,
+        field: &'a str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> StorageFuture<'a, Points> {
+        Box::pin(within_query_timeout(self, async move {
+            if let Some(rollup) = self.rollup_for(&from, &to) {
+                return self.rollup_history(rollup, field, &from, &to).await;
+            }
+            let mut points = self.field_history(field, &from).await?;
+            points.retain(|(at, _)| *at <= to);
+            Ok(points)
+        }))
+    }
+
+    fn aggregate<'a>(
+        &'a self,
+        field: &'a str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> StorageFuture<'a, Option<Aggregate>> {
+        Box::pin(within_query_timeout(self, async move {
+            // Directions need the vector mean, which SQL's AVG isn't
+            if field == "wind_dir" {
+                return Ok(Aggregate::of(field, &self.query(field, from, to).await?));
+            }
+            let (count, min, max, mean) = match self.rollup_for(&from, &to) {
+                Some(rollup) => self.rollup_aggregate(rollup, field, &from, &to).await?,
+                None => self.field_aggregate(field, &from, &to).await?,
+            };
+            Ok(match (min, max) {
+                (Some(min), Some(max)) if count > 0 => Some(Aggregate {
+                    count: count as usize,
+                    min,
+                    max,
+                    mean,
+                }),
+                _ => None,
+            })
+        }))
+    }
+
+    fn latest(&self) -> StorageFuture<'_, Option<Record>> {
+        Box::pin(within_query_timeout(self, self.latest_record()))
+    }
+}
+
+/// Give up on a history query after the writer's `query_timeout`,
+/// including any wait for a free connection. The query is cancelled on the
+/// server when its future is dropped.
+#[cfg(feature = "db")]
+async fn within_query_timeout<T>(
+    writer: &DatabaseWriter,
+    query: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    match writer.query_timeout() {
+        Some(timeout) => tokio::time::timeout(timeout, query)
+            .await
+            .map_err(|_| anyhow::anyhow!("History query timed out after {}s", timeout.as_secs()))?,
+        None => query.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone};
+    use tempfile::TempDir;
+
+    fn at(minute: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 4, 1, 12, 0, 0).unwrap() + TimeDelta::minutes(minute)
+    }
+
+    async fn fill(store: &dyn Storage) {
+        let provenance = Provenance::poll(None, None, 0);
+        for (minute, temp) in [(0, 10.0), (1, 11.0), (2, 12.0), (3, 13.0)] {
+            let data = HashMap::from([("outtemp".to_string(), temp)]);
+            store.save(at(minute), &data, &provenance).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryStore::new(3);
+        fill(&store).await;
+
+        // The oldest poll made room
+        let points = store.query("outtemp", at(0), at(10)).await.unwrap();
+        assert_eq!(points, vec![(at(1), 11.0), (at(2), 12.0), (at(3), 13.0)]);
+        assert!(store
+            .query("outhumid", at(0), at(10))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let aggregate = store.aggregate("outtemp", at(2), at(3)).await.unwrap();
+        assert_eq!(
+            aggregate,
+            Some(Aggregate {
+                count: 2,
+                min: 12.0,
+                max: 13.0,
+                mean: Some(12.5)
+            })
+        );
+
+        let (timestamp, data) = store.latest().await.unwrap().unwrap();
+        assert_eq!((timestamp, data["outtemp"]), (at(3), 13.0));
+        assert_eq!(MemoryStore::new(3).latest().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_file_store() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("readings.jsonl");
+        let store = FileStore::new(path.clone());
+        assert!(store
+            .query("outtemp", at(0), at(10))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(store.latest().await.unwrap(), None);
+
+        fill(&store).await;
+        // A torn last line is skipped
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"timestamp\":").unwrap();
+
+        // Survives a restart
+        let store = FileStore::new(path);
+        let points = store.query("outtemp", at(1), at(2)).await.unwrap();
+        assert_eq!(points, vec![(at(1), 11.0), (at(2), 12.0)]);
+        assert_eq!(
+            store
+                .aggregate("outtemp", at(0), at(3))
+                .await
+                .unwrap()
+                .unwrap()
+                .count,
+            4
+        );
+        let (timestamp, _) = store.latest().await.unwrap().unwrap();
+        assert_eq!(timestamp, at(3));
+    }
+
+    #[test]
+    fn test_aggregate_wind_direction() {
+        let points = [(at(0), 350.0), (at(1), 10.0)];
+        let aggregate = Aggregate::of("wind_dir", &points).unwrap();
+        let mean = aggregate.mean.unwrap();
+        assert!(mean.min(360.0 - mean) < 1e-6);
+        assert_eq!(Aggregate::of("outtemp", &[]), None);
+    }
+
+    #[test]
+    fn test_storage_config() {
+        let config: StorageConfig = toml::from_str("backend = \"memory\"").unwrap();
+        assert_eq!(
+            config,
+            StorageConfig::Memory {
+                max_records: 10_080
+            }
+        );
+        let config: StorageConfig =
+            toml::from_str("backend = \"file\"\npath = \"/var/lib/wxlistener/readings.jsonl\"")
+                .unwrap();
+        assert!(matches!(config, StorageConfig::File { .. }));
+        assert!(toml::from_str::<StorageConfig>("backend = \"file\"").is_err());
+    }
+}
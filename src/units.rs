@@ -0,0 +1,192 @@
+use serde::Deserialize;
+
+/// Ecowitt's conversion from the light sensor's lux to W/m²
+pub const LUX_PER_WM2: f64 = 126.7;
+
+/// Default units for every metric, refined by the per-metric settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    /// °C, m/s, hPa and mm, as the gateway reports them
+    #[default]
+    Metric,
+    /// °F, mph, inHg and inches
+    Imperial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum TemperatureUnit {
+    #[serde(rename = "c")]
+    Celsius,
+    #[serde(rename = "f")]
+    Fahrenheit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum SpeedUnit {
+    #[serde(rename = "m/s")]
+    MetersPerSecond,
+    #[serde(rename = "km/h")]
+    KilometersPerHour,
+    #[serde(rename = "mph")]
+    MilesPerHour,
+    #[serde(rename = "knots")]
+    Knots,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum PressureUnit {
+    #[serde(rename = "hpa")]
+    Hectopascal,
+    #[serde(rename = "inhg")]
+    InchesOfMercury,
+    #[serde(rename = "mmhg")]
+    MillimetersOfMercury,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum RainUnit {
+    #[serde(rename = "mm")]
+    Millimeters,
+    #[serde(rename = "in")]
+    Inches,
+}
+
+/// The `[units]` section: a unit system plus optional per-metric overrides,
+/// e.g. `system = "metric"` with `wind = "knots"`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UnitsConfig {
+    #[serde(default)]
+    pub system: UnitSystem,
+    pub temperature: Option<TemperatureUnit>,
+    pub wind: Option<SpeedUnit>,
+    pub pressure: Option<PressureUnit>,
+    pub rain: Option<RainUnit>,
+}
+
+impl UnitsConfig {
+    pub fn units(&self) -> Units {
+        let defaults = match self.system {
+            UnitSystem::Metric => Units::default(),
+            UnitSystem::Imperial => Units {
+                temperature: TemperatureUnit::Fahrenheit,
+                wind: SpeedUnit::MilesPerHour,
+                pressure: PressureUnit::InchesOfMercury,
+                rain: RainUnit::Inches,
+            },
+        };
+        Units {
+            temperature: self.temperature.unwrap_or(defaults.temperature),
+            wind: self.wind.unwrap_or(defaults.wind),
+            pressure: self.pressure.unwrap_or(defaults.pressure),
+            rain: self.rain.unwrap_or(defaults.rain),
+        }
+    }
+}
+
+/// Units for formatted readings. Readings are always held, stored and
+/// published in metric; these only change how they are displayed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Units {
+    pub temperature: TemperatureUnit,
+    pub wind: SpeedUnit,
+    pub pressure: PressureUnit,
+    pub rain: RainUnit,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self {
+            temperature: TemperatureUnit::Celsius,
+            wind: SpeedUnit::MetersPerSecond,
+            pressure: PressureUnit::Hectopascal,
+            rain: RainUnit::Millimeters,
+        }
+    }
+}
+
+impl TemperatureUnit {
+    /// Convert from °C, returning the value and its symbol
+    pub fn convert(self, celsius: f64) -> (f64, &'static str) {
+        match self {
+            Self::Celsius => (celsius, "°C"),
+            Self::Fahrenheit => (celsius * 9.0 / 5.0 + 32.0, "°F"),
+        }
+    }
+}
+
+impl SpeedUnit {
+    /// Convert from m/s, returning the value and its symbol
+    pub fn convert(self, meters_per_second: f64) -> (f64, &'static str) {
+        match self {
+            Self::MetersPerSecond => (meters_per_second, "m/s"),
+            Self::KilometersPerHour => (meters_per_second * 3.6, "km/h"),
+            Self::MilesPerHour => (meters_per_second / 0.447_04, "mph"),
+            Self::Knots => (meters_per_second * 3600.0 / 1852.0, "kn"),
+        }
+    }
+}
+
+impl PressureUnit {
+    /// Convert from hPa, returning the value, its symbol and decimal places
+    pub fn convert(self, hectopascals: f64) -> (f64, &'static str, usize) {
+        match self {
+            Self::Hectopascal => (hectopascals, "hPa", 1),
+            Self::InchesOfMercury => (hectopascals / 33.863_886, "inHg", 2),
+            Self::MillimetersOfMercury => (hectopascals / 1.333_224, "mmHg", 1),
+        }
+    }
+}
+
+impl RainUnit {
+    /// Convert from mm, returning the value, its symbol and decimal places
+    pub fn convert(self, millimeters: f64) -> (f64, &'static str, usize) {
+        match self {
+            Self::Millimeters => (millimeters, "mm", 1),
+            Self::Inches => (millimeters / 25.4, "in", 2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_config() {
+        assert_eq!(UnitsConfig::default().units(), Units::default());
+
+        let config: UnitsConfig =
+            toml::from_str("wind = \"knots\"\npressure = \"mmhg\"\nrain = \"in\"").unwrap();
+        let units = config.units();
+        assert_eq!(units.temperature, TemperatureUnit::Celsius);
+        assert_eq!(units.wind, SpeedUnit::Knots);
+        assert_eq!(units.pressure, PressureUnit::MillimetersOfMercury);
+        assert_eq!(units.rain, RainUnit::Inches);
+
+        // Overrides apply on top of the system
+        let config: UnitsConfig =
+            toml::from_str("system = \"imperial\"\ntemperature = \"c\"").unwrap();
+        let units = config.units();
+        assert_eq!(units.temperature, TemperatureUnit::Celsius);
+        assert_eq!(units.wind, SpeedUnit::MilesPerHour);
+        assert_eq!(units.pressure, PressureUnit::InchesOfMercury);
+
+        assert!(toml::from_str::<UnitsConfig>("wind = \"furlongs\"").is_err());
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(TemperatureUnit::Fahrenheit.convert(100.0), (212.0, "°F"));
+        assert_eq!(TemperatureUnit::Fahrenheit.convert(-40.0), (-40.0, "°F"));
+        let (knots, _) = SpeedUnit::Knots.convert(10.0);
+        assert!((knots - 19.438).abs() < 0.001);
+        let (mph, _) = SpeedUnit::MilesPerHour.convert(10.0);
+        assert!((mph - 22.369).abs() < 0.001);
+        let (inhg, _, _) = PressureUnit::InchesOfMercury.convert(1013.25);
+        assert!((inhg - 29.921).abs() < 0.001);
+        let (mmhg, _, _) = PressureUnit::MillimetersOfMercury.convert(1013.25);
+        assert!((mmhg - 760.0).abs() < 0.01);
+        assert_eq!(RainUnit::Inches.convert(25.4), (1.0, "in", 2));
+    }
+}
@@ -0,0 +1,185 @@
+/// Protocol conformance test vectors: live data frames as the gateway sent
+/// them, in hex, with the readings they must decode to. The golden set and
+/// vectors users submit live in tests/vectors/ and run in
+/// tests/vectors_test.rs, so a decoder change that breaks some firmware's
+/// frames shows up as a failing vector.
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::decoder::parse_livedata_with_warnings;
+use crate::protocol::{hex_frame, response_payload, verify_response};
+
+/// Live data command, the only frame vectors cover so far
+const CMD_GW1000_LIVEDATA: u8 = 0x27;
+
+/// Decoded values are compared to this tolerance
+const TOLERANCE: f64 = 1e-6;
+
+/// One `.toml` file under tests/vectors/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// What the frame exercises, e.g. "WS90 with piezo rain"
+    pub description: String,
+    /// Model and firmware the frame came from, if captured from a gateway
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<String>,
+    /// The whole response, header to checksum, as hex bytes; whitespace is
+    /// ignored
+    pub frame: String,
+    /// Decode warnings expected: runs of unknown bytes and a truncated
+    /// trailing field
+    #[serde(default)]
+    pub warnings: u32,
+    /// Every reading the frame decodes to, and nothing else
+    pub expected: BTreeMap<String, f64>,
+}
+
+/// Bytes from hex like "FF FF 27 00 08", ignoring whitespace
+pub fn parse_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid hex digit '{}'", c);
+    }
+    if !digits.len().is_multiple_of(2) {
+        anyhow::bail!("Odd number of hex digits");
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&digits[i..i + 2], 16)?))
+        .collect()
+}
+
+impl TestVector {
+    /// A vector for a live data frame as the current decoder reads it, to be
+    /// checked by hand before it is submitted
+    pub fn capture(description: String, firmware: Option<String>, frame: &[u8]) -> Result<Self> {
+        let (data, warnings) = decode(frame)?;
+        Ok(Self {
+            description,
+            firmware,
+            frame: hex_frame(frame),
+            warnings,
+            expected: data,
+        })
+    }
+
+    /// Decode the frame and list every difference from what is expected
+    pub fn check(&self) -> Result<()> {
+        let frame = parse_hex(&self.frame)?;
+        let (data, warnings) = decode(&frame)?;
+
+        let mut differences = Vec::new();
+        for (field, expected) in &self.expected {
+            match data.get(field) {
+                Some(value) if (value - expected).abs() <= TOLERANCE => {}
+                Some(value) => {
+                    differences.push(format!("{}: expected {}, got {}", field, expected, value))
+                }
+                None => differences.push(format!("{}: expected {}, missing", field, expected)),
+            }
+        }
+        for (field, value) in &data {
+            if !self.expected.contains_key(field) {
+                differences.push(format!("{}: unexpected, got {}", field, value));
+            }
+        }
+        if warnings != self.warnings {
+            differences.push(format!(
+                "expected {} decode warnings, got {}",
+                self.warnings, warnings
+            ));
+        }
+        if !differences.is_empty() {
+            anyhow::bail!("{}", differences.join("; "));
+        }
+        Ok(())
+    }
+}
+
+/// Verify a live data frame's header and checksum and decode it
+fn decode(frame: &[u8]) -> Result<(BTreeMap<String, f64>, u32)> {
+    if !verify_response(frame, CMD_GW1000_LIVEDATA) {
+        anyhow::bail!("Not a live data frame, or its checksum is wrong");
+    }
+    let payload = response_payload(frame, true)?;
+    let (data, warnings) = parse_livedata_with_warnings(payload);
+    Ok((data.into_iter().collect(), warnings))
+}
+
+/// Every vector under `dir` and its subdirectories, by path
+#[allow(dead_code)]
+pub fn load(dir: &Path) -> Result<Vec<(PathBuf, TestVector)>> {
+    let mut vectors = Vec::new();
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .context(format!("Failed to read {:?}", dir))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            vectors.extend(load(&path)?);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            let text = fs::read_to_string(&path)?;
+            let vector = toml::from_str(&text).context(format!("Invalid vector {:?}", path))?;
+            vectors.push((path, vector));
+        }
+    }
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// outtemp 25.5 °C and outhumid 65 %
+    const FRAME: &str = "FF FF 27 00 09 02 00 FF 07 41 79";
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("FF ff\n27").unwrap(), vec![0xFF, 0xFF, 0x27]);
+        assert!(parse_hex("FF F").is_err());
+        assert!(parse_hex("GG").is_err());
+        assert!(parse_hex("F°").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let frame = parse_hex(FRAME).unwrap();
+        let mut vector = TestVector::capture("outdoor".to_string(), None, &frame).unwrap();
+        assert_eq!(vector.frame, FRAME);
+        assert_eq!(
+            vector.expected,
+            BTreeMap::from([
+                ("outhumid".to_string(), 65.0),
+                ("outtemp".to_string(), 25.5)
+            ])
+        );
+        vector.check().unwrap();
+
+        // Captured vectors survive being written out and read back
+        let written: TestVector = toml::from_str(&toml::to_string(&vector).unwrap()).unwrap();
+        assert_eq!(written, vector);
+
+        vector.expected.insert("outtemp".to_string(), 25.4);
+        vector.expected.insert("intemp".to_string(), 21.0);
+        vector.expected.remove("outhumid");
+        let error = vector.check().unwrap_err().to_string();
+        assert!(
+            error.contains("outtemp: expected 25.4, got 25.5"),
+            "{}",
+            error
+        );
+        assert!(error.contains("intemp: expected 21, missing"), "{}", error);
+        assert!(error.contains("outhumid: unexpected, got 65"), "{}", error);
+
+        // A corrupted byte fails the checksum
+        vector.frame = FRAME.replace("41", "42");
+        assert!(vector.check().is_err());
+    }
+}
@@ -0,0 +1,260 @@
+/// Write-ahead log between the poller and the database: each reading is
+/// appended and synced to disk before it is written to the database, and a
+/// progress marker records the last one the database has. Readings survive
+/// crashes and outages, and because database writes are upserts on the
+/// timestamp, replaying one whose marker update was lost still leaves a
+/// single row.
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::database::DatabaseWriter;
+use crate::provenance::Provenance;
+
+/// Most entries written to the database per poll, so catching up after a
+/// long outage doesn't hold up polling
+pub const REPLAY_BATCH: usize = 1000;
+
+/// Entries written to the database per transaction, and between progress
+/// marker updates
+const MARK_EVERY: usize = 100;
+
+/// One reading in the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalEntry {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub data: HashMap<String, f64>,
+    pub provenance: Provenance,
+}
+
+#[derive(Debug)]
+pub struct WriteAheadLog {
+    path: PathBuf,
+    /// Holds the sequence number of the last delivered entry
+    marker: PathBuf,
+    delivered: u64,
+    /// Entries appended and not yet delivered, oldest first
+    pending: Vec<WalEntry>,
+}
+
+impl WriteAheadLog {
+    /// Open the log, creating it if needed, and load the entries after the
+    /// progress marker
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)
+                    .context(format!("Failed to create WAL directory: {:?}", parent))?;
+            }
+        }
+        let mut marker = path.as_os_str().to_owned();
+        marker.push(".pos");
+        let marker = PathBuf::from(marker);
+
+        let delivered = match fs::read_to_string(&marker) {
+            Ok(contents) => contents
+                .trim()
+                .parse()
+                .context(format!("Invalid WAL progress marker: {:?}", marker))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e).context(format!("Failed to read {:?}", marker)),
+        };
+
+        truncate_torn_line(path)?;
+        let mut pending = Vec::new();
+        if path.exists() {
+            let file = File::open(path).context(format!("Failed to open WAL: {:?}", path))?;
+            for line in BufReader::new(file).lines() {
+                let line = line.context(format!("Failed to read WAL: {:?}", path))?;
+                let entry: WalEntry = serde_json::from_str(&line)
+                    .context(format!("Corrupt entry in WAL {:?}: {}", path, line))?;
+                if entry.seq > delivered {
+                    pending.push(entry);
+                }
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            marker,
+            delivered,
+            pending,
+        })
+    }
+
+    /// Readings waiting for the database, oldest first
+    pub fn pending(&self) -> &[WalEntry] {
+        &self.pending
+    }
+
+    /// Append a reading and sync it to disk. Returns its sequence number.
+    pub fn append(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        data: &HashMap<String, f64>,
+        provenance: &Provenance,
+    ) -> Result<u64> {
+        let seq = self
+            .pending
+            .last()
+            .map_or(self.delivered, |entry| entry.seq)
+            + 1;
+        let entry = WalEntry {
+            seq,
+            timestamp,
+            data: data.clone(),
+            provenance: provenance.clone(),
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context(format!("Failed to open WAL: {:?}", self.path))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)
+            .and_then(|_| file.sync_data())
+            .context(format!("Failed to write WAL: {:?}", self.path))?;
+        self.pending.push(entry);
+        Ok(seq)
+    }
+
+    /// Record that the database has every entry up to `seq`. Once nothing is
+    /// pending the log is emptied.
+    pub fn mark_delivered(&mut self, seq: u64) -> Result<()> {
+        let tmp_path = self.marker.with_extension("pos.tmp");
+        fs::write(&tmp_path, seq.to_string())
+            .and_then(|_| File::open(&tmp_path)?.sync_all())
+            .context(format!(
+                "Failed to write WAL progress marker: {:?}",
+                tmp_path
+            ))?;
+        fs::rename(&tmp_path, &self.marker).context(format!(
+            "Failed to replace WAL progress marker: {:?}",
+            self.marker
+        ))?;
+
+        self.delivered = seq;
+        self.pending.retain(|entry| entry.seq > seq);
+        if self.pending.is_empty() {
+            // Entries at or before the marker are skipped on open, so a
+            // crash before this leaves nothing to replay twice
+            File::create(&self.path).context(format!("Failed to empty WAL: {:?}", self.path))?;
+        }
+        Ok(())
+    }
+}
+
+impl WriteAheadLog {
+    /// Write up to `limit` pending entries to the database, oldest first,
+    /// in transactions of up to `MARK_EVERY`, moving the progress marker
+    /// past each one committed. Stops at the first database error.
+    /// Returns the number written.
+    pub async fn deliver(&mut self, writer: &DatabaseWriter, limit: usize) -> Result<usize> {
+        let mut written = 0;
+        while written < limit && !self.pending.is_empty() {
+            let chunk = &self.pending[..MARK_EVERY.min(limit - written).min(self.pending.len())];
+            writer
+                .insert_batch(
+                    chunk
+                        .iter()
+                        .map(|entry| (&entry.timestamp, &entry.data, &entry.provenance)),
+                )
+                .await?;
+            let (count, seq) = (chunk.len(), chunk[chunk.len() - 1].seq);
+            self.mark_delivered(seq)?;
+            written += count;
+        }
+        Ok(written)
+    }
+
+    /// When the oldest pending entry was taken
+    pub fn oldest(&self) -> Option<DateTime<Utc>> {
+        self.pending.first().map(|entry| entry.timestamp)
+    }
+}
+
+/// Drop a final line cut short by a crash, so the next append starts on a
+/// line of its own. Its reading was never handed to the database.
+fn truncate_torn_line(path: &Path) -> Result<()> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).context(format!("Failed to open WAL: {:?}", path)),
+    };
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)
+        .context(format!("Failed to read WAL: {:?}", path))?;
+    if contents.last().is_some_and(|byte| *byte != b'\n') {
+        let keep = contents
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |i| i + 1);
+        eprintln!("[WARN] Dropping an incomplete entry at the end of the WAL");
+        file.set_len(keep as u64)
+            .context(format!("Failed to repair WAL: {:?}", path))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn reading(temp: f64) -> HashMap<String, f64> {
+        HashMap::from([("outtemp".to_string(), temp)])
+    }
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 17, 12, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_wal_replays_undelivered_entries() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("db.wal");
+        let provenance = Provenance::poll(None, None, 0);
+
+        let mut wal = WriteAheadLog::open(&path).unwrap();
+        assert_eq!(wal.append(at(0), &reading(10.0), &provenance).unwrap(), 1);
+        assert_eq!(wal.append(at(1), &reading(11.0), &provenance).unwrap(), 2);
+        assert_eq!(wal.append(at(2), &reading(12.0), &provenance).unwrap(), 3);
+        wal.mark_delivered(1).unwrap();
+
+        // As after a crash: the undelivered entries come back
+        let mut wal = WriteAheadLog::open(&path).unwrap();
+        let pending: Vec<u64> = wal.pending().iter().map(|entry| entry.seq).collect();
+        assert_eq!(pending, vec![2, 3]);
+        assert_eq!(wal.pending()[0].data["outtemp"], 11.0);
+
+        // Delivering everything empties the log and sequence numbers go on
+        wal.mark_delivered(3).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        let mut wal = WriteAheadLog::open(&path).unwrap();
+        assert!(wal.pending().is_empty());
+        assert_eq!(wal.append(at(3), &reading(13.0), &provenance).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_wal_drops_torn_last_line() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("db.wal");
+        let provenance = Provenance::poll(None, None, 0);
+
+        let mut wal = WriteAheadLog::open(&path).unwrap();
+        wal.append(at(0), &reading(10.0), &provenance).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"seq\":2,\"timest").unwrap();
+
+        let mut wal = WriteAheadLog::open(&path).unwrap();
+        assert_eq!(wal.pending().len(), 1);
+        assert_eq!(wal.append(at(1), &reading(11.0), &provenance).unwrap(), 2);
+        let wal = WriteAheadLog::open(&path).unwrap();
+        assert_eq!(wal.pending().len(), 2);
+    }
+}
@@ -1,23 +1,55 @@
+use anyhow::Context;
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        ConnectInfo, State,
+        ConnectInfo, Query, State,
     },
+    http::{header, StatusCode},
+    middleware,
     response::{Html, IntoResponse, Json},
-    routing::get,
-    Router,
+    routing::{get, patch, post},
+    Extension, Router,
 };
-use chrono::Utc;
+use chrono::{DateTime, FixedOffset, Local, Utc};
 use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::broadcast;
 use tokio::time;
 use tower_http::trace::{DefaultMakeSpan, TraceLayer};
 
+#[cfg(feature = "db")]
+use crate::annotations::{grafana_json, Annotation};
+use crate::auth::{require_scope, ApiToken, RequireScope, TokenScope};
+use crate::calibration::CalibrationConfig;
 use crate::client::GW1000Client;
-use crate::output::format_value;
+use crate::compare::{compare, default_step, Comparison, SeriesSpec};
+use crate::control::{handle_request, update_settings, SharedControls};
+#[cfg(feature = "db")]
+use crate::database::DatabaseWriter;
+use crate::degree_days::SharedDegreeDays;
+use crate::derived::{add_derived_fields, DerivedConfig};
+use crate::device_queue::{DeviceQueue, Priority};
+use crate::events::EVENT_LOG_CAPACITY;
+#[cfg(feature = "http")]
+use crate::federation::{SharedPeers, FEDERATION_PATH, SIGNATURE_HEADER};
+use crate::feed::{LiveFeed, Received};
+use crate::fixed::FixedReadings;
+use crate::geojson::{feature_collection, station_feature};
+use crate::history::parse_period;
+use crate::livedata_info::livedata_info;
+use crate::local_api::{Gateway, GatewayApi, LocalHttpClient};
+use crate::output::{FieldValue, PrecisionConfig};
+use crate::public::PublicConfig;
+use crate::realtime::{clientraw_txt, realtime_txt};
+use crate::schedule::{poll_interval, PollProfile, TimestampAlignment};
+use crate::station::{StationConfig, StationTime};
+use crate::status::SharedStatus;
+use crate::storage::{Record, SharedStorage};
+use crate::units::Units;
 
 const HTML_PAGE: &str = r#"<!DOCTYPE html>
 <html lang="en">
@@ -118,7 +150,11 @@ const HTML_PAGE: &str = r#"<!DOCTYPE html>
 
         function connect() {
             const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
-            ws = new WebSocket(`${protocol}//${window.location.host}/ws`);
+            // Relative to the page, so the dashboard also works behind a
+            // proxy under a path such as /station/cabin/. Pass ?token= from
+            // the page URL on to the WebSocket.
+            const base = window.location.pathname.replace(/\/?$/, '/');
+            ws = new WebSocket(`${protocol}//${window.location.host}${base}ws${window.location.search}`);
 
             ws.onopen = () => {
                 statusEl.className = 'status connected';
@@ -174,9 +210,335 @@ const HTML_PAGE: &str = r#"<!DOCTYPE html>
 "#;
 
 pub struct WebServerConfig {
-    pub ip: String,
-    pub port: u16,
+    /// Addresses to serve the same app on
+    pub listen: Vec<ListenAddr>,
+    /// Permissions of Unix socket files, e.g. 0o660
+    pub socket_mode: u32,
     pub interval: u64,
+    pub degree_days: Option<SharedDegreeDays>,
+    pub calibration: Option<CalibrationConfig>,
+    pub derived: DerivedConfig,
+    pub status: Option<SharedStatus>,
+    pub max_response_size: usize,
+    pub trace_protocol: bool,
+    /// How live data is read, and the gateway's HTTP port for it
+    pub api: GatewayApi,
+    pub http_port: u16,
+    pub alignment: TimestampAlignment,
+    pub poll_profiles: Vec<PollProfile>,
+    pub precision: PrecisionConfig,
+    /// Units for the formatted `data` strings
+    pub units: Units,
+    pub fixed_point: bool,
+    /// Stored readings for /api/v1/history.json
+    pub storage: Option<SharedStorage>,
+    /// Readings of other stations by name, for /api/v1/compare.json
+    pub stations: BTreeMap<String, SharedStorage>,
+    /// This station and the others in `stations`, by name, for
+    /// /api/v1/stations.geojson; those without coordinates are left off
+    pub station: StationConfig,
+    pub station_locations: BTreeMap<String, StationConfig>,
+    /// API tokens; without any, data routes are open and control is off
+    pub tokens: Vec<ApiToken>,
+    pub controls: Option<SharedControls>,
+    /// Paths serving Cumulus realtime.txt and Weather Display clientraw.txt
+    pub realtime_txt: Option<String>,
+    pub clientraw_txt: Option<String>,
+    pub station_name: String,
+    /// Local time for realtime.txt and clientraw.txt
+    pub station_time: StationTime,
+    /// What requests without a token see; None keeps them out when tokens
+    /// are configured
+    pub public: Option<PublicConfig>,
+    /// Where annotations are stored and read from
+    #[cfg(feature = "db")]
+    pub database: Option<Arc<DatabaseWriter>>,
+    /// Readings received from federation peers
+    #[cfg(feature = "http")]
+    pub peers: Option<SharedPeers>,
+    /// The daemon's gateway queue, so polls here take turns with its own;
+    /// None connects to `gw_ip` separately
+    pub device: Option<DeviceQueue>,
+}
+
+/// `[web]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebConfig {
+    /// Addresses to serve on, e.g. "127.0.0.1:8080", "[::1]:8080" or
+    /// "unix:/run/wxlistener/web.sock". When set, --web-host and --web-port
+    /// are ignored.
+    #[serde(default)]
+    pub listen: Vec<ListenAddr>,
+    /// Permissions of Unix socket files (default: 0o660, owner and group)
+    #[serde(default = "default_socket_mode")]
+    pub socket_mode: u32,
+    /// API tokens and their scopes
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+    /// Path to serve a Cumulus realtime.txt at, e.g. "/realtime.txt"
+    #[serde(default)]
+    pub realtime_txt: Option<String>,
+    /// Path to serve a Weather Display clientraw.txt at, e.g. "/clientraw.txt"
+    #[serde(default)]
+    pub clientraw_txt: Option<String>,
+    /// Station name in clientraw.txt (default: "wxlistener")
+    #[serde(default = "default_station_name")]
+    pub station_name: String,
+    /// Serve data routes without a token, with indoor sensors left out and
+    /// coarser readings and coordinates
+    #[serde(default)]
+    pub public: Option<PublicConfig>,
+}
+
+fn default_socket_mode() -> u32 {
+    0o660
+}
+
+fn default_station_name() -> String {
+    "wxlistener".to_string()
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            listen: Vec::new(),
+            socket_mode: default_socket_mode(),
+            tokens: Vec::new(),
+            realtime_txt: None,
+            clientraw_txt: None,
+            station_name: default_station_name(),
+            public: None,
+        }
+    }
+}
+
+/// One address the web server listens on
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum ListenAddr {
+    /// `host:port`, resolved when binding
+    Tcp(String),
+    /// `unix:<path>`, a Unix domain socket for a local reverse proxy
+    Unix(PathBuf),
+}
+
+impl From<String> for ListenAddr {
+    fn from(value: String) -> Self {
+        match value.strip_prefix("unix:") {
+            Some(path) => ListenAddr::Unix(PathBuf::from(path)),
+            None => ListenAddr::Tcp(value),
+        }
+    }
+}
+
+impl std::fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "http://{}", addr),
+            ListenAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// `host:port` for a listener, bracketing IPv6 literals such as `::`
+pub fn tcp_listen_addr(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// Build the app: the dashboard, the WebSocket feed, the JSON API, the
+/// gateway's own `/get_livedata_info` and any configured
+/// realtime.txt/clientraw.txt paths. Data routes need a read token and the
+/// control, settings and new annotation endpoints an admin token when
+/// `[web] tokens` are configured. With `[web.public]`, data routes also
+/// serve the public view to requests without a token.
+pub fn router(feed: Arc<LiveFeed>, config: &WebServerConfig) -> Router {
+    let tokens = Arc::new(config.tokens.clone());
+    let public = config.public.clone().map(|public| {
+        Arc::new(PublicConfig {
+            units: config.units,
+            ..public
+        })
+    });
+    let degree_days = config.degree_days.clone();
+    let status = config.status.clone();
+    let events_status = config.status.clone();
+    let storage = config.storage.clone();
+    let compare_storage = config.storage.clone();
+    let stations = Arc::new(config.stations.clone());
+    let map_station = Arc::new(StationConfig {
+        name: config
+            .station
+            .name
+            .clone()
+            .or_else(|| Some(config.station_name.clone())),
+        ..config.station.clone()
+    });
+    // Only stations with a location are looked up
+    let map_stations: Arc<Vec<_>> = Arc::new(
+        config
+            .station_locations
+            .iter()
+            .filter(|(_, station)| station.latitude.is_some() && station.longitude.is_some())
+            .filter_map(|(name, station)| {
+                let storage = config.stations.get(name)?.clone();
+                Some((name.clone(), station.clone(), storage))
+            })
+            .collect(),
+    );
+    let mut read = Router::new()
+        .route(
+            "/ws",
+            get(|State(feed), ws, public, addr| websocket_handler(ws, feed, public, addr)),
+        )
+        .route("/api/v1/current.json", get(api_current_handler))
+        .route("/api/v1/feed.json", get(api_feed_handler))
+        .route("/get_livedata_info", get(livedata_info_handler))
+        .route(
+            "/api/v1/summary.json",
+            get(move |addr| api_summary_handler(degree_days.clone(), addr)),
+        )
+        .route(
+            "/api/v1/status.json",
+            get(move |public, addr| api_status_handler(status.clone(), public, addr)),
+        )
+        .route(
+            "/api/v1/events.json",
+            get(move |query, addr| api_events_handler(events_status.clone(), query, addr)),
+        )
+        .route(
+            "/api/v1/history.json",
+            get(move |public, query, addr| {
+                api_history_handler(storage.clone(), public, query, addr)
+            }),
+        )
+        .route(
+            "/api/v1/stations.geojson",
+            get(move |State(feed), public, addr| {
+                api_stations_handler(
+                    feed,
+                    map_station.clone(),
+                    map_stations.clone(),
+                    public,
+                    addr,
+                )
+            }),
+        )
+        .route(
+            "/api/v1/compare.json",
+            get(move |public, query, addr| {
+                api_compare_handler(
+                    compare_storage.clone(),
+                    stations.clone(),
+                    public,
+                    query,
+                    addr,
+                )
+            }),
+        );
+    #[cfg(feature = "db")]
+    {
+        let database = config.database.clone();
+        read = read.route(
+            "/api/v1/annotations.json",
+            get(move |query, addr| api_annotations_handler(database.clone(), query, addr)),
+        );
+    }
+    let station_name: Arc<str> = config.station_name.as_str().into();
+    let station_time = config.station_time;
+    for (path, file) in [
+        (&config.realtime_txt, TemplateFile::Realtime),
+        (&config.clientraw_txt, TemplateFile::Clientraw),
+    ] {
+        if let Some(path) = path {
+            let station_name = station_name.clone();
+            read = read.route(
+                path,
+                get(move |State(feed), public, addr| {
+                    template_file_handler(
+                        feed,
+                        file,
+                        station_name.clone(),
+                        station_time,
+                        public,
+                        addr,
+                    )
+                }),
+            );
+        }
+    }
+    #[cfg(feature = "http")]
+    if let Some(peers) = &config.peers {
+        let peers = peers.clone();
+        read = read.route(
+            "/api/v1/peers.json",
+            get(move |public, addr| api_peers_handler(peers.clone(), public, addr)),
+        );
+    }
+    let read = read.route_layer(middleware::from_fn_with_state(
+        RequireScope::new(tokens.clone(), TokenScope::Read).with_public(public),
+        require_scope,
+    ));
+
+    let controls = config.controls.clone();
+    let status = config.status.clone();
+    let settings_controls = config.controls.clone();
+    let settings_status = config.status.clone();
+    #[cfg_attr(not(feature = "db"), allow(unused_mut))]
+    let mut admin = Router::new()
+        .route(
+            "/api/v1/control",
+            post(move |addr, body| {
+                api_control_handler(controls.clone(), status.clone(), addr, body)
+            }),
+        )
+        .route(
+            "/api/v1/settings",
+            patch(move |addr, body| {
+                api_settings_handler(
+                    settings_controls.clone(),
+                    settings_status.clone(),
+                    addr,
+                    body,
+                )
+            }),
+        );
+    #[cfg(feature = "db")]
+    {
+        let database = config.database.clone();
+        admin = admin.route(
+            "/api/v1/annotations",
+            post(move |addr, body| api_add_annotation_handler(database.clone(), addr, body)),
+        );
+    }
+    let admin = admin.route_layer(middleware::from_fn_with_state(
+        RequireScope::new(tokens, TokenScope::Admin),
+        require_scope,
+    ));
+
+    #[cfg_attr(not(feature = "http"), allow(unused_mut))]
+    let mut open = Router::new()
+        .route("/", get(index_handler))
+        .route("/api/v1/schema.json", get(api_schema_handler));
+    // Peers sign their readings instead of presenting a token
+    #[cfg(feature = "http")]
+    if let Some(peers) = &config.peers {
+        let peers = peers.clone();
+        open = open.route(
+            FEDERATION_PATH,
+            post(move |addr, headers, body| {
+                api_federation_handler(peers.clone(), addr, headers, body)
+            }),
+        );
+    }
+
+    open.merge(read).merge(admin).with_state(feed).layer(
+        TraceLayer::new_for_http()
+            .make_span_with(DefaultMakeSpan::new().level(tracing::Level::INFO)),
+    )
 }
 
 /// Spawns the web server as a background task
@@ -193,34 +555,54 @@ pub async fn run_web_server(
     gw_ip: String,
     gw_port: u16,
 ) -> anyhow::Result<()> {
-    let (tx, _rx) = broadcast::channel::<String>(100);
-    let tx = Arc::new(tx);
+    let feed = Arc::new(LiveFeed::default());
+    let app = router(feed.clone(), &config);
 
     // Spawn background task to fetch weather data
-    let tx_clone = tx.clone();
+    let poll_feed = feed.clone();
+    let device = config.device.clone().unwrap_or_else(|| {
+        DeviceQueue::start(Gateway::new(
+            config.api,
+            GW1000Client::new(gw_ip.clone(), gw_port)
+                .with_max_response_size(config.max_response_size)
+                .with_protocol_trace(config.trace_protocol),
+            LocalHttpClient::new(gw_ip, config.http_port)
+                .with_max_response_size(config.max_response_size)
+                .with_protocol_trace(config.trace_protocol),
+        ))
+    });
     tokio::spawn(async move {
-        let client = GW1000Client::new(gw_ip, gw_port);
-        let mut interval = time::interval(Duration::from_secs(config.interval));
+        // Poll immediately, then on the (possibly time-of-day dependent) interval
+        let mut first = true;
 
         loop {
-            interval.tick().await;
-
-            match client.get_livedata() {
-                Ok(data) => {
-                    let timestamp = Utc::now();
-                    let mut formatted_data = std::collections::HashMap::new();
+            let runtime_interval = match config.status {
+                Some(ref status) => status.lock().await.poll_interval(),
+                None => None,
+            };
+            let interval = runtime_interval.unwrap_or_else(|| {
+                poll_interval(&config.poll_profiles, Local::now().time(), config.interval)
+            });
+            if !first || config.alignment.is_aligned() {
+                time::sleep(config.alignment.next_poll_delay(Utc::now(), interval)).await;
+            }
+            first = false;
 
-                    for (key, value) in data.iter() {
-                        formatted_data.insert(key.clone(), format_value(key, *value));
+            match device.run(Priority::Poll, Gateway::get_livedata).await {
+                Ok(mut data) => {
+                    let timestamp = config.alignment.align(Utc::now(), interval);
+                    if let Some(ref calibration) = config.calibration {
+                        calibration.apply(&mut data);
                     }
-
-                    let message = serde_json::json!({
-                        "timestamp": timestamp.to_rfc3339(),
-                        "data": formatted_data,
-                    });
+                    if config.fixed_point {
+                        data = FixedReadings::from_readings(&data).to_readings();
+                    }
+                    add_derived_fields(&mut data, &config.derived);
+                    let message =
+                        current_payload(timestamp, &data, &config.precision, config.units);
 
                     if let Ok(json) = serde_json::to_string(&message) {
-                        let _ = tx_clone.send(json);
+                        poll_feed.publish(json);
                     }
                 }
                 Err(e) => {
@@ -230,67 +612,194 @@ pub async fn run_web_server(
                     });
 
                     if let Ok(json) = serde_json::to_string(&error_msg) {
-                        let _ = tx_clone.send(json);
+                        poll_feed.publish(json);
                     }
                 }
             }
         }
     });
 
-    // Build the router with logging
-    let tx_for_ws = tx.clone();
-    let app = Router::new()
-        .route("/", get(index_handler))
-        .route(
-            "/ws",
-            get(move |ws, addr| websocket_handler(ws, tx_for_ws.clone(), addr)),
-        )
-        .route("/api/v1/current.json", get(api_current_handler))
-        .with_state(tx)
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::new().level(tracing::Level::INFO)),
-        );
+    // Bind everything up front so a bad address fails startup, not later
+    if config.listen.is_empty() {
+        anyhow::bail!("No web server listen addresses configured");
+    }
+    let mut listeners = Vec::new();
+    for addr in &config.listen {
+        listeners.push(bind_listener(addr, config.socket_mode).await?);
+    }
 
-    let addr = format!("{}:{}", config.ip, config.port);
     println!("============================================================");
-    println!("Web server starting on http://{}", addr);
+    for listener in &listeners {
+        println!("Web server starting on {}", listener.describe()?);
+    }
     println!("Press Ctrl+C to stop");
     println!("============================================================\n");
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await?;
+    let mut servers = tokio::task::JoinSet::new();
+    for listener in listeners {
+        let app = app.clone();
+        servers.spawn(async move {
+            match listener {
+                Listener::Tcp(listener) => {
+                    axum::serve(
+                        listener,
+                        app.into_make_service_with_connect_info::<SocketAddr>(),
+                    )
+                    .await?;
+                    Ok(())
+                }
+                #[cfg(unix)]
+                Listener::Unix(listener, _) => serve_unix(listener, app).await,
+            }
+        });
+    }
+    // Any listener failing takes the web server down
+    while let Some(result) = servers.join_next().await {
+        result??;
+    }
 
     Ok(())
 }
 
-async fn index_handler(ConnectInfo(addr): ConnectInfo<SocketAddr>) -> impl IntoResponse {
-    println!("[{}] GET / - 200 OK", addr);
+enum Listener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, PathBuf),
+}
+
+impl Listener {
+    fn describe(&self) -> std::io::Result<String> {
+        match self {
+            Listener::Tcp(listener) => Ok(format!("http://{}", listener.local_addr()?)),
+            #[cfg(unix)]
+            Listener::Unix(_, path) => Ok(format!("unix:{}", path.display())),
+        }
+    }
+}
+
+async fn bind_listener(addr: &ListenAddr, socket_mode: u32) -> anyhow::Result<Listener> {
+    match addr {
+        ListenAddr::Tcp(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("Failed to bind web server to {}", addr))?;
+            Ok(Listener::Tcp(listener))
+        }
+        #[cfg(unix)]
+        ListenAddr::Unix(path) => {
+            let listener = bind_unix_socket(path, socket_mode)?;
+            Ok(Listener::Unix(listener, path.clone()))
+        }
+        #[cfg(not(unix))]
+        ListenAddr::Unix(path) => {
+            let _ = socket_mode;
+            anyhow::bail!(
+                "Unix sockets are not supported on this platform: {:?}",
+                path
+            )
+        }
+    }
+}
+
+/// Bind a Unix socket with the given permissions. A leftover socket from a
+/// previous run is replaced, but not one still in use or a non-socket file.
+#[cfg(unix)]
+pub fn bind_unix_socket(path: &Path, mode: u32) -> anyhow::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("{:?} exists and is not a socket", path);
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            anyhow::bail!("{:?} is in use by another process", path);
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale web socket: {:?}", path))?;
+    }
+
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind web server to {:?}", path))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Failed to set permissions on {:?}", path))?;
+    Ok(listener)
+}
+
+/// Serve the app on a Unix socket. axum::serve only takes TCP listeners, so
+/// each connection is driven by hyper directly, with upgrades for WebSockets.
+#[cfg(unix)]
+async fn serve_unix(listener: tokio::net::UnixListener, app: Router) -> anyhow::Result<()> {
+    use hyper_util::rt::TokioIo;
+    use hyper_util::service::TowerToHyperService;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            let _ = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades()
+                .await;
+        });
+    }
+}
+
+/// Client address for request logs; Unix socket clients have none
+fn peer(addr: Option<ConnectInfo<SocketAddr>>) -> String {
+    addr.map_or_else(|| "unix".to_string(), |ConnectInfo(addr)| addr.to_string())
+}
+
+async fn index_handler(addr: Option<ConnectInfo<SocketAddr>>) -> impl IntoResponse {
+    println!("[{}] GET / - 200 OK", peer(addr));
     Html(HTML_PAGE)
 }
 
+/// The public view set by `require_scope`, if this request gets it
+type PublicView = Option<Extension<Arc<PublicConfig>>>;
+
 async fn websocket_handler(
     ws: WebSocketUpgrade,
-    tx: Arc<broadcast::Sender<String>>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    feed: Arc<LiveFeed>,
+    public: PublicView,
+    addr: Option<ConnectInfo<SocketAddr>>,
 ) -> impl IntoResponse {
+    let addr = peer(addr);
     println!("[{}] WebSocket connection established", addr);
-    ws.on_upgrade(move |socket| handle_socket(socket, tx, addr))
+    let public = public.map(|Extension(public)| public);
+    ws.on_upgrade(move |socket| handle_socket(socket, feed, public, addr))
 }
 
-async fn handle_socket(socket: WebSocket, tx: Arc<broadcast::Sender<String>>, addr: SocketAddr) {
+async fn handle_socket(
+    socket: WebSocket,
+    feed: Arc<LiveFeed>,
+    public: Option<Arc<PublicConfig>>,
+    addr: String,
+) {
     let (mut sender, mut receiver) = socket.split();
-    let mut rx = tx.subscribe();
+    let mut subscription = feed.subscribe();
 
-    // Spawn a task to send messages from the broadcast channel to the WebSocket
+    // Spawn a task to send this client's queued messages to the WebSocket.
+    // Messages are full snapshots, so a client that falls behind just skips
+    // the oldest.
+    let send_addr = addr.clone();
     let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
-            if sender.send(Message::Text(msg)).await.is_err() {
-                break;
+        loop {
+            match subscription.recv().await {
+                Received::Message(msg) => {
+                    let msg = match &public {
+                        Some(public) => public_payload(public, &msg),
+                        None => msg.to_string(),
+                    };
+                    if sender.send(Message::Text(msg)).await.is_err() {
+                        break;
+                    }
+                }
+                Received::Lagged(skipped) => {
+                    eprintln!(
+                        "[{}] [WARN] WebSocket client too slow, skipped {} updates",
+                        send_addr, skipped
+                    );
+                }
             }
         }
     });
@@ -313,33 +822,654 @@ async fn handle_socket(socket: WebSocket, tx: Arc<broadcast::Sender<String>>, ad
     println!("[{}] WebSocket connection closed", addr);
 }
 
+pub use crate::status::API_SCHEMA_VERSION;
+
+/// JSON Schema for the /api/v1/ payloads, served at /api/v1/schema.json
+pub const API_SCHEMA: &str = include_str!("api_schema.json");
+
+/// Build the current.json / WebSocket message for one poll. `readings` holds
+/// the numbers, always metric; `data` keeps the formatted strings, in the
+/// configured units, for older clients.
+pub fn current_payload(
+    timestamp: DateTime<Utc>,
+    data: &HashMap<String, f64>,
+    precision: &PrecisionConfig,
+    units: Units,
+) -> serde_json::Value {
+    let readings: BTreeMap<&String, f64> = data
+        .iter()
+        .filter(|(_, value)| value.is_finite())
+        .map(|(key, value)| (key, precision.round(key, *value)))
+        .collect();
+    let formatted: BTreeMap<&String, String> = data
+        .iter()
+        .map(|(key, value)| (key, FieldValue(key, *value, units).to_string()))
+        .collect();
+
+    serde_json::json!({
+        "schema_version": API_SCHEMA_VERSION,
+        "timestamp": timestamp.to_rfc3339(),
+        "readings": readings,
+        "data": formatted,
+    })
+}
+
+/// A current.json / WebSocket message as the public view shows it. Messages
+/// without readings, such as poll errors, are passed on as they are.
+pub fn public_payload(public: &PublicConfig, message: &str) -> String {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(message) else {
+        return message.to_string();
+    };
+    let timestamp = json["timestamp"]
+        .as_str()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+    let readings = json
+        .get("readings")
+        .and_then(|r| serde_json::from_value::<HashMap<String, f64>>(r.clone()).ok());
+    let (Some(timestamp), Some(readings)) = (timestamp, readings) else {
+        return message.to_string();
+    };
+    // Readings are already rounded by `public`
+    current_payload(
+        timestamp.with_timezone(&Utc),
+        &public.readings(&readings),
+        &PrecisionConfig::default(),
+        public.units,
+    )
+    .to_string()
+}
+
+pub async fn api_schema_handler(addr: Option<ConnectInfo<SocketAddr>>) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] GET /api/v1/schema.json", addr);
+    }
+
+    (
+        [(header::CONTENT_TYPE, "application/schema+json")],
+        API_SCHEMA,
+    )
+}
+
 pub async fn api_current_handler(
-    State(tx): State<Arc<broadcast::Sender<String>>>,
+    State(feed): State<Arc<LiveFeed>>,
+    public: PublicView,
     addr: Option<ConnectInfo<SocketAddr>>,
 ) -> impl IntoResponse {
     if let Some(ConnectInfo(addr)) = addr {
         println!("[{}] GET /api/v1/current.json", addr);
     }
 
-    // Subscribe to the broadcast channel to get the latest data
-    let mut rx = tx.subscribe();
+    // The latest poll, waiting for the first one right after startup
+    let latest = feed.wait_latest(Duration::from_secs(16)).await;
+    match latest.map(|data| match &public {
+        Some(Extension(public)) => public_payload(public, &data),
+        None => data.to_string(),
+    }) {
+        Some(data) => match serde_json::from_str::<serde_json::Value>(&data) {
+            Ok(json) => Json(json),
+            Err(_) => Json(serde_json::json!({
+                "error": "Failed to parse weather data"
+            })),
+        },
+        None => Json(serde_json::json!({
+            "error": "Timeout waiting for data"
+        })),
+    }
+}
 
-    // Try to receive the latest message with a timeout
-    match tokio::time::timeout(Duration::from_secs(16), rx.recv()).await {
-        Ok(Ok(data)) => {
-            // Parse the JSON string and return it
-            match serde_json::from_str::<serde_json::Value>(&data) {
-                Ok(json) => Json(json),
-                Err(_) => Json(serde_json::json!({
-                    "error": "Failed to parse weather data"
-                })),
-            }
-        }
-        Ok(Err(_)) => Json(serde_json::json!({
-            "error": "No data available"
+/// Live feed counters: connected WebSocket clients and dropped updates
+pub async fn api_feed_handler(
+    State(feed): State<Arc<LiveFeed>>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] GET /api/v1/feed.json", addr);
+    }
+
+    let metrics = feed.metrics();
+    Json(serde_json::json!({
+        "schema_version": API_SCHEMA_VERSION,
+        "subscribers": metrics.subscribers,
+        "published": metrics.published,
+        "dropped": metrics.dropped,
+    }))
+}
+
+/// Timestamp and readings of the latest poll, as the public view shows them
+/// if the request gets it. Failed polls publish an error without readings.
+fn latest_readings(
+    feed: &LiveFeed,
+    public: &PublicView,
+) -> Option<(DateTime<FixedOffset>, HashMap<String, f64>)> {
+    let json: serde_json::Value = serde_json::from_str(&feed.latest()?).ok()?;
+    let timestamp = DateTime::parse_from_rfc3339(json["timestamp"].as_str()?).ok()?;
+    let readings = serde_json::from_value(json.get("readings")?.clone()).ok()?;
+    match public {
+        Some(Extension(public)) => Some((timestamp, public.readings(&readings))),
+        None => Some((timestamp, readings)),
+    }
+}
+
+/// The gateway's `/get_livedata_info` JSON, from the latest poll
+pub async fn livedata_info_handler(
+    State(feed): State<Arc<LiveFeed>>,
+    public: PublicView,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    println!("[{}] GET /get_livedata_info", peer(addr));
+
+    match latest_readings(&feed, &public) {
+        Some((_, readings)) => (StatusCode::OK, Json(livedata_info(&readings))),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "error": "No data available"
+            })),
+        ),
+    }
+}
+
+/// Files for weather website templates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateFile {
+    /// Cumulus realtime.txt
+    Realtime,
+    /// Weather Display clientraw.txt
+    Clientraw,
+}
+
+/// realtime.txt or clientraw.txt from the latest poll, in station local time
+pub async fn template_file_handler(
+    feed: Arc<LiveFeed>,
+    file: TemplateFile,
+    station_name: Arc<str>,
+    station_time: StationTime,
+    public: PublicView,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    let name = match file {
+        TemplateFile::Realtime => "realtime.txt",
+        TemplateFile::Clientraw => "clientraw.txt",
+    };
+    println!("[{}] GET {}", peer(addr), name);
+
+    let Some((timestamp, readings)) = latest_readings(&feed, &public) else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::CONTENT_TYPE, "text/plain")],
+            "No data available\n".to_string(),
+        );
+    };
+
+    let time = station_time.naive_local(timestamp.with_timezone(&Utc));
+    let body = match file {
+        TemplateFile::Realtime => realtime_txt(time, &readings),
+        TemplateFile::Clientraw => clientraw_txt(time, &readings, &station_name),
+    };
+    (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain")], body)
+}
+
+pub async fn api_summary_handler(
+    degree_days: Option<SharedDegreeDays>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] GET /api/v1/summary.json", addr);
+    }
+
+    let Some(degree_days) = degree_days else {
+        return Json(serde_json::json!({
+            "error": "Degree day tracking is not configured"
+        }));
+    };
+
+    let summary = degree_days.lock().await.summary();
+    match summary {
+        Some(summary) => Json(serde_json::json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "degree_days": summary,
         })),
-        Err(_) => Json(serde_json::json!({
-            "error": "Timeout waiting for data"
+        None => Json(serde_json::json!({
+            "error": "No data available"
         })),
     }
 }
+
+/// Runs a control command, the same JSON as on the control socket
+pub async fn api_control_handler(
+    controls: Option<SharedControls>,
+    status: Option<SharedStatus>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+    body: String,
+) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] POST /api/v1/control", addr);
+    }
+
+    let (Some(controls), Some(status)) = (controls, status) else {
+        return Json(serde_json::json!({
+            "ok": false,
+            "error": "Control is not available"
+        }));
+    };
+    Json(handle_request(&body, &controls, &status).await)
+}
+
+/// Changes runtime settings; the body lists only the settings to change
+pub async fn api_settings_handler(
+    controls: Option<SharedControls>,
+    status: Option<SharedStatus>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+    body: String,
+) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] PATCH /api/v1/settings", addr);
+    }
+
+    let (Some(controls), Some(status)) = (controls, status) else {
+        return Json(serde_json::json!({
+            "ok": false,
+            "error": "Settings are not available"
+        }));
+    };
+    Json(update_settings(&body, &controls, &status).await)
+}
+
+/// Time range for GET /api/v1/annotations.json, in epoch milliseconds as
+/// Grafana's `${__from}` and `${__to}` give them
+#[cfg(feature = "db")]
+#[derive(Debug, Deserialize)]
+pub struct AnnotationsQuery {
+    /// Default: the earliest annotation
+    from: Option<i64>,
+    /// Default: now
+    to: Option<i64>,
+}
+
+#[cfg(feature = "db")]
+pub async fn api_annotations_handler(
+    database: Option<Arc<DatabaseWriter>>,
+    Query(query): Query<AnnotationsQuery>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] GET /api/v1/annotations.json", addr);
+    }
+
+    let Some(database) = database else {
+        return Json(serde_json::json!({
+            "error": "Annotations need a [database] section"
+        }));
+    };
+
+    let from = query
+        .from
+        .and_then(DateTime::from_timestamp_millis)
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let to = query
+        .to
+        .and_then(DateTime::from_timestamp_millis)
+        .unwrap_or_else(Utc::now);
+    match database.annotations(&from, &to).await {
+        Ok(annotations) => Json(grafana_json(&annotations)),
+        Err(e) => Json(serde_json::json!({ "error": format!("{:#}", e) })),
+    }
+}
+
+#[cfg(feature = "db")]
+pub async fn api_add_annotation_handler(
+    database: Option<Arc<DatabaseWriter>>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+    body: String,
+) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] POST /api/v1/annotations", addr);
+    }
+
+    let Some(database) = database else {
+        return Json(serde_json::json!({
+            "ok": false,
+            "error": "Annotations need a [database] section"
+        }));
+    };
+
+    let annotation = match Annotation::parse(&body, Utc::now()) {
+        Ok(annotation) => annotation,
+        Err(e) => return Json(serde_json::json!({ "ok": false, "error": e.to_string() })),
+    };
+    match database.insert_annotation(&annotation).await {
+        Ok(()) => Json(serde_json::json!({ "ok": true, "annotation": annotation })),
+        Err(e) => Json(serde_json::json!({ "ok": false, "error": format!("{:#}", e) })),
+    }
+}
+
+pub async fn api_status_handler(
+    status: Option<SharedStatus>,
+    public: PublicView,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] GET /api/v1/status.json", addr);
+    }
+
+    let Some(status) = status else {
+        return Json(serde_json::json!({
+            "error": "Status is not available"
+        }));
+    };
+
+    let mut report = status.lock().await.report();
+    if let Some(Extension(public)) = public {
+        report.readings = report
+            .readings
+            .into_iter()
+            .filter(|(field, _)| public.shows(field))
+            .map(|(field, value)| {
+                let value = public.round(&field, value);
+                (field, value)
+            })
+            .collect();
+        report.station = public.station(&report.station);
+    }
+    Json(serde_json::to_value(report).unwrap_or_default())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    field: String,
+    /// Period up to now, e.g. 24h or 7d (default: 24h)
+    last: Option<String>,
+}
+
+pub async fn api_history_handler(
+    storage: Option<SharedStorage>,
+    public: PublicView,
+    Query(query): Query<HistoryQuery>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] GET /api/v1/history.json", addr);
+    }
+
+    if let Some(Extension(public)) = &public {
+        if !public.shows(&query.field) {
+            return Json(serde_json::json!({
+                "error": format!("{} is not public", query.field)
+            }));
+        }
+    }
+
+    let Some(storage) = storage else {
+        return Json(serde_json::json!({
+            "error": "History needs a [database] or [storage] section"
+        }));
+    };
+
+    let period = match parse_period(query.last.as_deref().unwrap_or("24h")) {
+        Ok(period) => period,
+        Err(e) => return Json(serde_json::json!({ "error": e.to_string() })),
+    };
+    let to = Utc::now();
+    let from = to - period;
+    let result = tokio::try_join!(
+        storage.query(&query.field, from, to),
+        storage.aggregate(&query.field, from, to)
+    );
+    let (mut points, mut aggregate) = match result {
+        Ok(result) => result,
+        Err(e) => return Json(serde_json::json!({ "error": format!("{:#}", e) })),
+    };
+    if let Some(Extension(public)) = &public {
+        let round = |value: f64| public.round(&query.field, value);
+        points
+            .iter_mut()
+            .for_each(|(_, value)| *value = round(*value));
+        if let Some(aggregate) = &mut aggregate {
+            aggregate.min = round(aggregate.min);
+            aggregate.max = round(aggregate.max);
+            aggregate.mean = aggregate.mean.map(round);
+        }
+    }
+    Json(serde_json::json!({
+        "field": query.field,
+        "from": from.to_rfc3339(),
+        "to": to.to_rfc3339(),
+        "aggregate": aggregate,
+        "points": points
+            .iter()
+            .map(|(at, value)| (at.timestamp_millis(), value))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareQuery {
+    /// Comma-separated series: field, or station:field
+    series: String,
+    /// Range in epoch milliseconds; both or neither
+    from: Option<i64>,
+    to: Option<i64>,
+    /// Period up to now when from and to are absent (default: 24h)
+    last: Option<String>,
+    /// Bucket size in seconds (default: whole minutes, at most 500 buckets)
+    step: Option<i64>,
+    /// Also return the first series minus each of the others
+    #[serde(default)]
+    difference: bool,
+}
+
+pub async fn api_compare_handler(
+    storage: Option<SharedStorage>,
+    stations: Arc<BTreeMap<String, SharedStorage>>,
+    public: PublicView,
+    Query(query): Query<CompareQuery>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] GET /api/v1/compare.json", addr);
+    }
+
+    let public = public.map(|Extension(public)| public);
+    match compare_series(storage, &stations, public.as_deref(), &query).await {
+        Ok(comparison) => Json(serde_json::to_value(comparison).unwrap_or_default()),
+        Err(e) => Json(serde_json::json!({ "error": format!("{:#}", e) })),
+    }
+}
+
+/// Fetch and align the series of a compare query
+async fn compare_series(
+    storage: Option<SharedStorage>,
+    stations: &BTreeMap<String, SharedStorage>,
+    public: Option<&PublicConfig>,
+    query: &CompareQuery,
+) -> anyhow::Result<Comparison> {
+    let specs = SeriesSpec::parse_list(&query.series)?;
+    if let Some(public) = public {
+        if let Some(spec) = specs.iter().find(|spec| !public.shows(&spec.field)) {
+            anyhow::bail!("{} is not public", spec.field);
+        }
+    }
+    let (from, to) = match (query.from, query.to) {
+        (Some(from), Some(to)) => (
+            DateTime::from_timestamp_millis(from).context("Invalid from")?,
+            DateTime::from_timestamp_millis(to).context("Invalid to")?,
+        ),
+        (None, None) => {
+            let to = Utc::now();
+            (
+                to - parse_period(query.last.as_deref().unwrap_or("24h"))?,
+                to,
+            )
+        }
+        _ => anyhow::bail!("Give both from and to, or neither"),
+    };
+    let step = match query.step {
+        Some(secs) => chrono::TimeDelta::try_seconds(secs).context("Invalid step")?,
+        None => default_step(from, to),
+    };
+
+    let sources = specs
+        .iter()
+        .map(|spec| match &spec.station {
+            None => storage
+                .clone()
+                .context("Comparing needs a [database] or [storage] section"),
+            Some(name) => stations.get(name).cloned().with_context(|| {
+                format!(
+                    "Unknown station '{}': name its table in [database] stations{}",
+                    name,
+                    if stations.is_empty() {
+                        String::new()
+                    } else {
+                        format!(
+                            " (configured: {})",
+                            stations.keys().cloned().collect::<Vec<_>>().join(", ")
+                        )
+                    }
+                )
+            }),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut points = futures_util::future::try_join_all(
+        specs
+            .iter()
+            .zip(&sources)
+            .map(|(spec, source)| source.query(&spec.field, from, to)),
+    )
+    .await?;
+    if let Some(public) = public {
+        for (spec, series) in specs.iter().zip(&mut points) {
+            for (_, value) in series.iter_mut() {
+                *value = public.round(&spec.field, *value);
+            }
+        }
+    }
+    compare(&specs, &points, from, to, step, query.difference)
+}
+
+/// This station and the other located ones, each with its latest readings,
+/// as a GeoJSON FeatureCollection
+pub async fn api_stations_handler(
+    feed: Arc<LiveFeed>,
+    station: Arc<StationConfig>,
+    others: Arc<Vec<(String, StationConfig, SharedStorage)>>,
+    public: PublicView,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    println!("[{}] GET /api/v1/stations.geojson", peer(addr));
+
+    let public = public.map(|Extension(public)| public);
+    let view = |station: &StationConfig, latest: anyhow::Result<Option<Record>>| match &public {
+        Some(public) => (
+            public.station(station),
+            latest.map(|latest| latest.map(|(at, data)| (at, public.readings(&data)))),
+        ),
+        None => (station.clone(), latest),
+    };
+
+    let own = latest_readings(&feed, &None).map(|(at, data)| (at.with_timezone(&Utc), data));
+    let (own_station, own) = view(&station, Ok(own));
+    let mut features: Vec<_> = station_feature(None, &own_station, &own)
+        .into_iter()
+        .collect();
+
+    let latest =
+        futures_util::future::join_all(others.iter().map(|(_, _, storage)| storage.latest())).await;
+    for ((name, station, _), latest) in others.iter().zip(latest) {
+        let (station, latest) = view(station, latest);
+        features.extend(station_feature(Some(name), &station, &latest));
+    }
+
+    (
+        [(header::CONTENT_TYPE, "application/geo+json")],
+        feature_collection(features).to_string(),
+    )
+}
+
+/// Readings sent by a federation peer, signed with the key it shares with
+/// this instance
+#[cfg(feature = "http")]
+pub async fn api_federation_handler(
+    peers: SharedPeers,
+    addr: Option<ConnectInfo<SocketAddr>>,
+    headers: axum::http::HeaderMap,
+    body: axum::body::Bytes,
+) -> impl IntoResponse {
+    let addr = peer(addr);
+    println!("[{}] POST {}", addr, FEDERATION_PATH);
+
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let result = peers.lock().await.receive(&body, signature, Utc::now());
+    match result {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "ok": true }))),
+        Err((status, message)) => {
+            eprintln!("[{}] [WARN] Refused peer readings: {}", addr, message);
+            (status, Json(serde_json::json!({ "error": message })))
+        }
+    }
+}
+
+/// The latest readings each federation peer sent
+#[cfg(feature = "http")]
+pub async fn api_peers_handler(
+    peers: SharedPeers,
+    public: PublicView,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    println!("[{}] GET /api/v1/peers.json", peer(addr));
+
+    let peers = peers.lock().await;
+    let peers: Vec<_> = peers
+        .peers()
+        .map(|(name, latest)| {
+            let readings: HashMap<String, f64> = latest
+                .map(|latest| latest.readings.clone().into_iter().collect())
+                .unwrap_or_default();
+            let readings = match &public {
+                Some(Extension(public)) => public.readings(&readings),
+                None => readings,
+            };
+            serde_json::json!({
+                "station": name,
+                "timestamp": latest.map(|latest| latest.timestamp.to_rfc3339()),
+                "received_at": latest.map(|latest| latest.received_at.to_rfc3339()),
+                "readings": readings.into_iter().collect::<BTreeMap<_, _>>(),
+            })
+        })
+        .collect();
+    Json(serde_json::json!({
+        "schema_version": API_SCHEMA_VERSION,
+        "peers": peers,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Most recent events to return (default: all kept)
+    limit: Option<usize>,
+}
+
+pub async fn api_events_handler(
+    status: Option<SharedStatus>,
+    Query(query): Query<EventsQuery>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+) -> impl IntoResponse {
+    if let Some(ConnectInfo(addr)) = addr {
+        println!("[{}] GET /api/v1/events.json", addr);
+    }
+
+    let Some(status) = status else {
+        return Json(serde_json::json!({
+            "error": "Events are not available"
+        }));
+    };
+
+    let limit = query.limit.unwrap_or(EVENT_LOG_CAPACITY);
+    let events = status.lock().await.recent_events(limit);
+    Json(serde_json::json!({
+        "schema_version": API_SCHEMA_VERSION,
+        "events": events,
+    }))
+}
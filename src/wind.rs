@@ -0,0 +1,79 @@
+/// Running vector mean of wind samples. Directions wrap at 360°, so an
+/// arithmetic mean of 350° and 10° gives 180°, due south for a northerly
+/// wind; averaging the east and north components of each sample doesn't.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WindVector {
+    /// Sum of speed * sin(direction)
+    east: f64,
+    /// Sum of speed * cos(direction)
+    north: f64,
+    /// Sum of the speeds
+    weight: f64,
+    count: u32,
+}
+
+impl WindVector {
+    /// Add a sample: the direction in degrees and its speed as the weight.
+    /// Use a speed of 1.0 to average directions alone.
+    pub fn add(&mut self, direction: f64, speed: f64) {
+        if !direction.is_finite() || !speed.is_finite() || speed < 0.0 {
+            return;
+        }
+        let radians = direction.to_radians();
+        self.east += speed * radians.sin();
+        self.north += speed * radians.cos();
+        self.weight += speed;
+        self.count += 1;
+    }
+
+    /// Mean direction in degrees, 0 to under 360. None without samples, in
+    /// calm air, or when the samples cancel out (e.g. 90° and 270°).
+    pub fn mean_direction(&self) -> Option<f64> {
+        let length = self.east.hypot(self.north);
+        if self.count == 0 || length <= self.weight * 1e-9 {
+            return None;
+        }
+        let degrees = self.east.atan2(self.north).to_degrees().rem_euclid(360.0);
+        // rem_euclid can round a tiny negative angle up to exactly 360
+        Some(if degrees >= 360.0 { 0.0 } else { degrees })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mean(samples: &[(f64, f64)]) -> WindVector {
+        let mut wind = WindVector::default();
+        for &(direction, speed) in samples {
+            wind.add(direction, speed);
+        }
+        wind
+    }
+
+    #[test]
+    fn test_mean_direction_wraps_north() {
+        let direction = mean(&[(350.0, 1.0), (10.0, 1.0)]).mean_direction().unwrap();
+        // Due north, give or take rounding either side of 0°
+        assert!(direction.min(360.0 - direction) < 1e-9, "{}", direction);
+        let direction = mean(&[(340.0, 1.0), (350.0, 1.0)])
+            .mean_direction()
+            .unwrap();
+        assert!((direction - 345.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mean_direction_weighted_by_speed() {
+        // A strong westerly outweighs a light easterly
+        let wind = mean(&[(270.0, 6.0), (90.0, 2.0)]);
+        assert!((wind.mean_direction().unwrap() - 270.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mean_direction_undefined() {
+        assert_eq!(WindVector::default().mean_direction(), None);
+        assert_eq!(mean(&[(90.0, 1.0), (270.0, 1.0)]).mean_direction(), None);
+        assert_eq!(mean(&[(180.0, 0.0)]).mean_direction(), None);
+        assert_eq!(mean(&[(f64::NAN, 1.0)]).mean_direction(), None);
+    }
+}
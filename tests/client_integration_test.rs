@@ -2,9 +2,23 @@
 mod mock_server;
 
 use mock_server::{
-    mock_firmware_response, mock_livedata_response, mock_mac_response, MockGW1000Server,
+    mock_broadcast_response, mock_calibration_response, mock_corrupt_response,
+    mock_firmware_response, mock_livedata_fields, mock_livedata_response,
+    mock_livedata_response_with, mock_mac_response, mock_sensor_id_response,
+    mock_system_params_response, mock_write_ack, with_bad_checksum, MockGW1000Server, Step,
 };
-use wxlistener::client::GW1000Client;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wxlistener::client::{
+    discover_at, parse_broadcast_response, AsyncGW1000Client, GW1000Client, RetryPolicy,
+};
+use wxlistener::control::{handle_request, Controls};
+use wxlistener::decoder::{ValueFormat, LIVEDATA_FIELDS};
+use wxlistener::device_queue::DeviceQueue;
+use wxlistener::local_api::{Gateway, GatewayApi, LocalHttpClient};
+use wxlistener::protocol::ResponseError;
+use wxlistener::status::DaemonStatus;
 
 #[test]
 fn test_client_get_firmware_version() {
@@ -89,6 +103,351 @@ fn test_client_get_livedata() {
     assert_eq!(data.get("outhumid"), Some(&65.0));
 }
 
+#[test]
+fn test_client_get_livedata_air_quality() {
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+
+    let mut data = vec![
+        0x70, // WH45 block
+        0x00, 0xD6, // co2_temp = 21.4°C
+        0x30, // co2_humid = 48%
+        0x00, 0x49, // co2_pm10 = 7.3
+        0x00, 0x52, // co2_pm10_24h = 8.2
+        0x00, 0x3A, // co2_pm25 = 5.8
+        0x00, 0x40, // co2_pm25_24h = 6.4
+        0x02, 0x64, // co2 = 612 ppm
+        0x02, 0x44, // co2_24h = 580 ppm
+        0x06, // co2_batt
+    ];
+    // 0x71: WH46 pm1 = 3.1, pm4 = 6.6
+    data.extend_from_slice(&[0x71, 0x00, 0x1F, 0x00, 0x42]);
+    server.add_response(mock_livedata_response_with(&data));
+
+    let _handle = server.start();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let client = GW1000Client::new("127.0.0.1".to_string(), port);
+    let data = client.get_livedata().unwrap();
+
+    assert_eq!(data.get("co2_temp"), Some(&21.4));
+    assert_eq!(data.get("co2_humid"), Some(&48.0));
+    assert_eq!(data.get("co2_pm10"), Some(&7.3));
+    assert_eq!(data.get("co2_pm25_24h"), Some(&6.4));
+    assert_eq!(data.get("co2"), Some(&612.0));
+    assert_eq!(data.get("co2_24h"), Some(&580.0));
+    assert_eq!(data.get("co2_pm1"), Some(&3.1));
+    assert_eq!(data.get("co2_pm4"), Some(&6.6));
+}
+
+#[test]
+fn test_client_get_livedata_ws90() {
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+
+    let data = [
+        0x6B, 0x00, 0x20, // ws90_cap_volt = 3.2 V
+        0x80, 0x00, 0x0C, // piezo_rain_rate = 1.2
+        0x81, 0x00, 0x22, // piezo_rain_event = 3.4
+        0x83, 0x00, 0x00, 0x00, 0x22, // piezo_rain_day = 3.4
+        0x86, 0x00, 0x00, 0x21, 0x37, // piezo_rain_year = 850.3
+    ];
+    server.add_response(mock_livedata_response_with(&data));
+
+    let _handle = server.start();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let client = GW1000Client::new("127.0.0.1".to_string(), port);
+    let data = client.get_livedata().unwrap();
+
+    assert_eq!(data.get("ws90_cap_volt"), Some(&3.2));
+    assert_eq!(data.get("piezo_rain_rate"), Some(&1.2));
+    assert_eq!(data.get("piezo_rain_event"), Some(&3.4));
+    assert_eq!(data.get("piezo_rain_day"), Some(&3.4));
+    assert_eq!(data.get("piezo_rain_year"), Some(&850.3));
+}
+
+/// A distinct value for the n-th field that survives encoding in `format`
+fn sample_value(format: ValueFormat, n: usize) -> f64 {
+    match format {
+        ValueFormat::Temp => (n as f64 - 150.0) / 10.0,
+        ValueFormat::Byte => n as f64,
+        ValueFormat::Short => 1000.0 + n as f64,
+        ValueFormat::Int => 100_000.0 + n as f64,
+        ValueFormat::ShortTenths
+        | ValueFormat::Wind
+        | ValueFormat::Rain
+        | ValueFormat::Pressure
+        | ValueFormat::IntTenths => (1000 + n) as f64 / 10.0,
+    }
+}
+
+#[test]
+fn test_client_livedata_field_table_roundtrip() {
+    // Every field in the table, each with a distinct value
+    let mut expected = HashMap::new();
+    for (n, (name, format)) in LIVEDATA_FIELDS
+        .iter()
+        .flat_map(|field| field.values.iter())
+        .enumerate()
+    {
+        expected.insert(name.to_string(), sample_value(*format, n));
+    }
+
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+    server.add_response(mock_livedata_fields(&expected));
+    let _handle = server.start();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let client = GW1000Client::new("127.0.0.1".to_string(), port);
+    let data = client.get_livedata().unwrap();
+
+    assert_eq!(data, expected);
+}
+
+#[test]
+fn test_client_get_sensor_signals() {
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+
+    // WH65 paired, WH68 still registering, WH31 channel 1 paired
+    server.add_response(mock_sensor_id_response(&[
+        (0, 0xC4, 0, 4),
+        (1, 0xFFFF_FFFF, 0, 0),
+        (6, 0x1A2B, 0, 2),
+    ]));
+
+    let _handle = server.start();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let client = GW1000Client::new("127.0.0.1".to_string(), port);
+    let signals = client.get_sensor_signals().unwrap();
+
+    assert_eq!(signals.len(), 2);
+    assert_eq!(signals[0].sensor, "wh65");
+    assert_eq!(signals[0].signal, 4);
+    assert_eq!(signals[1].field_name(), "sensor_rssi_wh31_ch1");
+    assert_eq!(signals[1].signal, 2);
+}
+
+/// Serve a single response and return a client pointed at it
+fn client_for(response: Vec<u8>) -> GW1000Client {
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+    server.add_response(response);
+    let _handle = server.start();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    GW1000Client::new("127.0.0.1".to_string(), port)
+}
+
+#[test]
+fn test_client_firmware_size_too_small() {
+    // Size 0x01 would underflow the old size - 3 arithmetic
+    let client = client_for(mock_corrupt_response(0x50, &[0x01], b""));
+    assert!(client.get_firmware_version().is_err());
+}
+
+#[test]
+fn test_client_firmware_size_too_large() {
+    // Claims 200 bytes but only a few follow
+    let client = client_for(mock_corrupt_response(0x50, &[0xC8], b"V1.0"));
+    assert!(client.get_firmware_version().is_err());
+}
+
+#[test]
+fn test_client_mac_size_too_large() {
+    let client = client_for(mock_corrupt_response(0x26, &[0xFF], &[0xAA, 0xBB]));
+    assert!(client.get_mac_address().is_err());
+}
+
+#[test]
+fn test_client_livedata_size_too_small() {
+    let client = client_for(mock_corrupt_response(0x27, &[0x00, 0x02], &[0x07, 0x41]));
+    assert!(client.get_livedata().is_err());
+}
+
+#[test]
+fn test_client_livedata_size_too_large() {
+    let client = client_for(mock_corrupt_response(0x27, &[0x10, 0x00], &[0x07, 0x41]));
+    assert!(client.get_livedata().is_err());
+}
+
+#[test]
+fn test_client_sensor_signals_size_too_large() {
+    let client = client_for(mock_corrupt_response(0x3C, &[0x00, 0x40], &[0x00]));
+    assert!(client.get_sensor_signals().is_err());
+}
+
+#[test]
+fn test_client_livedata_larger_than_1kb() {
+    // 600 outhumid records, well past the old fixed read buffer
+    let data = [0x07, 0x41].repeat(600);
+    let client = client_for(mock_livedata_response_with(&data));
+
+    let data = client.get_livedata().unwrap();
+    assert_eq!(data.get("outhumid"), Some(&65.0));
+}
+
+#[test]
+fn test_client_response_size_limit() {
+    let data = [0x07, 0x41].repeat(600);
+    let client = client_for(mock_livedata_response_with(&data)).with_max_response_size(1024);
+
+    let err = client.get_livedata().unwrap_err();
+    assert!(err.to_string().contains("1024 byte limit"));
+}
+
+#[test]
+fn test_client_protocol_trace() {
+    // Tracing only logs; results are unchanged
+    let client = client_for(mock_livedata_response()).with_protocol_trace(true);
+
+    let data = client.get_livedata().unwrap();
+    assert_eq!(data.get("outtemp"), Some(&25.5));
+}
+
+#[test]
+fn test_client_set_time() {
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+
+    // Responses pop in reverse order: read, then write
+    server.add_response(mock_write_ack(0x31, 0x00));
+    server.add_response(mock_system_params_response(1_700_000_100));
+    let _handle = server.start();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let client = GW1000Client::new("127.0.0.1".to_string(), port);
+    let drift = client.set_time(1_700_000_000).unwrap();
+    assert_eq!(drift, 100);
+}
+
+#[test]
+fn test_client_set_time_rejected() {
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+
+    server.add_response(mock_write_ack(0x31, 0x01));
+    server.add_response(mock_system_params_response(1_700_000_000));
+    let _handle = server.start();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let client = GW1000Client::new("127.0.0.1".to_string(), port);
+    assert!(client.set_time(1_700_000_000).is_err());
+}
+
+#[test]
+fn test_client_calibration_offsets_negative() {
+    let client = client_for(mock_calibration_response(&[
+        0xFF, 0xF1, // intemp -1.5
+        0xFB, // inhumid -5
+        0xFF, 0xFF, 0xFF, 0xE0, // abs -3.2
+        0x00, 0x00, 0x00, 0x64, // rel +10.0
+        0xFF, 0xFF, // outtemp -0.1
+        0x02, // outhumid +2
+        0xFF, 0x4C, // wind direction -180
+    ]));
+
+    let offsets = client.get_calibration_offsets().unwrap();
+    assert_eq!(offsets.intemp, -1.5);
+    assert_eq!(offsets.inhumid, -5.0);
+    assert_eq!(offsets.absbarometer, -3.2);
+    assert_eq!(offsets.relbarometer, 10.0);
+    assert_eq!(offsets.outtemp, -0.1);
+    assert_eq!(offsets.outhumid, 2.0);
+    assert_eq!(offsets.wind_dir, -180.0);
+}
+
+#[tokio::test]
+async fn test_control_calibration_through_queue() {
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+    server.add_response(mock_calibration_response(&[
+        0x00, 0x0A, // intemp +1.0
+        0x00, // inhumid 0
+        0x00, 0x00, 0x00, 0x00, // abs 0
+        0x00, 0x00, 0x00, 0x00, // rel 0
+        0x00, 0x00, // outtemp 0
+        0x00, // outhumid 0
+        0x00, 0x00, // wind direction 0
+    ]));
+    let _handle = server.start();
+
+    let device = DeviceQueue::start(Gateway::new(
+        GatewayApi::Tcp,
+        GW1000Client::new("127.0.0.1".to_string(), port),
+        LocalHttpClient::new("127.0.0.1".to_string(), 1),
+    ));
+    let controls = Controls::with_device_queue(device);
+    let status = Arc::new(tokio::sync::Mutex::new(DaemonStatus::new(
+        chrono::Utc::now(),
+    )));
+    let response = handle_request(r#"{"command":"calibration"}"#, &controls, &status).await;
+    assert_eq!(response["ok"], true, "{}", response);
+    assert_eq!(response["offsets"]["intemp"], 1.0);
+}
+
+#[test]
+fn test_parse_broadcast_response() {
+    let gateway = parse_broadcast_response(&mock_broadcast_response(
+        &[0xEC, 0x62, 0x60, 0xE0, 0x6E, 0x6F],
+        [192, 168, 1, 50],
+        45000,
+        "GW2000A-WIFI6E6F V3.1.4",
+    ))
+    .unwrap();
+    assert_eq!(gateway.ip.to_string(), "192.168.1.50");
+    assert_eq!(gateway.port, 45000);
+    assert_eq!(gateway.mac, "EC:62:60:E0:6E:6F");
+    assert_eq!(gateway.model, "GW2000A");
+    assert_eq!(gateway.firmware.as_deref(), Some("V3.1.4"));
+
+    // A name length running past the frame
+    let mut truncated = mock_broadcast_response(&[0; 6], [10, 0, 0, 2], 45000, "GW1000A");
+    truncated[17] = 40;
+    let checksum: u8 = truncated[2..truncated.len() - 1]
+        .iter()
+        .map(|&b| b as u32)
+        .sum::<u32>() as u8;
+    *truncated.last_mut().unwrap() = checksum;
+    assert!(parse_broadcast_response(&truncated).is_err());
+}
+
+#[test]
+fn test_discover() {
+    // Answers like two gateways would, one of them twice, plus some noise
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let target = socket.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        let (len, from) = socket.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], [0xFF, 0xFF, 0x12, 0x03, 0x15]);
+        for reply in [
+            mock_broadcast_response(&[2; 6], [192, 168, 1, 60], 45000, "GW1100A-WIFI0202 V2.3.1"),
+            b"not a gateway".to_vec(),
+            mock_broadcast_response(&[1; 6], [192, 168, 1, 50], 45000, "GW2000A-WIFI0101 V3.1.4"),
+            mock_broadcast_response(&[2; 6], [192, 168, 1, 60], 45000, "GW1100A-WIFI0202 V2.3.1"),
+        ] {
+            socket.send_to(&reply, from).unwrap();
+        }
+    });
+
+    let gateways = discover_at(target, Duration::from_millis(500)).unwrap();
+    let found: Vec<_> = gateways
+        .iter()
+        .map(|gateway| (gateway.ip.to_string(), gateway.model.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("192.168.1.50".to_string(), "GW2000A"),
+            ("192.168.1.60".to_string(), "GW1100A")
+        ]
+    );
+}
+
 #[test]
 fn test_client_connection_refused() {
     // Try to connect to a port that's not listening
@@ -130,3 +489,299 @@ fn test_client_multiple_requests() {
     let data = client.get_livedata();
     assert!(data.is_ok());
 }
+
+/// Run one script per connection and return a client pointed at the server,
+/// with the requests it receives
+fn client_for_scripts(scripts: Vec<Vec<Step>>) -> (GW1000Client, Arc<Mutex<Vec<Vec<u8>>>>) {
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+    for script in scripts {
+        server.add_script(script);
+    }
+    let requests = server.requests();
+    let _handle = server.start();
+
+    (GW1000Client::new("127.0.0.1".to_string(), port), requests)
+}
+
+#[test]
+fn test_client_partial_writes() {
+    // The frame arrives a few bytes at a time, split inside the header too
+    let (client, _) = client_for_scripts(vec![vec![Step::Partial {
+        data: mock_livedata_response(),
+        chunk: 3,
+        pause: Duration::from_millis(5),
+    }]]);
+
+    let data = client.get_livedata().unwrap();
+    assert_eq!(data.get("outtemp"), Some(&25.5));
+}
+
+#[test]
+fn test_client_slow_device() {
+    let (client, _) = client_for_scripts(vec![vec![
+        Step::Delay(Duration::from_millis(300)),
+        Step::Respond(mock_livedata_response()),
+    ]]);
+
+    assert!(client.get_livedata().is_ok());
+}
+
+#[test]
+fn test_client_bad_checksum() {
+    let (client, _) = client_for_scripts(vec![vec![Step::Respond(with_bad_checksum(
+        mock_livedata_response(),
+    ))]]);
+
+    let err = client.get_livedata().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ResponseError>(),
+        Some(ResponseError::BadChecksum { command: 0x27, .. })
+    ));
+}
+
+#[test]
+fn test_client_busy_then_answers() {
+    // A lone status byte where live data belongs is the gateway turning the
+    // request away; it is tried again on a new connection
+    let busy = mock_corrupt_response(0x27, &[0x00, 0x05], &[0x01]);
+    let (client, requests) = client_for_scripts(vec![
+        vec![Step::Respond(busy)],
+        vec![Step::Respond(mock_livedata_response())],
+    ]);
+
+    let data = client.get_livedata().unwrap();
+    assert_eq!(data.get("outtemp"), Some(&25.5));
+    assert_eq!(requests.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn test_client_stays_busy() {
+    let busy = mock_corrupt_response(0x27, &[0x00, 0x05], &[0x01]);
+    let (client, requests) = client_for_scripts(vec![vec![Step::Respond(busy)]; 3]);
+
+    let err = client.get_livedata().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ResponseError>(),
+        Some(&ResponseError::Busy {
+            command: 0x27,
+            status: 0x01
+        })
+    );
+    assert!(err.to_string().contains("Gateway busy"));
+    assert_eq!(requests.lock().unwrap().len(), 3);
+}
+
+#[test]
+fn test_client_closed_mid_frame() {
+    let response = mock_livedata_response();
+    let (client, _) = client_for_scripts(vec![vec![Step::Respond(
+        response[..response.len() / 2].to_vec(),
+    )]]);
+
+    let err = client.get_livedata().unwrap_err();
+    assert!(err.to_string().contains("Truncated response"));
+}
+
+#[test]
+fn test_client_oversized_frame() {
+    // The size field is checked before the body is read
+    let (client, _) = client_for_scripts(vec![vec![Step::Respond(mock_corrupt_response(
+        0x27,
+        &[0xFF, 0xFF],
+        &[],
+    ))]]);
+
+    let err = client.get_livedata().unwrap_err();
+    assert!(err.to_string().contains("65536 byte limit"));
+}
+
+#[test]
+fn test_client_connection_reset_then_recovers() {
+    let (client, requests) = client_for_scripts(vec![
+        vec![Step::Reset],
+        vec![Step::Respond(mock_livedata_response())],
+    ]);
+
+    assert!(client.get_livedata().is_err());
+    // Each poll is a fresh connection, so the next one succeeds
+    assert!(client.get_livedata().is_ok());
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0], [0xFF, 0xFF, 0x27, 0x03, 0x2A]);
+    assert_eq!(requests[0], requests[1]);
+}
+
+#[test]
+fn test_client_retries_with_backoff() {
+    let policy = RetryPolicy {
+        attempts: 3,
+        backoff_ms: 20,
+        max_backoff_ms: 30,
+        jitter: 0.0,
+    };
+
+    // Two dropped connections are ridden out within one call
+    let (client, requests) = client_for_scripts(vec![
+        vec![Step::Reset],
+        vec![Step::Reset],
+        vec![Step::Respond(mock_livedata_response())],
+    ]);
+    let started = std::time::Instant::now();
+    assert!(client.with_retry(policy).get_livedata().is_ok());
+    assert_eq!(requests.lock().unwrap().len(), 3);
+    // 20ms, then 40ms capped at 30ms
+    assert!(started.elapsed() >= Duration::from_millis(50));
+
+    // Out of attempts, the error says how many retries were made
+    let (client, requests) = client_for_scripts(vec![vec![Step::Reset], vec![Step::Reset]]);
+    let policy = RetryPolicy {
+        attempts: 2,
+        ..policy
+    };
+    let err = client.with_retry(policy).get_livedata().unwrap_err();
+    assert!(format!("{:#}", err).contains("after 1 retry"), "{:#}", err);
+    assert_eq!(requests.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn test_client_keep_alive_reuses_connection() {
+    let server = MockGW1000Server::new().unwrap().keep_alive();
+    let port = server.port();
+    server.add_script(vec![Step::Respond(mock_firmware_response(
+        "GW2000B_V3.1.4",
+    ))]);
+    server.add_script(vec![Step::Respond(mock_livedata_response())]);
+    server.add_script(vec![Step::Respond(mock_livedata_response())]);
+    let (requests, connections) = (server.requests(), server.connections());
+    let _handle = server.start();
+
+    let client = GW1000Client::new("127.0.0.1".to_string(), port).with_keep_alive(true);
+    assert_eq!(client.get_firmware_version().unwrap(), "GW2000B_V3.1.4");
+    assert!(client.get_livedata().is_ok());
+    assert!(client.get_livedata().is_ok());
+
+    assert_eq!(requests.lock().unwrap().len(), 3);
+    assert_eq!(*connections.lock().unwrap(), 1);
+}
+
+#[test]
+fn test_client_keep_alive_reconnects() {
+    // The second command finds the kept connection reset and goes again
+    // over a new one, without the caller seeing an error
+    let server = MockGW1000Server::new().unwrap().keep_alive();
+    let port = server.port();
+    server.add_script(vec![Step::Respond(mock_livedata_response())]);
+    server.add_script(vec![Step::Reset]);
+    server.add_script(vec![Step::Respond(mock_livedata_response())]);
+    let (requests, connections) = (server.requests(), server.connections());
+    let _handle = server.start();
+
+    let client = GW1000Client::new("127.0.0.1".to_string(), port).with_keep_alive(true);
+    assert!(client.get_livedata().is_ok());
+    assert!(client.get_livedata().is_ok());
+    assert_eq!(requests.lock().unwrap().len(), 3);
+    assert_eq!(*connections.lock().unwrap(), 2);
+
+    // A gateway that closes every connection still answers each command
+    let (client, requests) = client_for_scripts(vec![
+        vec![Step::Respond(mock_firmware_response("GW1100A_V2.3.1"))],
+        vec![Step::Respond(mock_mac_response(&[
+            0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF,
+        ]))],
+    ]);
+    let client = client.with_keep_alive(true);
+    assert_eq!(client.get_firmware_version().unwrap(), "GW1100A_V2.3.1");
+    assert_eq!(client.get_mac_address().unwrap(), "AA:BB:CC:DD:EE:FF");
+    assert_eq!(requests.lock().unwrap().len(), 2);
+}
+
+fn async_client_for_scripts(
+    scripts: Vec<Vec<Step>>,
+) -> (AsyncGW1000Client, Arc<Mutex<Vec<Vec<u8>>>>) {
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+    for script in scripts {
+        server.add_script(script);
+    }
+    let requests = server.requests();
+    let _handle = server.start();
+
+    (
+        AsyncGW1000Client::new("127.0.0.1".to_string(), port),
+        requests,
+    )
+}
+
+#[tokio::test]
+async fn test_async_client_reads_like_the_blocking_one() {
+    let (client, requests) = async_client_for_scripts(vec![
+        vec![Step::Respond(mock_firmware_response("GW2000B_V3.1.4"))],
+        vec![Step::Partial {
+            data: mock_livedata_response(),
+            chunk: 3,
+            pause: Duration::from_millis(5),
+        }],
+    ]);
+
+    assert_eq!(
+        client.get_firmware_version().await.unwrap(),
+        "GW2000B_V3.1.4"
+    );
+    let (data, warnings) = client.get_livedata_with_warnings().await.unwrap();
+    assert_eq!(data.get("outtemp"), Some(&25.5));
+    assert_eq!(warnings, 0);
+    assert_eq!(requests.lock().unwrap()[1], [0xFF, 0xFF, 0x27, 0x03, 0x2A]);
+}
+
+#[tokio::test]
+async fn test_async_client_set_time_and_busy() {
+    let busy = mock_corrupt_response(0x30, &[0x04], &[0x01]);
+    let (client, requests) = async_client_for_scripts(vec![
+        vec![Step::Respond(busy)],
+        vec![Step::Respond(mock_system_params_response(1_700_000_100))],
+        vec![Step::Respond(mock_write_ack(0x31, 0x00))],
+    ]);
+
+    assert_eq!(client.set_time(1_700_000_000).await.unwrap(), 100);
+    assert_eq!(requests.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_async_client_rejects_bad_frames() {
+    let (client, _) = async_client_for_scripts(vec![
+        vec![Step::Respond(with_bad_checksum(mock_livedata_response()))],
+        vec![Step::Respond(mock_corrupt_response(
+            0x27,
+            &[0xFF, 0xFF],
+            &[],
+        ))],
+    ]);
+
+    let err = client.get_livedata_with_warnings().await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ResponseError>(),
+        Some(ResponseError::BadChecksum { command: 0x27, .. })
+    ));
+    let err = client.get_livedata_with_warnings().await.unwrap_err();
+    assert!(err.to_string().contains("65536 byte limit"));
+}
+
+#[tokio::test]
+async fn test_async_client_leaves_the_runtime_free() {
+    // On a single-threaded runtime, a timer still fires on time while the
+    // gateway takes its time to answer
+    let (client, _) = async_client_for_scripts(vec![vec![
+        Step::Delay(Duration::from_millis(400)),
+        Step::Respond(mock_livedata_response()),
+    ]]);
+
+    let started = std::time::Instant::now();
+    let (poll, timer) = tokio::join!(client.get_livedata_with_warnings(), async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        started.elapsed()
+    });
+    assert!(poll.is_ok());
+    assert!(timer < Duration::from_millis(300), "{:?}", timer);
+}
@@ -0,0 +1,337 @@
+/// End-to-end tests: mock gateway -> decode -> PostgreSQL/MySQL/Mosquitto.
+///
+/// The services run in Docker via testcontainers, so these are ignored by
+/// default and need the `e2e` feature:
+///
+///   cargo test --features e2e --test e2e_test -- --ignored
+#[allow(dead_code)]
+mod mock_server;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use mock_server::{mock_livedata_fields, MockGW1000Server};
+use rumqttc::{AsyncClient, Event, EventLoop, Incoming, MqttOptions, Publish, QoS};
+use std::collections::HashMap;
+use std::time::Duration;
+use testcontainers_modules::mosquitto::Mosquitto;
+use testcontainers_modules::mysql::Mysql;
+use testcontainers_modules::postgres::Postgres;
+use testcontainers_modules::testcontainers::runners::AsyncRunner;
+use wxlistener::backup;
+use wxlistener::client::GW1000Client;
+use wxlistener::database::{DatabaseConfig, DatabaseWriter, WriteMode};
+use wxlistener::derived::{add_derived_fields, DerivedConfig};
+use wxlistener::metrics::MetricsRecorder;
+use wxlistener::mqtt::{MqttConfig, MqttPublisher};
+use wxlistener::output::PrecisionConfig;
+use wxlistener::provenance::Provenance;
+
+/// Readings the mock gateway reports
+fn gateway_readings() -> HashMap<String, f64> {
+    [
+        ("intemp", 21.5),
+        ("outtemp", 4.2),
+        ("inhumid", 45.0),
+        ("outhumid", 88.0),
+        ("absbarometer", 1013.2),
+        ("wind_speed", 3.4),
+        ("rain_day", 1.2),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value))
+    .collect()
+}
+
+/// One poll the way the daemon does it: fetch and decode from the mock
+/// gateway, then add derived fields. Returns the readings for the database
+/// and the rounded ones for publishing.
+fn poll() -> (DateTime<Utc>, HashMap<String, f64>, HashMap<String, f64>) {
+    let server = MockGW1000Server::new().unwrap();
+    let port = server.port();
+    server.add_response(mock_livedata_fields(&gateway_readings()));
+    let _handle = server.start();
+
+    let client = GW1000Client::new("127.0.0.1".to_string(), port);
+    let mut data = client.get_livedata().unwrap();
+    add_derived_fields(&mut data, &DerivedConfig::default());
+    let rounded = PrecisionConfig::default().rounded(&data);
+    // Databases keep microseconds at most
+    (Utc::now().trunc_subsecs(0), data, rounded)
+}
+
+/// A `[database]` section as it would appear in wxlistener.toml
+fn database_config(toml: &str) -> DatabaseConfig {
+    toml::from_str(toml).unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_pipeline_to_postgres() {
+    let node = Postgres::default().start().await.unwrap();
+    let url = format!(
+        "postgres://postgres:postgres@{}:{}/postgres",
+        node.get_host().await.unwrap(),
+        node.get_host_port_ipv4(5432).await.unwrap()
+    );
+
+    // The table doesn't exist yet; the writer creates it
+    let config = database_config(&format!("connection_string = \"{}\"", url));
+    let writer = DatabaseWriter::new(&config).await.unwrap();
+    let (timestamp, data, _) = poll();
+    let provenance = Provenance::poll(Some("AA:BB:CC:DD:EE:FF".to_string()), None, 0);
+    writer
+        .insert_data(&data, &timestamp, &provenance)
+        .await
+        .unwrap();
+
+    let pool = sqlx::PgPool::connect(&url).await.unwrap();
+    let (stored_at, outtemp, outhumid, dewpoint): (DateTime<Utc>, f64, f64, f64) =
+        sqlx::query_as("SELECT timestamp, outtemp, outhumid, dewpoint FROM wx_records")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(stored_at, timestamp);
+    assert_eq!(outtemp, 4.2);
+    assert_eq!(outhumid, 88.0);
+    // Derived fields with a column are stored too
+    assert_eq!(dewpoint, data["dewpoint"]);
+
+    // Timestamps are unique, so a replayed record replaces its own row
+    writer
+        .insert_data(&data, &timestamp, &provenance)
+        .await
+        .unwrap();
+    let (rows,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM wx_records")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(rows, 1);
+
+    // Backups export the stored records and restore them in place
+    let dir = tempfile::TempDir::new().unwrap();
+    let records = dir.path().join("records.jsonl");
+    assert_eq!(backup::export_records(&writer, &records).await.unwrap(), 1);
+    assert_eq!(backup::import_records(&records, &writer).await.unwrap(), 1);
+
+    // Imports are loaded with COPY; fields a record lacks keep their values
+    let replay = Provenance::replay("weewx", None);
+    let earlier = timestamp - chrono::Duration::minutes(5);
+    let records = vec![
+        (
+            earlier,
+            HashMap::from([("outtemp".to_string(), 3.9)]),
+            replay.clone(),
+        ),
+        (
+            timestamp,
+            HashMap::from([("outtemp".to_string(), 5.0)]),
+            replay,
+        ),
+    ];
+    writer.bulk_insert(&records).await.unwrap();
+    let stored: Vec<(f64, Option<f64>)> =
+        sqlx::query_as("SELECT outtemp, outhumid FROM wx_records ORDER BY timestamp")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(stored, vec![(3.9, None), (5.0, Some(88.0))]);
+
+    let mut recorder = MetricsRecorder::new(300);
+    recorder.record_poll(timestamp, Duration::from_millis(42), true);
+    writer.create_metrics_table().await.unwrap();
+    writer
+        .insert_metrics(&recorder.finish(timestamp).unwrap())
+        .await
+        .unwrap();
+    let (polls, latency): (i32, f64) =
+        sqlx::query_as("SELECT polls, latency_avg_ms FROM wx_records_metrics")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(polls, 1);
+    assert_eq!(latency, 42.0);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_postgres_table_without_unique_timestamp() {
+    let node = Postgres::default().start().await.unwrap();
+    let url = format!(
+        "postgres://postgres:postgres@{}:{}/postgres",
+        node.get_host().await.unwrap(),
+        node.get_host_port_ipv4(5432).await.unwrap()
+    );
+
+    // A table as versions before upserts created it
+    let pool = sqlx::PgPool::connect(&url).await.unwrap();
+    sqlx::query(
+        "CREATE TABLE wx_records (id SERIAL PRIMARY KEY, \
+         timestamp TIMESTAMP WITH TIME ZONE NOT NULL, outtemp DOUBLE PRECISION)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Writes fall back to plain inserts rather than failing
+    let config = database_config(&format!("connection_string = \"{}\"", url));
+    let writer = DatabaseWriter::new(&config).await.unwrap();
+    assert_eq!(
+        writer.allowed_write_mode().await.unwrap(),
+        WriteMode::Insert
+    );
+    let data = HashMap::from([("outtemp".to_string(), 4.2)]);
+    let timestamp = Utc::now().trunc_subsecs(0);
+    let provenance = Provenance::poll(None, None, 0);
+    for _ in 0..2 {
+        writer
+            .insert_data(&data, &timestamp, &provenance)
+            .await
+            .unwrap();
+    }
+    let count = || async {
+        let (rows,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM wx_records")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        rows
+    };
+    assert_eq!(count().await, 2);
+
+    // After the documented migration, writes are upserts again
+    sqlx::query("DELETE FROM wx_records WHERE id > (SELECT MIN(id) FROM wx_records)")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("CREATE UNIQUE INDEX wx_records_timestamp_key ON wx_records (timestamp)")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let writer = DatabaseWriter::new(&config).await.unwrap();
+    assert_eq!(
+        writer.allowed_write_mode().await.unwrap(),
+        WriteMode::Upsert
+    );
+    writer
+        .insert_data(&data, &timestamp, &provenance)
+        .await
+        .unwrap();
+    assert_eq!(count().await, 1);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_pipeline_to_mysql_over_tls() {
+    // MySQL 8 generates a self-signed certificate at startup
+    let node = Mysql::default().start().await.unwrap();
+    let url = format!(
+        "mysql://root@{}:{}/test",
+        node.get_host().await.unwrap(),
+        node.get_host_port_ipv4(3306).await.unwrap()
+    );
+
+    let config = database_config(&format!(
+        "connection_string = \"{}\"\n\
+         require_tls = true\n\
+         skip_ssl_verify = true\n\
+         mysql_partition_by_month = true\n\
+         store_metadata = true",
+        url
+    ));
+    let writer = DatabaseWriter::new(&config).await.unwrap();
+    let (timestamp, data, _) = poll();
+    let provenance = Provenance::poll(Some("AA:BB:CC:DD:EE:FF".to_string()), None, 1);
+    writer
+        .insert_data(&data, &timestamp, &provenance)
+        .await
+        .unwrap();
+
+    let pool = sqlx::MySqlPool::connect(&url).await.unwrap();
+    let (outtemp, absbarometer, metadata): (f64, f64, sqlx::types::Json<Provenance>) =
+        sqlx::query_as("SELECT outtemp, absbarometer, metadata FROM wx_records")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(outtemp, 4.2);
+    assert_eq!(absbarometer, 1013.2);
+    assert_eq!(metadata.0, provenance);
+
+    // The writer required TLS; check the server really negotiates it
+    let (_, cipher): (String, String) = sqlx::query_as("SHOW SESSION STATUS LIKE 'Ssl_cipher'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(!cipher.is_empty());
+
+    let partitions: Vec<(String,)> = sqlx::query_as(
+        "SELECT partition_name FROM information_schema.partitions \
+         WHERE table_name = 'wx_records' AND partition_name IS NOT NULL",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert!(partitions.iter().any(|(name,)| name == "pmax"));
+}
+
+/// The next message the subscriber receives
+async fn next_publish(eventloop: &mut EventLoop) -> Publish {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Event::Incoming(Incoming::Publish(publish)) = eventloop.poll().await.unwrap() {
+                break publish;
+            }
+        }
+    })
+    .await
+    .expect("no message from the broker")
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_pipeline_to_mosquitto() {
+    let node = Mosquitto::default().start().await.unwrap();
+    let host = node.get_host().await.unwrap().to_string();
+    let port = node.get_host_port_ipv4(1883).await.unwrap();
+
+    // Subscribe first; the broker doesn't keep non-retained messages
+    let mut options = MqttOptions::new("e2e-subscriber", host.clone(), port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (subscriber, mut eventloop) = AsyncClient::new(options, 10);
+    subscriber
+        .subscribe("wx/#", QoS::AtLeastOnce)
+        .await
+        .unwrap();
+    loop {
+        if let Event::Incoming(Incoming::SubAck(_)) = eventloop.poll().await.unwrap() {
+            break;
+        }
+    }
+
+    let config = MqttConfig {
+        connection_string: Some(format!("mqtt://{}:{}/wx/e2e", host, port)),
+        station: Some("e2e".to_string()),
+        availability_topic: Some("wx/{station}/status".to_string()),
+        ..MqttConfig::new()
+    };
+    let publisher = MqttPublisher::new(&config).await.unwrap();
+
+    // The birth message comes first
+    let birth = next_publish(&mut eventloop).await;
+    assert_eq!(birth.topic, "wx/e2e/status");
+    assert_eq!(&birth.payload[..], b"online");
+
+    let (timestamp, _, rounded) = poll();
+    publisher
+        .publish_readings(&rounded, &timestamp)
+        .await
+        .unwrap();
+    let message = next_publish(&mut eventloop).await;
+    assert_eq!(message.topic, "wx/e2e");
+    let received: serde_json::Value = serde_json::from_slice(&message.payload).unwrap();
+    assert_eq!(received["timestamp"], timestamp.to_rfc3339());
+    assert_eq!(received["data"]["outtemp"], 4.2);
+    assert_eq!(received["data"]["outhumid"], 88.0);
+
+    publisher.shutdown().await;
+    let death = next_publish(&mut eventloop).await;
+    assert_eq!(death.topic, "wx/e2e/status");
+    assert_eq!(&death.payload[..], b"offline");
+}
@@ -0,0 +1,106 @@
+// Allocation budget for the per-poll pipeline. A daemon polling every 16s
+// runs this path millions of times, so live heap must stop growing once the
+// rolling windows (24h air quality averages) are full.
+use chrono::{Duration, TimeZone, Utc};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wxlistener::air_quality::AirQualityAverager;
+use wxlistener::calibration::CalibrationConfig;
+use wxlistener::decoder::{parse_livedata, LIVEDATA_FIELDS};
+use wxlistener::derived::{add_derived_fields, DerivedConfig};
+use wxlistener::output::{FieldValue, PrecisionConfig};
+use wxlistener::sensor_watch::{SensorWatch, SensorWatchConfig};
+use wxlistener::status::DaemonStatus;
+use wxlistener::units::Units;
+
+struct CountingAlloc;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Polls covering more than the 24h averaging window at a 16s interval
+const WARMUP_POLLS: i64 = 6000;
+const MEASURED_POLLS: i64 = 6000;
+
+fn full_payload() -> Vec<u8> {
+    let values: HashMap<String, f64> = LIVEDATA_FIELDS
+        .iter()
+        .flat_map(|field| field.values.iter())
+        .map(|(name, _)| (name.to_string(), 12.3))
+        .collect();
+    // Every known field, including the WH46 PM1.0/PM4.0 whose 24h averages
+    // are computed from held samples
+    LIVEDATA_FIELDS
+        .iter()
+        .flat_map(|field| field.encode(&values))
+        .collect()
+}
+
+#[test]
+fn test_poll_pipeline_memory_is_bounded() {
+    let payload = full_payload();
+    let start = Utc.with_ymd_and_hms(2026, 1, 15, 0, 0, 0).unwrap();
+
+    let mut calibration = CalibrationConfig::default();
+    calibration
+        .adjustments
+        .insert("outtemp_offset".to_string(), -0.4);
+    let derived = DerivedConfig::default();
+    let precision = PrecisionConfig::default();
+    let mut sensor_watch = SensorWatch::new(SensorWatchConfig::default());
+    let mut averager = AirQualityAverager::new();
+    let mut status = DaemonStatus::new(start);
+    let mut line = String::new();
+
+    let mut poll = |i: i64| {
+        let now = start + Duration::seconds(16 * i);
+        let mut data = parse_livedata(&payload);
+        calibration.apply(&mut data);
+        averager.add_averages(&mut data, now);
+        add_derived_fields(&mut data, &derived);
+        sensor_watch.observe(&data, now);
+        let data = precision.rounded(&data);
+        status.record_poll(&data, now);
+        for (key, value) in &data {
+            line.clear();
+            write!(line, "{}", FieldValue(key, *value, Units::default())).unwrap();
+        }
+    };
+
+    for i in 0..WARMUP_POLLS {
+        poll(i);
+    }
+    let baseline = LIVE_BYTES.load(Ordering::Relaxed);
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+
+    for i in WARMUP_POLLS..WARMUP_POLLS + MEASURED_POLLS {
+        poll(i);
+    }
+    let growth = LIVE_BYTES.load(Ordering::Relaxed).saturating_sub(baseline);
+    let per_poll = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / MEASURED_POLLS as usize;
+    println!(
+        "live heap growth: {} bytes, {} allocations per poll",
+        growth, per_poll
+    );
+
+    // Small slack for hash map tables resizing at different points
+    assert!(growth < 16 * 1024, "live heap grew by {} bytes", growth);
+}
@@ -1,14 +1,39 @@
 /// Mock TCP server for testing GW1000 client
+use std::collections::{HashMap, VecDeque};
 use std::io::{Read, Write};
-use std::net::TcpListener;
+use std::net::{TcpListener, TcpStream};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
+use wxlistener::decoder::LIVEDATA_FIELDS;
+
+/// What the mock device does on a connection, in order, after reading the
+/// request
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Write the bytes in one go
+    Respond(Vec<u8>),
+    /// Write the bytes `chunk` at a time with a pause between writes, to
+    /// exercise reads that have to wait for the rest of a frame
+    Partial {
+        data: Vec<u8>,
+        chunk: usize,
+        pause: Duration,
+    },
+    /// Do nothing for a while
+    Delay(Duration),
+    /// Abort the connection with a TCP reset instead of a clean close
+    Reset,
+}
 
 /// Mock GW1000 device server
 pub struct MockGW1000Server {
     listener: TcpListener,
-    responses: Arc<Mutex<Vec<Vec<u8>>>>,
+    scripts: Arc<Mutex<VecDeque<Vec<Step>>>>,
+    requests: Arc<Mutex<Vec<Vec<u8>>>>,
+    connections: Arc<Mutex<usize>>,
+    keep_alive: bool,
 }
 
 impl MockGW1000Server {
@@ -19,10 +44,22 @@ impl MockGW1000Server {
 
         Ok(Self {
             listener,
-            responses: Arc::new(Mutex::new(Vec::new())),
+            scripts: Arc::new(Mutex::new(VecDeque::new())),
+            requests: Arc::new(Mutex::new(Vec::new())),
+            connections: Arc::new(Mutex::new(0)),
+            keep_alive: false,
         })
     }
 
+    /// Serve requests on a connection until the client closes it, one
+    /// script per request, instead of closing it after the first. A script
+    /// ending in `Step::Reset` still ends the connection.
+    #[allow(dead_code)]
+    pub fn keep_alive(mut self) -> Self {
+        self.keep_alive = true;
+        self
+    }
+
     /// Get the address the server is listening on
     #[allow(dead_code)]
     pub fn addr(&self) -> String {
@@ -34,36 +71,61 @@ impl MockGW1000Server {
         self.listener.local_addr().unwrap().port()
     }
 
-    /// Add a canned response for the next request
+    /// Add a canned response. Responses are served last added first, ahead
+    /// of any scripts.
     pub fn add_response(&self, response: Vec<u8>) {
-        self.responses.lock().unwrap().push(response);
+        self.scripts
+            .lock()
+            .unwrap()
+            .push_front(vec![Step::Respond(response)]);
+    }
+
+    /// Script the next connection. Scripts run in the order added, one per
+    /// connection; connections beyond them get no response.
+    #[allow(dead_code)]
+    pub fn add_script(&self, steps: Vec<Step>) {
+        self.scripts.lock().unwrap().push_back(steps);
+    }
+
+    /// Requests received so far, one per connection unless keep-alive
+    #[allow(dead_code)]
+    pub fn requests(&self) -> Arc<Mutex<Vec<Vec<u8>>>> {
+        Arc::clone(&self.requests)
+    }
+
+    /// Connections accepted so far
+    #[allow(dead_code)]
+    pub fn connections(&self) -> Arc<Mutex<usize>> {
+        Arc::clone(&self.connections)
     }
 
     /// Start the server in a background thread
     pub fn start(self) -> ServerHandle {
-        let responses = Arc::clone(&self.responses);
+        let scripts = Arc::clone(&self.scripts);
+        let requests = Arc::clone(&self.requests);
+        let connections = Arc::clone(&self.connections);
+        let keep_alive = self.keep_alive;
         let listener = self.listener;
 
         let handle = thread::spawn(move || {
             for stream in listener.incoming() {
                 match stream {
                     Ok(mut stream) => {
+                        *connections.lock().unwrap() += 1;
                         // Set a short timeout to avoid hanging
                         stream.set_read_timeout(Some(Duration::from_secs(1))).ok();
                         stream.set_write_timeout(Some(Duration::from_secs(1))).ok();
 
-                        // Read the request
+                        // Read requests until the client closes the connection
                         let mut buffer = vec![0u8; 1024];
-                        if let Ok(n) = stream.read(&mut buffer) {
-                            if n > 0 {
-                                // Get the next canned response
-                                let response = responses.lock().unwrap().pop();
-
-                                if let Some(resp) = response {
-                                    // Send the response
-                                    stream.write_all(&resp).ok();
-                                    stream.flush().ok();
-                                }
+                        while let Ok(n) = stream.read(&mut buffer) {
+                            if n == 0 {
+                                break;
+                            }
+                            requests.lock().unwrap().push(buffer[..n].to_vec());
+                            let script = scripts.lock().unwrap().pop_front();
+                            if !run_script(&mut stream, script.unwrap_or_default()) || !keep_alive {
+                                break;
                             }
                         }
                     }
@@ -76,6 +138,38 @@ impl MockGW1000Server {
     }
 }
 
+/// Run `steps` on the connection; false if it is no longer usable
+fn run_script(stream: &mut TcpStream, steps: Vec<Step>) -> bool {
+    for step in steps {
+        let written = match step {
+            Step::Respond(data) => stream.write_all(&data).and_then(|_| stream.flush()),
+            Step::Partial { data, chunk, pause } => {
+                data.chunks(chunk.max(1)).try_for_each(|part| {
+                    stream.write_all(part)?;
+                    stream.flush()?;
+                    thread::sleep(pause);
+                    Ok(())
+                })
+            }
+            Step::Delay(duration) => {
+                thread::sleep(duration);
+                Ok(())
+            }
+            Step::Reset => {
+                // A zero linger timeout makes close send RST
+                socket2::SockRef::from(&*stream)
+                    .set_linger(Some(Duration::ZERO))
+                    .ok();
+                return false;
+            }
+        };
+        if written.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
 /// Handle to a running server
 #[allow(dead_code)]
 pub struct ServerHandle {
@@ -128,19 +222,36 @@ pub fn mock_mac_response(mac: &[u8; 6]) -> Vec<u8> {
 
 /// Helper function to create a minimal live data response
 pub fn mock_livedata_response() -> Vec<u8> {
-    let mut response = vec![
-        0xFF, 0xFF, // Header
-        0x27, // Command (CMD_GW1000_LIVEDATA)
-    ];
+    let mut values = HashMap::new();
+    values.insert("outtemp".to_string(), 25.5);
+    values.insert("outhumid".to_string(), 65.0);
+
+    mock_livedata_fields(&values)
+}
 
-    // Build the data payload
+/// Helper function to create a live data response holding the given values,
+/// encoded with the parser's field table
+pub fn mock_livedata_fields(values: &HashMap<String, f64>) -> Vec<u8> {
     let mut data = Vec::new();
+    for field in LIVEDATA_FIELDS {
+        if field
+            .values
+            .iter()
+            .any(|(name, _)| values.contains_key(*name))
+        {
+            data.extend(field.encode(values));
+        }
+    }
 
-    // 0x02: outtemp = 25.5°C (255 = 0x00FF)
-    data.extend_from_slice(&[0x02, 0x00, 0xFF]);
+    mock_livedata_response_with(&data)
+}
 
-    // 0x07: outhumid = 65%
-    data.extend_from_slice(&[0x07, 0x41]);
+/// Helper function to wrap a raw field payload in a live data response
+pub fn mock_livedata_response_with(data: &[u8]) -> Vec<u8> {
+    let mut response = vec![
+        0xFF, 0xFF, // Header
+        0x27, // Command (CMD_GW1000_LIVEDATA)
+    ];
 
     // Calculate size: cmd(1) + size(2) + data + checksum(1)
     let size = 1 + 2 + data.len() + 1;
@@ -150,7 +261,7 @@ pub fn mock_livedata_response() -> Vec<u8> {
     response.push((size & 0xFF) as u8);
 
     // Add data
-    response.extend_from_slice(&data);
+    response.extend_from_slice(data);
 
     // Calculate checksum (from command onwards, excluding header and checksum itself)
     let checksum: u8 = response[2..].iter().map(|&b| b as u32).sum::<u32>() as u8;
@@ -159,6 +270,93 @@ pub fn mock_livedata_response() -> Vec<u8> {
     response
 }
 
+/// Helper function to create a sensor ID response from
+/// (type, id, battery, signal) records
+#[allow(dead_code)]
+pub fn mock_sensor_id_response(sensors: &[(u8, u32, u8, u8)]) -> Vec<u8> {
+    let mut response = vec![
+        0xFF, 0xFF, // Header
+        0x3C, // Command (CMD_READ_SENSOR_ID_NEW)
+    ];
+
+    let mut data = Vec::new();
+    for &(sensor_type, id, battery, signal) in sensors {
+        data.push(sensor_type);
+        data.extend_from_slice(&id.to_be_bytes());
+        data.push(battery);
+        data.push(signal);
+    }
+
+    // Calculate size: cmd(1) + size(2) + data + checksum(1)
+    let size = 1 + 2 + data.len() + 1;
+    response.push(((size >> 8) & 0xFF) as u8);
+    response.push((size & 0xFF) as u8);
+    response.extend_from_slice(&data);
+
+    let checksum: u8 = response[2..].iter().map(|&b| b as u32).sum::<u32>() as u8;
+    response.push(checksum);
+
+    response
+}
+
+/// Helper function to create a system parameters (CMD_READ_SSSS) response
+#[allow(dead_code)]
+pub fn mock_system_params_response(utc: u32) -> Vec<u8> {
+    let mut data = vec![0x03, 0x01]; // frequency, sensor type
+    data.extend_from_slice(&utc.to_be_bytes());
+    data.extend_from_slice(&[0x0B, 0x01]); // time zone index, DST status
+
+    mock_corrupt_response(0x30, &[(1 + 1 + data.len() + 1) as u8], &data)
+}
+
+/// Helper function to create a calibration (CMD_READ_CALIBRATION) response
+#[allow(dead_code)]
+pub fn mock_calibration_response(data: &[u8]) -> Vec<u8> {
+    mock_corrupt_response(0x38, &[(1 + 1 + data.len() + 1) as u8], data)
+}
+
+/// Helper function to create a write acknowledgement with the given status
+#[allow(dead_code)]
+pub fn mock_write_ack(cmd: u8, status: u8) -> Vec<u8> {
+    mock_corrupt_response(cmd, &[0x04], &[status])
+}
+
+/// Helper function to create a discovery (CMD_BROADCAST) response
+#[allow(dead_code)]
+pub fn mock_broadcast_response(mac: &[u8; 6], ip: [u8; 4], port: u16, name: &str) -> Vec<u8> {
+    let mut data = mac.to_vec();
+    data.extend_from_slice(&ip);
+    data.extend_from_slice(&port.to_be_bytes());
+    data.push(name.len() as u8);
+    data.extend_from_slice(name.as_bytes());
+
+    let size = (1 + 2 + data.len() + 1) as u16; // cmd + size + data + checksum
+    mock_corrupt_response(0x12, &size.to_be_bytes(), &data)
+}
+
+/// Helper function to build a frame with an arbitrary (possibly corrupt)
+/// size field. The checksum is valid so only the size check can reject it.
+#[allow(dead_code)]
+pub fn mock_corrupt_response(cmd: u8, size_field: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut response = vec![0xFF, 0xFF, cmd];
+    response.extend_from_slice(size_field);
+    response.extend_from_slice(data);
+
+    let checksum: u8 = response[2..].iter().map(|&b| b as u32).sum::<u32>() as u8;
+    response.push(checksum);
+
+    response
+}
+
+/// Helper function to corrupt a frame's checksum
+#[allow(dead_code)]
+pub fn with_bad_checksum(mut frame: Vec<u8>) -> Vec<u8> {
+    if let Some(checksum) = frame.last_mut() {
+        *checksum = checksum.wrapping_add(1);
+    }
+    frame
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,89 @@
+//! Outbound connections through a `[proxy]`. The proxy is set once per
+//! process, so everything that goes through it is checked in one test.
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use wxlistener::http_output::{HttpConfig, HttpPublisher};
+use wxlistener::mqtt::{MqttConfig, MqttPublisher};
+use wxlistener::proxy::{self, ProxyConfig};
+
+/// Accept one connection and return its request head, after answering with
+/// `response`
+async fn accept_request(listener: &TcpListener, response: &[u8]) -> String {
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    stream.write_all(response).await.unwrap();
+    String::from_utf8_lossy(&request).to_lowercase()
+}
+
+#[tokio::test]
+async fn test_outbound_connections_use_the_proxy() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    proxy::init(Some(&ProxyConfig {
+        url: format!("http://user:pw@{}", listener.local_addr().unwrap()),
+        no_proxy: vec![".lan".to_string()],
+    }))
+    .unwrap();
+
+    // Plain HTTP goes to the proxy in absolute form, with the credentials
+    let config = HttpConfig {
+        url: Some("http://weather.example.com/api".to_string()),
+        ..HttpConfig::new()
+    };
+    let publisher = HttpPublisher::new(&config).await.unwrap();
+    let post = tokio::spawn(async move {
+        let timestamp = chrono::DateTime::from_timestamp(1_792_238_400, 0).unwrap();
+        publisher.publish(&HashMap::new(), &timestamp).await;
+        publisher.queue_len().await
+    });
+    let request = accept_request(&listener, b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+    assert!(
+        request.starts_with("post http://weather.example.com/api http/1.1\r\n"),
+        "{}",
+        request
+    );
+    // "user:pw"
+    assert!(
+        request.contains("proxy-authorization: basic dxnlcjpwdw==\r\n"),
+        "{}",
+        request
+    );
+    assert_eq!(post.await.unwrap(), 0);
+
+    // MQTT over WebSocket is tunnelled with CONNECT
+    let config = MqttConfig {
+        connection_string: Some("ws://broker.example.com:8080/mqtt".to_string()),
+        ..MqttConfig::new()
+    };
+    let connect = tokio::spawn(async move {
+        let _ = tokio::time::timeout(Duration::from_secs(5), MqttPublisher::new(&config)).await;
+    });
+    let request = accept_request(&listener, b"HTTP/1.1 403 Forbidden\r\n\r\n").await;
+    assert!(
+        request.starts_with("connect broker.example.com:8080 http/1.1\r\n"),
+        "{}",
+        request
+    );
+    assert!(
+        request.contains("proxy-authorization: basic dxnlcjpwdw==\r\n"),
+        "{}",
+        request
+    );
+    connect.await.unwrap();
+
+    assert_eq!(
+        proxy::proxy_for("https", "api.example.com").map(|url| url.port()),
+        Some(Some(listener.local_addr().unwrap().port()))
+    );
+    assert_eq!(proxy::proxy_for("https", "nas.lan"), None);
+    assert_eq!(proxy::proxy_for("http", "127.0.0.1"), None);
+}
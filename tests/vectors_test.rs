@@ -0,0 +1,63 @@
+// Protocol conformance: every vector in tests/vectors/ (golden and contrib/)
+// must decode to exactly its expected readings. Vectors kept elsewhere, e.g.
+// captured from a gateway and not submitted yet, run too when their
+// directories are listed in WXLISTENER_VECTORS.
+use std::path::{Path, PathBuf};
+use wxlistener::decoder::LIVEDATA_FIELDS;
+use wxlistener::vectors::{load, TestVector};
+
+fn vector_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/vectors")];
+    if let Some(extra) = std::env::var_os("WXLISTENER_VECTORS") {
+        dirs.extend(std::env::split_paths(&extra));
+    }
+    dirs
+}
+
+fn all_vectors() -> Vec<(PathBuf, TestVector)> {
+    vector_dirs()
+        .iter()
+        .flat_map(|dir| load(dir).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_vectors_decode() {
+    let vectors = all_vectors();
+    assert!(!vectors.is_empty());
+
+    let failures: Vec<String> = vectors
+        .iter()
+        .filter_map(|(path, vector)| {
+            vector
+                .check()
+                .err()
+                .map(|e| format!("{} ({}): {:#}", path.display(), vector.description, e))
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{} of {} vectors failed:\n{}",
+        failures.len(),
+        vectors.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_golden_vectors_cover_every_field() {
+    // A field added to the decoder needs a golden vector too
+    let golden = load(&vector_dirs()[0]).unwrap();
+    for field in LIVEDATA_FIELDS {
+        for (name, _) in field.values {
+            assert!(
+                golden
+                    .iter()
+                    .any(|(_, vector)| vector.expected.contains_key(*name)),
+                "No golden vector decodes '{}' (0x{:02X})",
+                name,
+                field.addr
+            );
+        }
+    }
+}
@@ -4,17 +4,16 @@ use axum::{
 };
 use serde_json::Value;
 use std::sync::Arc;
-use tokio::sync::broadcast;
 use tower::util::ServiceExt;
 
 // Import the web module functions
+use wxlistener::feed::LiveFeed;
+use wxlistener::status::DaemonStatus;
 use wxlistener::web::*;
 
 #[tokio::test]
 async fn test_api_current_json_endpoint_exists() {
-    // Create a broadcast channel for testing
-    let (tx, _rx) = broadcast::channel::<String>(100);
-    let tx = Arc::new(tx);
+    let tx = Arc::new(LiveFeed::default());
 
     // Build the router
     let app = axum::Router::new()
@@ -41,10 +40,9 @@ async fn test_api_current_json_endpoint_exists() {
 
 #[tokio::test]
 async fn test_api_current_json_returns_json() {
-    let (tx, _rx) = broadcast::channel::<String>(100);
-    let tx = Arc::new(tx);
+    let tx = Arc::new(LiveFeed::default());
 
-    // Send some test data to the channel
+    // Publish before the request: the latest message is served right away
     let test_data = serde_json::json!({
         "timestamp": "2025-12-10 12:00:00 UTC",
         "data": {
@@ -53,7 +51,7 @@ async fn test_api_current_json_returns_json() {
             "outhumid": "65%"
         }
     });
-    let _ = tx.send(test_data.to_string());
+    tx.publish(test_data.to_string());
 
     let app = axum::Router::new()
         .route(
@@ -77,12 +75,48 @@ async fn test_api_current_json_returns_json() {
     // Check content type is JSON
     let content_type = response.headers().get("content-type").unwrap();
     assert!(content_type.to_str().unwrap().contains("application/json"));
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["data"]["outtemp"], "15.5°C");
+}
+
+#[tokio::test]
+async fn test_api_feed_json() {
+    let feed = Arc::new(LiveFeed::with_capacity(1));
+    let _slow = feed.subscribe();
+    feed.publish("{}");
+    feed.publish("{}");
+
+    let app = axum::Router::new()
+        .route("/api/v1/feed.json", axum::routing::get(api_feed_handler))
+        .with_state(feed);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/feed.json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["schema_version"], 1);
+    assert_eq!(json["subscribers"], 1);
+    assert_eq!(json["published"], 2);
+    assert_eq!(json["dropped"], 1);
 }
 
 #[tokio::test]
 async fn test_api_current_json_with_data() {
-    let (tx, _rx) = broadcast::channel::<String>(100);
-    let tx_clone = Arc::new(tx);
+    let tx_clone = Arc::new(LiveFeed::default());
 
     // Spawn a task to send data after a short delay
     let tx_for_task = tx_clone.clone();
@@ -95,7 +129,7 @@ async fn test_api_current_json_with_data() {
                 "intemp": "22.0°C"
             }
         });
-        let _ = tx_for_task.send(test_data.to_string());
+        tx_for_task.publish(test_data.to_string());
     });
 
     let app = axum::Router::new()
@@ -130,8 +164,7 @@ async fn test_api_current_json_with_data() {
 
 #[tokio::test]
 async fn test_api_current_json_timeout() {
-    let (tx, _rx) = broadcast::channel::<String>(100);
-    let tx = Arc::new(tx);
+    let tx = Arc::new(LiveFeed::default());
 
     // Don't send any data - should timeout
 
@@ -164,3 +197,841 @@ async fn test_api_current_json_timeout() {
     assert!(json.get("error").is_some());
     assert!(json["error"].as_str().unwrap().contains("Timeout"));
 }
+
+#[tokio::test]
+async fn test_api_summary_json_not_configured() {
+    let app = axum::Router::new().route(
+        "/api/v1/summary.json",
+        axum::routing::get(|| api_summary_handler(None, None)),
+    );
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/summary.json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+
+    assert!(json["error"].as_str().unwrap().contains("not configured"));
+}
+
+#[tokio::test]
+async fn test_api_status_json() {
+    let mut status = DaemonStatus::new(chrono::Utc::now());
+    let mut data = std::collections::HashMap::new();
+    data.insert("outtemp".to_string(), 12.5);
+    status.record_poll(&data, chrono::Utc::now());
+    status.record_output("database", Err("connection refused"));
+    let status = Arc::new(tokio::sync::Mutex::new(status));
+
+    let app = axum::Router::new().route(
+        "/api/v1/status.json",
+        axum::routing::get(move || api_status_handler(Some(status.clone()), None, None)),
+    );
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/status.json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["readings"]["outtemp"], 12.5);
+    assert_eq!(json["outputs"]["database"]["error_count"], 1);
+    assert!(json["uptime_secs"].is_number());
+}
+
+#[tokio::test]
+async fn test_api_schema_json() {
+    let app = axum::Router::new().route(
+        "/api/v1/schema.json",
+        axum::routing::get(|| api_schema_handler(None)),
+    );
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/schema.json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/schema+json"
+    );
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let schema: Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(schema["schema_version"], API_SCHEMA_VERSION);
+    assert!(schema["$defs"]["current"].is_object());
+    assert!(schema["$defs"]["status"].is_object());
+}
+
+/// Every field a payload carries must be described by the schema
+fn assert_matches_schema(payload: &Value, def: &str) {
+    let schema: Value = serde_json::from_str(API_SCHEMA).unwrap();
+    let properties = schema["$defs"][def]["properties"].as_object().unwrap();
+    let payload = payload.as_object().unwrap();
+
+    for key in payload.keys() {
+        assert!(
+            properties.contains_key(key),
+            "{} not in schema: {}",
+            def,
+            key
+        );
+    }
+    for key in schema["$defs"][def]["required"].as_array().unwrap() {
+        assert!(
+            payload.contains_key(key.as_str().unwrap()),
+            "{} payload missing {}",
+            def,
+            key
+        );
+    }
+    assert_eq!(payload["schema_version"], API_SCHEMA_VERSION);
+}
+
+#[test]
+fn test_current_payload_matches_schema() {
+    let mut data = std::collections::HashMap::new();
+    data.insert("outtemp".to_string(), 12.345);
+    data.insert("broken".to_string(), f64::NAN);
+    let precision: wxlistener::output::PrecisionConfig = toml::from_str("default = 1").unwrap();
+
+    let payload = current_payload(chrono::Utc::now(), &data, &precision, Default::default());
+
+    assert_matches_schema(&payload, "current");
+    assert_eq!(payload["readings"]["outtemp"], 12.3);
+    assert!(payload["readings"].get("broken").is_none());
+    assert!(payload["data"]["outtemp"].is_string());
+}
+
+#[test]
+fn test_status_report_matches_schema() {
+    let mut status = DaemonStatus::new(chrono::Utc::now());
+    let mut data = std::collections::HashMap::new();
+    data.insert("outtemp".to_string(), 12.5);
+    data.insert("broken".to_string(), f64::INFINITY);
+    status.record_poll(&data, chrono::Utc::now());
+    status.record_output("database", Err("connection refused"));
+
+    let report = serde_json::to_value(status.report()).unwrap();
+
+    assert_matches_schema(&report, "status");
+    assert_eq!(report["readings"]["outtemp"], 12.5);
+    assert!(report["readings"].get("broken").is_none());
+}
+
+#[test]
+fn test_tcp_listen_addr() {
+    assert_eq!(tcp_listen_addr("0.0.0.0", 18888), "0.0.0.0:18888");
+    assert_eq!(tcp_listen_addr("::", 18888), "[::]:18888");
+    assert_eq!(tcp_listen_addr("[::1]", 8080), "[::1]:8080");
+    assert_eq!(tcp_listen_addr("localhost", 8080), "localhost:8080");
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn test_server_config(listen: Vec<ListenAddr>) -> WebServerConfig {
+    WebServerConfig {
+        listen,
+        socket_mode: 0o600,
+        interval: 3600,
+        degree_days: None,
+        calibration: None,
+        derived: Default::default(),
+        status: None,
+        max_response_size: wxlistener::client::DEFAULT_MAX_RESPONSE_SIZE,
+        trace_protocol: false,
+        api: Default::default(),
+        http_port: 80,
+        alignment: Default::default(),
+        poll_profiles: Vec::new(),
+        precision: Default::default(),
+        units: Default::default(),
+        fixed_point: false,
+        storage: None,
+        stations: Default::default(),
+        station: Default::default(),
+        station_locations: Default::default(),
+        tokens: Vec::new(),
+        controls: None,
+        realtime_txt: None,
+        clientraw_txt: None,
+        station_name: "wxlistener".to_string(),
+        station_time: Default::default(),
+        public: None,
+        #[cfg(feature = "db")]
+        database: None,
+        #[cfg(feature = "http")]
+        peers: None,
+        device: None,
+    }
+}
+
+#[tokio::test]
+async fn test_web_server_multiple_listen_addresses() {
+    let ports = [free_port(), free_port()];
+    let listen = ports
+        .iter()
+        .map(|port| ListenAddr::Tcp(format!("127.0.0.1:{}", port)))
+        .collect();
+    // The gateway is unreachable; only the HTTP side is under test
+    run_web_server_background(test_server_config(listen), "127.0.0.1".to_string(), 1);
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    for port in ports {
+        let url = format!("http://127.0.0.1:{}/api/v1/schema.json", port);
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn test_web_server_bad_listen_address() {
+    let config = test_server_config(vec![ListenAddr::Tcp("not-an-address".to_string())]);
+    let result = run_web_server(config, "127.0.0.1".to_string(), 1).await;
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("Failed to bind web server to not-an-address"));
+}
+
+#[cfg(unix)]
+async fn unix_get(path: &std::path::Path, uri: &str) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::UnixStream::connect(path).await.unwrap();
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        uri
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_web_server_unix_socket() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("web.sock");
+    // A leftover socket from an earlier run is replaced
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+    let config = test_server_config(vec![ListenAddr::Unix(path.clone())]);
+    run_web_server_background(config, "127.0.0.1".to_string(), 1);
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let response = unix_get(&path, "/api/v1/schema.json").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.contains("\"schema_version\""));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_bind_unix_socket_refuses_regular_file() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("web.sock");
+    std::fs::write(&path, "not a socket").unwrap();
+
+    let result = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(async { bind_unix_socket(&path, 0o660).map(|_| ()) });
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("exists and is not a socket"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_bind_unix_socket_in_use() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("web.sock");
+    let _first = bind_unix_socket(&path, 0o660).unwrap();
+
+    let result = bind_unix_socket(&path, 0o660);
+    assert!(result.unwrap_err().to_string().contains("in use"));
+}
+
+fn token(token: &str, scope: wxlistener::auth::TokenScope) -> wxlistener::auth::ApiToken {
+    wxlistener::auth::ApiToken {
+        token: token.to_string(),
+        scope,
+    }
+}
+
+/// A router with a status, controls and the given tokens
+fn router_with_tokens(
+    tokens: Vec<wxlistener::auth::ApiToken>,
+) -> (axum::Router, wxlistener::status::SharedStatus) {
+    let status = Arc::new(tokio::sync::Mutex::new(DaemonStatus::new(
+        chrono::Utc::now(),
+    )));
+    let mut config = test_server_config(Vec::new());
+    config.tokens = tokens;
+    config.status = Some(status.clone());
+    config.controls = Some(Arc::new(wxlistener::control::Controls::default()));
+    (router(Arc::new(LiveFeed::default()), &config), status)
+}
+
+async fn send(
+    app: &axum::Router,
+    method: &str,
+    uri: &str,
+    token: Option<&str>,
+    body: &str,
+) -> StatusCode {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        request = request.header("authorization", format!("Bearer {}", token));
+    }
+    app.clone()
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_token_scopes() {
+    use wxlistener::auth::TokenScope;
+
+    let (app, status) = router_with_tokens(vec![
+        token("reader", TokenScope::Read),
+        token("boss", TokenScope::Admin),
+    ]);
+    let pause = r#"{"command":"pause","output":"mqtt"}"#;
+
+    // Data routes take either scope
+    assert_eq!(
+        send(&app, "GET", "/api/v1/status.json", None, "").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        send(&app, "GET", "/api/v1/status.json", Some("nope"), "").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        send(&app, "GET", "/api/v1/status.json", Some("reader"), "").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&app, "GET", "/api/v1/status.json", Some("boss"), "").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&app, "GET", "/api/v1/status.json?token=reader", None, "").await,
+        StatusCode::OK
+    );
+
+    // Control needs admin
+    assert_eq!(
+        send(&app, "POST", "/api/v1/control", Some("reader"), pause).await,
+        StatusCode::FORBIDDEN
+    );
+    assert!(!status.lock().await.is_paused("mqtt"));
+    assert_eq!(
+        send(&app, "POST", "/api/v1/control", Some("boss"), pause).await,
+        StatusCode::OK
+    );
+    assert!(status.lock().await.is_paused("mqtt"));
+
+    // So do settings
+    let settings = r#"{"poll_interval":30}"#;
+    assert_eq!(
+        send(&app, "PATCH", "/api/v1/settings", Some("reader"), settings).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(status.lock().await.poll_interval(), None);
+    assert_eq!(
+        send(&app, "PATCH", "/api/v1/settings", Some("boss"), settings).await,
+        StatusCode::OK
+    );
+    assert_eq!(status.lock().await.poll_interval(), Some(30));
+
+    // The dashboard page and schema stay public
+    assert_eq!(
+        send(&app, "GET", "/api/v1/schema.json", None, "").await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_no_tokens_configured() {
+    let (app, status) = router_with_tokens(Vec::new());
+
+    assert_eq!(
+        send(&app, "GET", "/api/v1/status.json", None, "").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(
+            &app,
+            "POST",
+            "/api/v1/control",
+            None,
+            r#"{"command":"pause"}"#
+        )
+        .await,
+        StatusCode::FORBIDDEN
+    );
+    assert!(status.lock().await.paused().is_empty());
+}
+
+#[tokio::test]
+async fn test_api_events_endpoint() {
+    let (app, status) = router_with_tokens(Vec::new());
+    {
+        let mut status = status.lock().await;
+        status.record_poll_error("timeout");
+        status.record_poll(&std::collections::HashMap::new(), chrono::Utc::now());
+    }
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/events.json?limit=1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_matches_schema(&json, "events");
+    let events = json["events"].as_array().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["kind"], "device_online");
+}
+
+#[cfg(feature = "db")]
+#[tokio::test]
+async fn test_annotations_without_database() {
+    use wxlistener::auth::TokenScope;
+
+    let (app, _) = router_with_tokens(vec![
+        token("reader", TokenScope::Read),
+        token("boss", TokenScope::Admin),
+    ]);
+    let note = r#"{"text":"Gauge cleaned"}"#;
+
+    // Adding needs admin, reading takes either scope
+    assert_eq!(
+        send(&app, "POST", "/api/v1/annotations", Some("reader"), note).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        send(&app, "POST", "/api/v1/annotations", Some("boss"), note).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&app, "GET", "/api/v1/annotations.json", Some("reader"), "").await,
+        StatusCode::OK
+    );
+
+    let (app, _) = router_with_tokens(Vec::new());
+    let (status, body) = get_text(&app, "/api/v1/annotations.json?from=0&to=1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Annotations need a [database] section"));
+}
+
+#[tokio::test]
+async fn test_api_history_json() {
+    use wxlistener::storage::{MemoryStore, Storage};
+
+    let (app, _) = router_with_tokens(Vec::new());
+    let (_, body) = get_text(&app, "/api/v1/history.json?field=outtemp").await;
+    assert!(body.contains("History needs a [database] or [storage] section"));
+
+    let store = Arc::new(MemoryStore::new(10));
+    let provenance = wxlistener::provenance::Provenance::poll(None, None, 0);
+    let now = chrono::Utc::now();
+    for (minutes, temp) in [(90, 8.0), (30, 10.0), (10, 12.0)] {
+        let data = std::collections::HashMap::from([("outtemp".to_string(), temp)]);
+        let at = now - chrono::TimeDelta::minutes(minutes);
+        store.save(at, &data, &provenance).await.unwrap();
+    }
+    let mut config = test_server_config(Vec::new());
+    config.storage = Some(store);
+    let app = router(Arc::new(LiveFeed::default()), &config);
+
+    let (status, body) = get_text(&app, "/api/v1/history.json?field=outtemp&last=1h").await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["field"], "outtemp");
+    assert_eq!(json["points"].as_array().unwrap().len(), 2);
+    assert_eq!(json["points"][1][1], 12.0);
+    assert_eq!(json["aggregate"]["count"], 2);
+    assert_eq!(json["aggregate"]["mean"], 11.0);
+
+    let (_, body) = get_text(&app, "/api/v1/history.json?field=outtemp&last=soon").await;
+    assert!(body.contains("error"));
+}
+
+#[tokio::test]
+async fn test_api_compare_json() {
+    use wxlistener::storage::{MemoryStore, Storage};
+
+    let provenance = wxlistener::provenance::Provenance::poll(None, None, 0);
+    let now = chrono::Utc::now();
+    let home = Arc::new(MemoryStore::new(10));
+    let cabin = Arc::new(MemoryStore::new(10));
+    for (store, temp) in [(&home, 12.0), (&cabin, 9.5)] {
+        let data = std::collections::HashMap::from([("outtemp".to_string(), temp)]);
+        let at = now - chrono::TimeDelta::minutes(5);
+        store.save(at, &data, &provenance).await.unwrap();
+    }
+    let mut config = test_server_config(Vec::new());
+    config.storage = Some(home);
+    config.stations.insert("cabin".to_string(), cabin);
+    let app = router(Arc::new(LiveFeed::default()), &config);
+
+    let (status, body) = get_text(
+        &app,
+        "/api/v1/compare.json?series=outtemp,cabin:outtemp&last=1h&step=3600&difference=true",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["step"], 3600);
+    assert_eq!(json["timestamps"].as_array().unwrap().len(), 1);
+    assert_eq!(json["series"][1]["name"], "cabin:outtemp");
+    assert_eq!(json["series"][1]["values"][0], 9.5);
+    assert_eq!(json["differences"][0]["values"][0], 2.5);
+
+    let (_, body) = get_text(&app, "/api/v1/compare.json?series=outtemp,shed:outtemp").await;
+    assert!(body.contains("Unknown station 'shed'"));
+}
+
+async fn get_text(app: &axum::Router, uri: &str) -> (StatusCode, String) {
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_template_files() {
+    let mut config = test_server_config(Vec::new());
+    config.realtime_txt = Some("/realtime.txt".to_string());
+    config.clientraw_txt = Some("/wx/clientraw.txt".to_string());
+    config.station_name = "Home".to_string();
+    let feed = Arc::new(LiveFeed::default());
+    let app = router(feed.clone(), &config);
+
+    // Nothing polled yet
+    let (status, _) = get_text(&app, "/realtime.txt").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let data = [("outtemp", 12.3), ("outhumid", 71.0), ("wind_dir", 225.0)]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+    let payload = current_payload(
+        chrono::Utc::now(),
+        &data,
+        &Default::default(),
+        Default::default(),
+    );
+    feed.publish(payload.to_string());
+
+    let (status, realtime) = get_text(&app, "/realtime.txt").await;
+    assert_eq!(status, StatusCode::OK);
+    let fields: Vec<&str> = realtime.split_whitespace().collect();
+    assert_eq!(fields.len(), 58);
+    assert_eq!(&fields[2..4], ["12.3", "71"]);
+    assert_eq!(fields[11], "SW");
+
+    let (status, clientraw) = get_text(&app, "/wx/clientraw.txt").await;
+    assert_eq!(status, StatusCode::OK);
+    let fields: Vec<&str> = clientraw.split(' ').collect();
+    assert_eq!(fields[0], "12345");
+    assert_eq!(fields[4], "12.3");
+    assert!(fields[32].starts_with("Home-"));
+
+    // A failed poll leaves nothing to serve
+    feed.publish(r#"{"error":"Failed to fetch data","timestamp":"2026-01-01T00:00:00Z"}"#);
+    let (status, _) = get_text(&app, "/realtime.txt").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    // Unconfigured files aren't served
+    let app = router(feed, &test_server_config(Vec::new()));
+    let (status, _) = get_text(&app, "/realtime.txt").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_livedata_info() {
+    let feed = Arc::new(LiveFeed::default());
+    let app = router(feed.clone(), &test_server_config(Vec::new()));
+
+    let (status, _) = get_text(&app, "/get_livedata_info").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let data = [("outtemp", 20.3), ("intemp", 22.1), ("inhumid", 50.0)]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+    let payload = current_payload(
+        chrono::Utc::now(),
+        &data,
+        &Default::default(),
+        Default::default(),
+    );
+    feed.publish(payload.to_string());
+
+    let (status, body) = get_text(&app, "/get_livedata_info").await;
+    assert_eq!(status, StatusCode::OK);
+    let info: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        info["common_list"][0],
+        serde_json::json!({"id": "0x02", "val": "20.3", "unit": "C"})
+    );
+    assert_eq!(info["wh25"][0]["inhumi"], "50%");
+}
+
+#[tokio::test]
+async fn test_public_view() {
+    use wxlistener::auth::TokenScope;
+
+    let feed = Arc::new(LiveFeed::default());
+    let mut config = test_server_config(Vec::new());
+    config.tokens = vec![token("reader", TokenScope::Read)];
+    config.public = Some(toml::from_str("precision = { outtemp = 0 }").unwrap());
+    let app = router(feed.clone(), &config);
+
+    let data = [("outtemp", 20.3), ("intemp", 22.1), ("co2", 640.0)]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+    let payload = current_payload(
+        chrono::Utc::now(),
+        &data,
+        &Default::default(),
+        Default::default(),
+    );
+    feed.publish(payload.to_string());
+
+    // Without a token: indoor sensors left out, readings rounded
+    let (status, body) = get_text(&app, "/api/v1/current.json").await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["readings"]["outtemp"], 20.0);
+    assert!(json["readings"].get("intemp").is_none());
+    assert!(json["data"].get("co2").is_none());
+
+    let (_, body) = get_text(&app, "/api/v1/history.json?field=intemp").await;
+    assert!(body.contains("intemp is not public"));
+
+    // A read token still sees everything
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/current.json")
+                .header("authorization", "Bearer reader")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["readings"]["outtemp"], 20.3);
+    assert_eq!(json["readings"]["intemp"], 22.1);
+
+    // Admin routes stay closed
+    assert_eq!(
+        send(&app, "POST", "/api/v1/control", None, "{}").await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn test_api_stations_geojson() {
+    use wxlistener::station::StationConfig;
+    use wxlistener::storage::{MemoryStore, Storage};
+
+    let cabin = Arc::new(MemoryStore::new(10));
+    let data = std::collections::HashMap::from([("outtemp".to_string(), 9.5)]);
+    let provenance = wxlistener::provenance::Provenance::poll(None, None, 0);
+    cabin
+        .save(chrono::Utc::now(), &data, &provenance)
+        .await
+        .unwrap();
+
+    let feed = Arc::new(LiveFeed::default());
+    let mut config = test_server_config(Vec::new());
+    config.station = StationConfig {
+        latitude: Some(52.37),
+        longitude: Some(4.89),
+        ..Default::default()
+    };
+    config.stations.insert("cabin".to_string(), cabin.clone());
+    config.stations.insert("shed".to_string(), cabin);
+    config.station_locations.insert(
+        "cabin".to_string(),
+        StationConfig {
+            name: Some("Cabin".to_string()),
+            latitude: Some(46.5),
+            longitude: Some(7.9),
+            ..Default::default()
+        },
+    );
+    let app = router(feed.clone(), &config);
+
+    let data = [("outtemp", 12.3)]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+    let payload = current_payload(
+        chrono::Utc::now(),
+        &data,
+        &Default::default(),
+        Default::default(),
+    );
+    feed.publish(payload.to_string());
+
+    let (status, body) = get_text(&app, "/api/v1/stations.geojson").await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["type"], "FeatureCollection");
+    // The shed has no location
+    let features = json["features"].as_array().unwrap();
+    assert_eq!(features.len(), 2);
+    assert_eq!(features[0]["properties"]["name"], "wxlistener");
+    assert_eq!(features[0]["properties"]["readings"]["outtemp"], 12.3);
+    assert_eq!(features[1]["properties"]["station"], "cabin");
+    assert_eq!(features[1]["properties"]["name"], "Cabin");
+    assert_eq!(
+        features[1]["geometry"]["coordinates"],
+        serde_json::json!([7.9, 46.5])
+    );
+    assert_eq!(features[1]["properties"]["readings"]["outtemp"], 9.5);
+}
+
+#[cfg(feature = "http")]
+#[tokio::test]
+async fn test_federation() {
+    use wxlistener::federation::{sign, FederationConfig, PeerReadings, SIGNATURE_HEADER};
+
+    let federation: FederationConfig = toml::from_str(
+        r#"
+        name = "home"
+        [[peers]]
+        name = "north"
+        url = "http://192.168.1.20:18888"
+        key = "0123456789abcdef"
+        "#,
+    )
+    .unwrap();
+    let mut config = test_server_config(Vec::new());
+    config.tokens = vec![token("reader", wxlistener::auth::TokenScope::Read)];
+    config.peers = Some(Arc::new(tokio::sync::Mutex::new(PeerReadings::new(
+        &federation,
+    ))));
+    let app = router(Arc::new(LiveFeed::default()), &config);
+
+    let body = serde_json::json!({
+        "station": "north",
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "readings": { "outtemp": 11.5 },
+    })
+    .to_string();
+    let post = |signature: String| {
+        Request::builder()
+            .method("POST")
+            .uri("/api/v1/federation")
+            .header(SIGNATURE_HEADER, signature)
+            .body(Body::from(body.clone()))
+            .unwrap()
+    };
+
+    // Signed instead of a token
+    let response = app
+        .clone()
+        .oneshot(post(sign("a different key!!", body.as_bytes())))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app
+        .clone()
+        .oneshot(post(sign("0123456789abcdef", body.as_bytes())))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/peers.json")
+                .header("authorization", "Bearer reader")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_matches_schema(&json, "peers");
+    assert_eq!(json["peers"][0]["station"], "north");
+    assert_eq!(json["peers"][0]["readings"]["outtemp"], 11.5);
+}